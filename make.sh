@@ -13,29 +13,32 @@ PREFIX="${PREFIX%%.osm.pbf}"
 #pyosmium-up-to-date -vv "$INPUTFILE" || true
 
 if [ "$INPUTFILE" -nt ${PREFIX}.place.osm.pbf ] ; then
-	echo "Extracting place nodes..."
-	osmium tags-filter --overwrite "$INPUTFILE" -o ${PREFIX}.place.osm.pbf n/place
+	echo "Extracting places..."
+	osmium tags-filter --overwrite "$INPUTFILE" -o ${PREFIX}.place.osm.pbf nwr/place
 fi
 
 if [ "$INPUTFILE" -nt "${PREFIX}.admin_level.osm.pbf" ] ; then
 	echo "Extracting admin_levels..."
-	osmium tags-filter --overwrite "$INPUTFILE" -o "${PREFIX}.admin_level.osm.pbf" admin_level
+	# non-administrative boundaries often have no admin_level
+	osmium tags-filter --overwrite "$INPUTFILE" -o "${PREFIX}.admin_level.osm.pbf" admin_level boundary=ceremonial,political,statistical
 fi
 
 if [ "${PREFIX}.place.osm.pbf" -nt ".${PREFIX}.place.imported" ] ; then
-	echo "Importing place nodes..."
-	osm2pgsql -l -S x-in-y.style --slim --drop -p place "${PREFIX}.place.osm.pbf"
-	for T in line polygon roads ; do
+	echo "Importing places..."
+	osm2pgsql -l -x -S x-in-y.style --slim --drop -p place "${PREFIX}.place.osm.pbf"
+	for T in line roads ; do
 		psql -c "drop table place_$T"
 	done
 	psql -c "create index place_point__place on place_point (place)"
+	psql -c "create index place_polygon__place on place_polygon (place)"
 	psql -c "analyze place_point;"
+	psql -c "analyze place_polygon;"
 	touch ".${PREFIX}.place.imported"
 fi
 
 if [ "${PREFIX}.admin_level.osm.pbf" -nt ".${PREFIX}.admin_level.imported" ] ; then
 	echo "Importing admin_levels..."
-	osm2pgsql -l -S x-in-y.style --slim --drop -p admin_level "${PREFIX}.admin_level.osm.pbf"
+	osm2pgsql -l -x -S x-in-y.style --slim --drop -p admin_level "${PREFIX}.admin_level.osm.pbf"
 	for T in line point roads ; do
 		psql -c "drop table admin_level_$T"
 	done
@@ -46,10 +49,31 @@ fi
 
 if [ ".${PREFIX}.place.osm.pbf" -nt "${PREFIX}.place-in-area.csv.gz" ] || [ ".${PREFIX}.admin_level.imported" -nt "${PREFIX}.place-in-area.csv.gz" ] || [ $0 -nt "${PREFIX}.place-in-area.csv.gz" ] ; then
 
+	# Places can be nodes, or areas (ways/relations). Areas use a point inside them
 	psql -c "COPY (
+		with place as (
+			select 'n' as osmtype, osm_id as id, name, \"name:en\", place, wikidata, wikipedia, population, osm_timestamp, way
+				from place_point
+			union all
+			select
+					(case when osm_id<0 then 'r' else 'w' end) as osmtype, abs(osm_id) as id,
+					name, \"name:en\", place, wikidata, wikipedia, population, osm_timestamp, ST_PointOnSurface(way) as way
+				from place_polygon
+		),
+		place_country as (
+			select place.osmtype, place.id, min(country.\"ISO3166-1\") as country_code
+				from place
+				JOIN admin_level_polygon as country
+					ON (
+						country.admin_level = '2'
+						AND country.way && place.way
+						AND ST_Contains(country.way, place.way)
+					)
+				group by place.osmtype, place.id
+		)
 		select
-				'n' as place_osmtype,
-				place.osm_id as place_id,
+				place.osmtype as place_osmtype,
+				place.id as place_id,
 				coalesce(place.\"name:en\", place.name) as place_name,
 				place.place as place_type,
 				st_y(place.way) as place_lat,
@@ -57,14 +81,26 @@ if [ ".${PREFIX}.place.osm.pbf" -nt "${PREFIX}.place-in-area.csv.gz" ] || [ ".${
 				(case when boundary.osm_id<0 then 'r' else 'w' end) as boundary_osmtype,
 				abs(boundary.osm_id) as boundary_id,
 				coalesce(boundary.\"name:en\", boundary.name) as boundary_name,
-				boundary.admin_level as boundary_admin_level
+				boundary.admin_level as boundary_admin_level,
+				boundary.boundary as boundary_type,
+				place_country.country_code as country_code,
+				place.wikidata as place_wikidata,
+				place.wikipedia as place_wikipedia,
+				boundary.wikidata as boundary_wikidata,
+				boundary.wikipedia as boundary_wikipedia,
+				place.population as place_population,
+				round(ST_Area(boundary.way::geography)) as boundary_area,
+				place.osm_timestamp as place_timestamp,
+				boundary.osm_timestamp as boundary_timestamp
 			from
-				place_point as place
+				place
 				JOIN admin_level_polygon as boundary
 					ON (
 						boundary.way && place.way
 						AND ST_Contains(boundary.way, place.way)
 					)
+				LEFT JOIN place_country
+					ON (place_country.osmtype = place.osmtype AND place_country.id = place.id)
 		) TO STDOUT WITH ( FORMAT CSV, HEADER )" \
 			| pv -l -N "calculating place/boundary combos" \
 			| gzip \
@@ -75,4 +111,4 @@ if [ ".${PREFIX}.place.osm.pbf" -nt "${PREFIX}.place-in-area.csv.gz" ] || [ ".${
 fi
 
 cd $ROOT
-exec cargo +nightly run --release -- "${PREFIX}.place-in-area.csv.gz" "${PREFIX}.distances.md"
+exec cargo run --release -- "${PREFIX}.place-in-area.csv.gz" "${PREFIX}.distances.md"
@@ -0,0 +1,204 @@
+"""Python bindings for the x-in-y chain search.
+
+Build the library first (from the top of the repo):
+
+    cargo rustc --release --lib --features python --crate-type cdylib
+
+then, with this directory on the PYTHONPATH:
+
+    import x_in_y
+
+    records = x_in_y.load_records("places.csv.gz")
+    chains = x_in_y.search(records, max_chain_len=50)
+    print(chains[0])
+
+    # The flags are as for the command line, for the filters & the search
+    records = x_in_y.load_records("places.csv.gz", "--min-name-length", "2", "-q")
+
+    # Prefer hops into bigger places
+    def score(chain):
+        return chain[-1].place_population or 0
+
+    chains = x_in_y.search(records, score=score)
+
+The library is looked for in ../target/release, or set X_IN_Y_LIB to its path.
+"""
+
+import ctypes
+import json
+import os
+import sys
+import warnings
+
+__all__ = ["Record", "Records", "Chain", "load_records", "search"]
+
+
+def _lib_path():
+    if "X_IN_Y_LIB" in os.environ:
+        return os.environ["X_IN_Y_LIB"]
+    name = {"darwin": "libx_in_y.dylib", "win32": "x_in_y.dll"}.get(
+        sys.platform, "libx_in_y.so"
+    )
+    here = os.path.dirname(os.path.abspath(__file__))
+    return os.path.join(here, "..", "target", "release", name)
+
+
+_lib = ctypes.CDLL(_lib_path())
+
+# Returned strings are kept as void pointers, so they can be freed
+_lib.x_in_y_last_error.restype = ctypes.c_char_p
+_lib.x_in_y_load_records.argtypes = [
+    ctypes.c_char_p,
+    ctypes.POINTER(ctypes.c_char_p),
+    ctypes.c_size_t,
+]
+_lib.x_in_y_load_records.restype = ctypes.c_void_p
+_lib.x_in_y_free_records.argtypes = [ctypes.c_void_p]
+_lib.x_in_y_records_len.argtypes = [ctypes.c_void_p]
+_lib.x_in_y_records_len.restype = ctypes.c_size_t
+_lib.x_in_y_parse_errors.argtypes = [ctypes.c_void_p]
+_lib.x_in_y_parse_errors.restype = ctypes.c_void_p
+_lib.x_in_y_record_json.argtypes = [ctypes.c_void_p, ctypes.c_size_t]
+_lib.x_in_y_record_json.restype = ctypes.c_void_p
+_SCORE_FN = ctypes.CFUNCTYPE(
+    ctypes.c_ssize_t, ctypes.POINTER(ctypes.c_size_t), ctypes.c_size_t, ctypes.c_void_p
+)
+_lib.x_in_y_search.argtypes = [ctypes.c_void_p, ctypes.c_size_t, _SCORE_FN, ctypes.c_void_p]
+_lib.x_in_y_search.restype = ctypes.c_void_p
+_lib.x_in_y_free_string.argtypes = [ctypes.c_void_p]
+
+
+def _error():
+    return RuntimeError((_lib.x_in_y_last_error() or b"Unknown error").decode())
+
+
+def _take_json(ptr):
+    if not ptr:
+        raise _error()
+    try:
+        return json.loads(ctypes.string_at(ptr).decode())
+    finally:
+        _lib.x_in_y_free_string(ptr)
+
+
+class Record:
+    """A place in a boundary, with the same properties as in the geojson &
+    ndjson output, e.g. place_name, boundary_name, admin_level."""
+
+    def __init__(self, index, properties):
+        self.index = index
+        self.properties = properties
+
+    def __getattr__(self, name):
+        try:
+            return self.properties[name]
+        except KeyError:
+            raise AttributeError(name) from None
+
+    def __repr__(self):
+        return "<Record {} in {}>".format(self.place_name, self.boundary_name)
+
+
+class Records:
+    """The records from a CSV file, which are left after the filters, as a
+    sequence of Record.
+
+    parse_errors is about the rows which couldn't be parsed, & were skipped:
+    {"total": ..., "counts": {kind: count}, "errors": [the first 100, each
+    {"filename", "line", "kind", "message"}]}."""
+
+    def __init__(self, handle):
+        self._handle = handle
+        self._cache = {}
+        self.parse_errors = _take_json(_lib.x_in_y_parse_errors(handle))
+
+    def __del__(self):
+        if getattr(self, "_handle", None):
+            _lib.x_in_y_free_records(self._handle)
+            self._handle = None
+
+    def __len__(self):
+        return _lib.x_in_y_records_len(self._handle)
+
+    def __getitem__(self, i):
+        if i < 0:
+            i += len(self)
+        if not 0 <= i < len(self):
+            raise IndexError(i)
+        if i not in self._cache:
+            self._cache[i] = Record(i, _take_json(_lib.x_in_y_record_json(self._handle, i)))
+        return self._cache[i]
+
+
+class Chain:
+    """Places, each in a boundary with the same name as the next place."""
+
+    def __init__(self, records):
+        self.records = records
+
+    def __len__(self):
+        return len(self.records)
+
+    def __getitem__(self, i):
+        return self.records[i]
+
+    def __iter__(self):
+        return iter(self.records)
+
+    def __repr__(self):
+        return "<Chain of {} places: {} → {}>".format(
+            len(self), self.records[0].place_name, self.records[-1].boundary_name
+        )
+
+
+def load_records(filename, *flags):
+    """Read the records from a CSV file (maybe compressed), and filter them as
+    the command line does. flags are command line flags, e.g. "--strict" to
+    raise an error for a row which can't be parsed, "--min-name-length", "2",
+    or "-q" for less logging. They're also used by search().
+
+    Rows which can't be parsed are skipped with a warning, and are in the
+    parse_errors of the Records."""
+    flags = [os.fsencode(flag) for flag in flags]
+    handle = _lib.x_in_y_load_records(
+        os.fsencode(filename), (ctypes.c_char_p * len(flags))(*flags), len(flags)
+    )
+    if not handle:
+        raise _error()
+    records = Records(handle)
+    total = records.parse_errors["total"]
+    if total:
+        warnings.warn(
+            "{} rows of {} couldn't be parsed, and were skipped: {}".format(
+                total,
+                filename,
+                ", ".join(
+                    "{} {}".format(count, kind)
+                    for kind, count in records.parse_errors["counts"].items()
+                ),
+            ),
+            stacklevel=2,
+        )
+    return records
+
+
+def search(records, max_chain_len=None, score=None):
+    """The longest chain from each record, longest first, searched as the
+    command line would with the flags given to load_records.
+
+    max_chain_len, if given, replaces --max-chain-len. score, if given, is called with a chain (a list of Record) and returns an
+    int for how good its last hop is. Of the chains of the same length, those
+    with the highest total score are extended first. It replaces --score,
+    which by default is how far the last hop jumps, in metres."""
+    if score is None:
+        score_fn = _SCORE_FN()
+    else:
+
+        def score_fn(chain, length, _user_data):
+            return int(score([records[chain[i]] for i in range(length)]))
+
+        score_fn = _SCORE_FN(score_fn)
+    chains = _take_json(
+        _lib.x_in_y_search(records._handle, max_chain_len or 0, score_fn, None)
+    )
+    return [Chain([records[i] for i in chain]) for chain in chains]
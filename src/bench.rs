@@ -0,0 +1,92 @@
+// Time the parts of a run which take the time (bench): loading the input, and a fixed number of
+// search steps. Without an INPUT, it times a gen-fixture file made up for it, so runs on different
+// machines or commits can be compared.
+
+use std::time::Instant;
+
+use anyhow::Result;
+use separator::Separatable;
+
+use crate::fixture;
+use crate::options::Options;
+use crate::record::Record;
+use crate::{metrics, timeline};
+
+pub fn run(options: &Options) -> Result<()> {
+    let generated = match options.input_filenames.first() {
+        Some(_) => None,
+        None => {
+            let filename = std::env::temp_dir()
+                .join(format!("x-in-y-bench-{}.csv.gz", std::process::id()))
+                .to_string_lossy()
+                .into_owned();
+            fixture::generate(
+                &filename,
+                options.fixture_places,
+                options.fixture_chain_depth,
+                options.fixture_seed,
+            )?;
+            Some(filename)
+        }
+    };
+    let filename = options
+        .input_filenames
+        .first()
+        .or(generated.as_ref())
+        .unwrap();
+
+    let started = Instant::now();
+    let loaded = timeline::load(filename, options);
+    let load_secs = started.elapsed().as_secs_f64();
+    if let Some(generated) = &generated {
+        let _ = std::fs::remove_file(generated);
+    }
+    let points_in_boundary = loaded?;
+    let records: Vec<&Record> = points_in_boundary
+        .values()
+        .flat_map(|recs| recs.iter())
+        .collect();
+
+    let started = Instant::now();
+    let (chains, search_stats) = options
+        .chain_search(&records, false)
+        .max_steps(options.bench_steps)
+        .run();
+    let search_secs = started.elapsed().as_secs_f64();
+    let longest = chains.values().map(|c| c.len()).max().unwrap_or(0);
+
+    let per_sec = |n: usize, secs: f64| ((n as f64 / secs.max(1e-9)) as u64).separated_string();
+    println!(
+        "Input: {}",
+        generated
+            .as_deref()
+            .map_or(filename.as_str(), |_| "gen-fixture")
+    );
+    println!(
+        "Load: {} records in {:.2}s, {} records/s",
+        records.len().separated_string(),
+        load_secs,
+        per_sec(records.len(), load_secs)
+    );
+    println!(
+        "Search: {} steps in {:.2}s, {} steps/s{}",
+        search_stats.steps.separated_string(),
+        search_secs,
+        per_sec(search_stats.steps, search_secs),
+        if search_stats.interrupted {
+            ""
+        } else {
+            " (finished before --steps)"
+        }
+    );
+    println!(
+        "Peak frontier: {} intermediate chains",
+        search_stats.peak_frontier.separated_string()
+    );
+    println!(
+        "Peak memory: {}",
+        metrics::peak_rss_bytes().map_or("unknown".to_string(), |b| format!("{} MB", b >> 20))
+    );
+    println!("Longest chain so far: {} places", longest);
+    Ok(())
+}
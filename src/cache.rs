@@ -0,0 +1,360 @@
+// --cache: the records of an input, as parsed from the CSV, in a simple binary file next to it
+// (INPUT.x-in-y-cache), which is much quicker to read than decompressing & parsing the CSV again.
+// It's used as long as the input's size & modification time, the --column/--delimiter/--quote
+// settings and this version are the same as when it was made. The filters still run every time,
+// so changing them doesn't need a new cache.
+//
+// The file is a header, then each record as a 1 byte, then its fields, then a 0 byte, then the rows
+// which couldn't be parsed (as a u64 count, then the line, kind & message of each), so a cached run
+// reports them too. Numbers are little endian, strings are a u32 length & UTF-8, and options are a
+// 0 or 1 byte first.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{bail, Context, Result};
+
+use crate::input::{CsvFormat, ParseErrors};
+use crate::record::Record;
+
+const MAGIC: &[u8] = b"x-in-y record cache 5\n";
+
+pub fn filename(input_filename: &str) -> String {
+    format!("{}.x-in-y-cache", input_filename)
+}
+
+// What the cache was made from. If any of it changes, the cache is out of date.
+fn fingerprint(input_filename: &str, csv_format: &CsvFormat) -> Result<String> {
+    let metadata = std::fs::metadata(input_filename)
+        .with_context(|| format!("Could not read {}", input_filename))?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(format!(
+        "{} {} {}.{:09} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        metadata.len(),
+        modified.as_secs(),
+        modified.subsec_nanos(),
+        csv_format
+    ))
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> std::io::Result<()> {
+    w.write_all(&(s.len() as u32).to_le_bytes())?;
+    w.write_all(s.as_bytes())
+}
+
+fn write_opt_str(w: &mut impl Write, s: &Option<String>) -> std::io::Result<()> {
+    match s {
+        None => w.write_all(&[0]),
+        Some(s) => {
+            w.write_all(&[1])?;
+            write_str(w, s)
+        }
+    }
+}
+
+fn read_bytes<const N: usize>(r: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u64(r: &mut impl Read) -> std::io::Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(r)?))
+}
+
+fn read_f64(r: &mut impl Read) -> std::io::Result<f64> {
+    Ok(f64::from_le_bytes(read_bytes(r)?))
+}
+
+fn read_u8(r: &mut impl Read) -> std::io::Result<u8> {
+    Ok(read_bytes::<1>(r)?[0])
+}
+
+fn read_str(r: &mut impl Read) -> std::io::Result<String> {
+    let len = u32::from_le_bytes(read_bytes(r)?) as usize;
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+fn read_opt_str(r: &mut impl Read) -> std::io::Result<Option<String>> {
+    Ok(match read_u8(r)? {
+        0 => None,
+        _ => Some(read_str(r)?),
+    })
+}
+
+// Writes the cache as the CSV is read. It's only renamed into place by `finish`, so a run which
+// stops half way doesn't leave half a cache.
+pub struct Writer {
+    filename: String,
+    tmp_filename: String,
+    file: BufWriter<File>,
+}
+
+impl Writer {
+    // None (with a warning) if the cache can't be written, e.g. the input is in a read only
+    // directory. The run goes on without it.
+    pub fn create(input_filename: &str, csv_format: &CsvFormat) -> Option<Writer> {
+        let filename = filename(input_filename);
+        let tmp_filename = format!("{}.tmp", filename);
+        let writer = (|| -> Result<Writer> {
+            let mut file = BufWriter::new(File::create(&tmp_filename)?);
+            file.write_all(MAGIC)?;
+            write_str(&mut file, &fingerprint(input_filename, csv_format)?)?;
+            Ok(Writer {
+                filename: filename.clone(),
+                tmp_filename: tmp_filename.clone(),
+                file,
+            })
+        })();
+        match writer {
+            Ok(w) => Some(w),
+            Err(e) => {
+                warn!("Not writing the cache {}: {:#}", filename, e);
+                let _ = std::fs::remove_file(&tmp_filename);
+                None
+            }
+        }
+    }
+
+    fn write_record(&mut self, r: &Record) -> std::io::Result<()> {
+        let w = &mut self.file;
+        w.write_all(&[1, r.place_osmtype as u8])?;
+        w.write_all(&r.place_id.to_le_bytes())?;
+        write_str(w, &r.place_name)?;
+        write_str(w, &r.place_type)?;
+        w.write_all(&r.place_lat.to_le_bytes())?;
+        w.write_all(&r.place_lon.to_le_bytes())?;
+        w.write_all(&[r.boundary_osmtype as u8])?;
+        w.write_all(&r.boundary_id.to_le_bytes())?;
+        write_str(w, &r.boundary_name)?;
+        match &r.boundary_admin_level {
+            None => w.write_all(&[0])?,
+            Some(levels) => {
+                w.write_all(&[levels.len() as u8 + 1])?;
+                w.write_all(levels)?;
+            }
+        }
+        for s in [
+            &r.boundary_type,
+            &r.country_code,
+            &r.place_wikidata,
+            &r.place_wikipedia,
+            &r.boundary_wikidata,
+            &r.boundary_wikipedia,
+        ] {
+            write_opt_str(w, s)?;
+        }
+        match r.place_population {
+            None => w.write_all(&[0])?,
+            Some(p) => {
+                w.write_all(&[1])?;
+                w.write_all(&p.to_le_bytes())?;
+            }
+        }
+        match r.boundary_area {
+            None => w.write_all(&[0])?,
+            Some(a) => {
+                w.write_all(&[1])?;
+                w.write_all(&a.to_le_bytes())?;
+            }
+        }
+        for t in [r.place_timestamp, r.boundary_timestamp] {
+            match t {
+                None => w.write_all(&[0])?,
+                Some(t) => {
+                    w.write_all(&[1])?;
+                    w.write_all(&t.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Give up on the cache if it can't be written, rather than stopping the run
+    pub fn write(writer: &mut Option<Writer>, record: &Record) {
+        if let Some(w) = writer {
+            if let Err(e) = w.write_record(record) {
+                warn!("Not writing the cache {}: {}", w.filename, e);
+                let _ = std::fs::remove_file(&w.tmp_filename);
+                *writer = None;
+            }
+        }
+    }
+
+    // `errors` are the rows of this input which couldn't be parsed, as in `ParseErrors`
+    pub fn finish(mut self, errors: &[(String, Option<u64>, &'static str, String)]) {
+        let result = (|| -> std::io::Result<()> {
+            let w = &mut self.file;
+            w.write_all(&[0])?;
+            w.write_all(&(errors.len() as u64).to_le_bytes())?;
+            for (_, line, kind, message) in errors {
+                match line {
+                    None => w.write_all(&[0])?,
+                    Some(line) => {
+                        w.write_all(&[1])?;
+                        w.write_all(&line.to_le_bytes())?;
+                    }
+                }
+                write_str(w, kind)?;
+                write_str(w, message)?;
+            }
+            w.flush()?;
+            std::fs::rename(&self.tmp_filename, &self.filename)
+        })();
+        match result {
+            Ok(()) => info!("Wrote the parsed records to {} for --cache", self.filename),
+            Err(e) => {
+                warn!("Not writing the cache {}: {}", self.filename, e);
+                let _ = std::fs::remove_file(&self.tmp_filename);
+            }
+        }
+    }
+}
+
+// The records from an up to date cache
+pub struct Reader {
+    filename: String,
+    file: BufReader<File>,
+    error: Option<std::io::Error>,
+}
+
+impl Reader {
+    // None if there's no cache, or it's out of date
+    pub fn open(input_filename: &str, csv_format: &CsvFormat) -> Option<Reader> {
+        let filename = filename(input_filename);
+        let mut file = BufReader::new(File::open(&filename).ok()?);
+        let mut magic = [0; MAGIC.len()];
+        file.read_exact(&mut magic).ok()?;
+        let made_from = read_str(&mut file).ok()?;
+        if magic != MAGIC || Some(made_from) != fingerprint(input_filename, csv_format).ok() {
+            info!(
+                "{} is out of date, reading {} again",
+                filename, input_filename
+            );
+            return None;
+        }
+        info!("Reading the records from {}", filename);
+        Some(Reader {
+            filename,
+            file,
+            error: None,
+        })
+    }
+
+    fn read_record(&mut self) -> std::io::Result<Option<Record>> {
+        let r = &mut self.file;
+        if read_u8(r)? == 0 {
+            return Ok(None);
+        }
+        let place_osmtype = read_u8(r)? as char;
+        let place_id = read_u64(r)?;
+        let place_name = read_str(r)?;
+        let place_type = read_str(r)?;
+        let place_lat = read_f64(r)?;
+        let place_lon = read_f64(r)?;
+        let boundary_osmtype = read_u8(r)? as char;
+        let boundary_id = read_u64(r)?;
+        let boundary_name = read_str(r)?;
+        let boundary_admin_level = match read_u8(r)? {
+            0 => None,
+            n => {
+                let mut levels = vec![0; n as usize - 1];
+                r.read_exact(&mut levels)?;
+                Some(levels)
+            }
+        };
+        Ok(Some(Record {
+            place_osmtype,
+            place_id,
+            place_name: place_name.into(),
+            place_type: place_type.into(),
+            place_lat,
+            place_lon,
+            boundary_osmtype,
+            boundary_id,
+            boundary_name: boundary_name.into(),
+            boundary_admin_level,
+            boundary_type: read_opt_str(r)?,
+            country_code: read_opt_str(r)?,
+            place_wikidata: read_opt_str(r)?,
+            place_wikipedia: read_opt_str(r)?,
+            boundary_wikidata: read_opt_str(r)?,
+            boundary_wikipedia: read_opt_str(r)?,
+            place_population: match read_u8(r)? {
+                0 => None,
+                _ => Some(read_u64(r)?),
+            },
+            boundary_area: match read_u8(r)? {
+                0 => None,
+                _ => Some(read_f64(r)?),
+            },
+            place_timestamp: match read_u8(r)? {
+                0 => None,
+                _ => Some(read_u64(r)? as i64),
+            },
+            boundary_timestamp: match read_u8(r)? {
+                0 => None,
+                _ => Some(read_u64(r)? as i64),
+            },
+        }))
+    }
+
+    fn read_parse_errors(
+        &mut self,
+        input_filename: &str,
+        parse_errors: &mut ParseErrors,
+    ) -> std::io::Result<()> {
+        let r = &mut self.file;
+        for _ in 0..read_u64(r)? {
+            let line = match read_u8(r)? {
+                0 => None,
+                _ => Some(read_u64(r)?),
+            };
+            let kind = read_str(r)?;
+            parse_errors.add_row(input_filename, line, &kind, read_str(r)?);
+        }
+        Ok(())
+    }
+
+    // Call after reading all the records, to add the rows which couldn't be parsed to
+    // `parse_errors`. An error part way through means it's corrupt.
+    pub fn finish(mut self, input_filename: &str, parse_errors: &mut ParseErrors) -> Result<()> {
+        if self.error.is_none() {
+            if let Err(e) = self.read_parse_errors(input_filename, parse_errors) {
+                self.error = Some(e);
+            }
+        }
+        if let Some(e) = self.error {
+            bail!(
+                "Could not read the cache {} ({}), delete it and run again",
+                self.filename,
+                e
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Reader {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.read_record() {
+            Ok(record) => record,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
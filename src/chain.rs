@@ -0,0 +1,181 @@
+// A chain of records, where each place has the same name as the boundary the previous place is
+// in. How far it travels & where, which nearly every output shows, is worked out once when the
+// chain is made, rather than by each output.
+
+use std::ops::Deref;
+
+use crate::distance;
+use crate::freshness::{self, Freshness};
+use crate::json::{obj, Value};
+use crate::output::geojson;
+use crate::record::Record;
+
+fn hop_distances_m(chain: &[&Record]) -> Vec<f64> {
+    std::iter::once(0.)
+        .chain(chain.windows(2).map(|pair| {
+            distance::metres(
+                pair[0].place_lat,
+                pair[0].place_lon,
+                pair[1].place_lat,
+                pair[1].place_lon,
+            )
+        }))
+        .collect()
+}
+
+// How far a chain travels, and where
+#[derive(Debug, Clone)]
+pub struct ChainStats {
+    // From the previous place, the first is 0
+    pub hop_m: Vec<f64>,
+    // From the start of the chain
+    pub cumulative_m: Vec<f64>,
+    pub total_m: f64,
+    // [min_lon, min_lat, max_lon, max_lat], like GeoJSON
+    pub bbox: [f64; 4],
+    // Of the places which have a population, None if none do
+    pub total_population: Option<u64>,
+    // The oldest & newest place_timestamp/boundary_timestamp, None if there are none
+    pub oldest_edit: Option<i64>,
+    pub newest_edit: Option<i64>,
+}
+
+impl ChainStats {
+    pub fn new(chain: &[&Record]) -> ChainStats {
+        let hop_m = hop_distances_m(chain);
+        let cumulative_m: Vec<f64> = hop_m
+            .iter()
+            .scan(0., |total, dist| {
+                *total += dist;
+                Some(*total)
+            })
+            .collect();
+        let total_m = cumulative_m.last().copied().unwrap_or(0.);
+        let mut bbox = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+        for r in chain {
+            bbox[0] = bbox[0].min(r.place_lon);
+            bbox[1] = bbox[1].min(r.place_lat);
+            bbox[2] = bbox[2].max(r.place_lon);
+            bbox[3] = bbox[3].max(r.place_lat);
+        }
+        let populations: Vec<u64> = chain.iter().filter_map(|r| r.place_population).collect();
+        let total_population = (!populations.is_empty()).then(|| populations.iter().sum());
+        let edits = || {
+            chain
+                .iter()
+                .flat_map(|r| [r.place_timestamp, r.boundary_timestamp])
+                .flatten()
+        };
+        ChainStats {
+            hop_m,
+            cumulative_m,
+            total_m,
+            bbox,
+            total_population,
+            oldest_edit: edits().min(),
+            newest_edit: edits().max(),
+        }
+    }
+
+    // None if there are no timestamps
+    pub fn freshness(&self) -> Option<Freshness> {
+        Some(freshness::freshness(self.oldest_edit?, self.newest_edit?))
+    }
+
+    // e.g. "-9.5,36.0,3.3,43.8"
+    pub fn bbox_str(&self) -> String {
+        self.bbox
+            .iter()
+            .map(|c| format!("{:.4}", c))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+// One place in a chain, and how far it is from the previous one & the start
+#[derive(Debug, Clone, Copy)]
+pub struct ChainHop<'a> {
+    // From 0
+    pub position: usize,
+    pub record: &'a Record,
+    pub hop_m: f64,
+    pub cumulative_m: f64,
+}
+
+// It derefs to the records, so `chain.len()`, `chain[0]`, `chain.iter()` etc. work as they did
+// on a `Vec<&Record>`
+#[derive(Debug, Clone)]
+pub struct Chain<'a> {
+    records: Vec<&'a Record>,
+    stats: ChainStats,
+}
+
+impl<'a> Chain<'a> {
+    pub fn new(records: Vec<&'a Record>) -> Chain<'a> {
+        let stats = ChainStats::new(&records);
+        Chain { records, stats }
+    }
+
+    pub fn records(&self) -> &[&'a Record] {
+        &self.records
+    }
+
+    pub fn stats(&self) -> &ChainStats {
+        &self.stats
+    }
+
+    pub fn total_distance_m(&self) -> f64 {
+        self.stats.total_m
+    }
+
+    // [min_lon, min_lat, max_lon, max_lat], like GeoJSON
+    pub fn bounding_box(&self) -> [f64; 4] {
+        self.stats.bbox
+    }
+
+    // The countries the places are in, in the order the chain goes through them, each once
+    pub fn countries(&self) -> Vec<&'a str> {
+        let mut countries: Vec<&str> = Vec::new();
+        for code in self
+            .records
+            .iter()
+            .filter_map(|r| r.country_code.as_deref())
+        {
+            if !countries.contains(&code) {
+                countries.push(code);
+            }
+        }
+        countries
+    }
+
+    pub fn iter_hops(&self) -> impl Iterator<Item = ChainHop<'a>> + '_ {
+        self.records
+            .iter()
+            .enumerate()
+            .map(move |(position, record)| ChainHop {
+                position,
+                record,
+                hop_m: self.stats.hop_m[position],
+                cumulative_m: self.stats.cumulative_m[position],
+            })
+    }
+
+    // A FeatureCollection of this chain, like one chain of --format geojson
+    pub fn to_geojson(&self, chain_idx: usize) -> Value {
+        obj(vec![
+            ("type", "FeatureCollection".into()),
+            (
+                "features",
+                Value::Array(geojson::chain_features(chain_idx, self)),
+            ),
+        ])
+    }
+}
+
+impl<'a> Deref for Chain<'a> {
+    type Target = [&'a Record];
+
+    fn deref(&self) -> &[&'a Record] {
+        &self.records
+    }
+}
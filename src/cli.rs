@@ -0,0 +1,777 @@
+// The command line's run: read the inputs, filter, search & write the results, or one of the
+// other commands. `main` only sets up the logging & exits with the status.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use separator::Separatable;
+
+use anyhow::{Context, Result};
+
+use crate::chain::Chain;
+use crate::graph::GraphFormat;
+use crate::options::{Command, Options};
+use crate::record::{OsmId, Record};
+use crate::{
+    bench, completions, continents, diff, distance, dry_run, exit, explain, extend, fixture,
+    freshness, graph, incremental, input, json, manifest, metrics, missing_links, notify, output,
+    prepare, progress, qa, query, record, repl, results, revalidate, scoring, serve, signals,
+    starts, stats, status_file, sweep, timeline, verify, watch,
+};
+
+pub fn run(options: &Options, started: std::time::Instant) -> Result<()> {
+    if let Some(shell) = options.completions_shell {
+        let mut stdout = std::io::stdout().lock();
+        completions::write(shell, &mut stdout)?;
+        return Ok(());
+    }
+    distance::set_model(options.distance_model);
+    info!(
+        "{} version {} Affero GPL source code: {}",
+        option_env!("CARGO_PKG_NAME").unwrap_or("NAME NOT SET"),
+        option_env!("CARGO_PKG_VERSION").unwrap_or("VERSION NOT SET"),
+        option_env!("CARGO_PKG_REPOSITORY").unwrap_or("SOURCE CODE REPO NOT SET"),
+    );
+
+    if let Some(dir) = &options.watch {
+        return watch::run(options, dir);
+    }
+    if options.command == Command::GenFixture {
+        return fixture::generate(
+            &options.output_filename,
+            options.fixture_places,
+            options.fixture_chain_depth,
+            options.fixture_seed,
+        );
+    }
+    if options.command == Command::Bench {
+        return bench::run(options);
+    }
+    if options.command == Command::Sweep {
+        return sweep::run(options);
+    }
+    if let Some(addr) = &options.metrics_addr {
+        metrics::serve(addr)?;
+        info!("Serving Prometheus metrics on http://{}/metrics", addr);
+    }
+    let mut run_stats = stats::RunStats::new();
+    let continents = options
+        .continents
+        .as_deref()
+        .map(continents::Continents::read)
+        .transpose()?;
+    let output_filename = &options.output_filename;
+
+    // Each input is searched separately
+    if options.command == Command::Timeline {
+        return timeline::run(options);
+    }
+    // The inputs are results, not the usual records
+    if options.command == Command::Diff {
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(output_filename)
+                .with_context(|| format!("Could not create {}", output_filename))?,
+        );
+        diff::write(
+            &options.input_filenames[0],
+            &options.input_filenames[1],
+            &mut file,
+        )?;
+        std::io::Write::flush(&mut file)?;
+        info!("Wrote the changes to {}", output_filename);
+        return Ok(());
+    }
+    if options.command == Command::Query {
+        let query = query::Query {
+            name: options.query_name.clone(),
+            osm_id: options.query_osm,
+        };
+        let all_chains = options
+            .input_filenames
+            .iter()
+            .map(|filename| results::read(filename))
+            .collect::<Result<Vec<_>>>()?;
+        let matches: Vec<_> = options
+            .input_filenames
+            .iter()
+            .zip(all_chains.iter())
+            .flat_map(|(filename, chains)| query.find(filename, chains))
+            .collect();
+        query::write_csv(output_filename, &matches)?;
+        info!(
+            "{} of {} chains go through it. Wrote them to {}",
+            matches.len().separated_string(),
+            all_chains
+                .iter()
+                .map(Vec::len)
+                .sum::<usize>()
+                .separated_string(),
+            output_filename
+        );
+        return Ok(());
+    }
+    if options.command == Command::Serve {
+        if let Some(old_results) = &options.old_results {
+            return serve::serve(&options.listen, results::read(old_results)?);
+        }
+    }
+    if options.command == Command::Verify {
+        let (num_checked, problems) = verify::verify(&options.input_filenames)?;
+        verify::write_csv(output_filename, &problems)?;
+        info!(
+            "{} of {} places & boundaries have changed in OSM. Wrote them to {}",
+            problems
+                .iter()
+                .map(|p| p.osm_id)
+                .collect::<HashSet<_>>()
+                .len()
+                .separated_string(),
+            num_checked.separated_string(),
+            output_filename
+        );
+        return Ok(());
+    }
+
+    let manifest = manifest::Manifest::start(&options.input_filenames);
+    let prepare::Inputs {
+        mut points_in_boundary,
+        num_records,
+        num_duplicates,
+        num_wrong_boundary_type,
+        unknown_place_tags,
+        unknown_tag_examples,
+        parse_errors,
+        empty_names,
+    } = prepare::read(options)?;
+    if parse_errors.total() > 0 {
+        warn!(
+            "Skipped {} rows which couldn't be parsed: {}",
+            parse_errors.total().separated_string(),
+            parse_errors
+                .counts
+                .iter()
+                .map(|(kind, count)| format!("{} {}", kind, count.separated_string()))
+                .collect::<Vec<String>>()
+                .join(", "),
+        );
+    }
+    if let Some(errors_report) = &options.errors_report {
+        info!("Writing unparseable rows to {}", errors_report);
+        parse_errors.write_report(errors_report)?;
+    }
+
+    // Some boundaries have no admin_level, a non-numeric one, or several ("7;8")
+    let mut boundaries_without_admin_level = HashSet::new();
+    let mut boundaries_with_many_admin_levels = HashSet::new();
+    for rec in points_in_boundary.values().flat_map(|recs| recs.iter()) {
+        match &rec.boundary_admin_level {
+            None => {
+                boundaries_without_admin_level.insert(rec.boundary_key());
+            }
+            Some(levels) if levels.len() > 1 => {
+                boundaries_with_many_admin_levels.insert(rec.boundary_key());
+            }
+            Some(_) => {}
+        }
+    }
+    if !boundaries_without_admin_level.is_empty() || !boundaries_with_many_admin_levels.is_empty() {
+        info!(
+            "{} boundaries have an unusable admin_level, {} have several admin_levels (the most specific is used)",
+            boundaries_without_admin_level.len().separated_string(),
+            boundaries_with_many_admin_levels.len().separated_string(),
+        );
+    }
+
+    if num_wrong_boundary_type > 0 {
+        info!(
+            "Skipped {} records with an unwanted boundary type",
+            num_wrong_boundary_type.separated_string()
+        );
+    }
+    if num_duplicates > 0 {
+        info!(
+            "Skipped {} duplicate place/boundary pairs",
+            num_duplicates.separated_string()
+        );
+    }
+    if num_records == 0 {
+        return Err(anyhow::anyhow!(
+            "No usable rows in {}: every row was unparseable, had no name, or had a place or boundary type which isn't used",
+            options.input_filenames.join(", ")
+        ))
+        .context(exit::Status::BadInput);
+    }
+    let num_unknown: usize = unknown_place_tags.values().sum();
+    let top_unknown = unknown_place_tags
+        .iter()
+        .fold(Vec::new(), |mut totals, el| {
+            totals.push((el.1, el.0));
+            totals.sort_by_key(|x| -(*x.0 as isize));
+            totals.truncate(5);
+            totals
+        });
+    info!(
+        "There are {} name/contain pairs ({} unknown place tags {}% of total. Top unknowns: {})",
+        num_records.separated_string(),
+        num_unknown.separated_string(),
+        (num_unknown * 100) / num_records,
+        top_unknown
+            .iter()
+            .map(|(count, tag)| format!(
+                "{} {} ({}%)",
+                tag,
+                count.separated_string(),
+                *count * 100 / num_unknown
+            ))
+            .collect::<Vec<String>>()
+            .join(", "),
+    );
+    if let Some(report) = &options.unknown_tags_report {
+        info!("Writing unknown place tags to {}", report);
+        input::write_unknown_tags_report(report, &unknown_place_tags, &unknown_tag_examples)?;
+    }
+
+    run_stats.num_records = num_records;
+    run_stats.num_duplicates = num_duplicates;
+    run_stats.num_wrong_boundary_type = num_wrong_boundary_type;
+    run_stats.num_parse_errors = parse_errors.total();
+    run_stats.num_unknown_place_tags = num_unknown;
+
+    if options.command == Command::Revalidate {
+        let old_results = options.old_results.as_deref().unwrap();
+        let old_chains = results::read(old_results)?;
+        let records: HashMap<(OsmId, OsmId), &Record> = points_in_boundary
+            .values()
+            .flat_map(|recs| recs.iter())
+            .map(|r| ((r.place_key(), r.boundary_key()), r))
+            .collect();
+        let breaks: Vec<_> = old_chains
+            .iter()
+            .map(|chain| revalidate::check(chain, &records))
+            .collect();
+        revalidate::write_csv(output_filename, &old_chains, &breaks)?;
+        info!(
+            "{} of {} chains from {} still hold. Wrote the report to {}",
+            breaks
+                .iter()
+                .filter(|b| b.is_none())
+                .count()
+                .separated_string(),
+            old_chains.len().separated_string(),
+            old_results,
+            output_filename
+        );
+        return Ok(());
+    }
+    if options.command == Command::Qa {
+        let suspects = qa::find_suspects(&points_in_boundary, &empty_names);
+        qa::write_csv(output_filename, &suspects)?;
+        info!(
+            "Wrote {} suspect records to {}",
+            suspects.len().separated_string(),
+            output_filename
+        );
+        return Ok(());
+    }
+
+    let prepare::Filtered {
+        self_contained,
+        duplicates,
+    } = prepare::filter(&mut points_in_boundary, options, &mut run_stats)?;
+
+    if let Some(filename) = &options.maproulette {
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(filename)
+                .with_context(|| format!("Could not create {}", filename))?,
+        );
+        let num_tasks = output::maproulette::write(&self_contained, &duplicates, &mut file)?;
+        std::io::Write::flush(&mut file)?;
+        info!(
+            "Wrote {} MapRoulette tasks to {}",
+            num_tasks.separated_string(),
+            filename
+        );
+    }
+    if let Some(filename) = &options.records_parquet {
+        info!("Writing records to {}", filename);
+        let mut file = std::io::BufWriter::new(std::fs::File::create(filename)?);
+        output::parquet::write_records(
+            points_in_boundary.values().flat_map(|recs| recs.iter()),
+            &mut file,
+        )?;
+        std::io::Write::flush(&mut file)?;
+    }
+
+    let records: Vec<&Record> = points_in_boundary
+        .values()
+        .flat_map(|recs| recs.iter())
+        .collect();
+    if options.scoring == scoring::Scoring::Population
+        && !records.iter().any(|r| r.place_population.is_some())
+    {
+        warn!("No places have a population, so --score population doesn't rank the chains. The input needs a place_population column");
+    }
+    freshness::set_reference(records.iter().copied());
+
+    if options.command == Command::GraphExport {
+        let graph = graph::NameGraph::new(&records);
+        let format = options
+            .graph_format
+            .unwrap_or_else(|| GraphFormat::from_filename(output_filename));
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(output_filename)
+                .with_context(|| format!("Could not create {}", output_filename))?,
+        );
+        let num_edges = output::graph::write(&graph, format, &mut file)?;
+        std::io::Write::flush(&mut file)?;
+        info!(
+            "Wrote {} edges to {}",
+            num_edges.separated_string(),
+            output_filename
+        );
+        if format == GraphFormat::MatrixMarket {
+            let filename = format!("{}.labels", output_filename);
+            let mut file = std::io::BufWriter::new(
+                std::fs::File::create(&filename)
+                    .with_context(|| format!("Could not create {}", filename))?,
+            );
+            output::graph::write_labels(&graph, &mut file)?;
+            std::io::Write::flush(&mut file)?;
+            info!("Wrote the names of the rows & columns to {}", filename);
+        }
+        return Ok(());
+    }
+    if options.command == Command::Analyze {
+        let analysis = graph::NameGraph::new(&records).analyze();
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(output_filename)
+                .with_context(|| format!("Could not create {}", output_filename))?,
+        );
+        output::analysis::write(&analysis, &mut file)?;
+        std::io::Write::flush(&mut file)?;
+        info!(
+            "{} connected components, no chain can be longer than {}. Wrote {}",
+            analysis.components.len().separated_string(),
+            analysis.components.first().map_or(0, |c| c.chain_bound),
+            output_filename
+        );
+        return Ok(());
+    }
+
+    if options.dry_run {
+        return dry_run::report(&records, options, &mut run_stats);
+    }
+
+    if options.command == Command::Repl {
+        if let Some(old_results) = &options.old_results {
+            return repl::run(&records, &results::read(old_results)?);
+        }
+    }
+    if options.command == Command::Explain {
+        let chain: Vec<results::Hop> = match (&options.old_results, options.chain_id) {
+            (Some(old_results), Some(chain_id)) => {
+                let mut chains = results::read(old_results)?;
+                if chain_id > chains.len() {
+                    anyhow::bail!("{} only has {} chains", old_results, chains.len());
+                }
+                chains.swap_remove(chain_id - 1)
+            }
+            (Some(old_results), None) => {
+                let start = options.start.unwrap();
+                results::read(old_results)?
+                    .into_iter()
+                    .find(|chain| chain[0].place == start)
+                    .with_context(|| {
+                        format!(
+                            "No chain in {} starts at {}/{}",
+                            old_results,
+                            record::osm_type_name(start.0),
+                            start.1
+                        )
+                    })?
+            }
+            (None, _) => {
+                let start = options.start.unwrap();
+                let starts: Vec<&Record> = records
+                    .iter()
+                    .filter(|r| r.place_key() == start)
+                    .copied()
+                    .collect();
+                let (found, _) = options.chain_search(&records, false).starts(&starts).run();
+                found
+                    .into_values()
+                    .max_by_key(|chain| chain.len())
+                    .with_context(|| {
+                        format!(
+                            "{}/{} isn't a place in any boundary",
+                            record::osm_type_name(start.0),
+                            start.1
+                        )
+                    })?
+                    .into_iter()
+                    .map(results::Hop::from)
+                    .collect()
+            }
+        };
+        let candidates = explain::candidates(
+            &chain,
+            &options.input_filenames,
+            &options.csv_format,
+            &options.boundary_types,
+            &explain::Filtered {
+                self_contained: &self_contained,
+                duplicates: &duplicates,
+                records: &records,
+                min_name_length: options.min_name_length,
+                junk_names_removed: options.remove_junk_names,
+            },
+        )?;
+        let mut explanation = Vec::new();
+        explain::write(&chain, &candidates, &mut explanation)?;
+        std::io::Write::write_all(&mut std::io::stdout(), &explanation)?;
+        std::fs::write(output_filename, &explanation)
+            .with_context(|| format!("Could not create {}", output_filename))?;
+        return Ok(());
+    }
+    drop(self_contained);
+    drop(duplicates);
+
+    let stop = signals::install_stop_handler();
+    // Stop the search after --max-runtime, just as if Ctrl-C was pressed
+    let out_of_time = Arc::new(AtomicBool::new(false));
+    if let Some(max_runtime) = options.max_runtime {
+        let out_of_time = out_of_time.clone();
+        let remaining = max_runtime.saturating_sub(started.elapsed());
+        std::thread::spawn(move || {
+            std::thread::sleep(remaining);
+            out_of_time.store(true, Ordering::SeqCst);
+            stop.store(true, Ordering::SeqCst);
+        });
+    }
+    signals::install_status_handler(options.status_report.clone());
+    if options.dashboard {
+        progress::use_dashboard();
+    }
+    if let Some(filename) = &options.status_file {
+        status_file::configure(
+            filename,
+            std::time::Duration::from_secs(options.status_interval),
+            json::obj(vec![
+                (
+                    "arguments",
+                    std::env::args().skip(1).collect::<Vec<_>>().into(),
+                ),
+                ("inputs", options.input_filenames.clone().into()),
+                ("output", output_filename.into()),
+            ]),
+        );
+    }
+
+    let mut stream = match &options.stream {
+        Some(filename) => Some(output::ndjson::Stream::create(filename)?),
+        None => None,
+    };
+    let mut stream_chain = |chain: &[&Record]| {
+        if let Some(stream) = &mut stream {
+            stream.chain(chain);
+        }
+    };
+
+    let start_filter = starts::StartFilter::new(options)?;
+    let filtered_starts = start_filter.as_ref().map(|f| f.starts(&records));
+
+    phase!("Starting main loop calculation. Press Ctrl-C to stop going further (twice to stop straight away)");
+    let search_started = std::time::Instant::now();
+    let (finished_chains, search_stats) = match &options.previous_results {
+        _ if options.command == Command::Extend => {
+            let old_results = options.old_results.as_deref().unwrap();
+            let mut prefixes = extend::prefixes(&results::read(old_results)?, &records);
+            if let Some(start_filter) = &start_filter {
+                prefixes.retain(|chain| start_filter.allows(chain[0]));
+            }
+            info!(
+                "Trying to extend {} chains from {}",
+                prefixes.len().separated_string(),
+                old_results
+            );
+            let (found, search_stats) = options
+                .chain_search(&records, true)
+                .prefixes(&prefixes)
+                .stop_when(stop)
+                .run_as_found(&mut stream_chain);
+            extend::report(&prefixes, &found);
+            (found, search_stats)
+        }
+        None => {
+            let mut search = options.chain_search(&records, true).stop_when(stop);
+            if let Some(starts) = &filtered_starts {
+                search = search.starts(starts);
+            }
+            search.run_as_found(&mut stream_chain)
+        }
+        Some(previous_results) => {
+            info!(
+                "Finding what has changed since {}",
+                options.previous_inputs.join(", ")
+            );
+            let changed = incremental::changed_names(
+                &options.previous_inputs,
+                &options.input_filenames,
+                &options.csv_format,
+            )?;
+            let affected = incremental::affected(&records, &changed);
+            let mut reused =
+                incremental::reuse_chains(&results::read(previous_results)?, &records, &affected);
+            if let Some(start_filter) = &start_filter {
+                reused.retain(|start, _| start_filter.allows(start));
+            }
+            let starts: Vec<&Record> = filtered_starts
+                .as_deref()
+                .unwrap_or(&records)
+                .iter()
+                .filter(|r| !reused.contains_key(*r))
+                .copied()
+                .collect();
+            info!(
+                "{} names have changed, affecting {} records. Re-using {} chains from {}, searching from the other {} records",
+                changed.len().separated_string(),
+                affected.len().separated_string(),
+                reused.len().separated_string(),
+                previous_results,
+                starts.len().separated_string()
+            );
+            for chain in reused.values().filter(|chain| chain.len() > 1) {
+                stream_chain(chain);
+            }
+            let (found, search_stats) = options
+                .chain_search(&records, true)
+                .starts(&starts)
+                .stop_when(stop)
+                .run_as_found(&mut stream_chain);
+            reused.extend(found);
+            (reused, search_stats)
+        }
+    };
+    if let Some(stream) = stream {
+        stream.finish();
+    }
+    if out_of_time.load(Ordering::SeqCst) {
+        warn!("Reached the --max-runtime, so the search was stopped early");
+    } else if let Some(signal) = signals::stop_signal_name() {
+        warn!("Stopped by {}, writing out the chains found so far", signal);
+    }
+    if search_stats.interrupted {
+        exit::partial(exit::Status::Interrupted);
+    }
+    if search_stats
+        .memory_cleanups
+        .iter()
+        .any(|c| c.removed_by_failsafe > 0)
+    {
+        exit::partial(exit::Status::MemoryLimited);
+    }
+    run_stats.search = search_stats;
+    run_stats.search_s = search_started.elapsed().as_secs_f64();
+    (run_stats.searched_places, run_stats.searched_records) = stats::count(&points_in_boundary);
+
+    let totals_per_len = finished_chains.iter().fold(
+        BTreeMap::new() as BTreeMap<usize, usize>,
+        |mut tot, (_, chain)| {
+            *tot.entry(chain.len()).or_default() += 1;
+            tot
+        },
+    );
+    for (len, total) in totals_per_len.iter() {
+        summary!("{:>6}: {:>10}", len, total.separated_string());
+    }
+    run_stats.totals_per_len = totals_per_len.clone();
+    let summary = output::Summary { totals_per_len };
+
+    let total_finished_chains = finished_chains.len();
+    phase!(
+        "Have {} chains. Writing to {}",
+        total_finished_chains.separated_string(),
+        output_filename
+    );
+    // Print out chains (except the 1 element chains)
+    let mut chains = finished_chains
+        .into_values()
+        .filter(|chain| chain.len() > 1)
+        .map(Chain::new)
+        .collect::<Vec<_>>();
+    debug!("{} chains are longer than 1 place", chains.len());
+    if options.deterministic {
+        // Chains of the same length by their OSM ids, rather than in whatever order the HashMap
+        // had them, so the same input always gives the same output
+        chains.sort_by(|a, b| {
+            b.len()
+                .cmp(&a.len())
+                .then_with(|| a.records().cmp(b.records()))
+        });
+    } else {
+        chains.sort_by_key(|ch| -(ch.len() as isize));
+    }
+    if options.stats_out.is_some() || options.output_dir.is_some() {
+        run_stats.add_chains(&chains);
+    }
+    notify::chains_found(&chains);
+
+    if let Some(continents) = &continents {
+        continents::print_report(&chains, continents);
+    }
+
+    if let Some(filename) = &options.endpoints {
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(filename)
+                .with_context(|| format!("Could not create {}", filename))?,
+        );
+        if filename.ends_with(".geojson") {
+            output::endpoints::write_geojson(&chains, &mut file)?;
+        } else {
+            output::endpoints::write_csv(&chains, &mut file)?;
+        }
+        std::io::Write::flush(&mut file)?;
+        info!(
+            "Wrote the start & end of {} chains to {}",
+            chains.len().separated_string(),
+            filename
+        );
+    }
+
+    if let Some(filename) = &options.missing_links {
+        let links = missing_links::find(&chains, &records);
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(filename)
+                .with_context(|| format!("Could not create {}", filename))?,
+        );
+        missing_links::write(&links, &mut file)?;
+        std::io::Write::flush(&mut file)?;
+        info!("Wrote {} ways to join chains to {}", links.len(), filename);
+    }
+
+    if options.hubs_report.is_some() || options.name_stats.is_some() {
+        let mut graph = graph::NameGraph::new(&records);
+        graph.add_chains(&chains);
+        if let Some(filename) = &options.hubs_report {
+            let mut file = std::io::BufWriter::new(
+                std::fs::File::create(filename)
+                    .with_context(|| format!("Could not create {}", filename))?,
+            );
+            output::hubs::write(&graph, &mut file)?;
+            std::io::Write::flush(&mut file)?;
+            info!("Wrote the most connective names to {}", filename);
+        }
+        if let Some(filename) = &options.name_stats {
+            let mut file = std::io::BufWriter::new(
+                std::fs::File::create(filename)
+                    .with_context(|| format!("Could not create {}", filename))?,
+            );
+            output::hubs::write_csv(&graph, &mut file)?;
+            std::io::Write::flush(&mut file)?;
+            info!("Wrote the statistics for each name to {}", filename);
+        }
+    }
+
+    chains.truncate(options.max_chains());
+    let num_written_out = chains.len();
+    output::write_chains(&chains, &summary, options)?;
+    manifest.write(options)?;
+    if let Some(filename) = &options.overpass_checks {
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(filename)
+                .with_context(|| format!("Could not create {}", filename))?,
+        );
+        output::overpass_checks::write(&chains, &mut file)?;
+        std::io::Write::flush(&mut file)?;
+        info!("Wrote Overpass queries to check each chain to {}", filename);
+    }
+
+    summary!(
+        "Wrote out {} of {} ({:.1}%)",
+        num_written_out.separated_string(),
+        total_finished_chains.separated_string(),
+        (num_written_out as f64 / total_finished_chains as f64) * 100.
+    );
+    if let Some(filename) = &options.per_country {
+        info!("Finding the longest chain in each country");
+        let mut records_per_country: BTreeMap<&str, Vec<&Record>> = BTreeMap::new();
+        for rec in records.iter() {
+            if let Some(code) = &rec.country_code {
+                records_per_country.entry(code).or_default().push(rec);
+            }
+        }
+        let mut country_chains = Vec::new();
+        for (country, records) in records_per_country {
+            let longest = options
+                .chain_search(&records, false)
+                .stop_when(stop)
+                .run()
+                .0
+                .into_values()
+                .filter(|chain| chain.len() > 1)
+                .map(Chain::new)
+                .max_by(|a, b| {
+                    let key =
+                        |chain: &Chain| (chain.len(), chain.total_distance_m().round() as u64);
+                    key(a).cmp(&key(b)).then_with(|| {
+                        if options.deterministic {
+                            b.records().cmp(a.records())
+                        } else {
+                            std::cmp::Ordering::Equal
+                        }
+                    })
+                });
+            if let Some(chain) = longest {
+                country_chains.push((country, chain));
+            }
+        }
+        let mut file = std::io::BufWriter::new(
+            std::fs::File::create(filename)
+                .with_context(|| format!("Could not create {}", filename))?,
+        );
+        output::per_country::write(&country_chains, &options.messages, &mut file)?;
+        std::io::Write::flush(&mut file)?;
+        info!(
+            "Wrote the longest chain of {} countries to {}",
+            country_chains.len(),
+            filename
+        );
+    }
+
+    if distance::num_not_converged() > 0 {
+        info!(
+            "Vincenty's formula didn't converge {} times, for places on nearly opposite sides of the world, so those distances were worked out another way",
+            distance::num_not_converged().separated_string()
+        );
+    }
+    if let Some(filename) = &options.stats_out {
+        run_stats.write(filename)?;
+        info!("Wrote the statistics of this run to {}", filename);
+    }
+    if let Some(dir) = &options.output_dir {
+        let filename = std::path::Path::new(dir).join("stats.json");
+        run_stats.write(&filename.to_string_lossy())?;
+    }
+
+    if options.command == Command::Serve {
+        return serve::serve(
+            &options.listen,
+            chains
+                .iter()
+                .map(|chain| chain.iter().copied().map(results::Hop::from).collect())
+                .collect(),
+        );
+    }
+
+    if options.command == Command::Repl {
+        let chains: Vec<Vec<results::Hop>> = chains
+            .iter()
+            .map(|chain| chain.iter().copied().map(results::Hop::from).collect())
+            .collect();
+        return repl::run(&records, &chains);
+    }
+
+    info!("Finished");
+    Ok(())
+}
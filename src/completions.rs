@@ -0,0 +1,343 @@
+// `x-in-y completions SHELL` prints a completion script for bash, zsh or fish, or the man page,
+// made from the same list of commands & flags as --help, so they're never out of date.
+
+use std::io::Write;
+
+use anyhow::{bail, Result};
+
+use crate::options::{Flag, COMMANDS, FLAGS};
+
+const NAME: &str = "x-in-y";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    // The man page
+    Man,
+}
+
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish", "man"];
+
+impl std::str::FromStr for Shell {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Shell> {
+        Ok(match s {
+            "bash" => Shell::Bash,
+            "zsh" => Shell::Zsh,
+            "fish" => Shell::Fish,
+            "man" => Shell::Man,
+            x => bail!(
+                "Can't make completions for {:?}, only bash, zsh, fish or man",
+                x
+            ),
+        })
+    }
+}
+
+// The flags which only take some values
+const FLAG_VALUES: &[(&str, &[&str])] = &[
+    (
+        "--format",
+        &[
+            "text",
+            "markdown",
+            "html",
+            "geojson",
+            "html-map",
+            "kml",
+            "gpx",
+            "sqlite",
+            "ndjson",
+            "csv",
+            "parquet",
+            "template",
+            "story",
+            "thread",
+            "osmwiki",
+            "umap",
+            "atom",
+            "quiz",
+            "tree",
+            "json-bundle",
+        ],
+    ),
+    ("--graph-format", &["graphml", "dot", "mtx"]),
+    (
+        "--score",
+        &[
+            "distance",
+            "compact",
+            "none",
+            "place-type",
+            "antipodal",
+            "population",
+        ],
+    ),
+    ("--distinct-place-types", &["consecutive", "all"]),
+    ("--failsafe", &["drop-shortest", "off"]),
+    ("--distance-model", &["sphere", "ellipsoid"]),
+    ("--color", &["auto", "always", "never"]),
+    ("--output-lang", &["en", "de", "fr", "es"]),
+];
+
+// What can come after a flag
+enum Value {
+    None,
+    Choice(&'static [&'static str]),
+    File,
+    // Anything, e.g. a number
+    Other,
+}
+
+fn value(flag: &Flag) -> Value {
+    if let Some((_, values)) = FLAG_VALUES.iter().find(|(name, _)| *name == flag.name) {
+        return Value::Choice(values);
+    }
+    match flag.value {
+        None => Value::None,
+        Some("FILE" | "DIR" | "GEOJSON") => Value::File,
+        Some(_) => Value::Other,
+    }
+}
+
+// e.g. -v for "(-v) Also print debugging details"
+fn short(flag: &Flag) -> Option<char> {
+    let rest = flag.help.strip_prefix("(-")?;
+    let c = rest.chars().next()?;
+    rest[c.len_utf8()..].starts_with(')').then_some(c)
+}
+
+// The help without the short flag, up to the end of the first sentence, for the menus
+fn summary(help: &str) -> &str {
+    let help = match help.strip_prefix('(') {
+        Some(rest) if rest.starts_with('-') => rest.split_once(") ").map_or(help, |(_, h)| h),
+        _ => help,
+    };
+    help.split_once(". ").map_or(help, |(first, _)| first)
+}
+
+fn bash(output: &mut impl Write) -> Result<()> {
+    let commands: Vec<&str> = COMMANDS.iter().map(|(_, name, _)| *name).collect();
+    let flags: Vec<&str> = FLAGS.iter().map(|f| f.name).collect();
+    writeln!(output, "# bash completion for {}", NAME)?;
+    writeln!(output, "_x_in_y() {{")?;
+    writeln!(output, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+    writeln!(output, "    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"")?;
+    writeln!(output, "    case \"$prev\" in")?;
+    let mut files = Vec::new();
+    let mut others = Vec::new();
+    for flag in FLAGS {
+        match value(flag) {
+            Value::Choice(values) => writeln!(
+                output,
+                "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;",
+                flag.name,
+                values.join(" ")
+            )?,
+            Value::File => files.push(flag.name),
+            Value::Other => others.push(flag.name),
+            Value::None => {}
+        }
+    }
+    writeln!(
+        output,
+        "        {}) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;",
+        files.join("|")
+    )?;
+    writeln!(
+        output,
+        "        {}) COMPREPLY=(); return ;;",
+        others.join("|")
+    )?;
+    writeln!(output, "    esac")?;
+    writeln!(
+        output,
+        "    if [[ $COMP_CWORD -eq 2 && \"${{COMP_WORDS[1]}}\" == completions ]]; then"
+    )?;
+    writeln!(
+        output,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        SHELLS.join(" ")
+    )?;
+    writeln!(output, "    elif [[ \"$cur\" == -* ]]; then")?;
+    writeln!(
+        output,
+        "        COMPREPLY=($(compgen -W \"{} -v -q\" -- \"$cur\"))",
+        flags.join(" ")
+    )?;
+    writeln!(output, "    elif [[ $COMP_CWORD -eq 1 ]]; then")?;
+    writeln!(
+        output,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\") $(compgen -f -- \"$cur\"))",
+        commands.join(" ")
+    )?;
+    writeln!(output, "    else")?;
+    writeln!(output, "        COMPREPLY=($(compgen -f -- \"$cur\"))")?;
+    writeln!(output, "    fi")?;
+    writeln!(output, "}}")?;
+    writeln!(output, "complete -o filenames -F _x_in_y {}", NAME)?;
+    Ok(())
+}
+
+// In a zsh single quoted string
+fn zsh_quote(s: &str) -> String {
+    s.replace('\'', "'\\''")
+}
+
+fn zsh(output: &mut impl Write) -> Result<()> {
+    writeln!(output, "#compdef {}", NAME)?;
+    writeln!(output)?;
+    writeln!(output, "_x_in_y_first() {{")?;
+    writeln!(output, "    local -a commands")?;
+    writeln!(output, "    commands=(")?;
+    for (_, name, help) in COMMANDS {
+        writeln!(output, "        '{}:{}'", name, zsh_quote(summary(help)))?;
+    }
+    writeln!(output, "    )")?;
+    writeln!(output, "    _describe -t commands command commands")?;
+    writeln!(output, "    _files")?;
+    writeln!(output, "}}")?;
+    writeln!(output)?;
+    writeln!(output, "_x_in_y() {{")?;
+    writeln!(output, "    if [[ $words[2] == completions ]]; then")?;
+    writeln!(
+        output,
+        "        _arguments '2:shell:({})'",
+        SHELLS.join(" ")
+    )?;
+    writeln!(output, "        return")?;
+    writeln!(output, "    fi")?;
+    writeln!(output, "    _arguments \\")?;
+    for flag in FLAGS {
+        let help = zsh_quote(&summary(flag.help).replace('[', "\\[").replace(']', "\\]"));
+        let spec = match value(flag) {
+            Value::None => String::new(),
+            Value::Choice(values) => format!(":{}:({})", flag.value.unwrap(), values.join(" ")),
+            Value::File => format!(":{}:_files", flag.value.unwrap()),
+            Value::Other => format!(":{}: ", flag.value.unwrap()),
+        };
+        // Brace expansion makes a spec for each name, so it's outside the quotes
+        let names = match short(flag) {
+            Some(c) => format!("'(-{c} {n})'{{-{c},{n}}}'", c = c, n = flag.name),
+            None => format!("'{}", flag.name),
+        };
+        writeln!(output, "        {}[{}]{}' \\", names, help, spec)?;
+    }
+    writeln!(output, "        '1: :_x_in_y_first' \\")?;
+    writeln!(output, "        '*:file:_files'")?;
+    writeln!(output, "}}")?;
+    writeln!(output)?;
+    writeln!(output, "_x_in_y \"$@\"")?;
+    Ok(())
+}
+
+// In a fish single quoted string
+fn fish_quote(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish(output: &mut impl Write) -> Result<()> {
+    writeln!(output, "# fish completion for {}", NAME)?;
+    for (_, name, help) in COMMANDS {
+        writeln!(
+            output,
+            "complete -c {} -n __fish_use_subcommand -a {} -d '{}'",
+            NAME,
+            name,
+            fish_quote(summary(help))
+        )?;
+    }
+    writeln!(
+        output,
+        "complete -c {} -n '__fish_seen_subcommand_from completions' -x -a '{}'",
+        NAME,
+        SHELLS.join(" ")
+    )?;
+    for flag in FLAGS {
+        let mut line = format!("complete -c {}", NAME);
+        if let Some(c) = short(flag) {
+            line.push_str(&format!(" -s {}", c));
+        }
+        line.push_str(&format!(" -l {}", &flag.name[2..]));
+        match value(flag) {
+            Value::None => {}
+            Value::Choice(values) => line.push_str(&format!(" -x -a '{}'", values.join(" "))),
+            Value::File => line.push_str(" -r -F"),
+            Value::Other => line.push_str(" -x"),
+        }
+        line.push_str(&format!(" -d '{}'", fish_quote(summary(flag.help))));
+        writeln!(output, "{}", line)?;
+    }
+    Ok(())
+}
+
+// Text in a roff line
+fn roff(s: &str) -> String {
+    let s = s.replace('\\', "\\e").replace('-', "\\-");
+    if s.starts_with('.') || s.starts_with('\'') {
+        format!("\\&{}", s)
+    } else {
+        s
+    }
+}
+
+fn man(output: &mut impl Write) -> Result<()> {
+    writeln!(
+        output,
+        ".TH {} 1 \"\" \"{} {}\"",
+        NAME.to_uppercase(),
+        NAME,
+        env!("CARGO_PKG_VERSION")
+    )?;
+    writeln!(output, ".SH NAME")?;
+    writeln!(
+        output,
+        "{} \\- find the longest chains of OpenStreetMap places which are in a boundary with the name of another place",
+        roff(NAME)
+    )?;
+    writeln!(output, ".SH SYNOPSIS")?;
+    writeln!(
+        output,
+        ".B {}\n[\\fICOMMAND\\fR] [\\fIOPTIONS\\fR] \\fIINPUT.csv\\fR... \\fIOUTPUT\\fR",
+        roff(NAME)
+    )?;
+    writeln!(output, ".SH DESCRIPTION")?;
+    writeln!(
+        output,
+        "Reads CSV files of places and the boundaries they are in (gzip, zstd, bzip2 or xz compressed, or not), and finds the longest chains where each place is in a boundary with the same name as the next place. Without a \\fICOMMAND\\fR it writes the longest chains to \\fIOUTPUT\\fR."
+    )?;
+    writeln!(output, ".SH COMMANDS")?;
+    for (_, name, help) in COMMANDS {
+        writeln!(output, ".TP\n.B {}\n{}", roff(name), roff(help))?;
+    }
+    writeln!(output, ".SH OPTIONS")?;
+    for flag in FLAGS {
+        let names = match short(flag) {
+            Some(c) => format!("\\fB\\-{}\\fR, \\fB{}\\fR", c, roff(flag.name)),
+            None => format!("\\fB{}\\fR", roff(flag.name)),
+        };
+        let value = flag
+            .value
+            .map(|v| format!(" \\fI{}\\fR", roff(v)))
+            .unwrap_or_default();
+        writeln!(output, ".TP\n{}{}\n{}", names, value, roff(flag.help))?;
+    }
+    writeln!(output, ".SH SIGNALS")?;
+    writeln!(
+        output,
+        "SIGINT (Ctrl\\-C) stops the search and writes out the chains found so far. SIGUSR1 prints a status report without stopping."
+    )?;
+    Ok(())
+}
+
+pub fn write(shell: Shell, output: &mut impl Write) -> Result<()> {
+    match shell {
+        Shell::Bash => bash(output),
+        Shell::Zsh => zsh(output),
+        Shell::Fish => fish(output),
+        Shell::Man => man(output),
+    }
+}
@@ -0,0 +1,71 @@
+// Rules about which places can follow which in a chain, from the command line. They're checked
+// as each chain is extended, next to the check that a place isn't in the chain already, so a
+// chain which breaks one ends there.
+
+use anyhow::{bail, Result};
+
+use crate::options::Options;
+use crate::record::Record;
+use crate::search::ChainSearch;
+
+// --distinct-place-types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistinctPlaceTypes {
+    // A place can't have the same place_type as the one before it
+    Consecutive,
+    // No 2 places in the chain can have the same place_type
+    All,
+}
+
+impl std::str::FromStr for DistinctPlaceTypes {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<DistinctPlaceTypes> {
+        Ok(match s {
+            "consecutive" => DistinctPlaceTypes::Consecutive,
+            "all" => DistinctPlaceTypes::All,
+            x => bail!(
+                "Unknown --distinct-place-types {:?}, should be consecutive or all",
+                x
+            ),
+        })
+    }
+}
+
+impl DistinctPlaceTypes {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DistinctPlaceTypes::Consecutive => "consecutive",
+            DistinctPlaceTypes::All => "all",
+        }
+    }
+}
+
+// Add the constraints the options ask for to the search
+pub fn apply<'s, 'a>(mut search: ChainSearch<'s, 'a>, options: &'s Options) -> ChainSearch<'s, 'a> {
+    match options.distinct_place_types {
+        None => {}
+        Some(DistinctPlaceTypes::Consecutive) => {
+            search =
+                search.constraint(|chain, rec| chain[chain.len() - 1].place_type != rec.place_type);
+        }
+        Some(DistinctPlaceTypes::All) => {
+            search = search
+                .constraint(|chain, rec| chain.iter().all(|r| r.place_type != rec.place_type));
+        }
+    }
+    // Places are given a country when they're read, and a place's boundaries are in its country
+    if let Some(country) = &options.within_country {
+        let in_country = move |r: &Record| r.country_code.as_ref() == Some(country);
+        search = search.constraint(move |chain, rec| in_country(chain[0]) && in_country(rec));
+    }
+    // The previous place's boundary is what links it to this place
+    if let Some(min) = options.boundary_admin_at_least {
+        search = search.constraint(move |chain, _rec| {
+            chain[chain.len() - 1]
+                .admin_level()
+                .is_some_and(|level| level >= min)
+        });
+    }
+    search
+}
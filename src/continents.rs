@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{bail, Context, Result};
+use separator::Separatable;
+
+use crate::chain::Chain;
+use crate::json::{self, Value};
+use crate::record::{OsmId, Record};
+
+// The feature properties which could have the continent's name, in order. Natural Earth's
+// admin_0 countries have CONTINENT.
+const NAME_PROPERTIES: &[&str] = &["CONTINENT", "continent", "REGION_UN", "name", "NAME"];
+
+// A list of rings, the first is the outside, the others are holes. (lon, lat)
+type Polygon = Vec<Vec<(f64, f64)>>;
+
+struct Region {
+    name: String,
+    // [min_lon, min_lat, max_lon, max_lat]
+    bbox: [f64; 4],
+    polygons: Vec<Polygon>,
+}
+
+pub struct Continents {
+    regions: Vec<Region>,
+}
+
+fn ring(value: &Value) -> Option<Vec<(f64, f64)>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|point| {
+            let point = point.as_array()?;
+            Some((point.first()?.as_f64()?, point.get(1)?.as_f64()?))
+        })
+        .collect()
+}
+
+fn polygon(value: &Value) -> Option<Polygon> {
+    value.as_array()?.iter().map(ring).collect()
+}
+
+fn ring_contains(ring: &[(f64, f64)], lon: f64, lat: f64) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+    for i in 0..ring.len() {
+        let (xi, yi) = ring[i];
+        let (xj, yj) = ring[j];
+        if (yi > lat) != (yj > lat) && lon < (xj - xi) * (lat - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+impl Region {
+    fn contains(&self, lat: f64, lon: f64) -> bool {
+        if lon < self.bbox[0] || lat < self.bbox[1] || lon > self.bbox[2] || lat > self.bbox[3] {
+            return false;
+        }
+        self.polygons.iter().any(|rings| {
+            ring_contains(&rings[0], lon, lat)
+                && !rings[1..].iter().any(|hole| ring_contains(hole, lon, lat))
+        })
+    }
+}
+
+impl Continents {
+    // A GeoJSON FeatureCollection of (Multi)Polygons, e.g. Natural Earth countries. Features with
+    // the same name are one continent.
+    pub fn read(filename: &str) -> Result<Continents> {
+        let contents = std::fs::read_to_string(filename)
+            .with_context(|| format!("Could not read {}", filename))?;
+        let geojson =
+            json::parse(&contents).with_context(|| format!("Could not parse {}", filename))?;
+        let features = match geojson.get("features").and_then(Value::as_array) {
+            Some(f) => f,
+            None => bail!("{} isn't a GeoJSON FeatureCollection", filename),
+        };
+
+        let mut regions = Vec::new();
+        for feature in features {
+            let name = match NAME_PROPERTIES
+                .iter()
+                .find_map(|p| feature.get("properties")?.get(p)?.as_str())
+            {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let geometry = match feature.get("geometry") {
+                Some(g) => g,
+                None => continue,
+            };
+            let coordinates = geometry.get("coordinates").unwrap_or(&Value::Null);
+            let polygons = match geometry.get("type").and_then(Value::as_str) {
+                Some("Polygon") => polygon(coordinates).map(|p| vec![p]),
+                Some("MultiPolygon") => coordinates
+                    .as_array()
+                    .and_then(|ps| ps.iter().map(polygon).collect()),
+                _ => continue,
+            };
+            let polygons: Vec<Polygon> = match polygons {
+                Some(p) => p.into_iter().filter(|rings| !rings.is_empty()).collect(),
+                None => bail!("Invalid geometry for {} in {}", name, filename),
+            };
+            let mut bbox = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+            for &(lon, lat) in polygons.iter().flat_map(|rings| rings[0].iter()) {
+                bbox = [
+                    bbox[0].min(lon),
+                    bbox[1].min(lat),
+                    bbox[2].max(lon),
+                    bbox[3].max(lat),
+                ];
+            }
+            regions.push(Region {
+                name,
+                bbox,
+                polygons,
+            });
+        }
+        if regions.is_empty() {
+            bail!(
+                "No polygons with a {} property in {}",
+                NAME_PROPERTIES.join("/"),
+                filename
+            );
+        }
+        Ok(Continents { regions })
+    }
+
+    pub fn lookup(&self, lat: f64, lon: f64) -> Option<&str> {
+        self.regions
+            .iter()
+            .find(|r| r.contains(lat, lon))
+            .map(|r| r.name.as_str())
+    }
+}
+
+// Print the longest chain and the number of chains of each length for each continent, and how
+// many chains go from one continent to another.
+pub fn print_report(chains: &[Chain], continents: &Continents) {
+    let mut place_continent: HashMap<OsmId, Option<&str>> = HashMap::new();
+    // continent → chain length → number of chains
+    let mut lengths: BTreeMap<&str, BTreeMap<usize, usize>> = BTreeMap::new();
+    let mut longest: BTreeMap<&str, &[&Record]> = BTreeMap::new();
+    let mut num_crossing = 0;
+    for chain in chains {
+        let mut chain_continents: Vec<&str> = chain
+            .iter()
+            .map(|r| {
+                *place_continent
+                    .entry(r.place_key())
+                    .or_insert_with(|| continents.lookup(r.place_lat, r.place_lon))
+            })
+            .map(|c| c.unwrap_or("?"))
+            .collect();
+        chain_continents.dedup();
+        if chain_continents.len() > 1 {
+            num_crossing += 1;
+            continue;
+        }
+        let continent = chain_continents[0];
+        *lengths
+            .entry(continent)
+            .or_default()
+            .entry(chain.len())
+            .or_default() += 1;
+        let current = longest.entry(continent).or_insert(chain);
+        if chain.len() > current.len() {
+            *current = chain;
+        }
+    }
+
+    println!("Chains in each continent (? is outside all of them):");
+    for (continent, lengths) in lengths.iter() {
+        let chain = longest[continent];
+        println!(
+            "{}: {} chains, the longest has {} places, from {} to {}",
+            continent,
+            lengths.values().sum::<usize>().separated_string(),
+            chain.len(),
+            chain[0].place_name,
+            chain[chain.len() - 1].boundary_name
+        );
+        for (len, total) in lengths.iter().rev() {
+            println!("{:>6}: {:>10}", len, total.separated_string());
+        }
+    }
+    println!(
+        "{} chains go between continents",
+        num_crossing.separated_string()
+    );
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::record::{OsmId, Record};
+
+// Tidy up a country code from the input, "se " → "SE". Empty is None.
+pub fn normalize_country_code(code: &str) -> Option<String> {
+    let code = code.trim();
+    if code.is_empty() {
+        None
+    } else {
+        Some(code.to_uppercase())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CountryBoundary {
+    boundary_id: u64,
+    country_code: String,
+}
+
+// Read a CSV file of `boundary_id,country_code`, for the admin_level=2 relations
+pub fn read_country_boundaries(filename: &str) -> Result<HashMap<OsmId, String>> {
+    let mut rdr =
+        csv::Reader::from_path(filename).with_context(|| format!("Could not open {}", filename))?;
+    let mut country_boundaries = HashMap::new();
+    for row in rdr.deserialize() {
+        let row: CountryBoundary = row.with_context(|| format!("Error reading {}", filename))?;
+        if let Some(code) = normalize_country_code(&row.country_code) {
+            country_boundaries.insert(('r', row.boundary_id), code);
+        }
+    }
+    Ok(country_boundaries)
+}
+
+// Give every record we can a country code, if it doesn't have one from the input.
+//
+// A place is in the country of the `admin_level=2` boundary it's inside. The code for an
+// `admin_level=2` boundary comes from `country_boundaries`, or else is the most common country
+// code of the places inside it which already have one. All records of a place get the same code.
+// Returns the number of places which don't have a country.
+pub fn attribute_countries(
+    points_in_boundary: &mut HashMap<OsmId, Vec<Record>>,
+    country_boundaries: &HashMap<OsmId, String>,
+) -> usize {
+    // learn the country of each country boundary from the places which have codes
+    let mut votes: HashMap<OsmId, HashMap<&str, usize>> = HashMap::new();
+    for rec in points_in_boundary.values().flat_map(|recs| recs.iter()) {
+        if let (Some(2), Some(code)) = (rec.admin_level(), &rec.country_code) {
+            *votes
+                .entry(rec.boundary_key())
+                .or_default()
+                .entry(code)
+                .or_default() += 1;
+        }
+    }
+    let mut country_boundaries = country_boundaries.clone();
+    for (boundary, codes) in votes {
+        country_boundaries.entry(boundary).or_insert_with(|| {
+            codes
+                .into_iter()
+                .max_by_key(|(code, count)| (*count, std::cmp::Reverse(*code)))
+                .unwrap()
+                .0
+                .to_string()
+        });
+    }
+
+    let mut num_without_country = 0;
+    for records in points_in_boundary.values_mut() {
+        let code = records
+            .iter()
+            .find_map(|r| r.country_code.clone())
+            .or_else(|| {
+                records
+                    .iter()
+                    .filter(|r| r.admin_level() == Some(2))
+                    .find_map(|r| country_boundaries.get(&r.boundary_key()).cloned())
+            });
+        if code.is_none() {
+            num_without_country += 1;
+        }
+        for rec in records.iter_mut() {
+            rec.country_code = code.clone();
+        }
+    }
+    num_without_country
+}
@@ -0,0 +1,199 @@
+// A full screen view of a long search (--dashboard): how the frontier & finished chains have
+// grown, the chain lengths so far, memory used, and the longest chain's places. Drawn with plain
+// ANSI escape codes, redrawn every second.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+
+use separator::Separatable;
+
+use crate::metrics;
+use crate::progress::Status;
+use crate::record::Record;
+use crate::signals;
+
+const SPARKS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const NUM_MESSAGES: usize = 3;
+const NUM_LENGTHS: usize = 8;
+
+pub struct Dashboard {
+    frontier: VecDeque<usize>,
+    finished: VecDeque<usize>,
+    messages: VecDeque<String>,
+}
+
+// (columns, rows) of the terminal
+#[cfg(unix)]
+fn terminal_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    if ok && size.ws_col > 0 && size.ws_row > 0 {
+        (size.ws_col as usize, size.ws_row as usize)
+    } else {
+        (80, 24)
+    }
+}
+
+#[cfg(not(unix))]
+fn terminal_size() -> (usize, usize) {
+    (80, 24)
+}
+
+fn memory_used() -> Option<String> {
+    let bytes = metrics::rss_bytes()?;
+    Some(if bytes >= 1 << 30 {
+        format!("{:.1} GB", bytes as f64 / (1u64 << 30) as f64)
+    } else {
+        format!("{} MB", bytes >> 20)
+    })
+}
+
+fn sparkline(values: &VecDeque<usize>, width: usize) -> String {
+    let values: Vec<usize> = values
+        .iter()
+        .skip(values.len().saturating_sub(width))
+        .copied()
+        .collect();
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+    values
+        .iter()
+        .map(|v| SPARKS[v * (SPARKS.len() - 1) / max])
+        .collect()
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
+
+impl Dashboard {
+    pub fn new() -> Dashboard {
+        // Switch to the alternate screen, so the terminal is back as it was afterwards
+        print!("\x1b[?1049h\x1b[?25l");
+        signals::set_dashboard_shown(true);
+        Dashboard {
+            frontier: VecDeque::new(),
+            finished: VecDeque::new(),
+            messages: VecDeque::new(),
+        }
+    }
+
+    pub fn message(&mut self, msg: &str) {
+        self.messages.push_back(msg.to_string());
+        if self.messages.len() > NUM_MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        status_line: &str,
+        status: &Status,
+        finished_chains: &HashMap<&Record, Vec<&Record>>,
+        in_progress: Option<&[&Record]>,
+    ) {
+        let (width, height) = terminal_size();
+        self.frontier.push_back(status.frontier);
+        self.finished.push_back(status.finished);
+        for samples in [&mut self.frontier, &mut self.finished] {
+            while samples.len() > width {
+                samples.pop_front();
+            }
+        }
+
+        let mut lines: Vec<String> = status_line.split(", ").map(str::to_string).collect();
+        lines.insert(
+            0,
+            format!(
+                "Searching for the longest chains, Ctrl-C to stop. Memory used: {}",
+                memory_used().unwrap_or_else(|| "?".to_string())
+            ),
+        );
+        let chart_width = width.saturating_sub(22);
+        lines.push(format!(
+            "Frontier {:>12} {}",
+            status.frontier.separated_string(),
+            sparkline(&self.frontier, chart_width)
+        ));
+        lines.push(format!(
+            "Finished {:>12} {}",
+            status.finished.separated_string(),
+            sparkline(&self.finished, chart_width)
+        ));
+
+        let mut lengths: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut longest: Option<&[&Record]> = None;
+        for chain in finished_chains.values() {
+            *lengths.entry(chain.len()).or_default() += 1;
+            if longest.is_none_or(|l| chain.len() > l.len()) {
+                longest = Some(chain);
+            }
+        }
+        if let Some(chain) = in_progress {
+            if longest.is_none_or(|l| chain.len() > l.len()) {
+                longest = Some(chain);
+            }
+        }
+        lines.push(String::new());
+        lines.push("Finished chains of each length:".to_string());
+        let shown: Vec<(usize, usize)> = lengths
+            .iter()
+            .rev()
+            .take(NUM_LENGTHS)
+            .map(|(l, n)| (*l, *n))
+            .collect();
+        let max_count = shown.iter().map(|(_, n)| *n).max().unwrap_or(1);
+        for (len, count) in shown {
+            lines.push(format!(
+                "{:>6} {:>12} {}",
+                len,
+                count.separated_string(),
+                "█".repeat((count * chart_width / max_count).max(1))
+            ));
+        }
+
+        lines.push(String::new());
+        let messages_height = if self.messages.is_empty() {
+            0
+        } else {
+            self.messages.len() + 2
+        };
+        if let Some(chain) = longest {
+            lines.push(format!("The longest chain so far, {} places:", chain.len()));
+            let room = height.saturating_sub(lines.len() + messages_height + 1);
+            for (i, r) in chain.iter().enumerate().take(room) {
+                lines.push(format!(
+                    "{:>5}. {} in {}",
+                    i + 1,
+                    r.place_name,
+                    r.boundary_name
+                ));
+            }
+            if chain.len() > room {
+                lines.push(format!("       … and {} more", chain.len() - room));
+            }
+        }
+        if !self.messages.is_empty() {
+            lines.push(String::new());
+            lines.push("Messages:".to_string());
+            lines.extend(self.messages.iter().cloned());
+        }
+
+        // Each line clears the rest of its old line, and no newline after the last one so the
+        // screen doesn't scroll
+        let screen = lines
+            .iter()
+            .take(height)
+            .map(|line| format!("{}\x1b[K", truncate(line, width)))
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        print!("\x1b[H{}\x1b[J", screen);
+        let _ = std::io::stdout().flush();
+    }
+
+    // Back to the normal screen
+    pub fn close(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        signals::set_dashboard_shown(false);
+    }
+}
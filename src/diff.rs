@@ -0,0 +1,230 @@
+// Compare two runs: either two results files, or two --stats-out JSON files
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::prelude::*;
+
+use anyhow::{bail, Context, Result};
+use separator::Separatable;
+
+use crate::json::{self, Value};
+use crate::record::OsmId;
+use crate::results::{self, Hop};
+
+// How many chains to list in each section
+const NUM_LISTED: usize = 100;
+
+fn is_json(filename: &str) -> Result<bool> {
+    let contents =
+        std::fs::read(filename).with_context(|| format!("Could not read {}", filename))?;
+    Ok(contents
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .is_some_and(|b| *b == b'{'))
+}
+
+fn change(old: usize, new: usize) -> String {
+    match new.cmp(&old) {
+        std::cmp::Ordering::Greater => format!("+{}", (new - old).separated_string()),
+        std::cmp::Ordering::Less => format!("-{}", (old - new).separated_string()),
+        std::cmp::Ordering::Equal => String::new(),
+    }
+}
+
+fn write_histogram(
+    old: &BTreeMap<usize, usize>,
+    new: &BTreeMap<usize, usize>,
+    output: &mut impl Write,
+) -> Result<()> {
+    writeln!(output, "## Chain lengths\n")?;
+    writeln!(
+        output,
+        "| Length | Old | New | Change |\n| --: | --: | --: | --: |"
+    )?;
+    let lengths: BTreeSet<usize> = old.keys().chain(new.keys()).copied().collect();
+    for len in lengths.iter().rev() {
+        let (o, n) = (
+            old.get(len).copied().unwrap_or(0),
+            new.get(len).copied().unwrap_or(0),
+        );
+        writeln!(
+            output,
+            "| {} | {} | {} | {} |",
+            len,
+            o.separated_string(),
+            n.separated_string(),
+            change(o, n)
+        )?;
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+fn chain_summary(chain: &[Hop]) -> String {
+    format!(
+        "{} → … → {} ({})",
+        chain[0].place_name,
+        chain[chain.len() - 1].boundary_name,
+        chain.len()
+    )
+    .replace('|', "\\|")
+}
+
+fn write_list(title: &str, lines: &[String], output: &mut impl Write) -> Result<()> {
+    writeln!(
+        output,
+        "## {} ({})\n",
+        title,
+        lines.len().separated_string()
+    )?;
+    if lines.is_empty() {
+        return Ok(());
+    }
+    for line in lines.iter().take(NUM_LISTED) {
+        writeln!(output, "* {}", line)?;
+    }
+    if lines.len() > NUM_LISTED {
+        writeln!(
+            output,
+            "* …and {} more",
+            (lines.len() - NUM_LISTED).separated_string()
+        )?;
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+// Chains are the same chain if they start with the same place in the same boundary, since
+// there's only one chain for each start.
+fn write_results_diff(old: &[Vec<Hop>], new: &[Vec<Hop>], output: &mut impl Write) -> Result<()> {
+    let by_start = |chains: &[Vec<Hop>]| -> HashMap<(OsmId, OsmId), usize> {
+        chains
+            .iter()
+            .enumerate()
+            .map(|(i, c)| ((c[0].place, c[0].boundary), i))
+            .collect()
+    };
+    let histogram = |chains: &[Vec<Hop>]| {
+        let mut totals: BTreeMap<usize, usize> = BTreeMap::new();
+        for c in chains {
+            *totals.entry(c.len()).or_default() += 1;
+        }
+        totals
+    };
+    let (old_starts, new_starts) = (by_start(old), by_start(new));
+
+    write_histogram(&histogram(old), &histogram(new), output)?;
+
+    let mut added = Vec::new();
+    let mut grew = Vec::new();
+    let mut shrank = Vec::new();
+    let mut changed = Vec::new();
+    for chain in new {
+        let old_chain = match old_starts.get(&(chain[0].place, chain[0].boundary)) {
+            None => {
+                added.push(chain_summary(chain));
+                continue;
+            }
+            Some(i) => &old[*i],
+        };
+        let describe = || format!("{}, was {}", chain_summary(chain), chain_summary(old_chain));
+        if chain.len() > old_chain.len() {
+            grew.push(describe());
+        } else if chain.len() < old_chain.len() {
+            shrank.push(describe());
+        } else if chain
+            .iter()
+            .zip(old_chain.iter())
+            .any(|(a, b)| (a.place, a.boundary) != (b.place, b.boundary))
+        {
+            changed.push(describe());
+        }
+    }
+    let vanished: Vec<String> = old
+        .iter()
+        .filter(|c| !new_starts.contains_key(&(c[0].place, c[0].boundary)))
+        .map(|c| chain_summary(c))
+        .collect();
+
+    write_list("New chains", &added, output)?;
+    write_list("Vanished chains", &vanished, output)?;
+    write_list("Chains which grew", &grew, output)?;
+    write_list("Chains which shrank", &shrank, output)?;
+    write_list(
+        "Chains with the same length, but different places",
+        &changed,
+        output,
+    )?;
+    Ok(())
+}
+
+// Every number in this JSON, by its path (e.g. `input.records`). Arrays are skipped.
+fn numbers(value: &Value, path: &str, out: &mut BTreeMap<String, f64>) {
+    match value {
+        Value::Number(n) => {
+            out.insert(path.to_string(), *n);
+        }
+        Value::Object(pairs) => {
+            for (k, v) in pairs {
+                let path = if path.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{}.{}", path, k)
+                };
+                numbers(v, &path, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn write_stats_diff(old: &Value, new: &Value, output: &mut impl Write) -> Result<()> {
+    let lengths = |stats: &Value| -> BTreeMap<usize, usize> {
+        match stats.get("chains").and_then(|c| c.get("lengths")) {
+            Some(Value::Object(pairs)) => pairs
+                .iter()
+                .filter_map(|(len, total)| Some((len.parse().ok()?, total.as_f64()? as usize)))
+                .collect(),
+            _ => BTreeMap::new(),
+        }
+    };
+    write_histogram(&lengths(old), &lengths(new), output)?;
+
+    let (mut old_numbers, mut new_numbers) = (BTreeMap::new(), BTreeMap::new());
+    numbers(old, "", &mut old_numbers);
+    numbers(new, "", &mut new_numbers);
+    writeln!(output, "## Statistics\n")?;
+    writeln!(
+        output,
+        "| | Old | New | Change |\n| :-- | --: | --: | --: |"
+    )?;
+    let keys: BTreeSet<&String> = old_numbers.keys().chain(new_numbers.keys()).collect();
+    for key in keys {
+        if key.starts_with("chains.lengths.") {
+            continue;
+        }
+        let fmt = |n: Option<&f64>| n.map(|n| n.to_string()).unwrap_or_default();
+        let (o, n) = (old_numbers.get(key), new_numbers.get(key));
+        let diff = match (o, n) {
+            (Some(o), Some(n)) if o != n => format!("{:+}", ((n - o) * 10.).round() / 10.),
+            _ => String::new(),
+        };
+        writeln!(output, "| {} | {} | {} | {} |", key, fmt(o), fmt(n), diff)?;
+    }
+    Ok(())
+}
+
+// A markdown report of what changed from `old` to `new`
+pub fn write(old: &str, new: &str, output: &mut impl Write) -> Result<()> {
+    writeln!(output, "# Changes from {} to {}\n", old, new)?;
+    match (is_json(old)?, is_json(new)?) {
+        (true, true) => {
+            let read = |f: &str| -> Result<Value> {
+                json::parse(&std::fs::read_to_string(f)?)
+                    .with_context(|| format!("Could not parse {}", f))
+            };
+            write_stats_diff(&read(old)?, &read(new)?, output)
+        }
+        (false, false) => write_results_diff(&results::read(old)?, &results::read(new)?, output),
+        _ => bail!("Can only compare 2 results files, or 2 statistics files"),
+    }
+}
@@ -0,0 +1,225 @@
+// How far apart 2 places are. --distance-model sphere (the default) is the haversine distance on
+// a sphere, which is quick and fine for comparing hops. ellipsoid is the geodesic distance on the
+// WGS84 ellipsoid (Vincenty's formula), which is what the published distances should be: the
+// sphere can be 0.5% off. It's used for everything, the scores, the filters & all the outputs,
+// so they agree.
+//
+// Vincenty's formula doesn't converge for places nearly on opposite sides of the world. Those
+// distances are worked out another (slower) way, and counted, for the --stats-out.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use anyhow::bail;
+
+use crate::record::haversine_dist;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceModel {
+    Sphere,
+    Ellipsoid,
+}
+
+impl std::str::FromStr for DistanceModel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<DistanceModel> {
+        Ok(match s {
+            "sphere" => DistanceModel::Sphere,
+            "ellipsoid" | "wgs84" => DistanceModel::Ellipsoid,
+            x => bail!(
+                "Unknown distance model {:?}, it can be sphere or ellipsoid",
+                x
+            ),
+        })
+    }
+}
+
+impl DistanceModel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DistanceModel::Sphere => "sphere",
+            DistanceModel::Ellipsoid => "ellipsoid",
+        }
+    }
+}
+
+static ELLIPSOID: AtomicBool = AtomicBool::new(false);
+static NOT_CONVERGED: AtomicUsize = AtomicUsize::new(0);
+
+// For the whole run, before any distances are worked out
+pub fn set_model(model: DistanceModel) {
+    ELLIPSOID.store(model == DistanceModel::Ellipsoid, Ordering::Relaxed);
+}
+
+pub fn model() -> DistanceModel {
+    match ELLIPSOID.load(Ordering::Relaxed) {
+        true => DistanceModel::Ellipsoid,
+        false => DistanceModel::Sphere,
+    }
+}
+
+// How many times Vincenty's formula didn't converge, so far. The same distance can be worked out
+// more than once, e.g. for the score & for the output.
+pub fn num_not_converged() -> usize {
+    NOT_CONVERGED.load(Ordering::Relaxed)
+}
+
+// In metres
+pub fn metres(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    if ELLIPSOID.load(Ordering::Relaxed) {
+        ellipsoid_m(lat1, lon1, lat2, lon2)
+    } else {
+        haversine_dist(lat1, lon1, lat2, lon2)
+    }
+}
+
+fn ellipsoid_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    vincenty_m(lat1, lon1, lat2, lon2).unwrap_or_else(|| {
+        NOT_CONVERGED.fetch_add(1, Ordering::Relaxed);
+        // The sphere if even that fails, which hasn't been seen
+        via_midpoint_m(lat1, lon1, lat2, lon2)
+            .unwrap_or_else(|| haversine_dist(lat1, lon1, lat2, lon2))
+    })
+}
+
+// WGS84
+const A: f64 = 6_378_137.;
+const F: f64 = 1. / 298.257_223_563;
+const B: f64 = (1. - F) * A;
+
+// Vincenty's inverse formula, see https://en.wikipedia.org/wiki/Vincenty%27s_formulae. None if
+// it doesn't converge.
+fn vincenty_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> Option<f64> {
+    let l = (lon2 - lon1).to_radians();
+    let u1 = ((1. - F) * lat1.to_radians().tan()).atan();
+    let u2 = ((1. - F) * lat2.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..200 {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+        .sqrt();
+        if sin_sigma == 0. {
+            // The same place
+            return Some(0.);
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos2_alpha = 1. - sin_alpha * sin_alpha;
+        // On the equator
+        let cos_2sigma_m = if cos2_alpha == 0. {
+            0.
+        } else {
+            cos_sigma - 2. * sin_u1 * sin_u2 / cos2_alpha
+        };
+        let c = F / 16. * cos2_alpha * (4. + F * (4. - 3. * cos2_alpha));
+        let previous = lambda;
+        lambda = l
+            + (1. - c)
+                * F
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m + c * cos_sigma * (-1. + 2. * cos_2sigma_m.powi(2))));
+        if (lambda - previous).abs() < 1e-12 {
+            let u_sq = cos2_alpha * (A * A - B * B) / (B * B);
+            let a = 1. + u_sq / 16384. * (4096. + u_sq * (-768. + u_sq * (320. - 175. * u_sq)));
+            let b = u_sq / 1024. * (256. + u_sq * (-128. + u_sq * (74. - 47. * u_sq)));
+            let delta_sigma = b
+                * sin_sigma
+                * (cos_2sigma_m
+                    + b / 4.
+                        * (cos_sigma * (-1. + 2. * cos_2sigma_m.powi(2))
+                            - b / 6.
+                                * cos_2sigma_m
+                                * (-3. + 4. * sin_sigma.powi(2))
+                                * (-3. + 4. * cos_2sigma_m.powi(2))));
+            return Some(B * a * (sigma - delta_sigma));
+        }
+    }
+    None
+}
+
+// Halfway between 2 places on a sphere, as (lat, lon). For places exactly on opposite sides, a
+// pole, which is on a shortest way between them.
+fn sphere_midpoint(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> (f64, f64) {
+    let unit = |lat: f64, lon: f64| {
+        let (lat, lon) = (lat.to_radians(), lon.to_radians());
+        [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+    };
+    let (a, b) = (unit(lat1, lon1), unit(lat2, lon2));
+    let [x, y, z] = [a[0] + b[0], a[1] + b[1], a[2] + b[2]];
+    if x.hypot(y).hypot(z) < 1e-9 {
+        return (90., 0.);
+    }
+    (z.atan2(x.hypot(y)).to_degrees(), y.atan2(x).to_degrees())
+}
+
+// The geodesic distance between places nearly on opposite sides of the world, where Vincenty's
+// formula doesn't converge. The geodesic is the shortest way through some point in between, and
+// from there to each place is only about a quarter of the way round, so it converges for those.
+// This searches for the point with the shortest total, starting halfway on the sphere. Near the
+// shortest, the total hardly changes as the point moves, so it doesn't have to be found exactly.
+fn via_midpoint_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> Option<f64> {
+    let total = |lat: f64, lon: f64| {
+        Some(vincenty_m(lat1, lon1, lat, lon)? + vincenty_m(lat, lon, lat2, lon2)?)
+    };
+    let (mut lat, mut lon) = sphere_midpoint(lat1, lon1, lat2, lon2);
+    let mut best = total(lat, lon)?;
+    // In degrees
+    let mut step = 1.;
+    while step > 1e-7 {
+        let moved = [(step, 0.), (-step, 0.), (0., step), (0., -step)]
+            .iter()
+            .map(|&(dlat, dlon)| ((lat + dlat).clamp(-90., 90.), lon + dlon))
+            .filter_map(|(lat, lon)| Some((total(lat, lon)?, lat, lon)))
+            .filter(|(dist, _, _)| *dist < best)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        match moved {
+            Some((dist, new_lat, new_lon)) => (best, lat, lon) = (dist, new_lat, new_lon),
+            None => step /= 2.,
+        }
+    }
+    Some(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dms(d: f64, m: f64, s: f64) -> f64 {
+        d.signum() * (d.abs() + m / 60. + s / 3600.)
+    }
+
+    #[test]
+    fn vincenty() {
+        // Flinders Peak to Buninyong, from Vincenty's paper
+        let dist = ellipsoid_m(
+            dms(-37., 57., 3.7203),
+            dms(144., 25., 29.5244),
+            dms(-37., 39., 10.1561),
+            dms(143., 55., 35.3839),
+        );
+        assert!((dist - 54_972.271).abs() < 0.01, "{}", dist);
+        assert_eq!(ellipsoid_m(52., 0., 52., 0.), 0.);
+    }
+
+    #[test]
+    fn nearly_opposite_sides() {
+        // From Karney's "Algorithms for geodesics" (2013), where Vincenty's formula doesn't
+        // converge
+        assert!(vincenty_m(-30., 0., 29.9, 179.8).is_none());
+        let before = num_not_converged();
+        let dist = ellipsoid_m(-30., 0., 29.9, 179.8);
+        assert!((dist - 19_989_832.828).abs() < 1., "{}", dist);
+        assert_eq!(num_not_converged(), before + 1);
+
+        // Exactly opposite, on the equator, the shortest way is over a pole: half a meridian
+        let dist = ellipsoid_m(0., 0., 0., 180.);
+        assert!((dist - 20_003_931.459).abs() < 1., "{}", dist);
+    }
+}
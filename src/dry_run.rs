@@ -0,0 +1,63 @@
+// --dry-run: everything up to the search (reading, filtering, the name graph), then how big the
+// search looks & how much memory it could need, so wrong filters or a too small machine show up
+// straight away, not hours into the search.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use separator::Separatable;
+
+use crate::graph::NameGraph;
+use crate::metrics;
+use crate::options::Options;
+use crate::record::Record;
+use crate::search;
+use crate::stats::RunStats;
+
+pub fn report(records: &[&Record], options: &Options, run_stats: &mut RunStats) -> Result<()> {
+    let graph = NameGraph::new(records);
+    let analysis = graph.analyze();
+    let place_names: HashSet<&str> = records.iter().map(|r| r.place_name.as_ref()).collect();
+    let num_starts = records
+        .iter()
+        .filter(|r| place_names.contains(r.boundary_name.as_ref()))
+        .count();
+    let chain_bound = analysis.components.first().map_or(0, |c| c.chain_bound);
+    (run_stats.searched_places, run_stats.searched_records) = (
+        records
+            .iter()
+            .map(|r| r.place_key())
+            .collect::<HashSet<_>>()
+            .len(),
+        records.len(),
+    );
+
+    info!(
+        "Dry run: {} records of {} places would be searched, {} names linked by {} edges in {} connected components",
+        run_stats.searched_records.separated_string(),
+        run_stats.searched_places.separated_string(),
+        graph.linkable().count().separated_string(),
+        analysis.num_edges.separated_string(),
+        analysis.components.len().separated_string(),
+    );
+    info!(
+        "{} records can start a chain, and no chain can be longer than {}",
+        num_starts.separated_string(),
+        chain_bound.separated_string()
+    );
+    let frontier_bytes =
+        search::estimate_frontier_bytes(&options.search_limits, num_starts, chain_bound);
+    info!(
+        "Memory: {} MB used now (mostly the records), and the intermediate chains could use up to about {} MB more",
+        metrics::rss_bytes()
+            .map_or("unknown".to_string(), |b| (b >> 20).separated_string()),
+        (frontier_bytes >> 20).separated_string(),
+    );
+
+    if let Some(filename) = &options.stats_out {
+        run_stats.write(filename)?;
+        info!("Wrote the statistics of this run to {}", filename);
+    }
+    info!("Not searching, because of --dry-run");
+    Ok(())
+}
@@ -0,0 +1,124 @@
+// How the run ended, as the exit code, and optionally as JSON (--errors-json), so a wrapper script
+// can tell "stopped early, but wrote what it had" from "failed outright".
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::json::obj;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Finished = 0,
+    // Anything not below
+    Failed = 1,
+    // Bad command line arguments
+    Usage = 2,
+    // An input file couldn't be opened or read, or isn't the right sort of file
+    BadInput = 3,
+    // A row couldn't be parsed, with --strict
+    ParseError = 4,
+    // Ctrl-C or --max-runtime stopped the search, the results so far were written
+    Interrupted = 5,
+    // The memory clean up's failsafe threw away chains which might have been longer, the results
+    // were written
+    MemoryLimited = 6,
+}
+
+impl Status {
+    pub const ALL: &'static [Status] = &[
+        Status::Finished,
+        Status::Failed,
+        Status::Usage,
+        Status::BadInput,
+        Status::ParseError,
+        Status::Interrupted,
+        Status::MemoryLimited,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Finished => "finished",
+            Status::Failed => "failed",
+            Status::Usage => "usage",
+            Status::BadInput => "bad_input",
+            Status::ParseError => "parse_error",
+            Status::Interrupted => "interrupted",
+            Status::MemoryLimited => "memory_limited",
+        }
+    }
+
+    pub fn from_code(code: i32) -> Option<Status> {
+        Status::ALL.iter().copied().find(|s| *s as i32 == code)
+    }
+
+    // Results were written, even if not complete
+    pub fn wrote_results(self) -> bool {
+        matches!(
+            self,
+            Status::Finished | Status::Interrupted | Status::MemoryLimited
+        )
+    }
+}
+
+// Used as an anyhow context to say what sort of failure it was, so it's also the headline of the
+// error message
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Status::Finished => "Finished",
+            Status::Failed => "Failed",
+            Status::Usage => "Invalid arguments",
+            Status::BadInput => "Could not read the input",
+            Status::ParseError => "Invalid input row",
+            Status::Interrupted => "Stopped early",
+            Status::MemoryLimited => "Chains were thrown away to save memory",
+        })
+    }
+}
+
+// Set when the run finishes, but without everything
+static PARTIAL: AtomicU8 = AtomicU8::new(Status::Finished as u8);
+
+pub fn partial(status: Status) {
+    // Being interrupted is the more important one to know about
+    if status == Status::Interrupted || PARTIAL.load(Ordering::Relaxed) == Status::Finished as u8 {
+        PARTIAL.store(status as u8, Ordering::Relaxed);
+    }
+}
+
+// Print the error, write the --errors-json, and exit with the code
+pub fn exit(result: &anyhow::Result<()>, errors_json: Option<&str>) -> ! {
+    let status = match result {
+        Ok(()) => Status::from_code(PARTIAL.load(Ordering::Relaxed) as i32).unwrap(),
+        Err(e) => e
+            .downcast_ref::<Status>()
+            .copied()
+            .unwrap_or(Status::Failed),
+    };
+    if let Err(e) = result {
+        eprintln!("{} {:#}", crate::log::paint("1;31", "Error:"), e);
+    }
+    if let Some(filename) = errors_json {
+        let json = obj(vec![
+            ("status", status.name().into()),
+            ("exit_code", (status as u8).into()),
+            ("wrote_results", status.wrote_results().into()),
+            (
+                "error",
+                result.as_ref().err().map(|e| format!("{:#}", e)).into(),
+            ),
+            (
+                "causes",
+                result
+                    .as_ref()
+                    .err()
+                    .map(|e| e.chain().map(|c| c.to_string()).collect::<Vec<_>>())
+                    .into(),
+            ),
+        ]);
+        if let Err(e) = std::fs::write(filename, format!("{}\n", json)) {
+            eprintln!("Error: Could not write {}: {}", filename, e);
+        }
+    }
+    std::process::exit(status as i32)
+}
@@ -0,0 +1,195 @@
+// Why does a chain stop where it does? Every place with the same name as the chain's last
+// boundary, and why it couldn't be the next place in the chain.
+
+use std::collections::HashMap;
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::filters::{self, BoundaryTypeFilter};
+use crate::input::{self, CsvFormat};
+use crate::record::{osm_type_name, OsmId, PlaceTypeUse, Record};
+use crate::results::Hop;
+
+// A record of a place with the chain's last boundary name
+pub struct Candidate {
+    pub record: Record,
+    pub reason: String,
+}
+
+// What the filters removed, and what was searched
+pub struct Filtered<'a> {
+    pub self_contained: &'a [(&'static str, Record)],
+    pub duplicates: &'a [(Record, Record)],
+    pub records: &'a [&'a Record],
+    // --min-name-length & --remove-junk-names
+    pub min_name_length: usize,
+    pub junk_names_removed: bool,
+}
+
+fn describe(t: char, id: u64) -> String {
+    format!("{} {}", osm_type_name(t), id)
+}
+
+// Why this record (which was in the input) can't follow the chain
+fn reason(
+    chain: &[Hop],
+    record: &Record,
+    searched: &HashMap<(OsmId, OsmId), &Record>,
+    boundary_types: &BoundaryTypeFilter,
+    filtered: &Filtered,
+) -> String {
+    if searched.contains_key(&(record.place_key(), record.boundary_key())) {
+        if let Some(i) = chain.iter().position(|h| h.place == record.place_key()) {
+            return format!("the place is already in the chain (place {})", i + 1);
+        }
+        if let Some(i) = chain
+            .iter()
+            .position(|h| h.boundary == record.boundary_key())
+        {
+            return format!(
+                "its boundary {} is already in the chain (after place {})",
+                record.boundary_name,
+                i + 1
+            );
+        }
+        return "nothing, it could be next. The search was stopped early (Ctrl-C or a memory clean up), or the results are from a different input".to_string();
+    }
+
+    if record.boundary_name.is_empty() {
+        return "its boundary has no name".to_string();
+    }
+    if filters::is_junk_name(
+        &record.boundary_name,
+        filtered.min_name_length,
+        filtered.junk_names_removed,
+    ) {
+        return format!(
+            "its boundary name {:?} is too short, has no letters or is a placeholder (see --min-name-length & --remove-junk-names)",
+            record.boundary_name
+        );
+    }
+    if !boundary_types.allows(record.boundary_type()) {
+        return format!("boundary type {} isn't used", record.boundary_type());
+    }
+    match crate::record::place_type_use(&record.place_type) {
+        PlaceTypeUse::Use => {}
+        PlaceTypeUse::Ignore => return format!("place={} is ignored", record.place_type),
+        PlaceTypeUse::Unknown => {
+            return format!(
+                "place={} is unknown (see --auto-accept-threshold)",
+                record.place_type
+            )
+        }
+    }
+    if let Some((rule, r)) = filtered
+        .self_contained
+        .iter()
+        .find(|(_, r)| r.place_key() == record.place_key())
+    {
+        return format!(
+            "removed by the {} self-containment rule, since it's in the boundary {} ({})",
+            rule,
+            r.boundary_name,
+            describe(r.boundary_osmtype, r.boundary_id)
+        );
+    }
+    if let Some((_, kept)) = filtered
+        .duplicates
+        .iter()
+        .find(|(removed, _)| removed.place_key() == record.place_key())
+    {
+        return format!(
+            "merged into the nearby place with the same name, {}",
+            describe(kept.place_osmtype, kept.place_id)
+        );
+    }
+    if let Some(r) = filtered
+        .records
+        .iter()
+        .find(|r| r.place_key() == record.place_key() && r.boundary_name == record.boundary_name)
+    {
+        return format!(
+            "only the more specific boundary with the same name, {}, is used",
+            describe(r.boundary_osmtype, r.boundary_id)
+        );
+    }
+    "it isn't in the records which were searched".to_string()
+}
+
+// Read the inputs again, since most of the records with this name were thrown away while reading
+pub fn candidates(
+    chain: &[Hop],
+    input_filenames: &[String],
+    csv_format: &CsvFormat,
+    boundary_types: &BoundaryTypeFilter,
+    filtered: &Filtered,
+) -> Result<Vec<Candidate>> {
+    let name = &chain[chain.len() - 1].boundary_name;
+    let searched: HashMap<(OsmId, OsmId), &Record> = filtered
+        .records
+        .iter()
+        .map(|r| ((r.place_key(), r.boundary_key()), *r))
+        .collect();
+    let mut candidates: Vec<Candidate> = Vec::new();
+    for input_filename in input_filenames {
+        let mut rdr = input::csv_reader(input_filename, csv_format)?;
+        for record in rdr.deserialize::<Record>() {
+            let record = match record {
+                Ok(r) if &r.place_name == name => r,
+                _ => continue,
+            };
+            // The same (place, boundary) can be in several inputs
+            if candidates.iter().any(|c| {
+                c.record.place_key() == record.place_key()
+                    && c.record.boundary_key() == record.boundary_key()
+            }) {
+                continue;
+            }
+            let reason = reason(chain, &record, &searched, boundary_types, filtered);
+            candidates.push(Candidate { record, reason });
+        }
+    }
+    candidates.sort_by_key(|c| (c.record.place_key(), c.record.boundary_key()));
+    Ok(candidates)
+}
+
+pub fn write(chain: &[Hop], candidates: &[Candidate], output: &mut impl Write) -> Result<()> {
+    let last = &chain[chain.len() - 1];
+    writeln!(
+        output,
+        "The chain of {} places from {} ({}) ends in the boundary {} ({}).",
+        chain.len(),
+        chain[0].place_name,
+        describe(chain[0].place.0, chain[0].place.1),
+        last.boundary_name,
+        describe(last.boundary.0, last.boundary.1)
+    )?;
+    if candidates.is_empty() {
+        writeln!(
+            output,
+            "No place is called {}, so the chain can't go any further.",
+            last.boundary_name
+        )?;
+        return Ok(());
+    }
+    writeln!(
+        output,
+        "{} places called {} are inside a boundary:",
+        candidates.len(),
+        last.boundary_name
+    )?;
+    for c in candidates {
+        let r = &c.record;
+        writeln!(
+            output,
+            "* {} (place={}) in {} ({}): {}",
+            describe(r.place_osmtype, r.place_id),
+            r.place_type,
+            r.boundary_name,
+            describe(r.boundary_osmtype, r.boundary_id),
+            c.reason
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+// `extend`: rather than searching from every place, only try to make the chains from an earlier
+// run longer at their ends, with a newer input. After a planet update, "did last month's longest
+// chains get any longer?" takes seconds rather than a whole search.
+
+use std::collections::HashMap;
+
+use separator::Separatable;
+
+use crate::record::{OsmId, Record};
+use crate::results::Hop;
+use crate::revalidate;
+
+// The earlier chains which still hold in these records (see revalidate), as these records, the
+// longest first. The search keeps one chain per start, so only the longest from each start is
+// kept.
+pub fn prefixes<'a>(old_chains: &[Vec<Hop>], records: &[&'a Record]) -> Vec<Vec<&'a Record>> {
+    let by_key: HashMap<(OsmId, OsmId), &'a Record> = records
+        .iter()
+        .map(|r| ((r.place_key(), r.boundary_key()), *r))
+        .collect();
+    let mut by_start: HashMap<&Record, Vec<&Record>> = HashMap::new();
+    let mut num_broken = 0;
+    for hops in old_chains {
+        if revalidate::check(hops, &by_key).is_some() {
+            num_broken += 1;
+            continue;
+        }
+        let chain: Vec<&Record> = hops
+            .iter()
+            .map(|h| by_key[&(h.place, h.boundary)])
+            .collect();
+        if by_start
+            .get(chain[0])
+            .is_none_or(|curr| chain.len() > curr.len())
+        {
+            by_start.insert(chain[0], chain);
+        }
+    }
+    if num_broken > 0 {
+        warn!(
+            "{} of the {} earlier chains no longer hold in the input (see the revalidate command), so they can't be extended",
+            num_broken.separated_string(),
+            old_chains.len().separated_string()
+        );
+    }
+    let mut prefixes: Vec<Vec<&Record>> = by_start.into_values().collect();
+    prefixes.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    prefixes
+}
+
+// Print which chains got longer
+pub fn report(prefixes: &[Vec<&Record>], finished_chains: &HashMap<&Record, Vec<&Record>>) {
+    let mut num_longer = 0;
+    for prefix in prefixes {
+        let chain = match finished_chains.get(prefix[0]) {
+            Some(chain) if chain.len() > prefix.len() => chain,
+            _ => continue,
+        };
+        num_longer += 1;
+        info!(
+            "{} to {} is now {} places long (was {}), it ends in {}",
+            prefix[0].place_name,
+            prefix[prefix.len() - 1].boundary_name,
+            chain.len(),
+            prefix.len(),
+            chain[chain.len() - 1].boundary_name
+        );
+    }
+    summary!(
+        "{} of {} chains got longer",
+        num_longer.separated_string(),
+        prefixes.len().separated_string()
+    );
+}
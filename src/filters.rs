@@ -0,0 +1,598 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use separator::Separatable;
+
+use crate::distance;
+use crate::record::{osm_type_name, OsmId, Record};
+
+// Which `boundary=*` types can be part of a chain
+#[derive(Debug, Default)]
+pub struct BoundaryTypeFilter {
+    // None = all types
+    pub include: Option<Vec<String>>,
+    pub exclude: Vec<String>,
+}
+
+impl BoundaryTypeFilter {
+    pub fn parse_list(s: &str) -> Vec<String> {
+        s.split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect()
+    }
+
+    pub fn allows(&self, boundary_type: &str) -> bool {
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.iter().any(|t| t == boundary_type))
+            && !self.exclude.iter().any(|t| t == boundary_type)
+    }
+}
+
+// Ways of deciding that a place is "the same" as the boundary it's in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfContainmentRule {
+    // Byte for byte equal names
+    Exact,
+    // Equal after lowercasing, removing accents & punctuation
+    Normalized,
+    // Equal after also removing things like "City of" or " County"
+    Prefix,
+    // Normalized names within a small edit distance
+    Fuzzy,
+}
+
+impl SelfContainmentRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SelfContainmentRule::Exact => "exact",
+            SelfContainmentRule::Normalized => "normalized",
+            SelfContainmentRule::Prefix => "prefix",
+            SelfContainmentRule::Fuzzy => "fuzzy",
+        }
+    }
+
+    // Parse a comma separated list like "exact,prefix". "off" means no rules.
+    pub fn parse_list(s: &str) -> Result<Vec<SelfContainmentRule>> {
+        let mut rules = Vec::new();
+        for part in s.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let rule = match part {
+                "off" | "none" => continue,
+                "exact" => SelfContainmentRule::Exact,
+                "normalized" | "normalised" => SelfContainmentRule::Normalized,
+                "prefix" => SelfContainmentRule::Prefix,
+                "fuzzy" => SelfContainmentRule::Fuzzy,
+                x => bail!("Unknown self-containment rule {:?}", x),
+            };
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn matches(&self, place_name: &str, boundary_name: &str, fuzzy_distance: usize) -> bool {
+        match self {
+            SelfContainmentRule::Exact => place_name == boundary_name,
+            SelfContainmentRule::Normalized => {
+                normalize_name(place_name) == normalize_name(boundary_name)
+            }
+            SelfContainmentRule::Prefix => {
+                let place_name = normalize_name(place_name);
+                let boundary_name = normalize_name(boundary_name);
+                strip_admin_words(&place_name) == strip_admin_words(&boundary_name)
+            }
+            SelfContainmentRule::Fuzzy => {
+                let place_name = normalize_name(place_name);
+                let boundary_name = normalize_name(boundary_name);
+                // Very short names are too easily "close" to each other
+                place_name.chars().count() >= 4
+                    && boundary_name.chars().count() >= 4
+                    && edit_distance(&place_name, &boundary_name) <= fuzzy_distance
+            }
+        }
+    }
+}
+
+// Remove accents from the common latin letters
+fn fold_diacritic(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => 'c',
+        'ď' | 'đ' => 'd',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => 'e',
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => 'g',
+        'ĥ' | 'ħ' => 'h',
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => 'i',
+        'ĵ' => 'j',
+        'ķ' => 'k',
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => 'l',
+        'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => 'o',
+        'ŕ' | 'ŗ' | 'ř' => 'r',
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => 's',
+        'ţ' | 'ť' | 'ŧ' | 'ț' => 't',
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => 'u',
+        'ŵ' => 'w',
+        'ý' | 'ÿ' | 'ŷ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        c => c,
+    }
+}
+
+// Lowercase, no accents, punctuation turned into spaces, and whitespace collapsed.
+pub fn normalize_name(name: &str) -> String {
+    let folded = name
+        .chars()
+        .flat_map(|c| c.to_lowercase())
+        .map(fold_diacritic)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Words which say what sort of admin area something is, rather than which one it is.
+// Expects an already normalized name.
+const ADMIN_PREFIXES: &[&str] = &[
+    "city of ",
+    "town of ",
+    "village of ",
+    "municipality of ",
+    "borough of ",
+    "county of ",
+    "district of ",
+    "township of ",
+    "city and county of ",
+    "comune di ",
+    "municipio de ",
+    "commune de ",
+    "gemeinde ",
+    "stadt ",
+    "landkreis ",
+    "kreis ",
+];
+const ADMIN_SUFFIXES: &[&str] = &[
+    " city",
+    " town",
+    " county",
+    " municipality",
+    " borough",
+    " district",
+    " township",
+    " parish",
+];
+
+fn strip_admin_words(name: &str) -> &str {
+    let mut name = name;
+    for prefix in ADMIN_PREFIXES {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            name = rest;
+            break;
+        }
+    }
+    for suffix in ADMIN_SUFFIXES {
+        if let Some(rest) = name.strip_suffix(suffix) {
+            name = rest;
+            break;
+        }
+    }
+    name
+}
+
+// Levenshtein distance, in chars
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+// Tags which say the name isn't known, rather than being a name. Compared lowercase.
+const PLACEHOLDER_NAMES: &[&str] = &[
+    "unknown", "noname", "no name", "fixme", "todo", "tbd", "n/a", "null", "none",
+];
+
+// Names which aren't really names: shorter than `min_len` chars, or (with `placeholders`) with no
+// letters at all ("12345", "?", "-") or a placeholder like "unknown". They match each other by
+// accident and make nonsense chains.
+pub fn is_junk_name(name: &str, min_len: usize, placeholders: bool) -> bool {
+    let name = name.trim();
+    name.chars().count() < min_len
+        || (placeholders
+            && (!name.chars().any(char::is_alphabetic)
+                || PLACEHOLDER_NAMES.contains(&name.to_lowercase().as_str())))
+}
+
+// Remove the records where the place or boundary has a junk name (see `is_junk_name`). Returns
+// the number of records removed.
+pub fn remove_junk_names(
+    points_in_boundary: &mut HashMap<OsmId, Vec<Record>>,
+    min_len: usize,
+    placeholders: bool,
+) -> usize {
+    let mut num_removed = 0;
+    points_in_boundary.retain(|_, records| {
+        let before = records.len();
+        records.retain(|r| {
+            !is_junk_name(&r.place_name, min_len, placeholders)
+                && !is_junk_name(&r.boundary_name, min_len, placeholders)
+        });
+        num_removed += before - records.len();
+        !records.is_empty()
+    });
+    num_removed
+}
+
+// Remove every place which is in a boundary with the "same" name (according to `rules`),
+// and print how many places & records each rule removed.
+// Returns the records which matched a rule, with the name of that rule.
+pub fn remove_self_contained(
+    points_in_boundary: &mut HashMap<OsmId, Vec<Record>>,
+    rules: &[SelfContainmentRule],
+    fuzzy_distance: usize,
+) -> Vec<(&'static str, Record)> {
+    if rules.is_empty() {
+        info!("Self-containment filter is off, not removing any places");
+        return Vec::new();
+    }
+
+    // rule → (num places, num records)
+    let mut removed: Vec<(usize, usize)> = vec![(0, 0); rules.len()];
+    let mut matching_records = Vec::new();
+    points_in_boundary.retain(|_point_id, records| {
+        let matched = rules.iter().position(|rule| {
+            records
+                .iter()
+                .any(|r| rule.matches(&r.place_name, &r.boundary_name, fuzzy_distance))
+        });
+        match matched {
+            None => true,
+            Some(i) => {
+                removed[i].0 += 1;
+                removed[i].1 += records.len();
+                let rule = &rules[i];
+                matching_records.extend(
+                    std::mem::take(records)
+                        .into_iter()
+                        .filter(|r| rule.matches(&r.place_name, &r.boundary_name, fuzzy_distance))
+                        .map(|r| (rule.name(), r)),
+                );
+                false
+            }
+        }
+    });
+
+    for (rule, (places, records)) in rules.iter().zip(removed) {
+        info!(
+            "  rule {:<10} removed {} places ({} records)",
+            rule.name(),
+            places.separated_string(),
+            records.separated_string()
+        );
+    }
+    matching_records
+}
+
+// The places removed by `remove_self_contained`, as CSV. Useful for finding redundant place
+// nodes in OSM.
+pub fn write_self_contained_report(filename: &str, removed: &[(&str, Record)]) -> Result<()> {
+    let mut wtr = csv::Writer::from_path(filename)
+        .with_context(|| format!("Could not create {}", filename))?;
+    wtr.write_record([
+        "rule",
+        "place_osm_type",
+        "place_id",
+        "place_name",
+        "place_type",
+        "place_lat",
+        "place_lon",
+        "boundary_osm_type",
+        "boundary_id",
+        "boundary_name",
+        "admin_level",
+    ])?;
+    for (rule, r) in removed {
+        wtr.write_record([
+            rule,
+            osm_type_name(r.place_osmtype),
+            &r.place_id.to_string(),
+            &r.place_name,
+            &r.place_type,
+            &r.place_lat.to_string(),
+            &r.place_lon.to_string(),
+            osm_type_name(r.boundary_osmtype),
+            &r.boundary_id.to_string(),
+            &r.boundary_name,
+            &r.admin_level_str(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// OSM often has several `place` nodes with the same name very close together (old imports,
+// duplicate mapping). Merge places with the same name within `radius_m` metres into the one
+// with the lowest type & id, so the search doesn't branch over what is really one place.
+// Returns a record of each place merged away, with a record of the place it was merged into.
+pub fn merge_nearby_duplicates(
+    points_in_boundary: &mut HashMap<OsmId, Vec<Record>>,
+    radius_m: f64,
+) -> Vec<(Record, Record)> {
+    let mut ids_by_name: HashMap<&str, Vec<OsmId>> = HashMap::new();
+    for (place_key, records) in points_in_boundary.iter() {
+        ids_by_name
+            .entry(&records[0].place_name)
+            .or_default()
+            .push(*place_key);
+    }
+
+    // (place that will be removed, place it is merged into)
+    let mut merges: Vec<(OsmId, OsmId)> = Vec::new();
+    for ids in ids_by_name.values_mut().filter(|ids| ids.len() > 1) {
+        ids.sort_unstable();
+        let mut kept: Vec<&Record> = Vec::new();
+        for id in ids.iter() {
+            let rec = &points_in_boundary[id][0];
+            match kept.iter().find(|k| {
+                distance::metres(k.place_lat, k.place_lon, rec.place_lat, rec.place_lon) <= radius_m
+            }) {
+                Some(k) => merges.push((*id, k.place_key())),
+                None => kept.push(rec),
+            }
+        }
+    }
+
+    let mut duplicates = Vec::with_capacity(merges.len());
+    for (from, to) in merges.iter() {
+        let records = points_in_boundary.remove(from).unwrap();
+        let target = points_in_boundary.get_mut(to).unwrap();
+        duplicates.push((records[0].clone(), target[0].clone()));
+        for mut rec in records {
+            if target
+                .iter()
+                .any(|r| r.boundary_key() == rec.boundary_key())
+            {
+                continue;
+            }
+            // It's now a record for the kept place
+            rec.place_osmtype = target[0].place_osmtype;
+            rec.place_id = target[0].place_id;
+            rec.place_type = target[0].place_type.clone();
+            rec.place_lat = target[0].place_lat;
+            rec.place_lon = target[0].place_lon;
+            rec.country_code = target[0].country_code.clone();
+            rec.place_wikidata = target[0].place_wikidata.clone();
+            rec.place_wikipedia = target[0].place_wikipedia.clone();
+            rec.place_population = target[0].place_population;
+            rec.place_timestamp = target[0].place_timestamp;
+            target.push(rec);
+        }
+    }
+
+    duplicates
+}
+
+// A place is often inside an admin_level=6 and an admin_level=8 boundary with the same name,
+// which just gives duplicate branches in the search. Keep only the most specific (numerically
+// highest admin_level) boundary for each (place, boundary name). Returns the number of records
+// removed.
+pub fn keep_most_specific_boundary(points_in_boundary: &mut HashMap<OsmId, Vec<Record>>) -> usize {
+    let mut num_removed = 0;
+    for records in points_in_boundary.values_mut() {
+        // Unusable admin_levels are the least specific. Ties are broken by boundary id
+        records.sort_by(|a, b| {
+            a.boundary_name
+                .cmp(&b.boundary_name)
+                .then(b.admin_level().cmp(&a.admin_level()))
+                .then(a.boundary_key().cmp(&b.boundary_key()))
+        });
+        let before = records.len();
+        records.dedup_by(|later, earlier| later.boundary_name == earlier.boundary_name);
+        num_removed += before - records.len();
+    }
+    num_removed
+}
+
+// A place is in many boundaries at once (suburb, city, county, state, ...). Keep only the smallest
+// one of each place (--smallest-boundary), so the chains follow a strict hierarchy, rather than
+// jumping from a place to any boundary it's in. The smallest is the one with the smallest
+// boundary_area, if the input has that column for all the place's boundaries, otherwise the most
+// specific (numerically highest) admin_level. Returns the number of records removed.
+pub fn keep_smallest_boundary(points_in_boundary: &mut HashMap<OsmId, Vec<Record>>) -> usize {
+    let mut num_removed = 0;
+    for records in points_in_boundary.values_mut() {
+        let by_area = records.iter().all(|r| r.boundary_area.is_some());
+        // Unusable admin_levels are the least specific. Ties are broken by boundary id
+        records.sort_by(|a, b| {
+            let size = match by_area {
+                true => a
+                    .boundary_area
+                    .unwrap_or(f64::INFINITY)
+                    .total_cmp(&b.boundary_area.unwrap_or(f64::INFINITY)),
+                false => b.admin_level().cmp(&a.admin_level()),
+            };
+            size.then(a.boundary_key().cmp(&b.boundary_key()))
+        });
+        num_removed += records.len().saturating_sub(1);
+        records.truncate(1);
+    }
+    num_removed
+}
+
+// Notable places & boundaries have a `wikidata=*` or `wikipedia=*` tag. Keep only the records
+// where both the place & the boundary have one (--require-wikidata). Returns the number of records
+// removed.
+pub fn keep_with_wikidata(points_in_boundary: &mut HashMap<OsmId, Vec<Record>>) -> usize {
+    let mut num_removed = 0;
+    points_in_boundary.retain(|_, records| {
+        let before = records.len();
+        records.retain(|r| {
+            (r.place_wikidata.is_some() || r.place_wikipedia.is_some())
+                && (r.boundary_wikidata.is_some() || r.boundary_wikipedia.is_some())
+        });
+        num_removed += before - records.len();
+        !records.is_empty()
+    });
+    num_removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::add_record;
+    use crate::record;
+
+    fn by_place(text: &str) -> HashMap<OsmId, Vec<Record>> {
+        let mut points_in_boundary = HashMap::new();
+        for r in record::from_csv(text) {
+            add_record(&mut points_in_boundary, r);
+        }
+        points_in_boundary
+    }
+
+    fn place_names(points_in_boundary: &HashMap<OsmId, Vec<Record>>) -> Vec<&str> {
+        let mut names: Vec<&str> = points_in_boundary
+            .values()
+            .flatten()
+            .map(|r| r.place_name.as_ref())
+            .collect();
+        names.sort();
+        names
+    }
+
+    const SELF_CONTAINED: &str = "\
+place_osmtype,place_id,place_name,place_type,place_lat,place_lon,boundary_osmtype,boundary_id,boundary_name,boundary_admin_level
+n,1,Springfield,town,0,0,r,10,Springfield,8
+n,2,Paris,city,0,0,r,20,City of Paris,8
+n,2,Paris,city,0,0,r,21,France,2
+n,3,Lyon,city,0,0,r,30,Rhône,6
+n,4,Sankt Gallen,city,0,0,r,40,St. Gallen,8
+";
+
+    #[test]
+    fn self_containment_rules() {
+        let mut points_in_boundary = by_place(SELF_CONTAINED);
+        let removed = remove_self_contained(&mut points_in_boundary, &[], 2);
+        assert!(removed.is_empty());
+        assert_eq!(points_in_boundary.len(), 4);
+
+        let removed =
+            remove_self_contained(&mut points_in_boundary, &[SelfContainmentRule::Exact], 2);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].0, "exact");
+        assert_eq!(removed[0].1.place_name, "Springfield");
+        assert_eq!(
+            place_names(&points_in_boundary),
+            ["Lyon", "Paris", "Paris", "Sankt Gallen"]
+        );
+
+        // The whole place goes, with its other boundaries, but only the matching records are
+        // returned
+        let rules = SelfContainmentRule::parse_list("exact,prefix").unwrap();
+        let removed = remove_self_contained(&mut points_in_boundary, &rules, 2);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].0, "prefix");
+        assert_eq!(removed[0].1.boundary_name, "City of Paris");
+        assert_eq!(place_names(&points_in_boundary), ["Lyon", "Sankt Gallen"]);
+    }
+
+    #[test]
+    fn self_containment_matches() {
+        use SelfContainmentRule::*;
+        assert!(Exact.matches("Cork", "Cork", 2));
+        assert!(!Exact.matches("Cork", "cork", 2));
+        assert!(Normalized.matches("Zürich", "zurich", 2));
+        assert!(Prefix.matches("Cork", "Cork County", 2));
+        assert!(!Prefix.matches("Cork", "Corkbeg", 2));
+        assert!(Fuzzy.matches("Sankt Gallen", "Sankt Galen", 2));
+        assert!(!Fuzzy.matches("Bern", "Born", 0));
+        assert!(SelfContainmentRule::parse_list("exact,bogus").is_err());
+        assert!(SelfContainmentRule::parse_list("off").unwrap().is_empty());
+    }
+
+    #[test]
+    fn smallest_boundary_by_admin_level() {
+        let mut points_in_boundary = by_place(
+            "\
+place_osmtype,place_id,place_name,place_type,place_lat,place_lon,boundary_osmtype,boundary_id,boundary_name,boundary_admin_level
+n,1,Ballincollig,town,0,0,r,10,Ireland,2
+n,1,Ballincollig,town,0,0,r,11,Cork County,6
+n,1,Ballincollig,town,0,0,r,12,Ballincollig,8
+n,1,Ballincollig,town,0,0,r,13,Munster,5
+n,2,Cobh,town,0,0,r,20,Cork County,6
+",
+        );
+        assert_eq!(keep_smallest_boundary(&mut points_in_boundary), 3);
+        assert!(points_in_boundary
+            .values()
+            .all(|records| records.len() == 1));
+        let kept = |place_id| {
+            points_in_boundary[&('n', place_id)][0]
+                .boundary_name
+                .as_ref()
+        };
+        assert_eq!(kept(1), "Ballincollig");
+        assert_eq!(kept(2), "Cork County");
+    }
+
+    #[test]
+    fn smallest_boundary_by_area() {
+        // The area wins over the admin_level, if every boundary of the place has one
+        let mut points_in_boundary = by_place(
+            "\
+place_osmtype,place_id,place_name,place_type,place_lat,place_lon,boundary_osmtype,boundary_id,boundary_name,boundary_admin_level,boundary_area
+n,1,Eyre,hamlet,0,0,r,10,Big,8,5000000
+n,1,Eyre,hamlet,0,0,r,11,Small,6,20000
+n,2,Ford,hamlet,0,0,r,20,Big,8,5000000
+n,2,Ford,hamlet,0,0,r,21,Unknown,6,
+",
+        );
+        assert_eq!(keep_smallest_boundary(&mut points_in_boundary), 2);
+        let kept = |place_id| {
+            points_in_boundary[&('n', place_id)][0]
+                .boundary_name
+                .as_ref()
+        };
+        assert_eq!(kept(1), "Small");
+        assert_eq!(kept(2), "Big");
+    }
+
+    const JUNK_NAMES: &str = "\
+place_osmtype,place_id,place_name,place_type,place_lat,place_lon,boundary_osmtype,boundary_id,boundary_name,boundary_admin_level
+n,1,市,city,0,0,r,10,県,4
+n,2,Unknown,village,0,0,r,20,Cork County,6
+n,3,Tobar,village,0,0,r,30,12345,8
+n,4,Lusk,town,0,0,r,40,Fingal,6
+";
+
+    #[test]
+    fn junk_names_are_kept_by_default() {
+        // Neither filter is on unless asked for, so single character (e.g. CJK) names are kept
+        let options = crate::options::Options::default();
+        let mut points_in_boundary = by_place(JUNK_NAMES);
+        let removed = remove_junk_names(
+            &mut points_in_boundary,
+            options.min_name_length,
+            options.remove_junk_names,
+        );
+        assert_eq!(removed, 0);
+        assert_eq!(
+            place_names(&points_in_boundary),
+            ["Lusk", "Tobar", "Unknown", "市"]
+        );
+
+        assert_eq!(remove_junk_names(&mut points_in_boundary, 2, false), 1);
+        assert_eq!(remove_junk_names(&mut points_in_boundary, 1, true), 2);
+        assert_eq!(place_names(&points_in_boundary), ["Lusk"]);
+    }
+}
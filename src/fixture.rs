@@ -0,0 +1,268 @@
+// Make up an input file with chains whose lengths are known (gen-fixture), for testing &
+// benchmarking. There's one chain of --chain-depth places, which is the longest, then shorter
+// chains, cycles (A is in a boundary called B, B in C, C in A) and places with the same name as a
+// place in another chain (so the search has to try both), until there are --places places. The
+// same --seed always gives the same file.
+
+use std::io::Write;
+
+use anyhow::{bail, Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+const SYLLABLES: &[&str] = &[
+    "ka", "lo", "mar", "ven", "dor", "si", "tan", "bel", "ru", "fen", "gar", "mi", "os", "wyn",
+    "hal", "ze",
+];
+const PLACE_TYPES: &[&str] = &["city", "town", "village", "suburb", "hamlet"];
+const COUNTRY_CODES: &[&str] = &["IE", "FR", "DE", "BR", "JP"];
+
+// SplitMix64. There's no random number crate, and this only has to be quick & repeatable.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // From `range.start` up to, but not including, `range.end`
+    fn range(&mut self, range: std::ops::Range<usize>) -> usize {
+        range.start + (self.next() % (range.end - range.start) as u64) as usize
+    }
+
+    fn coord(&mut self, max: f64) -> f64 {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64 * 2. - 1.) * max
+    }
+
+    fn choose<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.range(0..items.len())]
+    }
+}
+
+struct Generator {
+    rng: Rng,
+    // Which names are used, in an order which depends on the seed
+    name_multiplier: u64,
+    name_offset: u64,
+    // Names are this many syllables, enough for 2 names per place
+    name_syllables: u32,
+    next_name: u64,
+    next_id: u64,
+    csv: csv::Writer<GzEncoder<std::fs::File>>,
+    num_records: usize,
+}
+
+impl Generator {
+    // A name which hasn't been used yet, e.g. "Venmarsi"
+    fn name(&mut self) -> String {
+        // Multiplying by an odd number, modulo a power of 2, is a permutation, so they're all
+        // different. No syllable is the start of another, so the names are too.
+        let mut n = self
+            .next_name
+            .wrapping_mul(self.name_multiplier)
+            .wrapping_add(self.name_offset)
+            % (1 << (4 * self.name_syllables));
+        self.next_name += 1;
+        let mut name = String::new();
+        for _ in 0..self.name_syllables {
+            name.push_str(SYLLABLES[n as usize % SYLLABLES.len()]);
+            n /= SYLLABLES.len() as u64;
+        }
+        let mut chars = name.chars();
+        chars.next().unwrap().to_uppercase().chain(chars).collect()
+    }
+
+    // A place called `place_name` in a boundary called `boundary_name`
+    fn record(&mut self, place_name: &str, boundary_name: &str) -> Result<()> {
+        self.next_id += 1;
+        let (lat, lon) = (self.rng.coord(80.), self.rng.coord(180.));
+        let row = [
+            "n".to_string(),
+            self.next_id.to_string(),
+            place_name.to_string(),
+            self.rng.choose(PLACE_TYPES).to_string(),
+            format!("{:.5}", lat),
+            format!("{:.5}", lon),
+            "r".to_string(),
+            self.next_id.to_string(),
+            boundary_name.to_string(),
+            self.rng.range(4..11).to_string(),
+            self.rng.choose(COUNTRY_CODES).to_string(),
+        ];
+        self.csv.write_record(&row)?;
+        self.num_records += 1;
+        Ok(())
+    }
+
+    // `len` places, each in a boundary with the name of the next, so the longest chain from the
+    // first is `len`. The last is in a boundary which no place has the name of.
+    fn chain(&mut self, len: usize) -> Result<Vec<String>> {
+        let names: Vec<String> = (0..=len).map(|_| self.name()).collect();
+        for pair in names.windows(2) {
+            self.record(&pair[0], &pair[1])?;
+        }
+        Ok(names[..len].to_vec())
+    }
+
+    // `len` places, each in a boundary with the name of the next, and the last in a boundary with
+    // the name of the first, so the longest chain from each is `len`
+    fn cycle(&mut self, len: usize) -> Result<()> {
+        let names: Vec<String> = (0..len).map(|_| self.name()).collect();
+        for i in 0..len {
+            self.record(&names[i], &names[(i + 1) % len])?;
+        }
+        Ok(())
+    }
+}
+
+pub fn generate(filename: &str, places: usize, chain_depth: usize, seed: u64) -> Result<()> {
+    if chain_depth < 2 {
+        bail!("--chain-depth needs to be at least 2");
+    }
+    if places < chain_depth {
+        bail!("--places needs to be at least the --chain-depth");
+    }
+    if !filename.ends_with(".csv.gz") {
+        bail!("gen-fixture writes a gzipped CSV, so the OUTPUT should end with .csv.gz");
+    }
+    let file = std::fs::File::create(filename)
+        .with_context(|| format!("Could not create {}", filename))?;
+    let mut csv = csv::Writer::from_writer(GzEncoder::new(file, Compression::default()));
+    csv.write_record([
+        "place_osmtype",
+        "place_id",
+        "place_name",
+        "place_type",
+        "place_lat",
+        "place_lon",
+        "boundary_osmtype",
+        "boundary_id",
+        "boundary_name",
+        "boundary_admin_level",
+        "country_code",
+    ])?;
+    let mut rng = Rng(seed);
+    let mut gen = Generator {
+        name_multiplier: rng.next() | 1,
+        name_offset: rng.next(),
+        name_syllables: (places as u64 * 2 + 64)
+            .next_power_of_two()
+            .trailing_zeros()
+            .div_ceil(4)
+            .clamp(3, 15),
+        rng,
+        next_name: 0,
+        next_id: 0,
+        csv,
+        num_records: 0,
+    };
+
+    let mut names = gen.chain(chain_depth)?;
+    let longest = [names[0].clone(), names[chain_depth - 1].clone()];
+    while gen.num_records < places {
+        let left = places - gen.num_records;
+        match gen.rng.range(0..4) {
+            // A place with the same name as one already made, in a boundary no place has the name
+            // of. So it's a branch which ends straight away.
+            0 => {
+                let i = gen.rng.range(0..names.len());
+                let (place_name, boundary_name) = (names[i].clone(), gen.name());
+                gen.record(&place_name, &boundary_name)?;
+            }
+            // Shorter than the longest chain. A place in a boundary with its own name is
+            // filtered out, so at least 2.
+            1 if chain_depth > 2 && left >= 2 => {
+                let len = gen.rng.range(2..chain_depth.min(6)).min(left);
+                gen.cycle(len)?;
+            }
+            _ => {
+                let len = gen.rng.range(1..chain_depth).min(left);
+                names.extend(gen.chain(len)?);
+            }
+        }
+    }
+
+    let mut file = gen
+        .csv
+        .into_inner()
+        .map_err(|e| anyhow::anyhow!("Could not write {}: {}", filename, e.error()))?
+        .finish()?;
+    file.flush()?;
+    info!(
+        "Wrote {} records to {}. The longest chain has {} places, from {} to {}",
+        gen.num_records, filename, chain_depth, longest[0], longest[1]
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::*;
+    use crate::input::{self, CsvFormat};
+    use crate::record::Record;
+    use crate::search::ChainSearch;
+
+    // The records of a gen-fixture file
+    fn fixture(places: usize, chain_depth: usize, seed: u64) -> Vec<Record> {
+        let filename = std::env::temp_dir().join(format!(
+            "x-in-y-test-fixture-{}-{}.csv.gz",
+            std::process::id(),
+            seed
+        ));
+        let filename = filename.to_str().unwrap();
+        generate(filename, places, chain_depth, seed).unwrap();
+        let records = input::csv_reader(filename, &CsvFormat::default())
+            .unwrap()
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        std::fs::remove_file(filename).unwrap();
+        records
+    }
+
+    // By the place ids, so they can be compared
+    fn chain_ids(chains: HashMap<&Record, Vec<&Record>>) -> BTreeMap<u64, Vec<u64>> {
+        chains
+            .into_iter()
+            .map(|(start, chain)| (start.place_id, chain.iter().map(|r| r.place_id).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_longest_chain() {
+        for seed in 0..5 {
+            let records = fixture(300, 12, seed);
+            let refs: Vec<&Record> = records.iter().collect();
+            let (chains, _stats) = ChainSearch::new(&refs).run();
+
+            // It's the first chain made, from the first record. The last place could be another
+            // with the same name, which is in a different boundary.
+            let longest = &chains[&records[0]];
+            assert_eq!(longest.len(), 12, "seed {}", seed);
+            for (r, made) in longest.iter().zip(&records[..12]) {
+                assert_eq!(r.place_name, made.place_name);
+            }
+            for pair in longest.windows(2) {
+                assert_eq!(pair[0].boundary_name, pair[1].place_name);
+            }
+            assert!(chains.values().all(|chain| chain.len() <= 12));
+        }
+    }
+
+    #[test]
+    fn same_chains_in_any_order() {
+        let records = fixture(300, 10, 7);
+        let refs: Vec<&Record> = records.iter().collect();
+        let mut reversed = refs.clone();
+        reversed.reverse();
+        let (chains, _stats) = ChainSearch::new(&refs).run();
+        let (reversed_chains, _stats) = ChainSearch::new(&reversed).run();
+        assert_eq!(chain_ids(chains), chain_ids(reversed_chains));
+    }
+}
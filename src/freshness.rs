@@ -0,0 +1,126 @@
+// The optional place_timestamp & boundary_timestamp columns: when each place & boundary was last
+// edited. A chain is only as good as its weakest link, so each chain reports its oldest & newest
+// edit, and whether that's a reason to check it again before publishing it: "stale" if part of it
+// hasn't been touched for years (so it may no longer be true), "recently-edited" if part of it
+// was changed in the last month (someone may still be working on it, or it's vandalism).
+//
+// Ages are from the newest edit in the input, rather than today, so an old export gives the same
+// answer as it did then.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::record::Record;
+use crate::status_file::civil_from_days;
+
+const DAY: i64 = 86_400;
+const STALE_AFTER: i64 = 5 * 365 * DAY;
+const RECENT_WITHIN: i64 = 30 * DAY;
+
+// Days since 1970-01-01 of this date, Howard Hinnant's days_from_civil
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Seconds since 1970 of an OSM timestamp, e.g. "2023-05-01T12:34:56Z", "2023-05-01 12:34:56+00"
+// (as PostgreSQL writes them), "2023-05-01", or already in seconds. The timezone is ignored, OSM's
+// are all UTC. None if it isn't one of those.
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    let s = s.trim();
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        return s.parse().ok();
+    }
+    let number = |from: usize, len: usize| -> Option<i64> {
+        let digits = s.get(from..from + len)?;
+        if digits.bytes().all(|b| b.is_ascii_digit()) {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    };
+    if s.as_bytes().get(4) != Some(&b'-') || s.as_bytes().get(7) != Some(&b'-') {
+        return None;
+    }
+    let (year, month, day) = (number(0, 4)?, number(5, 2)?, number(8, 2)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut secs = days_from_civil(year, month, day) * DAY;
+    if matches!(s.as_bytes().get(10), Some(b'T' | b' ')) {
+        secs += number(11, 2)? * 3600 + number(14, 2)? * 60 + number(17, 2).unwrap_or(0);
+    }
+    Some(secs)
+}
+
+// e.g. 2023-05-01T12:34:56Z
+pub fn timestamp_str(secs: i64) -> String {
+    let (year, month, day) = civil_from_days(secs.div_euclid(DAY));
+    let time = secs.rem_euclid(DAY);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        (time % 3600) / 60,
+        time % 60
+    )
+}
+
+// e.g. 2023-05-01
+pub fn date_str(secs: i64) -> String {
+    timestamp_str(secs)[..10].to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    Fresh,
+    Stale,
+    RecentlyEdited,
+}
+
+impl Freshness {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Freshness::Fresh => "fresh",
+            Freshness::Stale => "stale",
+            Freshness::RecentlyEdited => "recently-edited",
+        }
+    }
+}
+
+// The newest edit in the input, 0 if it hasn't been set
+static REFERENCE: AtomicI64 = AtomicI64::new(0);
+
+// For the whole run, once the records are read
+pub fn set_reference<'a>(records: impl Iterator<Item = &'a Record>) {
+    let newest = records
+        .flat_map(|r| [r.place_timestamp, r.boundary_timestamp])
+        .flatten()
+        .max();
+    if let Some(newest) = newest {
+        REFERENCE.store(newest, Ordering::Relaxed);
+    }
+}
+
+// Of a chain whose edits are between these. A recent edit is the more urgent to check.
+pub fn freshness(oldest: i64, newest: i64) -> Freshness {
+    let reference = match REFERENCE.load(Ordering::Relaxed) {
+        0 => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64),
+        reference => reference,
+    };
+    if reference - newest < RECENT_WITHIN {
+        Freshness::RecentlyEdited
+    } else if reference - oldest > STALE_AFTER {
+        Freshness::Stale
+    } else {
+        Freshness::Fresh
+    }
+}
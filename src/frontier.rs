@@ -0,0 +1,211 @@
+// The intermediate chains of the search, as a priority queue: the next to extend is the longest,
+// then the one with the best score. The heap only holds each chain's key & a handle to where the
+// chain is kept, so pushing & popping never moves or compares the chains themselves.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::record::Record;
+
+type Chain<'a> = Vec<&'a Record>;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Entry<'a> {
+    len: usize,
+    // Total score of the hops, higher first
+    score: isize,
+    // Ties are broken by the first & last records (which compare by OSM id), so the same records
+    // are searched in the same order each time
+    first: &'a Record,
+    last: &'a Record,
+    handle: usize,
+}
+
+impl Ord for Entry<'_> {
+    // The greatest is taken first
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.len
+            .cmp(&other.len)
+            .then(self.score.cmp(&other.score))
+            .then_with(|| other.first.cmp(self.first))
+            .then_with(|| other.last.cmp(self.last))
+            .then(other.handle.cmp(&self.handle))
+    }
+}
+
+impl PartialOrd for Entry<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Roughly how much memory an intermediate chain with room for `capacity` records takes: its heap
+// entry (& the heap's spare room), its slot, and the chain's allocation
+pub fn chain_bytes(capacity: usize) -> usize {
+    std::mem::size_of::<Entry>() * 3 / 2
+        + std::mem::size_of::<Option<Chain>>()
+        + capacity * std::mem::size_of::<&Record>()
+}
+
+#[derive(Default)]
+pub struct Frontier<'a> {
+    heap: BinaryHeap<Entry<'a>>,
+    // The chains, by handle. None once it's been taken out, until the slot is used again.
+    chains: Vec<Option<Chain<'a>>>,
+    free: Vec<usize>,
+    // Estimated, of all the chains
+    bytes: usize,
+}
+
+impl<'a> Frontier<'a> {
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn push(&mut self, score: isize, chain: Chain<'a>) {
+        self.bytes += chain_bytes(chain.capacity());
+        let entry = |handle| Entry {
+            len: chain.len(),
+            score,
+            first: chain[0],
+            last: chain[chain.len() - 1],
+            handle,
+        };
+        let handle = match self.free.pop() {
+            Some(handle) => handle,
+            None => {
+                self.chains.push(None);
+                self.chains.len() - 1
+            }
+        };
+        self.heap.push(entry(handle));
+        self.chains[handle] = Some(chain);
+    }
+
+    fn take(&mut self, handle: usize) -> Chain<'a> {
+        let chain = self.chains[handle].take().unwrap();
+        self.free.push(handle);
+        self.bytes -= chain_bytes(chain.capacity());
+        chain
+    }
+
+    // The next chain to extend, and its score
+    pub fn pop(&mut self) -> Option<(isize, Chain<'a>)> {
+        let entry = self.heap.pop()?;
+        Some((entry.score, self.take(entry.handle)))
+    }
+
+    // The chain `pop` would return
+    pub fn peek(&self) -> Option<&[&'a Record]> {
+        self.heap
+            .peek()
+            .and_then(|entry| self.chains[entry.handle].as_deref())
+    }
+
+    // In no particular order
+    pub fn chains(&self) -> impl Iterator<Item = &Chain<'a>> {
+        self.chains.iter().flatten()
+    }
+
+    pub fn into_chains(self) -> impl Iterator<Item = Chain<'a>> {
+        self.chains.into_iter().flatten()
+    }
+
+    // Only keep the chains `keep` is true for
+    pub fn retain(&mut self, mut keep: impl FnMut(&[&'a Record]) -> bool) {
+        for handle in 0..self.chains.len() {
+            if self.chains[handle]
+                .as_ref()
+                .is_some_and(|chain| !keep(chain))
+            {
+                self.take(handle);
+            }
+        }
+        let chains = &self.chains;
+        self.heap.retain(|entry| chains[entry.handle].is_some());
+    }
+
+    // Throw away the chains which would be taken last (the shortest, with the worst score) while
+    // `over(number of chains, bytes)`. Returns their first records.
+    pub fn drop_worst_while(&mut self, over: impl Fn(usize, usize) -> bool) -> Vec<&'a Record> {
+        let mut dropped = Vec::new();
+        let mut len = self.len();
+        if !over(len, self.bytes) {
+            return dropped;
+        }
+        // Worst first. Heaps can't take from that end, but this is only in a memory clean up.
+        let mut entries = std::mem::take(&mut self.heap).into_sorted_vec().into_iter();
+        for entry in entries.by_ref() {
+            dropped.push(self.take(entry.handle)[0]);
+            len -= 1;
+            if !over(len, self.bytes) {
+                break;
+            }
+        }
+        self.heap = entries.collect();
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record;
+
+    const RECORDS: &str = "\
+place_osmtype,place_id,place_name,place_type,place_lat,place_lon,boundary_osmtype,boundary_id,boundary_name,boundary_admin_level
+n,1,A,town,0,0,r,10,B,8
+n,2,B,town,0,0,r,20,C,8
+n,3,C,town,0,0,r,30,D,8
+n,4,D,town,0,0,r,40,E,8
+";
+
+    #[test]
+    fn ties_in_the_same_order() {
+        let records = record::from_csv(RECORDS);
+        let r: Vec<&Record> = records.iter().collect();
+        // All the same length & score
+        let chains = [
+            vec![r[2], r[3]],
+            vec![r[0], r[1]],
+            vec![r[1], r[2]],
+            vec![r[0], r[3]],
+        ];
+        let popped = |order: &[usize]| {
+            let mut frontier = Frontier::default();
+            for &i in order {
+                frontier.push(0, chains[i].clone());
+            }
+            std::iter::from_fn(|| frontier.pop().map(|(_score, chain)| chain)).collect::<Vec<_>>()
+        };
+        let expected = vec![
+            vec![r[0], r[1]],
+            vec![r[0], r[3]],
+            vec![r[1], r[2]],
+            vec![r[2], r[3]],
+        ];
+        assert_eq!(popped(&[0, 1, 2, 3]), expected);
+        assert_eq!(popped(&[3, 2, 1, 0]), expected);
+        assert_eq!(popped(&[1, 3, 0, 2]), expected);
+    }
+
+    #[test]
+    fn longest_then_best_score_first() {
+        let records = record::from_csv(RECORDS);
+        let r: Vec<&Record> = records.iter().collect();
+        let mut frontier = Frontier::default();
+        frontier.push(5, vec![r[0], r[1]]);
+        frontier.push(1, vec![r[1], r[2], r[3]]);
+        frontier.push(9, vec![r[2], r[3]]);
+        assert_eq!(frontier.len(), 3);
+        assert_eq!(frontier.pop().unwrap().1.len(), 3);
+        assert_eq!(frontier.pop().unwrap().0, 9);
+        assert_eq!(frontier.pop().unwrap().0, 5);
+        assert!(frontier.pop().is_none());
+        assert_eq!(frontier.bytes(), 0);
+    }
+}
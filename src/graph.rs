@@ -0,0 +1,309 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{bail, Result};
+
+use crate::chain::Chain;
+use crate::record::{OsmId, Record};
+
+// The graph of names behind the chains. There's an edge from name A to name B when a place called
+// A is inside a boundary called B. A chain is a path in this graph.
+#[derive(Debug, Default)]
+pub struct NameGraph<'a> {
+    pub names: BTreeMap<&'a str, Name<'a>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Name<'a> {
+    // The places & boundaries with this name
+    pub places: BTreeSet<OsmId>,
+    pub boundaries: BTreeSet<OsmId>,
+    // Other name → number of records
+    pub out_edges: BTreeMap<&'a str, usize>,
+    pub in_edges: BTreeMap<&'a str, usize>,
+    // Number of chains this name is in
+    pub num_chains: usize,
+    // Longest of those chains
+    pub longest_chain: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    GraphMl,
+    Dot,
+    // A sparse matrix, with the names in a separate labels file
+    MatrixMarket,
+}
+
+impl std::str::FromStr for GraphFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<GraphFormat> {
+        Ok(match s {
+            "graphml" => GraphFormat::GraphMl,
+            "dot" => GraphFormat::Dot,
+            "mtx" => GraphFormat::MatrixMarket,
+            x => bail!("Unknown graph format {:?}", x),
+        })
+    }
+}
+
+impl GraphFormat {
+    // DOT for .dot/.gv files, Matrix Market for .mtx, otherwise GraphML
+    pub fn from_filename(filename: &str) -> GraphFormat {
+        if filename.ends_with(".dot") || filename.ends_with(".gv") {
+            GraphFormat::Dot
+        } else if filename.ends_with(".mtx") {
+            GraphFormat::MatrixMarket
+        } else {
+            GraphFormat::GraphMl
+        }
+    }
+}
+
+impl<'a> NameGraph<'a> {
+    pub fn new(records: &[&'a Record]) -> NameGraph<'a> {
+        let mut graph = NameGraph::default();
+        for rec in records {
+            let place = graph.names.entry(&rec.place_name).or_default();
+            place.places.insert(rec.place_key());
+            *place.out_edges.entry(&rec.boundary_name).or_default() += 1;
+            let boundary = graph.names.entry(&rec.boundary_name).or_default();
+            boundary.boundaries.insert(rec.boundary_key());
+            *boundary.in_edges.entry(&rec.place_name).or_default() += 1;
+        }
+        graph
+    }
+
+    // Count how many of these chains each name is in
+    pub fn add_chains(&mut self, chains: &[Chain]) {
+        for chain in chains {
+            let names: BTreeSet<&str> = chain
+                .iter()
+                .map(|r| r.place_name.as_ref())
+                .chain(std::iter::once(
+                    chain[chain.len() - 1].boundary_name.as_ref(),
+                ))
+                .collect();
+            for name in names {
+                if let Some(n) = self.names.get_mut(name) {
+                    n.num_chains += 1;
+                    n.longest_chain = n.longest_chain.max(chain.len());
+                }
+            }
+        }
+    }
+
+    pub fn is_linkable(&self, name: &str) -> bool {
+        self.names
+            .get(name)
+            .is_some_and(|n| !n.places.is_empty() && !n.boundaries.is_empty())
+    }
+
+    // The edges which can be in a chain of 2 or more, i.e. one end is a linkable name.
+    // (from, to, number of records)
+    pub fn chain_edges(&self) -> impl Iterator<Item = (&'a str, &'a str, usize)> + '_ {
+        self.names.iter().flat_map(move |(from, n)| {
+            n.out_edges
+                .iter()
+                .filter(move |(to, _)| self.is_linkable(from) || self.is_linkable(to))
+                .map(move |(to, count)| (*from, *to, *count))
+        })
+    }
+
+    // Names which are both a place and a boundary, so can be in the middle of a chain
+    pub fn linkable(&self) -> impl Iterator<Item = (&&'a str, &Name<'a>)> {
+        self.names
+            .iter()
+            .filter(|(_, n)| !n.places.is_empty() && !n.boundaries.is_empty())
+    }
+}
+
+// A weakly connected part of the name graph
+#[derive(Debug)]
+pub struct Component<'a> {
+    pub num_names: usize,
+    pub num_edges: usize,
+    pub num_places: usize,
+    // Number of names in the biggest strongly connected component
+    pub largest_scc: usize,
+    // No chain in this component can be longer than this
+    pub chain_bound: usize,
+    // The name with the most edges
+    pub example: &'a str,
+}
+
+#[derive(Debug)]
+pub struct Analysis<'a> {
+    // Biggest chain_bound first
+    pub components: Vec<Component<'a>>,
+    // Size of strongly connected component → how many there are, for the ones with a cycle
+    pub cyclic_scc_sizes: BTreeMap<usize, usize>,
+    pub num_edges: usize,
+}
+
+// Tarjan's algorithm, without recursion since the graph can be huge. Returns the SCC of each node.
+// SCCs are numbered in reverse topological order, i.e. edges only go to the same or lower numbers.
+fn strongly_connected(adj: &[Vec<usize>]) -> Vec<usize> {
+    let n = adj.len();
+    let mut index = vec![usize::MAX; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = Vec::new();
+    let mut scc = vec![usize::MAX; n];
+    let mut next_index = 0;
+    let mut next_scc = 0;
+    for start in 0..n {
+        if index[start] != usize::MAX {
+            continue;
+        }
+        index[start] = next_index;
+        low[start] = next_index;
+        next_index += 1;
+        stack.push(start);
+        on_stack[start] = true;
+        // (node, next edge to look at)
+        let mut calls = vec![(start, 0)];
+        while let Some((v, i)) = calls.last_mut() {
+            let v = *v;
+            if *i < adj[v].len() {
+                let w = adj[v][*i];
+                *i += 1;
+                if index[w] == usize::MAX {
+                    index[w] = next_index;
+                    low[w] = next_index;
+                    next_index += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    calls.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+            } else {
+                calls.pop();
+                if let Some((u, _)) = calls.last() {
+                    low[*u] = low[*u].min(low[v]);
+                }
+                if low[v] == index[v] {
+                    loop {
+                        let w = stack.pop().unwrap();
+                        on_stack[w] = false;
+                        scc[w] = next_scc;
+                        if w == v {
+                            break;
+                        }
+                    }
+                    next_scc += 1;
+                }
+            }
+        }
+    }
+    scc
+}
+
+fn find_root(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+impl<'a> NameGraph<'a> {
+    // The structure of the graph, and an upper bound on the chain length in each part of it.
+    //
+    // A chain is a walk through the graph, which can leave a name at most once for each place
+    // with that name, and leave a name which isn't in a cycle at most once. So the longest chain
+    // is at most the heaviest path through the strongly connected components, where each one
+    // weighs how often a chain can leave it.
+    pub fn analyze(&self) -> Analysis<'a> {
+        let names: Vec<(&'a str, &Name<'a>)> = self.names.iter().map(|(k, v)| (*k, v)).collect();
+        let ids: BTreeMap<&str, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (*name, i))
+            .collect();
+        let adj: Vec<Vec<usize>> = names
+            .iter()
+            .map(|(_, n)| n.out_edges.keys().map(|to| ids[to]).collect())
+            .collect();
+        let num_edges = adj.iter().map(|a| a.len()).sum();
+
+        let scc = strongly_connected(&adj);
+        let num_sccs = scc.iter().map(|s| s + 1).max().unwrap_or(0);
+        let mut scc_names: Vec<Vec<usize>> = vec![Vec::new(); num_sccs];
+        for (node, s) in scc.iter().enumerate() {
+            scc_names[*s].push(node);
+        }
+        let mut cyclic_scc_sizes = BTreeMap::new();
+        // Longest chain which starts in this SCC
+        let mut best = vec![0; num_sccs];
+        for (s, members) in scc_names.iter().enumerate() {
+            let cyclic = members.len() > 1 || adj[members[0]].contains(&members[0]);
+            let weight = if cyclic {
+                *cyclic_scc_sizes.entry(members.len()).or_default() += 1;
+                members.iter().map(|m| names[*m].1.places.len()).sum()
+            } else {
+                names[members[0]].1.places.len().min(1)
+            };
+            let after = members
+                .iter()
+                .flat_map(|m| adj[*m].iter())
+                .map(|to| scc[*to])
+                .filter(|to| *to != s)
+                .map(|to| best[to])
+                .max()
+                .unwrap_or(0);
+            best[s] = weight + after;
+        }
+
+        // Weakly connected components
+        let mut parent: Vec<usize> = (0..names.len()).collect();
+        for (from, tos) in adj.iter().enumerate() {
+            for to in tos {
+                let (a, b) = (find_root(&mut parent, from), find_root(&mut parent, *to));
+                parent[a] = b;
+            }
+        }
+        let mut components: BTreeMap<usize, Component<'a>> = BTreeMap::new();
+        for (node, (name, n)) in names.iter().enumerate() {
+            let root = find_root(&mut parent, node);
+            let c = components.entry(root).or_insert(Component {
+                num_names: 0,
+                num_edges: 0,
+                num_places: 0,
+                largest_scc: 0,
+                chain_bound: 0,
+                example: name,
+            });
+            c.num_names += 1;
+            c.num_edges += adj[node].len();
+            c.num_places += n.places.len();
+            c.chain_bound = c.chain_bound.max(best[scc[node]]);
+            let size = scc_names[scc[node]].len();
+            c.largest_scc = c.largest_scc.max(size);
+            if n.in_edges.len() + n.out_edges.len() > {
+                let e = &self.names[c.example];
+                e.in_edges.len() + e.out_edges.len()
+            } {
+                c.example = name;
+            }
+        }
+        let mut components: Vec<Component<'a>> = components
+            .into_values()
+            .filter(|c| c.num_edges > 0)
+            .collect();
+        components.sort_by_key(|c| {
+            (
+                std::cmp::Reverse(c.chain_bound),
+                std::cmp::Reverse(c.num_names),
+                c.example,
+            )
+        });
+
+        Analysis {
+            components,
+            cyclic_scc_sizes,
+            num_edges,
+        }
+    }
+}
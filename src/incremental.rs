@@ -0,0 +1,122 @@
+// Re-use the chains from an earlier run, and only search again from the records which a change
+// in the input could affect.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use anyhow::Result;
+
+use crate::input::{self, CsvFormat};
+use crate::record::{OsmId, Record};
+use crate::results::Hop;
+
+fn record_hash(rec: &Record) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    rec.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Every record in these files (unparseable rows are skipped), and do something with each
+fn for_each_record(
+    filenames: &[String],
+    format: &CsvFormat,
+    mut f: impl FnMut(Record),
+) -> Result<()> {
+    for filename in filenames {
+        let mut rdr = input::csv_reader(filename, format)?;
+        for rec in rdr.deserialize().flatten() {
+            f(rec);
+        }
+    }
+    Ok(())
+}
+
+// The names of places & boundaries in records which are only in one of these inputs
+pub fn changed_names(
+    old_inputs: &[String],
+    new_inputs: &[String],
+    format: &CsvFormat,
+) -> Result<HashSet<String>> {
+    let mut changed = HashSet::new();
+    let mut add = |rec: Record| {
+        changed.insert(rec.place_name.into_owned());
+        changed.insert(rec.boundary_name.into_owned());
+    };
+
+    let mut new_hashes = HashSet::new();
+    for_each_record(new_inputs, format, |rec| {
+        new_hashes.insert(record_hash(&rec));
+    })?;
+    let mut old_hashes = HashSet::new();
+    for_each_record(old_inputs, format, |rec| {
+        let hash = record_hash(&rec);
+        old_hashes.insert(hash);
+        if !new_hashes.contains(&hash) {
+            add(rec);
+        }
+    })?;
+    drop(new_hashes);
+    for_each_record(new_inputs, format, |rec| {
+        if !old_hashes.contains(&record_hash(&rec)) {
+            add(rec);
+        }
+    })?;
+    Ok(changed)
+}
+
+// The records whose longest chain could be different, i.e. a changed name can be reached from it
+pub fn affected<'a>(records: &[&'a Record], changed: &HashSet<String>) -> HashSet<&'a Record> {
+    // boundary name → names of the places in it
+    let mut places_in: HashMap<&str, Vec<&str>> = HashMap::new();
+    for rec in records {
+        places_in
+            .entry(&rec.boundary_name)
+            .or_default()
+            .push(&rec.place_name);
+    }
+    // Names which lead to a changed name
+    let mut reaches_changed: HashSet<&str> = changed.iter().map(|n| n.as_str()).collect();
+    let mut todo: Vec<&str> = reaches_changed.iter().copied().collect();
+    while let Some(name) = todo.pop() {
+        for place_name in places_in.get(name).into_iter().flatten() {
+            if reaches_changed.insert(place_name) {
+                todo.push(place_name);
+            }
+        }
+    }
+    records
+        .iter()
+        .filter(|r| {
+            changed.contains(r.place_name.as_ref())
+                || reaches_changed.contains(r.boundary_name.as_ref())
+        })
+        .copied()
+        .collect()
+}
+
+// The earlier chains which start with an unaffected record, with the records from this input.
+pub fn reuse_chains<'a>(
+    old_chains: &[Vec<Hop>],
+    records: &[&'a Record],
+    affected: &HashSet<&'a Record>,
+) -> HashMap<&'a Record, Vec<&'a Record>> {
+    let by_key: HashMap<(OsmId, OsmId), &'a Record> = records
+        .iter()
+        .map(|r| ((r.place_key(), r.boundary_key()), *r))
+        .collect();
+    old_chains
+        .iter()
+        .filter_map(|hops| {
+            let chain: Vec<&Record> = hops
+                .iter()
+                .map(|h| by_key.get(&(h.place, h.boundary)).copied())
+                .collect::<Option<_>>()?;
+            if affected.contains(chain[0]) {
+                None
+            } else {
+                Some((chain[0], chain))
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,308 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+use flate2::read::MultiGzDecoder;
+
+use crate::exit::Status;
+use crate::record::{osm_type_name, OsmId, Record};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    // Look at the first few bytes of the file, falling back to the file extension
+    pub fn detect(magic: &[u8], filename: &str) -> Compression {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if magic.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if !magic.is_empty() {
+            Compression::None
+        } else if filename.ends_with(".gz") {
+            Compression::Gzip
+        } else if filename.ends_with(".zst") {
+            Compression::Zstd
+        } else if filename.ends_with(".bz2") {
+            Compression::Bzip2
+        } else if filename.ends_with(".xz") {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+
+    // The external program used to decompress this, if we don't do it ourselves
+    fn decompressor(&self) -> Option<&'static str> {
+        match self {
+            Compression::None | Compression::Gzip => None,
+            Compression::Zstd => Some("zstd"),
+            Compression::Bzip2 => Some("bzip2"),
+            Compression::Xz => Some("xz"),
+        }
+    }
+}
+
+// Reads the stdout of a decompressor process, and fails if that process fails.
+struct ChildReader {
+    program: &'static str,
+    child: Child,
+    stdout: ChildStdout,
+}
+
+impl Read for ChildReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            let status = self.child.wait()?;
+            if !status.success() {
+                return Err(std::io::Error::other(format!(
+                    "{} failed: {}",
+                    self.program, status
+                )));
+            }
+        }
+        Ok(n)
+    }
+}
+
+// Open a (possibly compressed) input file. gzip is decompressed in process, zstd, bzip2 & xz
+// are decompressed with the usual command line tool (which needs to be installed).
+pub fn open_input(filename: &str) -> Result<Box<dyn Read>> {
+    let mut file = BufReader::new(
+        File::open(filename).with_context(|| format!("Could not open {}", filename))?,
+    );
+    let compression = Compression::detect(file.fill_buf()?, filename);
+
+    Ok(match compression.decompressor() {
+        None if compression == Compression::Gzip => Box::new(MultiGzDecoder::new(file)),
+        None => Box::new(file),
+        Some(program) => {
+            let mut child = Command::new(program)
+                .arg("-dc")
+                .arg("--")
+                .arg(filename)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .spawn()
+                .with_context(|| {
+                    format!(
+                        "{} looks {:?} compressed, but could not run {}",
+                        filename, compression, program
+                    )
+                })?;
+            let stdout = child.stdout.take().unwrap();
+            Box::new(ChildReader {
+                program,
+                child,
+                stdout,
+            })
+        }
+    })
+}
+
+// Add this record to its place, unless that place already has a record for this boundary.
+// Returns false for a duplicate.
+pub fn add_record(points_in_boundary: &mut HashMap<OsmId, Vec<Record>>, record: Record) -> bool {
+    let records = points_in_boundary.entry(record.place_key()).or_default();
+    if records
+        .iter()
+        .any(|r| r.boundary_key() == record.boundary_key())
+    {
+        return false;
+    }
+    records.push(record);
+    true
+}
+
+// How the input CSV files are laid out
+#[derive(Debug, Clone)]
+pub struct CsvFormat {
+    pub delimiter: u8,
+    // None = no quoting at all
+    pub quote: Option<u8>,
+    // (expected field name, column name in the file)
+    pub columns: Vec<(String, String)>,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        CsvFormat {
+            delimiter: b',',
+            quote: Some(b'"'),
+            columns: Vec::new(),
+        }
+    }
+}
+
+// A single byte char, with some names for ones which are awkward to type on a command line
+pub fn parse_csv_char(s: &str) -> Result<u8> {
+    Ok(match s {
+        "tab" | "\\t" | "\t" => b'\t',
+        "comma" => b',',
+        "semicolon" => b';',
+        "pipe" => b'|',
+        s if s.len() == 1 => s.as_bytes()[0],
+        s => bail!("{:?} is not a single byte character", s),
+    })
+}
+
+// Parse a `FIELD=COLUMN` column mapping
+pub fn parse_column_mapping(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((field, column)) if !field.is_empty() && !column.is_empty() => {
+            Ok((field.to_string(), column.to_string()))
+        }
+        _ => bail!("Column mapping {:?} should be FIELD=COLUMN", s),
+    }
+}
+
+// Open an input file as CSV, renaming the columns from the file to what we expect.
+pub fn csv_reader(filename: &str, format: &CsvFormat) -> Result<csv::Reader<Box<dyn Read>>> {
+    let mut builder = csv::ReaderBuilder::new();
+    builder.delimiter(format.delimiter);
+    match format.quote {
+        Some(q) => builder.quote(q),
+        None => builder.quoting(false),
+    };
+    let mut rdr = builder.from_reader(open_input(filename).context(Status::BadInput)?);
+
+    if !format.columns.is_empty() {
+        let headers = rdr
+            .byte_headers()
+            .with_context(|| format!("Could not read header of {}", filename))
+            .context(Status::BadInput)?
+            .iter()
+            .map(|column| {
+                format
+                    .columns
+                    .iter()
+                    .find(|(_field, source)| source.as_bytes() == column)
+                    .map_or(column, |(field, _source)| field.as_bytes())
+            })
+            .collect::<csv::ByteRecord>();
+        rdr.set_byte_headers(headers);
+    }
+
+    Ok(rdr)
+}
+
+// Rows which couldn't be parsed, when we're not being strict about it
+#[derive(Debug, Default)]
+pub struct ParseErrors {
+    // error kind → count
+    pub counts: BTreeMap<&'static str, usize>,
+    // (filename, line, kind, message)
+    pub errors: Vec<(String, Option<u64>, &'static str, String)>,
+}
+
+// Every kind of error `ParseErrors::add` records
+const PARSE_ERROR_KINDS: &[&str] = &[
+    "invalid utf8",
+    "wrong number of fields",
+    "invalid integer",
+    "invalid float",
+    "invalid bool",
+    "missing field",
+    "invalid value",
+];
+
+impl ParseErrors {
+    // Record this error, or return it if it's not about one bad row (e.g. an IO error), in which
+    // case we can't go on.
+    pub fn add(&mut self, filename: &str, err: csv::Error) -> Result<()> {
+        let kind = match err.kind() {
+            csv::ErrorKind::Utf8 { .. } => "invalid utf8",
+            csv::ErrorKind::UnequalLengths { .. } => "wrong number of fields",
+            csv::ErrorKind::Deserialize { err: de_err, .. } => match de_err.kind() {
+                csv::DeserializeErrorKind::ParseInt(_) => "invalid integer",
+                csv::DeserializeErrorKind::ParseFloat(_) => "invalid float",
+                csv::DeserializeErrorKind::ParseBool(_) => "invalid bool",
+                csv::DeserializeErrorKind::UnexpectedEndOfRow => "missing field",
+                _ => "invalid value",
+            },
+            _ => {
+                return Err(err).with_context(|| format!("Error reading {}", filename));
+            }
+        };
+        let line = err.position().map(|p| p.line());
+        self.add_row(filename, line, kind, err.to_string());
+        Ok(())
+    }
+
+    // Record an error found before, e.g. kept in the --cache
+    pub fn add_row(&mut self, filename: &str, line: Option<u64>, kind: &str, message: String) {
+        let kind = PARSE_ERROR_KINDS
+            .iter()
+            .find(|k| **k == kind)
+            .unwrap_or(&"invalid value");
+        *self.counts.entry(kind).or_default() += 1;
+        self.errors
+            .push((filename.to_string(), line, kind, message));
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    pub fn write_report(&self, filename: &str) -> Result<()> {
+        let mut wtr = csv::Writer::from_path(filename)
+            .with_context(|| format!("Could not create {}", filename))?;
+        wtr.write_record(["filename", "line", "kind", "message"])?;
+        for (input, line, kind, message) in self.errors.iter() {
+            wtr.write_record([
+                input.as_str(),
+                &line.map(|l| l.to_string()).unwrap_or_default(),
+                kind,
+                message.as_str(),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+// How many example places are kept for each unknown place tag
+pub const NUM_UNKNOWN_TAG_EXAMPLES: usize = 5;
+
+// Every unknown `place=*` value with how many records have it & some example places, most common
+// first, as CSV. For curating the lists of place values which are used or ignored.
+pub fn write_unknown_tags_report(
+    filename: &str,
+    counts: &HashMap<String, usize>,
+    examples: &HashMap<String, Vec<OsmId>>,
+) -> Result<()> {
+    let mut counts: Vec<(&String, &usize)> = counts.iter().collect();
+    counts.sort_by_key(|(tag, count)| (std::cmp::Reverse(**count), *tag));
+
+    let mut wtr = csv::Writer::from_path(filename)
+        .with_context(|| format!("Could not create {}", filename))?;
+    wtr.write_record(["place", "count", "examples"])?;
+    for (tag, count) in counts {
+        let examples = examples
+            .get(tag)
+            .map(|ids| {
+                ids.iter()
+                    .map(|(t, id)| format!("{}/{}", osm_type_name(*t), id))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .unwrap_or_default();
+        wtr.write_record([tag.as_str(), &count.to_string(), &examples])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,631 @@
+// The text in the human readable outputs, in different languages.
+//
+// Each message is a template where `{name}` is replaced. A language is either one of the built
+// in ones, or a file of `key = message` lines (`#` for comments) which replace the English
+// messages, so a translation can be tried out without changing the code.
+
+use anyhow::{bail, Context, Result};
+use separator::Separatable;
+
+use crate::chain::ChainStats;
+use crate::freshness::{self, Freshness};
+use crate::output::{chain_shortlink, html_escape, markdown_escape, overpass_url};
+use crate::record::Record;
+
+#[derive(Debug, Clone)]
+pub struct Messages {
+    // "There is a {place_tag} called {place}{country} in {boundary} ({admin_level})"
+    pub sentence: String,
+    pub node: String,
+    pub way: String,
+    pub relation: String,
+    // text output, "chain of len {length}:"
+    pub chain_of_len: String,
+    // "Chain {rank}: {length} places, from {start} to {end}"
+    pub chain_title: String,
+    // "{total} km in total, bounding box {bbox}"
+    pub chain_distance: String,
+    // "{population} people live in its places", when some have a population
+    pub chain_population: String,
+    // "Edited between {oldest} and {newest}", when there are timestamps, and one of these if the
+    // chain isn't fresh (see freshness)
+    pub chain_edits: String,
+    pub chain_stale: String,
+    pub chain_recently_edited: String,
+    // The tree format: "{chains} chains of up to {longest} places, ending in:", and what its `*`
+    // means
+    pub family_title: String,
+    pub tree_start_note: String,
+    // "{hop} km from the previous place, {cumulative} km so far"
+    pub hop_distance: String,
+    // Link texts for the Overpass query and osm.org map of a chain
+    pub overpass_link: String,
+    pub map_link: String,
+    pub report_title: String,
+    // The story format: "Start in the {place_type} of {place}{country}, which sits inside {boundary}."
+    pub story_start: String,
+    // "Inside {container} you'll find a {place_type} also called {place}{country}, {hop} km away…"
+    pub story_hop: String,
+    // "After {hops} hops and {total} km you end in {end}."
+    pub story_end: String,
+    // One post in a thread: "{place} ({place_type}) is in {boundary}."
+    pub thread_hop: String,
+    // "Found {num_chains} chains, the longest has {longest} places. These are the top {shown}."
+    pub report_summary: String,
+    pub length: String,
+    pub chains: String,
+    // osmwiki table headings
+    pub rank: String,
+    pub distance_km: String,
+    pub places: String,
+    // --per-country report
+    pub per_country_title: String,
+    pub country: String,
+    pub thousands_separator: String,
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Messages {
+            sentence: "There is a {place_tag} called {place}{country} in {boundary} ({admin_level})"
+                .to_string(),
+            node: "node".to_string(),
+            way: "way".to_string(),
+            relation: "rel.".to_string(),
+            chain_of_len: "chain of len {length}:".to_string(),
+            chain_title: "Chain {rank}: {length} places, from {start} to {end}".to_string(),
+            chain_distance: "{total} km in total, bounding box {bbox}".to_string(),
+            chain_population: "{population} people live in its places".to_string(),
+            chain_edits: "Edited between {oldest} and {newest}".to_string(),
+            chain_stale: "parts of it haven't been edited for years, check they're still right"
+                .to_string(),
+            chain_recently_edited: "parts of it were edited recently, and may still be changing"
+                .to_string(),
+            family_title: "{chains} chains of up to {longest} places, ending in:".to_string(),
+            tree_start_note: "* a chain starts here, and longer ones come in before it"
+                .to_string(),
+            hop_distance: "{hop} km from the previous place, {cumulative} km so far".to_string(),
+            overpass_link: "All the objects on Overpass Turbo".to_string(),
+            map_link: "Map".to_string(),
+            report_title: "Longest place/boundary chains".to_string(),
+            story_start: "Start in the {place_type} of {place}{country}, which sits inside {boundary}."
+                .to_string(),
+            story_hop: "Inside {container} you'll find a {place_type} also called {place}{country}, {hop} km away, which is in {boundary}."
+                .to_string(),
+            story_end: "After {hops} hops and {total} km you end in {end}.".to_string(),
+            thread_hop: "{place} ({place_type}{country}) is in {boundary}.".to_string(),
+            report_summary:
+                "Found {num_chains} chains, the longest has {longest} places. These are the top {shown}."
+                    .to_string(),
+            length: "Length".to_string(),
+            chains: "Chains".to_string(),
+            rank: "Rank".to_string(),
+            distance_km: "Distance (km)".to_string(),
+            places: "Places".to_string(),
+            per_country_title: "Longest chain in each country".to_string(),
+            country: "Country".to_string(),
+            thousands_separator: ",".to_string(),
+        }
+    }
+}
+
+const GERMAN: &[(&str, &str)] = &[
+    ("sentence", "Es gibt einen {place_tag} namens {place}{country} in {boundary} ({admin_level})"),
+    ("node", "Knoten"),
+    ("way", "Weg"),
+    ("relation", "Rel."),
+    ("chain_of_len", "Kette der Länge {length}:"),
+    ("chain_title", "Kette {rank}: {length} Orte, von {start} bis {end}"),
+    ("chain_distance", "Insgesamt {total} km, Begrenzungsrahmen {bbox}"),
+    ("chain_population", "{population} Menschen leben in ihren Orten"),
+    ("chain_edits", "Bearbeitet zwischen {oldest} und {newest}"),
+    ("chain_stale", "Teile davon wurden seit Jahren nicht bearbeitet, bitte prüfen, ob sie noch stimmen"),
+    ("chain_recently_edited", "Teile davon wurden kürzlich bearbeitet und könnten sich noch ändern"),
+    ("family_title", "{chains} Ketten mit bis zu {longest} Orten, die so enden:"),
+    ("tree_start_note", "* hier beginnt eine Kette, und längere kommen davor hinzu"),
+    ("hop_distance", "{hop} km vom vorherigen Ort, bisher {cumulative} km"),
+    ("overpass_link", "Alle Objekte in Overpass Turbo"),
+    ("map_link", "Karte"),
+    ("report_title", "Die längsten Ort/Grenze-Ketten"),
+    ("story_start", "Es beginnt im Ort ({place_type}) {place}{country}, der in {boundary} liegt."),
+    ("story_hop", "In {container} gibt es einen Ort ({place_type}), der auch {place}{country} heißt, {hop} km entfernt, und in {boundary} liegt."),
+    ("story_end", "Nach {hops} Schritten und {total} km endet es in {end}."),
+    ("thread_hop", "{place} ({place_type}{country}) liegt in {boundary}."),
+    ("report_summary", "{num_chains} Ketten gefunden, die längste hat {longest} Orte. Hier sind die besten {shown}."),
+    ("length", "Länge"),
+    ("chains", "Ketten"),
+    ("rank", "Rang"),
+    ("distance_km", "Entfernung (km)"),
+    ("places", "Orte"),
+    ("per_country_title", "Die längste Kette in jedem Land"),
+    ("country", "Land"),
+    ("thousands_separator", "."),
+];
+
+const FRENCH: &[(&str, &str)] = &[
+    ("sentence", "Il y a un {place_tag} appelé {place}{country} dans {boundary} ({admin_level})"),
+    ("node", "nœud"),
+    ("way", "chemin"),
+    ("relation", "rel."),
+    ("chain_of_len", "chaîne de longueur {length} :"),
+    ("chain_title", "Chaîne {rank} : {length} lieux, de {start} à {end}"),
+    ("chain_distance", "{total} km au total, emprise {bbox}"),
+    ("chain_population", "{population} habitants dans ses lieux"),
+    ("chain_edits", "Modifiée entre le {oldest} et le {newest}"),
+    ("chain_stale", "certaines parties n'ont pas été modifiées depuis des années, vérifiez qu'elles sont toujours justes"),
+    ("chain_recently_edited", "certaines parties ont été modifiées récemment et peuvent encore changer"),
+    ("family_title", "{chains} chaînes d'au plus {longest} lieux, qui finissent par :"),
+    ("tree_start_note", "* une chaîne commence ici, et de plus longues arrivent avant"),
+    ("hop_distance", "{hop} km depuis le lieu précédent, {cumulative} km jusqu'ici"),
+    ("overpass_link", "Tous les objets dans Overpass Turbo"),
+    ("map_link", "Carte"),
+    ("report_title", "Les plus longues chaînes lieu/limite"),
+    ("story_start", "Commencez au lieu ({place_type}) {place}{country}, qui se trouve dans {boundary}."),
+    ("story_hop", "Dans {container}, on trouve un lieu ({place_type}) qui s'appelle aussi {place}{country}, à {hop} km, qui est dans {boundary}."),
+    ("story_end", "Après {hops} étapes et {total} km, vous arrivez à {end}."),
+    ("thread_hop", "{place} ({place_type}{country}) est dans {boundary}."),
+    ("report_summary", "{num_chains} chaînes trouvées, la plus longue a {longest} lieux. Voici les {shown} premières."),
+    ("length", "Longueur"),
+    ("chains", "Chaînes"),
+    ("rank", "Rang"),
+    ("distance_km", "Distance (km)"),
+    ("places", "Lieux"),
+    ("per_country_title", "La plus longue chaîne de chaque pays"),
+    ("country", "Pays"),
+    ("thousands_separator", "\u{202f}"),
+];
+
+const SPANISH: &[(&str, &str)] = &[
+    ("sentence", "Hay un {place_tag} llamado {place}{country} en {boundary} ({admin_level})"),
+    ("node", "nodo"),
+    ("way", "vía"),
+    ("relation", "rel."),
+    ("chain_of_len", "cadena de longitud {length}:"),
+    ("chain_title", "Cadena {rank}: {length} lugares, de {start} a {end}"),
+    ("chain_distance", "{total} km en total, recuadro {bbox}"),
+    ("chain_population", "{population} habitantes en sus lugares"),
+    ("chain_edits", "Editada entre el {oldest} y el {newest}"),
+    ("chain_stale", "partes de ella no se han editado en años, comprueba que siguen siendo correctas"),
+    ("chain_recently_edited", "partes de ella se editaron hace poco y pueden seguir cambiando"),
+    ("family_title", "{chains} cadenas de hasta {longest} lugares, que terminan en:"),
+    ("tree_start_note", "* aquí empieza una cadena, y otras más largas llegan antes"),
+    ("hop_distance", "{hop} km desde el lugar anterior, {cumulative} km hasta ahora"),
+    ("overpass_link", "Todos los objetos en Overpass Turbo"),
+    ("map_link", "Mapa"),
+    ("report_title", "Las cadenas lugar/límite más largas"),
+    ("story_start", "Empieza en el lugar ({place_type}) {place}{country}, que está dentro de {boundary}."),
+    ("story_hop", "Dentro de {container} hay un lugar ({place_type}) que también se llama {place}{country}, a {hop} km, que está en {boundary}."),
+    ("story_end", "Tras {hops} saltos y {total} km terminas en {end}."),
+    ("thread_hop", "{place} ({place_type}{country}) está en {boundary}."),
+    ("report_summary", "Se encontraron {num_chains} cadenas, la más larga tiene {longest} lugares. Estas son las {shown} primeras."),
+    ("length", "Longitud"),
+    ("chains", "Cadenas"),
+    ("rank", "Puesto"),
+    ("distance_km", "Distancia (km)"),
+    ("places", "Lugares"),
+    ("per_country_title", "La cadena más larga de cada país"),
+    ("country", "País"),
+    ("thousands_separator", "."),
+];
+
+pub const LANGUAGES: &[&str] = &["en", "de", "fr", "es"];
+
+// " ([Wikipedia](…), [Wikidata](…))", or nothing
+fn wiki_links_markdown((wikipedia, wikidata): (Option<String>, Option<String>)) -> String {
+    let links: Vec<String> = vec![("Wikipedia", wikipedia), ("Wikidata", wikidata)]
+        .into_iter()
+        .filter_map(|(name, url)| url.map(|url| format!("[{}]({})", name, url)))
+        .collect();
+    if links.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", links.join(", "))
+    }
+}
+
+pub fn wiki_links_html((wikipedia, wikidata): (Option<String>, Option<String>)) -> String {
+    let links: Vec<String> = vec![("Wikipedia", wikipedia), ("Wikidata", wikidata)]
+        .into_iter()
+        .filter_map(|(name, url)| {
+            url.map(|url| format!("<a href=\"{}\">{}</a>", html_escape(&url), name))
+        })
+        .collect();
+    if links.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", links.join(", "))
+    }
+}
+
+// Replace each `{name}` in `template`, in one pass so values can contain `{`
+fn fill(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut s = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        s.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            vars.iter()
+                .find(|(name, _)| *name == &rest[1..end])
+                .map(|(_, value)| (end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                s.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                s.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    s.push_str(rest);
+    s
+}
+
+impl Messages {
+    // A built in language code, or the filename of a translation
+    pub fn load(lang: &str) -> Result<Messages> {
+        let mut messages = Messages::default();
+        match lang {
+            "en" => {}
+            "de" => messages.set_all(GERMAN)?,
+            "fr" => messages.set_all(FRENCH)?,
+            "es" => messages.set_all(SPANISH)?,
+            filename => {
+                let contents = std::fs::read_to_string(filename).with_context(|| {
+                    format!(
+                        "{} is not a language ({}) or a translation file",
+                        filename,
+                        LANGUAGES.join(", ")
+                    )
+                })?;
+                for (lineno, line) in contents.lines().enumerate() {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    let (key, value) = match line.split_once('=') {
+                        Some(kv) => kv,
+                        None => bail!("{}:{}: expected key = message", filename, lineno + 1),
+                    };
+                    messages
+                        .set(key.trim(), value.trim())
+                        .with_context(|| format!("{}:{}", filename, lineno + 1))?;
+                }
+            }
+        }
+        Ok(messages)
+    }
+
+    fn set_all(&mut self, pairs: &[(&str, &str)]) -> Result<()> {
+        for (key, value) in pairs {
+            self.set(key, value)?;
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let field = match key {
+            "sentence" => &mut self.sentence,
+            "node" => &mut self.node,
+            "way" => &mut self.way,
+            "relation" => &mut self.relation,
+            "chain_of_len" => &mut self.chain_of_len,
+            "chain_title" => &mut self.chain_title,
+            "chain_distance" => &mut self.chain_distance,
+            "chain_population" => &mut self.chain_population,
+            "chain_edits" => &mut self.chain_edits,
+            "chain_stale" => &mut self.chain_stale,
+            "chain_recently_edited" => &mut self.chain_recently_edited,
+            "family_title" => &mut self.family_title,
+            "tree_start_note" => &mut self.tree_start_note,
+            "hop_distance" => &mut self.hop_distance,
+            "overpass_link" => &mut self.overpass_link,
+            "map_link" => &mut self.map_link,
+            "report_title" => &mut self.report_title,
+            "story_start" => &mut self.story_start,
+            "story_hop" => &mut self.story_hop,
+            "story_end" => &mut self.story_end,
+            "thread_hop" => &mut self.thread_hop,
+            "report_summary" => &mut self.report_summary,
+            "length" => &mut self.length,
+            "chains" => &mut self.chains,
+            "rank" => &mut self.rank,
+            "distance_km" => &mut self.distance_km,
+            "places" => &mut self.places,
+            "per_country_title" => &mut self.per_country_title,
+            "country" => &mut self.country,
+            "thousands_separator" => &mut self.thousands_separator,
+            _ => bail!("Unknown message {:?}", key),
+        };
+        *field = value.to_string();
+        Ok(())
+    }
+
+    pub fn number(&self, n: usize) -> String {
+        n.separated_string().replace(',', &self.thousands_separator)
+    }
+
+    fn osm_id(&self, t: char, id: u64) -> String {
+        let t = match t {
+            'n' => &self.node,
+            'w' => &self.way,
+            'r' => &self.relation,
+            _ => unreachable!(),
+        };
+        format!("{} {}", t, self.number(id as usize))
+    }
+
+    pub fn chain_of_len(&self, len: usize) -> String {
+        fill(&self.chain_of_len, &[("length", &len.to_string())])
+    }
+
+    pub fn family_title(&self, num_chains: usize, longest: usize) -> String {
+        fill(
+            &self.family_title,
+            &[
+                ("chains", &self.number(num_chains)),
+                ("longest", &longest.to_string()),
+            ],
+        )
+    }
+
+    // e.g. "Chain 3: 17 places, from Paris to Texas"
+    pub fn chain_title(&self, idx: usize, chain: &[&Record]) -> String {
+        fill(
+            &self.chain_title,
+            &[
+                ("rank", &(idx + 1).to_string()),
+                ("length", &chain.len().to_string()),
+                ("start", &chain[0].place_name),
+                ("end", &chain[chain.len() - 1].boundary_name),
+            ],
+        )
+    }
+
+    pub fn chain_distance(&self, stats: &ChainStats) -> String {
+        fill(
+            &self.chain_distance,
+            &[
+                ("total", &self.km(stats.total_m)),
+                ("bbox", &stats.bbox_str()),
+            ],
+        )
+    }
+
+    // None if none of the places have a population
+    pub fn chain_population(&self, stats: &ChainStats) -> Option<String> {
+        let population = stats.total_population?;
+        Some(fill(
+            &self.chain_population,
+            &[("population", &self.number(population as usize))],
+        ))
+    }
+
+    // e.g. "Edited between 2012-03-04 and 2024-01-02, parts of it were edited recently…". None if
+    // there are no timestamps.
+    pub fn chain_edits(&self, stats: &ChainStats) -> Option<String> {
+        let edits = fill(
+            &self.chain_edits,
+            &[
+                ("oldest", &freshness::date_str(stats.oldest_edit?)),
+                ("newest", &freshness::date_str(stats.newest_edit?)),
+            ],
+        );
+        Some(match stats.freshness()? {
+            Freshness::Fresh => edits,
+            Freshness::Stale => format!("{}, {}", edits, self.chain_stale),
+            Freshness::RecentlyEdited => format!("{}, {}", edits, self.chain_recently_edited),
+        })
+    }
+
+    // Links to see the whole chain, in markdown
+    pub fn chain_links_markdown(&self, chain: &[&Record], stats: &ChainStats) -> String {
+        format!(
+            "[{}]({}) · [{}]({})",
+            self.overpass_link,
+            overpass_url(chain),
+            self.map_link,
+            chain_shortlink(stats)
+        )
+    }
+
+    pub fn chain_links_html(&self, chain: &[&Record], stats: &ChainStats) -> String {
+        format!(
+            "<a href=\"{}\">{}</a> · <a href=\"{}\">{}</a>",
+            html_escape(&overpass_url(chain)),
+            html_escape(&self.overpass_link),
+            chain_shortlink(stats),
+            html_escape(&self.map_link)
+        )
+    }
+
+    // How far it is to the place at `i` in the chain
+    pub fn hop_distance(&self, stats: &ChainStats, i: usize) -> String {
+        fill(
+            &self.hop_distance,
+            &[
+                ("hop", &self.km(stats.hop_m[i])),
+                ("cumulative", &self.km(stats.cumulative_m[i])),
+            ],
+        )
+    }
+
+    pub fn km(&self, metres: f64) -> String {
+        self.number((metres / 1000.).round() as usize)
+    }
+
+    // The whole chain as one paragraph of prose, without any markup
+    pub fn story(&self, chain: &[&Record], stats: &ChainStats) -> String {
+        let country = |r: &Record| {
+            r.country_code
+                .as_ref()
+                .map(|c| format!(" ({})", c))
+                .unwrap_or_default()
+        };
+        let place_type = |r: &Record| r.place_type.replace('_', " ");
+        let mut sentences = Vec::with_capacity(chain.len() + 1);
+        sentences.push(fill(
+            &self.story_start,
+            &[
+                ("place_type", &place_type(chain[0])),
+                ("place", &chain[0].place_name),
+                ("country", &country(chain[0])),
+                ("boundary", &chain[0].boundary_name),
+            ],
+        ));
+        for (i, pair) in chain.windows(2).enumerate() {
+            let r = pair[1];
+            sentences.push(fill(
+                &self.story_hop,
+                &[
+                    ("container", &pair[0].boundary_name),
+                    ("place_type", &place_type(r)),
+                    ("place", &r.place_name),
+                    ("country", &country(r)),
+                    ("hop", &self.km(stats.hop_m[i + 1])),
+                    ("boundary", &r.boundary_name),
+                ],
+            ));
+        }
+        sentences.push(fill(
+            &self.story_end,
+            &[
+                ("hops", &self.number(chain.len() - 1)),
+                ("total", &self.km(stats.total_m)),
+                ("end", &chain[chain.len() - 1].boundary_name),
+            ],
+        ));
+        sentences.join(" ")
+    }
+
+    pub fn thread_hop(&self, r: &Record) -> String {
+        fill(
+            &self.thread_hop,
+            &[
+                ("place", &r.place_name),
+                ("place_type", &r.place_type.replace('_', " ")),
+                (
+                    "country",
+                    &r.country_code
+                        .as_ref()
+                        .map(|c| format!(", {}", c))
+                        .unwrap_or_default(),
+                ),
+                ("boundary", &r.boundary_name),
+            ],
+        )
+    }
+
+    pub fn report_summary(&self, num_chains: usize, longest: usize, shown: usize) -> String {
+        fill(
+            &self.report_summary,
+            &[
+                ("num_chains", &self.number(num_chains)),
+                ("longest", &self.number(longest)),
+                ("shown", &self.number(shown)),
+            ],
+        )
+    }
+
+    // The sentence for one place in a chain, in markdown
+    pub fn sentence_markdown(&self, r: &Record) -> String {
+        let mut s = fill(
+            &self.sentence,
+            &[
+                ("place_tag", &format!("`place={}`", r.place_type)),
+                (
+                    "place",
+                    &format!(
+                        "[{} ({})]({}){}",
+                        markdown_escape(&r.place_name),
+                        self.osm_id(r.place_osmtype, r.place_id),
+                        r.place_url(),
+                        wiki_links_markdown(r.place_wiki_urls()),
+                    ),
+                ),
+                (
+                    "country",
+                    &r.country_code
+                        .as_ref()
+                        .map(|c| format!(" ({})", c))
+                        .unwrap_or_default(),
+                ),
+                (
+                    "boundary",
+                    &format!(
+                        "[{} ({})]({}){}",
+                        markdown_escape(&r.boundary_name),
+                        self.osm_id(r.boundary_osmtype, r.boundary_id),
+                        r.boundary_url(),
+                        wiki_links_markdown(r.boundary_wiki_urls()),
+                    ),
+                ),
+                (
+                    "admin_level",
+                    &format!("`admin_level={}`", r.admin_level_str()),
+                ),
+            ],
+        );
+        if r.boundary_type() != "administrative" {
+            s.push_str(&format!(" (`boundary={}`)", r.boundary_type()));
+        }
+        s
+    }
+
+    // The sentence for one place in a chain, in HTML
+    pub fn sentence_html(&self, r: &Record) -> String {
+        let mut s = fill(
+            &html_escape(&self.sentence),
+            &[
+                (
+                    "place_tag",
+                    &format!("<code>place={}</code>", html_escape(&r.place_type)),
+                ),
+                (
+                    "place",
+                    &format!(
+                        "<a href=\"{}\">{} ({})</a>{}",
+                        r.place_url(),
+                        html_escape(&r.place_name),
+                        html_escape(&self.osm_id(r.place_osmtype, r.place_id)),
+                        wiki_links_html(r.place_wiki_urls()),
+                    ),
+                ),
+                (
+                    "country",
+                    &r.country_code
+                        .as_ref()
+                        .map(|c| format!(" ({})", html_escape(c)))
+                        .unwrap_or_default(),
+                ),
+                (
+                    "boundary",
+                    &format!(
+                        "<a href=\"{}\">{} ({})</a>{}",
+                        r.boundary_url(),
+                        html_escape(&r.boundary_name),
+                        html_escape(&self.osm_id(r.boundary_osmtype, r.boundary_id)),
+                        wiki_links_html(r.boundary_wiki_urls()),
+                    ),
+                ),
+                (
+                    "admin_level",
+                    &format!(
+                        "<code>admin_level={}</code>",
+                        html_escape(&r.admin_level_str())
+                    ),
+                ),
+            ],
+        );
+        if r.boundary_type() != "administrative" {
+            s.push_str(&format!(
+                " (<code>boundary={}</code>)",
+                html_escape(r.boundary_type())
+            ));
+        }
+        s
+    }
+}
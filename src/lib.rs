@@ -0,0 +1,67 @@
+// Find long chains of "place X is in the boundary Y, a place called Y is in the boundary Z, …" in
+// OpenStreetMap data. The `x-in-y` command line is `cli::run`; other code can read the records,
+// filter them & search for chains itself, e.g.
+//
+//     let records: Vec<Record> = input::csv_reader(path, &CsvFormat::default())?.deserialize().collect::<Result<_, _>>()?;
+//     let records: Vec<&Record> = records.iter().collect();
+//     let (chains, stats) = ChainSearch::new(&records).max_chain_len(10).run();
+
+#[macro_use]
+pub mod log;
+
+pub mod bench;
+pub mod cache;
+pub mod chain;
+pub mod cli;
+pub mod completions;
+pub mod constraints;
+pub mod continents;
+pub mod countries;
+mod dashboard;
+pub mod diff;
+pub mod distance;
+pub mod dry_run;
+pub mod exit;
+pub mod explain;
+pub mod extend;
+pub mod filters;
+pub mod fixture;
+pub mod freshness;
+mod frontier;
+pub mod graph;
+pub mod incremental;
+pub mod input;
+pub mod json;
+pub mod lang;
+pub mod manifest;
+pub mod memory;
+pub mod metrics;
+pub mod missing_links;
+pub mod mmap;
+pub mod nominatim;
+pub mod notify;
+pub mod options;
+pub mod output;
+pub mod prepare;
+pub mod progress;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod qa;
+pub mod query;
+pub mod record;
+pub mod repl;
+pub mod results;
+pub mod revalidate;
+pub mod scoring;
+pub mod search;
+pub mod serve;
+pub mod signals;
+pub mod starts;
+pub mod stats;
+pub mod status_file;
+pub mod sweep;
+pub mod timeline;
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watch;
@@ -0,0 +1,176 @@
+// What the program is doing, as it goes. `-q` only shows errors and the summary at the end, `-v`
+// also shows the debugging details. Everything, including the debugging details, is also written
+// to the --log-file with the time since the start.
+//
+// On a terminal, each phase of the run (reading, filtering, searching, writing) is a coloured
+// banner, new longest chains are highlighted, and warnings & errors stand out. NO_COLOR or
+// `--color never` turns that off.
+
+use std::fs::File;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    // The summary at the end, which -q still shows
+    Summary = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+// How a line is shown on the console. The log file is always plain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    // The start of one part of the run
+    Phase,
+    // Good news, e.g. a new longest chain
+    Highlight,
+    Summary,
+}
+
+// --color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    // When stdout is a terminal, and NO_COLOR isn't set
+    Auto,
+    Always,
+    Never,
+}
+
+impl std::str::FromStr for Color {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Color> {
+        Ok(match s {
+            "auto" => Color::Auto,
+            "always" => Color::Always,
+            "never" => Color::Never,
+            x => bail!("Unknown --color {:?}, it can be auto, always or never", x),
+        })
+    }
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static COLOR: AtomicBool = AtomicBool::new(false);
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+pub fn init(verbosity: Level, color: Color, log_file: Option<&str>) -> Result<()> {
+    STARTED.get_or_init(Instant::now);
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+    let color = match color {
+        Color::Auto => {
+            std::io::stdout().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        }
+        Color::Always => true,
+        Color::Never => false,
+    };
+    COLOR.store(color, Ordering::Relaxed);
+    if let Some(filename) = log_file {
+        let file = File::create(filename)
+            .with_context(|| format!("Could not create log file {}", filename))?;
+        *LOG_FILE.lock().unwrap() = Some(file);
+    }
+    Ok(())
+}
+
+// Would this be printed?
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= VERBOSITY.load(Ordering::Relaxed)
+}
+
+// Only write it to the log file, e.g. for things already shown some other way
+pub fn to_file(level: Level, msg: &str) {
+    if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+        let elapsed = STARTED.get_or_init(Instant::now).elapsed();
+        // Losing a log line isn't worth stopping a long search for
+        let _ = writeln!(
+            file,
+            "{:>10.1}s {:<5} {}",
+            elapsed.as_secs_f64(),
+            format!("{:?}", level).to_uppercase(),
+            msg
+        );
+    }
+}
+
+// `s` in an ANSI SGR style (e.g. "1;36" for bold cyan), if there's colour
+pub fn paint(sgr: &str, s: &str) -> String {
+    if COLOR.load(Ordering::Relaxed) {
+        format!("\x1b[{}m{}\x1b[0m", sgr, s)
+    } else {
+        s.to_string()
+    }
+}
+
+// The line as it's shown on the console
+pub fn styled(style: Style, msg: &str) -> String {
+    match style {
+        Style::Plain => msg.to_string(),
+        Style::Phase => paint("1;36", &format!("==> {}", msg)),
+        Style::Highlight => paint("1;32", msg),
+        Style::Summary => paint("1", msg),
+    }
+}
+
+pub fn log_styled(level: Level, style: Style, args: std::fmt::Arguments) {
+    let msg = args.to_string();
+    if enabled(level) {
+        match level {
+            Level::Warn => eprintln!("{} {}", paint("1;33", "Warning:"), msg),
+            Level::Summary | Level::Info | Level::Debug => println!("{}", styled(style, &msg)),
+        }
+    }
+    to_file(level, &msg);
+}
+
+pub fn log(level: Level, args: std::fmt::Arguments) {
+    log_styled(level, Style::Plain, args)
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Info, format_args!($($arg)*))
+    };
+}
+
+// The start of one part of the run
+macro_rules! phase {
+    ($($arg:tt)*) => {
+        $crate::log::log_styled(
+            $crate::log::Level::Info,
+            $crate::log::Style::Phase,
+            format_args!($($arg)*),
+        )
+    };
+}
+
+// Shown even with -q
+macro_rules! summary {
+    ($($arg:tt)*) => {
+        $crate::log::log_styled(
+            $crate::log::Level::Summary,
+            $crate::log::Style::Summary,
+            format_args!($($arg)*),
+        )
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Debug, format_args!($($arg)*))
+    };
+}
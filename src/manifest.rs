@@ -0,0 +1,241 @@
+// What produced the results: the inputs & their SHA-256, this program's version & git commit, all
+// the parameters, when it ran, and on how much RAM. Written next to the OUTPUT as
+// OUTPUT.run.json (or run.json in a json-bundle directory), so months later it's clear which
+// settings made which file.
+
+use std::io::Read;
+use std::path::Path;
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+
+use crate::json::{obj, Value};
+use crate::options::Options;
+use crate::output::Format;
+use crate::search::Failsafe;
+use crate::status_file::iso8601;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// There's no hashing crate available, so this is SHA-256 from FIPS 180-4
+struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Sha256 {
+    fn new() -> Sha256 {
+        Sha256 {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            block: [0; 64],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (i, word) in self.block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        while !data.is_empty() {
+            let n = (64 - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&data[..n]);
+            self.block_len += n;
+            data = &data[n..];
+            if self.block_len == 64 {
+                self.compress();
+                self.block_len = 0;
+            }
+        }
+    }
+
+    fn hex_digest(mut self) -> String {
+        let bit_len = self.total_len * 8;
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bit_len.to_be_bytes());
+        self.state.iter().map(|s| format!("{:08x}", s)).collect()
+    }
+}
+
+fn sha256_file(filename: &str) -> Result<String> {
+    let mut file =
+        std::fs::File::open(filename).with_context(|| format!("Could not open {}", filename))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.hex_digest())
+}
+
+// Total RAM of this machine, on Linux
+fn total_ram_bytes() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let kb: u64 = meminfo
+        .lines()
+        .find_map(|l| l.strip_prefix("MemTotal:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+pub struct Manifest {
+    started: SystemTime,
+    // The inputs are hashed in the background while the search runs
+    hashes: JoinHandle<Vec<(String, Option<String>)>>,
+}
+
+impl Manifest {
+    pub fn start(input_filenames: &[String]) -> Manifest {
+        let input_filenames = input_filenames.to_vec();
+        Manifest {
+            started: SystemTime::now(),
+            hashes: std::thread::spawn(move || {
+                input_filenames
+                    .into_iter()
+                    .map(|filename| {
+                        let hash = match sha256_file(&filename) {
+                            Ok(hash) => Some(hash),
+                            Err(e) => {
+                                warn!("Could not hash {} for the run.json: {:#}", filename, e);
+                                None
+                            }
+                        };
+                        (filename, hash)
+                    })
+                    .collect()
+            }),
+        }
+    }
+
+    pub fn write(self, options: &Options) -> Result<()> {
+        let hashes = self.hashes.join().unwrap_or_default();
+        let limits = &options.search_limits;
+        let json = obj(vec![
+            ("program", env!("CARGO_PKG_NAME").into()),
+            ("version", env!("CARGO_PKG_VERSION").into()),
+            ("git_commit", option_env!("X_IN_Y_GIT_COMMIT").into()),
+            (
+                "inputs",
+                Value::Array(
+                    hashes
+                        .into_iter()
+                        .map(|(filename, hash)| {
+                            obj(vec![("filename", filename.into()), ("sha256", hash.into())])
+                        })
+                        .collect(),
+                ),
+            ),
+            ("outputs", options.all_outputs().into()),
+            (
+                "arguments",
+                std::env::args().skip(1).collect::<Vec<_>>().into(),
+            ),
+            (
+                "search",
+                obj(vec![
+                    ("max_chain_len", limits.max_chain_len.into()),
+                    ("memory_limit", limits.memory_limit.into()),
+                    ("max_intermediate", limits.max_intermediate.into()),
+                    ("prune_slack", limits.prune_slack.into()),
+                    (
+                        "failsafe",
+                        match limits.failsafe {
+                            Failsafe::DropShortest => "drop-shortest",
+                            Failsafe::Off => "off",
+                        }
+                        .into(),
+                    ),
+                    ("score", options.scoring.name().into()),
+                    ("distance_model", options.distance_model.name().into()),
+                    ("fill_names", options.fill_names.is_some().into()),
+                    (
+                        "distinct_place_types",
+                        options.distinct_place_types.map(|d| d.name()).into(),
+                    ),
+                    ("within_country", options.within_country.as_deref().into()),
+                    (
+                        "boundary_admin_at_least",
+                        options.boundary_admin_at_least.into(),
+                    ),
+                ]),
+            ),
+            ("started", iso8601(self.started).into()),
+            ("finished", iso8601(SystemTime::now()).into()),
+            ("machine_ram_bytes", total_ram_bytes().into()),
+        ]);
+
+        let output = &options.output_filename;
+        let filename = if options.format == Format::JsonBundle || options.output_dir.is_some() {
+            Path::new(output).join("run.json")
+        } else {
+            format!("{}.run.json", output).into()
+        };
+        std::fs::write(&filename, format!("{}\n", json))
+            .with_context(|| format!("Could not write {:?}", filename))?;
+        info!("Wrote how these results were made to {:?}", filename);
+        Ok(())
+    }
+}
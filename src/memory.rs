@@ -0,0 +1,104 @@
+// Where the memory goes: estimates of what the records, the name index, the intermediate chains
+// (the frontier) & the finished chains take, worked out from what's stored, next to what the
+// process really uses. For tuning --max-intermediate, --memory-limit & --prune-slack.
+
+use std::collections::HashMap;
+
+use separator::Separatable;
+
+use crate::json::{obj, Value};
+use crate::metrics;
+use crate::record::{Record, Text};
+
+// Estimated bytes
+#[derive(Debug, Default, Clone)]
+pub struct MemoryUsage {
+    pub records: usize,
+    pub name_index: usize,
+    pub frontier: usize,
+    pub finished_chains: usize,
+}
+
+// Names which point into a --mmap'ed input take no memory of their own
+fn text_bytes(s: &Text) -> usize {
+    match s {
+        Text::Owned(s) => s.len(),
+        Text::Borrowed(_) => 0,
+    }
+}
+
+// A record & its strings
+pub fn record_bytes(r: &Record) -> usize {
+    std::mem::size_of::<Record>()
+        + text_bytes(&r.place_name)
+        + text_bytes(&r.place_type)
+        + text_bytes(&r.boundary_name)
+        + r.boundary_admin_level.as_ref().map_or(0, |l| l.len())
+        + [
+            &r.boundary_type,
+            &r.country_code,
+            &r.place_wikidata,
+            &r.place_wikipedia,
+        ]
+        .iter()
+        .map(|s| s.as_ref().map_or(0, |s| s.len()))
+        .sum::<usize>()
+}
+
+// The place name → records lookup of the search
+pub fn name_index_bytes(index: &HashMap<&str, Vec<&Record>>) -> usize {
+    index.capacity() * std::mem::size_of::<(&str, Vec<&Record>)>() * 8 / 7
+        + index
+            .values()
+            .map(|recs| recs.capacity() * std::mem::size_of::<&Record>())
+            .sum::<usize>()
+}
+
+// A finished chain, in the map of them by start
+pub fn finished_chain_bytes(chain: &Vec<&Record>) -> usize {
+    std::mem::size_of::<(&Record, Vec<&Record>)>() * 8 / 7
+        + chain.capacity() * std::mem::size_of::<&Record>()
+}
+
+fn mb(bytes: usize) -> String {
+    format!("{} MB", (bytes >> 20).separated_string())
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.records + self.name_index + self.frontier + self.finished_chains
+    }
+
+    // e.g. "records 1,200 MB, name index 300 MB, frontier 2,000 MB, finished chains 40 MB, RSS
+    // 3,700 MB (peak 4,100 MB)"
+    pub fn summary(&self) -> String {
+        let rss = match (metrics::rss_bytes(), metrics::peak_rss_bytes()) {
+            (Some(rss), Some(peak)) => {
+                format!(", RSS {} (peak {})", mb(rss as usize), mb(peak as usize))
+            }
+            _ => String::new(),
+        };
+        format!(
+            "records {}, name index {}, frontier {}, finished chains {}{}",
+            mb(self.records),
+            mb(self.name_index),
+            mb(self.frontier),
+            mb(self.finished_chains),
+            rss
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        obj(vec![
+            ("records_bytes", self.records.into()),
+            ("name_index_bytes", self.name_index.into()),
+            ("frontier_bytes", self.frontier.into()),
+            ("finished_chains_bytes", self.finished_chains.into()),
+            ("total_bytes", self.total().into()),
+            (
+                "peak_rss_bytes",
+                metrics::peak_rss_bytes().map_or(Value::Null, Value::from),
+            ),
+        ])
+    }
+}
@@ -0,0 +1,157 @@
+// Prometheus metrics (--metrics-addr), so a long run can be monitored & alerted on like any other
+// job. A background thread serves them over plain HTTP (with a thread per connection), the
+// counters are updated as we go.
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::progress::Status;
+use crate::serve;
+
+// For reading the request & writing the answer, a scrape usually times out in 10s anyway
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub static RECORDS_READ: AtomicU64 = AtomicU64::new(0);
+static STEPS: AtomicU64 = AtomicU64::new(0);
+static FRONTIER: AtomicU64 = AtomicU64::new(0);
+static FINISHED_CHAINS: AtomicU64 = AtomicU64::new(0);
+static LONGEST_CHAIN: AtomicU64 = AtomicU64::new(0);
+static MEMORY_CLEANUPS: AtomicU64 = AtomicU64::new(0);
+
+// (name, type, help, value)
+fn metrics() -> Vec<(&'static str, &'static str, &'static str, u64)> {
+    let get = |m: &AtomicU64| m.load(Ordering::Relaxed);
+    let mut metrics = vec![
+        (
+            "records_read_total",
+            "counter",
+            "Input rows read",
+            get(&RECORDS_READ),
+        ),
+        (
+            "search_steps_total",
+            "counter",
+            "Intermediate chains the search has tried to extend",
+            get(&STEPS),
+        ),
+        (
+            "search_frontier",
+            "gauge",
+            "Intermediate chains still to be tried",
+            get(&FRONTIER),
+        ),
+        (
+            "search_finished_chains",
+            "gauge",
+            "Finished chains, one per start place",
+            get(&FINISHED_CHAINS),
+        ),
+        (
+            "search_longest_chain",
+            "gauge",
+            "Places in the longest chain so far",
+            get(&LONGEST_CHAIN),
+        ),
+        (
+            "search_memory_cleanups_total",
+            "counter",
+            "Times intermediate chains were thrown away to save memory",
+            get(&MEMORY_CLEANUPS),
+        ),
+    ];
+    if let Some(rss) = rss_bytes() {
+        metrics.push((
+            "resident_memory_bytes",
+            "gauge",
+            "Resident memory of the process",
+            rss,
+        ));
+    }
+    metrics
+}
+
+// A "123 kB" field of /proc/self/status, in bytes
+fn proc_status_bytes(field: &str) -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb: u64 = status
+        .lines()
+        .find_map(|l| l.strip_prefix(field)?.strip_prefix(':'))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+// Resident memory of this process, on Linux
+pub fn rss_bytes() -> Option<u64> {
+    proc_status_bytes("VmRSS")
+}
+
+// The most resident memory this process has used, on Linux
+pub fn peak_rss_bytes() -> Option<u64> {
+    proc_status_bytes("VmHWM")
+}
+
+pub fn update_search(status: &Status) {
+    STEPS.store(status.steps as u64, Ordering::Relaxed);
+    FRONTIER.store(status.frontier as u64, Ordering::Relaxed);
+    FINISHED_CHAINS.store(status.finished as u64, Ordering::Relaxed);
+    LONGEST_CHAIN.store(status.longest as u64, Ordering::Relaxed);
+    MEMORY_CLEANUPS.store(status.memory_cleanups as u64, Ordering::Relaxed);
+}
+
+// The Prometheus text format
+fn render() -> String {
+    let mut s = String::new();
+    for (name, kind, help, value) in metrics() {
+        s.push_str(&format!(
+            "# HELP x_in_y_{name} {help}\n# TYPE x_in_y_{name} {kind}\nx_in_y_{name} {value}\n",
+            name = name,
+            help = help,
+            kind = kind,
+            value = value
+        ));
+    }
+    s
+}
+
+fn respond(stream: TcpStream) -> std::io::Result<()> {
+    // A client which never sends anything (or reads the answer) shouldn't tie up a thread forever
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let (status, body) = match serve::read_request(&stream)? {
+        Ok(request_line) => match request_line.split_whitespace().nth(1).unwrap_or("") {
+            "/metrics" | "/" => ("200 OK", render()),
+            _ => ("404 Not Found", "Not found, try /metrics\n".to_string()),
+        },
+        Err((status, message)) => (status, format!("{}\n", message)),
+    };
+    write!(
+        &stream,
+        "HTTP/1.0 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+pub fn serve(addr: &str) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .with_context(|| format!("Could not listen on {} for --metrics-addr", addr))?;
+    std::thread::spawn(move || {
+        // A thread per connection, so a slow client doesn't hold up the next scrape
+        for stream in listener.incoming().flatten() {
+            std::thread::spawn(move || {
+                // A broken scrape shouldn't stop the run
+                let _ = respond(stream);
+            });
+        }
+    });
+    Ok(())
+}
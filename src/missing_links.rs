@@ -0,0 +1,183 @@
+// Chains which would join up into a longer chain if one name was different, or one more place was
+// mapped. i.e. ideas for what to map (or fix) next.
+
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::filters::edit_distance;
+use crate::record::{OsmId, Record};
+
+// Only the longest chains are tried, since every pair of them is
+const NUM_CANDIDATES: usize = 1_000;
+const NUM_LINKS: usize = 100;
+
+pub enum Link<'a> {
+    // The first chain ends in a boundary whose name is one letter different from the first place
+    // of the second chain
+    Typo,
+    // A place with the first chain's last boundary name, inside this boundary (which has the name
+    // of the first place of the second chain)
+    MissingPlace(&'a Record),
+}
+
+pub struct MissingLink<'a> {
+    pub first: &'a [&'a Record],
+    pub second: &'a [&'a Record],
+    pub link: Link<'a>,
+}
+
+impl MissingLink<'_> {
+    pub fn joined_len(&self) -> usize {
+        self.first.len()
+            + self.second.len()
+            + match self.link {
+                Link::Typo => 0,
+                Link::MissingPlace(_) => 1,
+            }
+    }
+}
+
+fn keys(chain: &[&Record]) -> (HashSet<OsmId>, HashSet<OsmId>) {
+    (
+        chain.iter().map(|r| r.place_key()).collect(),
+        chain.iter().map(|r| r.boundary_key()).collect(),
+    )
+}
+
+// `chains` should be longest first
+pub fn find<'a>(chains: &'a [Chain<'a>], records: &[&'a Record]) -> Vec<MissingLink<'a>> {
+    let candidates = &chains[..chains.len().min(NUM_CANDIDATES)];
+    let chain_keys: Vec<_> = candidates.iter().map(|c| keys(c)).collect();
+    let disjoint = |a: usize, b: usize| {
+        chain_keys[a].0.is_disjoint(&chain_keys[b].0)
+            && chain_keys[a].1.is_disjoint(&chain_keys[b].1)
+    };
+
+    // One record for each boundary with the name of the start of a candidate
+    let start_names: HashSet<&str> = candidates
+        .iter()
+        .map(|c| c[0].place_name.as_ref())
+        .collect();
+    let mut boundaries: HashMap<&str, BTreeMap<OsmId, &'a Record>> = HashMap::new();
+    for rec in records {
+        if start_names.contains(rec.boundary_name.as_ref()) {
+            boundaries
+                .entry(&rec.boundary_name)
+                .or_default()
+                .entry(rec.boundary_key())
+                .or_insert(rec);
+        }
+    }
+
+    // (first end name, second start name, typo?) → best link
+    let mut links: BTreeMap<(&str, &str, bool), MissingLink<'a>> = BTreeMap::new();
+    for (a, first) in candidates.iter().enumerate() {
+        let end_name = first[first.len() - 1].boundary_name.as_ref();
+        for (b, second) in candidates.iter().enumerate() {
+            let start_name = second[0].place_name.as_ref();
+            if a == b || end_name == start_name || !disjoint(a, b) {
+                continue;
+            }
+            let typo = end_name
+                .chars()
+                .count()
+                .abs_diff(start_name.chars().count())
+                <= 1
+                && edit_distance(end_name, start_name) == 1;
+            let link = if typo {
+                Link::Typo
+            } else {
+                // Any boundary with the right name which isn't already in either chain
+                match boundaries.get(start_name).and_then(|bs| {
+                    bs.values().find(|r| {
+                        !chain_keys[a].1.contains(&r.boundary_key())
+                            && !chain_keys[b].1.contains(&r.boundary_key())
+                    })
+                }) {
+                    Some(boundary) => Link::MissingPlace(boundary),
+                    None => continue,
+                }
+            };
+            let link = MissingLink {
+                first,
+                second,
+                link,
+            };
+            match links.entry((end_name, start_name, typo)) {
+                Entry::Vacant(e) => {
+                    e.insert(link);
+                }
+                Entry::Occupied(mut e) => {
+                    if link.joined_len() > e.get().joined_len() {
+                        e.insert(link);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut links: Vec<MissingLink> = links.into_values().collect();
+    // Typos first, since they're a quicker fix
+    links.sort_by_key(|l| {
+        (
+            std::cmp::Reverse(l.joined_len()),
+            matches!(l.link, Link::MissingPlace(_)),
+        )
+    });
+    links.truncate(NUM_LINKS);
+    links
+}
+
+fn chain_summary(chain: &[&Record]) -> String {
+    format!(
+        "{} → … → {} ({})",
+        chain[0].place_name,
+        chain[chain.len() - 1].boundary_name,
+        chain.len()
+    )
+}
+
+// A markdown list of what would join the chains
+pub fn write(links: &[MissingLink], output: &mut impl Write) -> Result<()> {
+    writeln!(output, "# Missing links\n")?;
+    writeln!(
+        output,
+        "Chains which would join into a longer one, if one name was different or one more place was mapped.\n"
+    )?;
+    writeln!(
+        output,
+        "| Joined length | First chain | Second chain | What would join them |\n| --: | :-- | :-- | :-- |"
+    )?;
+    for l in links {
+        let end = l.first[l.first.len() - 1];
+        let start = l.second[0];
+        let suggestion = match l.link {
+            Link::Typo => format!(
+                "The boundary [{}]({}) and the place [{}]({}) are one letter different",
+                end.boundary_name,
+                end.boundary_url(),
+                start.place_name,
+                start.place_url()
+            ),
+            Link::MissingPlace(boundary) => format!(
+                "A place called {} in [{}]({})",
+                end.boundary_name,
+                boundary.boundary_name,
+                boundary.boundary_url()
+            ),
+        };
+        writeln!(
+            output,
+            "| {} | {} | {} | {} |",
+            l.joined_len(),
+            chain_summary(l.first).replace('|', "\\|"),
+            chain_summary(l.second).replace('|', "\\|"),
+            suggestion.replace('|', "\\|"),
+        )?;
+    }
+    Ok(())
+}
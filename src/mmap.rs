@@ -0,0 +1,263 @@
+// --mmap: an uncompressed input is mapped into memory, and the names of its records point into
+// the mapping, rather than each being copied into its own String. On a planet that's millions of
+// small allocations, & much of the memory the records take. The mapping is never unmapped, since
+// the records are kept until the end.
+//
+// Plain rows are split here. Rows with quotes, or which don't parse, are given to the csv crate as
+// usual, so the records (& errors) are the same as without --mmap, apart from the errors' line
+// numbers, which are within the row.
+
+use std::borrow::Cow;
+use std::io::Read;
+
+use anyhow::{Context, Result};
+
+use crate::countries::normalize_country_code;
+use crate::exit::Status;
+use crate::freshness::parse_timestamp;
+use crate::input::{self, Compression, CsvFormat};
+use crate::record::{parse_admin_level, parse_osm_type, parse_population, Record};
+
+// The whole file, for the rest of the run
+#[cfg(unix)]
+fn map_file(filename: &str) -> Result<Option<&'static [u8]>> {
+    use std::os::unix::io::AsRawFd;
+
+    let file =
+        std::fs::File::open(filename).with_context(|| format!("Could not open {}", filename))?;
+    let len = file.metadata()?.len() as usize;
+    if len == 0 {
+        return Ok(Some(&[]));
+    }
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Could not map {} into memory", filename));
+    }
+    // Nothing unmaps it. If the file is changed while we're running, all bets are off.
+    Ok(Some(unsafe {
+        std::slice::from_raw_parts(ptr as *const u8, len)
+    }))
+}
+
+#[cfg(not(unix))]
+fn map_file(_filename: &str) -> Result<Option<&'static [u8]>> {
+    info!("--mmap only works on unix, reading the input as usual");
+    Ok(None)
+}
+
+// Where each field is in a row, if the file has it
+struct Columns {
+    place_osmtype: Option<usize>,
+    place_id: Option<usize>,
+    place_name: Option<usize>,
+    place_type: Option<usize>,
+    place_lat: Option<usize>,
+    place_lon: Option<usize>,
+    boundary_osmtype: Option<usize>,
+    boundary_id: Option<usize>,
+    boundary_name: Option<usize>,
+    boundary_admin_level: Option<usize>,
+    boundary_type: Option<usize>,
+    country_code: Option<usize>,
+    place_wikidata: Option<usize>,
+    place_wikipedia: Option<usize>,
+    boundary_wikidata: Option<usize>,
+    boundary_wikipedia: Option<usize>,
+    place_population: Option<usize>,
+    boundary_area: Option<usize>,
+    place_timestamp: Option<usize>,
+    boundary_timestamp: Option<usize>,
+}
+
+impl Columns {
+    fn new(headers: &csv::ByteRecord) -> Columns {
+        let find = |name: &str| headers.iter().position(|h| h == name.as_bytes());
+        Columns {
+            place_osmtype: find("place_osmtype"),
+            place_id: find("place_id"),
+            place_name: find("place_name"),
+            place_type: find("place_type"),
+            place_lat: find("place_lat"),
+            place_lon: find("place_lon"),
+            boundary_osmtype: find("boundary_osmtype"),
+            boundary_id: find("boundary_id"),
+            boundary_name: find("boundary_name"),
+            boundary_admin_level: find("boundary_admin_level"),
+            boundary_type: find("boundary_type"),
+            country_code: find("country_code"),
+            place_wikidata: find("place_wikidata"),
+            place_wikipedia: find("place_wikipedia"),
+            boundary_wikidata: find("boundary_wikidata"),
+            boundary_wikipedia: find("boundary_wikipedia"),
+            place_population: find("place_population"),
+            boundary_area: find("boundary_area"),
+            place_timestamp: find("place_timestamp"),
+            boundary_timestamp: find("boundary_timestamp"),
+        }
+    }
+}
+
+pub struct Reader {
+    data: &'static [u8],
+    // Where the next row starts
+    pos: usize,
+    format: CsvFormat,
+    // The header line as it is in the file, & with the --column mappings done
+    header_line: &'static [u8],
+    headers: csv::ByteRecord,
+    columns: Columns,
+    fields: Vec<&'static str>,
+}
+
+impl Reader {
+    // None if the file is compressed, or can't be mapped here, so it should be read as usual
+    pub fn open(filename: &str, format: &CsvFormat) -> Result<Option<Reader>> {
+        // Checked before mapping it, since nothing unmaps it
+        let mut magic = Vec::new();
+        std::fs::File::open(filename)
+            .and_then(|file| file.take(6).read_to_end(&mut magic))
+            .with_context(|| format!("Could not open {}", filename))
+            .context(Status::BadInput)?;
+        if Compression::detect(&magic, filename) != Compression::None {
+            info!(
+                "{} is compressed, so it's read as usual. Decompress it first to use --mmap",
+                filename
+            );
+            return Ok(None);
+        }
+        let data = match map_file(filename).context(Status::BadInput)? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        // The csv crate reads the header, so it's quoted & renamed as usual
+        let headers = input::csv_reader(filename, format)?
+            .byte_headers()
+            .with_context(|| format!("Could not read header of {}", filename))
+            .context(Status::BadInput)?
+            .clone();
+        let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+        let mut reader = Reader {
+            data,
+            pos: 0,
+            format: format.clone(),
+            header_line: &[],
+            columns: Columns::new(&headers),
+            headers,
+            fields: Vec::new(),
+        };
+        let end = reader.row_end(0);
+        reader.header_line = &data[..(end + 1).min(data.len())];
+        reader.pos = end + 1;
+        Ok(Some(reader))
+    }
+
+    // Where the row starting at `start` ends: the next newline which isn't in quotes
+    fn row_end(&self, start: usize) -> usize {
+        let mut in_quotes = false;
+        for (i, &b) in self.data[start..].iter().enumerate() {
+            if Some(b) == self.format.quote {
+                in_quotes = !in_quotes;
+            } else if b == b'\n' && !in_quotes {
+                return start + i;
+            }
+        }
+        self.data.len()
+    }
+
+    // A plain row, without quotes, which has every field. None if it's anything else.
+    fn parse(&mut self, row: &'static [u8]) -> Option<Record> {
+        if self.format.quote.is_some_and(|q| row.contains(&q)) {
+            return None;
+        }
+        self.fields.clear();
+        let delimiter = self.format.delimiter;
+        for field in row.split(|&b| b == delimiter) {
+            self.fields.push(std::str::from_utf8(field).ok()?);
+        }
+        if self.fields.len() != self.headers.len() {
+            return None;
+        }
+        let fields = &self.fields;
+        let get = |column: Option<usize>| column.map(|i| fields[i]);
+        let optional =
+            |column: Option<usize>| get(column).filter(|s| !s.is_empty()).map(str::to_string);
+        let c = &self.columns;
+        Some(Record {
+            place_osmtype: parse_osm_type(get(c.place_osmtype)?)?,
+            place_id: get(c.place_id)?.parse().ok()?,
+            place_name: Cow::Borrowed(get(c.place_name)?),
+            place_type: Cow::Borrowed(get(c.place_type)?),
+            place_lat: get(c.place_lat)?.parse().ok()?,
+            place_lon: get(c.place_lon)?.parse().ok()?,
+            boundary_osmtype: parse_osm_type(get(c.boundary_osmtype)?)?,
+            boundary_id: get(c.boundary_id)?.parse().ok()?,
+            boundary_name: Cow::Borrowed(get(c.boundary_name)?),
+            boundary_admin_level: parse_admin_level(get(c.boundary_admin_level)?),
+            boundary_type: optional(c.boundary_type),
+            country_code: get(c.country_code).and_then(normalize_country_code),
+            place_wikidata: optional(c.place_wikidata),
+            place_wikipedia: optional(c.place_wikipedia),
+            boundary_wikidata: optional(c.boundary_wikidata),
+            boundary_wikipedia: optional(c.boundary_wikipedia),
+            place_population: get(c.place_population).and_then(parse_population),
+            boundary_area: get(c.boundary_area)
+                .filter(|s| !s.is_empty())
+                .map(str::parse)
+                .transpose()
+                .ok()?,
+            place_timestamp: get(c.place_timestamp).and_then(parse_timestamp),
+            boundary_timestamp: get(c.boundary_timestamp).and_then(parse_timestamp),
+        })
+    }
+
+    // The csv crate's reading of this row, after the header, for its quoting & errors
+    fn parse_with_csv(&self, row: &'static [u8]) -> Option<csv::Result<Record>> {
+        let mut builder = csv::ReaderBuilder::new();
+        builder.delimiter(self.format.delimiter);
+        match self.format.quote {
+            Some(q) => builder.quote(q),
+            None => builder.quoting(false),
+        };
+        let mut rdr = builder.from_reader(self.header_line.chain(row));
+        // Skip the file's header, which could have other column names
+        if let Err(e) = rdr.byte_headers() {
+            return Some(Err(e));
+        }
+        rdr.set_byte_headers(self.headers.clone());
+        rdr.deserialize().next()
+    }
+}
+
+impl Iterator for Reader {
+    type Item = csv::Result<Record>;
+
+    fn next(&mut self) -> Option<csv::Result<Record>> {
+        while self.pos < self.data.len() {
+            let end = self.row_end(self.pos);
+            let row = &self.data[self.pos..end];
+            self.pos = end + 1;
+            let row = row.strip_suffix(b"\r").unwrap_or(row);
+            if row.is_empty() {
+                continue;
+            }
+            match self.parse(row) {
+                Some(record) => return Some(Ok(record)),
+                None => match self.parse_with_csv(row) {
+                    Some(result) => return Some(result),
+                    None => continue,
+                },
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,161 @@
+// --fill-names: records where the place or boundary has no name in the input are usually skipped.
+// With this, the names are looked up with Nominatim instead, e.g. for objects which only have a
+// name:en, or were named after the export was made.
+//
+// Nominatim's usage policy allows at most 1 request a second, with up to 50 objects in each, so
+// every answer (including "no name") is saved to the CACHE file, and isn't asked for again. A
+// stopped run carries on where it was. Uses `curl`, which needs to be installed.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, BufReader, Write};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use separator::Separatable;
+
+use crate::json::{self, Value};
+use crate::record::{OsmId, Record};
+use crate::verify::http_get;
+
+pub const DEFAULT_URL: &str = "https://nominatim.openstreetmap.org";
+// The most ids Nominatim's /lookup takes at once
+const BATCH_SIZE: usize = 50;
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+// "n123" etc., as in the cache file
+fn key(osm_id: OsmId) -> String {
+    format!("{}{}", osm_id.0, osm_id.1)
+}
+
+// Each line is an object, a tab, and its name, which is empty if it has none
+fn read_cache(filename: &str) -> Result<HashMap<OsmId, String>> {
+    let mut names = HashMap::new();
+    let file = match std::fs::File::open(filename) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(names),
+        Err(e) => return Err(e).with_context(|| format!("Could not read {}", filename)),
+    };
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let (object, name) = line.split_once('\t').unwrap_or((&line, ""));
+        let mut chars = object.chars();
+        if let (Some(t @ ('n' | 'w' | 'r')), Ok(id)) = (chars.next(), chars.as_str().parse()) {
+            names.insert((t, id), name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+// The names of these objects. Objects which Nominatim doesn't have, or have no name, are "".
+fn lookup(url: &str, ids: &[OsmId]) -> Result<HashMap<OsmId, String>> {
+    let osm_ids = ids
+        .iter()
+        .map(|(t, id)| format!("{}{}", t.to_ascii_uppercase(), id))
+        .collect::<Vec<_>>()
+        .join(",");
+    let (status, body) = http_get(&format!(
+        "{}/lookup?format=jsonv2&namedetails=1&osm_ids={}",
+        url.trim_end_matches('/'),
+        osm_ids
+    ))?;
+    if status != 200 {
+        anyhow::bail!("Nominatim returned HTTP {}", status);
+    }
+    let response = json::parse(&body).context("Invalid response from Nominatim")?;
+    let mut names: HashMap<OsmId, String> = ids.iter().map(|id| (*id, String::new())).collect();
+    for place in response.as_array().unwrap_or(&[]) {
+        let osm_type = match place.get("osm_type").and_then(Value::as_str) {
+            Some("node") => 'n',
+            Some("way") => 'w',
+            Some("relation") => 'r',
+            _ => continue,
+        };
+        let id = place.get("osm_id").and_then(Value::as_f64).unwrap_or(0.) as u64;
+        // The name tag, or else the name in the default language
+        let name = place
+            .get("namedetails")
+            .and_then(|n| n.get("name"))
+            .or_else(|| place.get("name"))
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .trim();
+        names.insert((osm_type, id), name.replace(['\t', '\n'], " "));
+    }
+    Ok(names)
+}
+
+// Fill in the empty place & boundary names in `records`, from the cache or Nominatim. Returns
+// the records which now have both names.
+pub fn fill_names(records: Vec<Record>, cache_filename: &str, url: &str) -> Result<Vec<Record>> {
+    let mut names = read_cache(cache_filename)?;
+    let missing: BTreeSet<OsmId> = records
+        .iter()
+        .flat_map(|r| {
+            [
+                Some(r.place_key()).filter(|_| r.place_name.is_empty()),
+                Some(r.boundary_key()).filter(|_| r.boundary_name.is_empty()),
+            ]
+        })
+        .flatten()
+        .filter(|id| !names.contains_key(id))
+        .collect();
+    let missing: Vec<OsmId> = missing.into_iter().collect();
+    if !missing.is_empty() {
+        let num_requests = missing.len().div_ceil(BATCH_SIZE);
+        info!(
+            "Looking up the names of {} objects with Nominatim, which will take about {}s",
+            missing.len().separated_string(),
+            (num_requests as u64 * REQUEST_INTERVAL.as_secs()).separated_string()
+        );
+        let mut cache = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(cache_filename)
+            .with_context(|| format!("Could not write {}", cache_filename))?;
+        let mut last_request: Option<Instant> = None;
+        for (i, batch) in missing.chunks(BATCH_SIZE).enumerate() {
+            if let Some(last) = last_request {
+                std::thread::sleep(REQUEST_INTERVAL.saturating_sub(last.elapsed()));
+            }
+            last_request = Some(Instant::now());
+            let found = lookup(url, batch).with_context(|| {
+                format!(
+                    "Could not look up names with Nominatim (the {} found so far are in {})",
+                    i * BATCH_SIZE,
+                    cache_filename
+                )
+            })?;
+            for (osm_id, name) in found {
+                writeln!(cache, "{}\t{}", key(osm_id), name)?;
+                names.insert(osm_id, name);
+            }
+            if (i + 1) % 100 == 0 {
+                info!(
+                    "Looked up {} of {} names",
+                    ((i + 1) * BATCH_SIZE).separated_string(),
+                    missing.len().separated_string()
+                );
+            }
+        }
+    }
+
+    let num_unnamed = records.len();
+    let filled: Vec<Record> = records
+        .into_iter()
+        .filter_map(|mut r| {
+            if r.place_name.is_empty() {
+                r.place_name = names.get(&r.place_key())?.clone().into();
+            }
+            if r.boundary_name.is_empty() {
+                r.boundary_name = names.get(&r.boundary_key())?.clone().into();
+            }
+            Some(r).filter(|r| !r.place_name.is_empty() && !r.boundary_name.is_empty())
+        })
+        .collect();
+    info!(
+        "Filled in the names of {} of {} records with an unnamed place or boundary",
+        filled.len().separated_string(),
+        num_unnamed.separated_string()
+    );
+    Ok(filled)
+}
@@ -0,0 +1,144 @@
+// Tell someone when a run finishes or fails (--notify-url, --notify-email), so a multi-day run on
+// a server doesn't need watching. The webhook is a POST of a JSON summary with curl, the email is
+// sent with sendmail.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context, Result};
+
+use crate::chain::Chain;
+use crate::json::{obj, Value};
+use crate::options::Options;
+
+// What the search found, if it got that far
+static CHAINS: Mutex<Option<Value>> = Mutex::new(None);
+
+// The chains, longest first
+pub fn chains_found(chains: &[Chain]) {
+    let longest = chains.first().map(|chain| {
+        obj(vec![
+            ("length", chain.len().into()),
+            (
+                "names",
+                chain
+                    .iter()
+                    .map(|r| r.place_name.as_ref())
+                    .chain(chain.last().map(|r| r.boundary_name.as_ref()))
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+        ])
+    });
+    *CHAINS.lock().unwrap() = Some(obj(vec![
+        ("num_chains", chains.len().into()),
+        ("longest_chain", longest.into()),
+    ]));
+}
+
+fn summary(options: &Options, runtime: Duration, result: &Result<()>) -> Value {
+    let mut summary = obj(vec![
+        (
+            "state",
+            if result.is_ok() { "finished" } else { "failed" }.into(),
+        ),
+        (
+            "error",
+            result.as_ref().err().map(|e| format!("{:#}", e)).into(),
+        ),
+        (
+            "finished",
+            crate::status_file::iso8601(SystemTime::now()).into(),
+        ),
+        ("runtime_s", runtime.as_secs().into()),
+        (
+            "arguments",
+            std::env::args().skip(1).collect::<Vec<_>>().into(),
+        ),
+        ("inputs", options.input_filenames.clone().into()),
+        ("outputs", options.all_outputs().into()),
+    ]);
+    if let (Value::Object(pairs), Some(Value::Object(chains))) =
+        (&mut summary, CHAINS.lock().unwrap().clone())
+    {
+        pairs.extend(chains);
+    }
+    summary
+}
+
+fn post(url: &str, body: &str) -> Result<()> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", "60"])
+        .args(["--header", "Content-Type: application/json"])
+        .args(["--data-binary", "@-"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Could not run curl")?;
+    child.stdin.take().unwrap().write_all(body.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "curl {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn email(to: &str, subject: &str, body: &str) -> Result<()> {
+    let mut child = Command::new("sendmail")
+        .args(["-t"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Could not run sendmail, is it installed?")?;
+    write!(
+        child.stdin.take().unwrap(),
+        "To: {}\nSubject: {}\nContent-Type: text/plain; charset=utf-8\n\n{}\n",
+        to,
+        subject,
+        body
+    )?;
+    if !child.wait()?.success() {
+        bail!("sendmail failed sending to {}", to);
+    }
+    Ok(())
+}
+
+// A failed notification is only a warning, the run itself is done
+pub fn send(options: &Options, runtime: Duration, result: &Result<()>) {
+    let summary = summary(options, runtime, result);
+    if let Some(url) = &options.notify_url {
+        match post(url, &summary.to_string()) {
+            Ok(()) => info!("Notified {}", url),
+            Err(e) => warn!("Could not notify {}: {:#}", url, e),
+        }
+    }
+    if let Some(to) = &options.notify_email {
+        let subject = match result {
+            Ok(()) => format!("x-in-y finished {}", options.output_filename),
+            Err(_) => format!("x-in-y failed {}", options.output_filename),
+        };
+        // One `key: value` per line is easier to read in an email
+        let body = match &summary {
+            Value::Object(pairs) => pairs
+                .iter()
+                .map(|(key, value)| match value {
+                    Value::String(s) => format!("{}: {}", key, s),
+                    value => format!("{}: {}", key, value),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => summary.to_string(),
+        };
+        match email(to, &subject, &body) {
+            Ok(()) => info!("Emailed {}", to),
+            Err(e) => warn!("Could not email {}: {:#}", to, e),
+        }
+    }
+}
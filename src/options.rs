@@ -0,0 +1,1153 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crate::completions::Shell;
+use crate::constraints::{self, DistinctPlaceTypes};
+use crate::countries;
+use crate::distance::DistanceModel;
+use crate::filters::{BoundaryTypeFilter, SelfContainmentRule};
+use crate::graph::GraphFormat;
+use crate::input::{self, CsvFormat};
+use crate::lang::Messages;
+use crate::log::{Color, Level};
+use crate::nominatim;
+use crate::output::Format;
+use crate::progress::Reporter;
+use crate::record::{OsmId, Record};
+use crate::scoring::{self, PlaceTypeWeights, Scoring};
+use crate::search::{ChainSearch, SearchLimits};
+use crate::starts;
+
+// A command line flag. `value` is the placeholder shown in the usage text, or None if the flag
+// doesn't take a value.
+pub struct Flag {
+    pub name: &'static str,
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+pub const FLAGS: &[Flag] = &[
+    Flag {
+        name: "--self-containment",
+        value: Some("RULES"),
+        help: "Comma separated rules for removing places inside a boundary with the same name: exact, normalized, prefix, fuzzy, or off. Default: exact",
+    },
+    Flag {
+        name: "--fuzzy-distance",
+        value: Some("N"),
+        help: "Maximum edit distance between names for the fuzzy self-containment rule. Default: 1",
+    },
+    Flag {
+        name: "--self-containment-report",
+        value: Some("FILE"),
+        help: "Write the places removed by the self-containment filter, and the boundary they're in, to this CSV file",
+    },
+    Flag {
+        name: "--maproulette",
+        value: Some("FILE"),
+        help: "Write the places removed by the self-containment filter, and the duplicates merged by --dedup-radius, as a MapRoulette challenge (line-by-line GeoJSON)",
+    },
+    Flag {
+        name: "--dedup-radius",
+        value: Some("METRES"),
+        help: "Merge places with identical names which are within this distance of each other. Default: 0 (off)",
+    },
+    Flag {
+        name: "--one-boundary-per-name",
+        value: None,
+        help: "If a place is in several boundaries with the same name, only keep the most specific one (highest admin_level)",
+    },
+    Flag {
+        name: "--smallest-boundary",
+        value: None,
+        help: "Only keep the smallest boundary each place is in (by the optional boundary_area column, else the highest admin_level), so the chains follow a strict hierarchy rather than every boundary a place is in",
+    },
+    Flag {
+        name: "--min-name-length",
+        value: Some("N"),
+        help: "Don't use places or boundaries whose name is shorter than N characters, e.g. 2 for one letter names, which match each other by accident. Default: 1, so single character names (e.g. in Chinese or Japanese) are kept",
+    },
+    Flag {
+        name: "--remove-junk-names",
+        value: None,
+        help: "Don't use places & boundaries whose name has no letters (e.g. 12345, ?, -) or is a placeholder (e.g. unknown, noname)",
+    },
+    Flag {
+        name: "--require-wikidata",
+        value: None,
+        help: "Only use places in boundaries where both have a wikidata=* or wikipedia=* tag (the optional place_wikidata, place_wikipedia, boundary_wikidata & boundary_wikipedia columns)",
+    },
+    Flag {
+        name: "--column",
+        value: Some("FIELD=COLUMN"),
+        help: "Read FIELD (e.g. place_name) from the input column called COLUMN. Can be given several times",
+    },
+    Flag {
+        name: "--delimiter",
+        value: Some("CHAR"),
+        help: "Field delimiter of the input CSV (a single character, or tab/comma/semicolon/pipe). Default: ,",
+    },
+    Flag {
+        name: "--quote",
+        value: Some("CHAR"),
+        help: "Quote character of the input CSV. Default: \"",
+    },
+    Flag {
+        name: "--no-quoting",
+        value: None,
+        help: "Input CSV fields aren't quoted",
+    },
+    Flag {
+        name: "--strict",
+        value: None,
+        help: "Stop at the first input row which can't be parsed, rather than skipping it",
+    },
+    Flag {
+        name: "--errors-report",
+        value: Some("FILE"),
+        help: "Write every input row which couldn't be parsed to this CSV file",
+    },
+    Flag {
+        name: "--unknown-tags-report",
+        value: Some("FILE"),
+        help: "Write every unknown place=* value, how often it appears and some example places to this CSV file",
+    },
+    Flag {
+        name: "--cache",
+        value: None,
+        help: "Save the parsed records of each input next to it (INPUT.x-in-y-cache), and read them from there next time if the input hasn't changed, which is much quicker",
+    },
+    Flag {
+        name: "--mmap",
+        value: None,
+        help: "Map uncompressed inputs into memory, and point the records' names into them rather than copying each one, which uses much less memory on a planet. Compressed inputs are read as usual. Don't change the input while it runs",
+    },
+    Flag {
+        name: "--boundary-types",
+        value: Some("TYPES"),
+        help: "Comma separated list of boundary types (e.g. administrative,ceremonial) which can be used. Boundaries without a boundary_type are administrative. Default: all",
+    },
+    Flag {
+        name: "--exclude-boundary-types",
+        value: Some("TYPES"),
+        help: "Comma separated list of boundary types which can't be used",
+    },
+    Flag {
+        name: "--country-boundaries",
+        value: Some("FILE"),
+        help: "CSV of boundary_id,country_code for admin_level=2 relations, used to give places a country when the input has no country_code for them",
+    },
+    Flag {
+        name: "--auto-accept-threshold",
+        value: Some("N"),
+        help: "Use places with an unknown place=* value if that value appears more than N times",
+    },
+    Flag {
+        name: "--format",
+        value: Some("FORMAT"),
+        help: "Output format: text, markdown, html, geojson, html-map, kml, gpx, sqlite, ndjson, csv, parquet, template, story, thread, osmwiki, umap, atom, quiz, tree or json-bundle (OUTPUT is a directory). Default: text",
+    },
+    Flag {
+        name: "--max-chains",
+        value: Some("N"),
+        help: "Write out at most the N longest chains. Default: 1000, or all of them with --output-dir",
+    },
+    Flag {
+        name: "--output-dir",
+        value: Some("DIR"),
+        help: "Instead of an OUTPUT file, write summary.json, stats.json, run.json and each chain in its own file in the --format, in chains/len-N/ directories",
+    },
+    Flag {
+        name: "--post-limit",
+        value: Some("N"),
+        help: "Maximum number of characters in each post of --format thread. Default: 500",
+    },
+    Flag {
+        name: "--per-country",
+        value: Some("FILE"),
+        help: "Also find the longest chain which stays in each country, and write a markdown report of them to FILE",
+    },
+    Flag {
+        name: "--continents",
+        value: Some("GEOJSON"),
+        help: "Print the longest chain and chain lengths for each continent, from these polygons (e.g. Natural Earth countries, grouped by their CONTINENT property)",
+    },
+    Flag {
+        name: "--hubs-report",
+        value: Some("FILE"),
+        help: "Write a markdown table of the names which are in the most chains, and link the most other names",
+    },
+    Flag {
+        name: "--name-stats",
+        value: Some("FILE"),
+        help: "Write a CSV of every name which is a place and a boundary, with its degrees and chains",
+    },
+    Flag {
+        name: "--graph-format",
+        value: Some("FORMAT"),
+        help: "graph-export format: graphml, dot, or mtx (a Matrix Market sparse matrix, with the names in OUTPUT.labels). Default: from the OUTPUT extension (.dot/.gv is dot, .mtx is mtx)",
+    },
+    Flag {
+        name: "--overpass-checks",
+        value: Some("FILE"),
+        help: "Write an Overpass query for each chain written out, which checks every place is still inside its boundary with the same names",
+    },
+    Flag {
+        name: "--endpoints",
+        value: Some("FILE"),
+        help: "Write where every chain (not just the ones written out) starts & ends, with its length as a weight, for a heatmap. GeoJSON points for a .geojson FILE, otherwise CSV",
+    },
+    Flag {
+        name: "--missing-links",
+        value: Some("FILE"),
+        help: "Write a markdown report of pairs of long chains which would join if a name was one letter different, or one more place was mapped",
+    },
+    Flag {
+        name: "--stats-out",
+        value: Some("FILE"),
+        help: "Write the numbers about this run (records, filters, chain lengths & distances, steps, runtime) to this JSON file",
+    },
+    Flag {
+        name: "--previous-results",
+        value: Some("FILE"),
+        help: "Re-use the chains in this results file (--format text or csv, with a big --max-chains) from an earlier run, and only search from the records which changes since --previous-input can affect",
+    },
+    Flag {
+        name: "--previous-input",
+        value: Some("FILE"),
+        help: "The input of the --previous-results run. Can be given more than once",
+    },
+    Flag {
+        name: "--old-results",
+        value: Some("FILE"),
+        help: "revalidate, extend, explain & serve: the earlier results file (--format text, csv, ndjson or sqlite)",
+    },
+    Flag {
+        name: "--chain-id",
+        value: Some("N"),
+        help: "explain: the Nth chain in --old-results",
+    },
+    Flag {
+        name: "--start",
+        value: Some("OSMID"),
+        help: "explain: the chain starting at this place, e.g. n123 or node/123. From --old-results if given, otherwise it's searched for",
+    },
+    Flag {
+        name: "--name",
+        value: Some("NAME"),
+        help: "query: find the chains with a place or boundary with this name (ignoring case)",
+    },
+    Flag {
+        name: "--osm",
+        value: Some("OSMID"),
+        help: "query: find the chains with this place or boundary, e.g. n123 or relation/456",
+    },
+    Flag {
+        name: "--output-lang",
+        value: Some("LANG"),
+        help: "Language of the text, markdown, html, kml & gpx output: en (default), de, fr, es, or a file of `key = message` lines (see src/lang.rs)",
+    },
+    Flag {
+        name: "--template",
+        value: Some("FILE"),
+        help: "Render each chain with this template (implies --format template). {{var}} is a variable, {{#hops}}...{{/hops}} repeats for each place",
+    },
+    Flag {
+        name: "--records-parquet",
+        value: Some("FILE"),
+        help: "Write all the records which are used for the search (i.e. after filtering) to this Parquet file",
+    },
+    Flag {
+        name: "--dashboard",
+        value: None,
+        help: "Show the search as a full screen dashboard (frontier & finished chains over time, chain lengths, memory used, the longest chain so far), rather than a status line",
+    },
+    Flag {
+        name: "--max-chain-len",
+        value: Some("N"),
+        help: "Stop extending a chain once it has N places. Much quicker, when you only want to know if there are chains of at least N",
+    },
+    Flag {
+        name: "--memory-limit",
+        value: Some("SIZE"),
+        help: "Throw away the least promising intermediate chains when they'd take memory use over this, e.g. 24GB or 512MB. Default: keep --max-intermediate of them",
+    },
+    Flag {
+        name: "--max-intermediate",
+        value: Some("N"),
+        help: "Clean up the intermediate chains when there are more than this many, unless there's a --memory-limit (default 8000000)",
+    },
+    Flag {
+        name: "--prune-slack",
+        value: Some("N"),
+        help: "The memory clean up keeps intermediate chains at most N places shorter than the longest from their start (default 10). Lower saves more memory, but might miss the longest chain",
+    },
+    Flag {
+        name: "--failsafe",
+        value: Some("MODE"),
+        help: "If there are still too many intermediate chains after the clean up: drop-shortest (default) throws away the shortest ones, off keeps them all, and might run out of memory",
+    },
+    Flag {
+        name: "--starts",
+        value: Some("FILE"),
+        help: "Only start chains from the places in this file, one OSM id (n123 or node/123) or place name per line. The rest of a chain can go anywhere",
+    },
+    Flag {
+        name: "--start-bbox",
+        value: Some("BBOX"),
+        help: "Only start chains from places in this box, min_lon,min_lat,max_lon,max_lat (e.g. -10.7,51.4,-5.4,55.4 for Ireland). The rest of a chain can go anywhere",
+    },
+    Flag {
+        name: "--distinct-place-types",
+        value: Some("MODE"),
+        help: "consecutive: a place can't have the same place type as the one before it in a chain (e.g. village → town → city → suburb), all: no place type can be in a chain twice",
+    },
+    Flag {
+        name: "--within-country",
+        value: Some("CC"),
+        help: "Only chains where every place (and so every boundary) is in this country, by ISO 3166-1 code, e.g. IE. Places without a country can't be in a chain",
+    },
+    Flag {
+        name: "--boundary-admin-at-least",
+        value: Some("N"),
+        help: "Only boundaries with an admin_level of at least N (e.g. 6 for counties & municipalities, not countries & states) can link one place in a chain to the next",
+    },
+    Flag {
+        name: "--score",
+        value: Some("MODE"),
+        help: "Which of the chains of the same length are searched (and kept) first: distance (default) prefers long hops which zigzag over the world, compact short hops, none doesn't prefer any (taking them in OSM id order), place-type prefers places with a high --place-type-weights, antipodal prefers chains whose shortest hop is longest, i.e. every hop goes nearly to the other side of the world, population prefers places with a big population (the optional place_population column, summed on a log scale)",
+    },
+    Flag {
+        name: "--fill-names",
+        value: Some("CACHE"),
+        help: "Look up the names of places & boundaries which have none in the input with Nominatim (at most 1 request a second), rather than skipping them. Every answer is saved in the CACHE file and used next time. Needs curl",
+    },
+    Flag {
+        name: "--nominatim-url",
+        value: Some("URL"),
+        help: "The Nominatim server for --fill-names. Default: https://nominatim.openstreetmap.org",
+    },
+    Flag {
+        name: "--distance-model",
+        value: Some("MODEL"),
+        help: "How distances are worked out, for the scores & all the outputs: sphere (default, haversine) or ellipsoid (the geodesic on the WGS84 ellipsoid, more accurate but slower)",
+    },
+    Flag {
+        name: "--place-type-weights",
+        value: Some("WEIGHTS"),
+        help: "Comma separated weights of place types for --score place-type, e.g. city=3,village=1,city_block=0.2. Unlisted types weigh 1. Default: city=3, town & municipality 2, suburb, quarter & district 0.5, neighbourhood 0.3, square & blocks 0.2",
+    },
+    Flag {
+        name: "--max-runtime",
+        value: Some("DURATION"),
+        help: "Stop the search after this long (e.g. 90m, 12h, 2d, or seconds) and write out what it has found, like Ctrl-C",
+    },
+    Flag {
+        name: "--status-report",
+        value: Some("FILE"),
+        help: "Write the status report to this file when sent SIGUSR1 (e.g. `pkill -USR1 x-in-y`), rather than printing it",
+    },
+    Flag {
+        name: "--metrics-addr",
+        value: Some("ADDR"),
+        help: "Serve Prometheus metrics (records read, search steps, frontier size, finished chains, longest chain, memory used) on this address, e.g. 0.0.0.0:9100",
+    },
+    Flag {
+        name: "--watch",
+        value: Some("DIR"),
+        help: "Keep running, and search each new export (.csv, .csv.gz, …) which appears in DIR, with these options. Only takes the OUTPUT, which is written to a directory per date next to it, e.g. results/2024-05-01/chains.txt for results/chains.txt",
+    },
+    Flag {
+        name: "--deterministic",
+        value: None,
+        help: "Put chains of the same length in the order of their OSM ids, so the same input always gives exactly the same output",
+    },
+    Flag {
+        name: "--stream",
+        value: Some("FILE"),
+        help: "Write each chain to this file (as --format ndjson) as soon as the search has finished with its start point, rather than only at the end, so a run which crashes or is killed has still saved most of what it found",
+    },
+    Flag {
+        name: "--errors-json",
+        value: Some("FILE"),
+        help: "When the program ends, write how it ended to this file as JSON: the status, the exit code, whether results were written, and the error",
+    },
+    Flag {
+        name: "--notify-url",
+        value: Some("URL"),
+        help: "POST a JSON summary (state, error, runtime, outputs, number of chains, the longest chain) to this URL when the run finishes or fails. Needs curl",
+    },
+    Flag {
+        name: "--notify-email",
+        value: Some("ADDRESS"),
+        help: "Email the same summary as --notify-url to this address. Needs sendmail",
+    },
+    Flag {
+        name: "--listen",
+        value: Some("ADDR"),
+        help: "serve: the address to serve the results on (default 127.0.0.1:8080)",
+    },
+    Flag {
+        name: "--places",
+        value: Some("N"),
+        help: "gen-fixture: how many places to make up (default 1000)",
+    },
+    Flag {
+        name: "--chain-depth",
+        value: Some("N"),
+        help: "gen-fixture: how long the longest chain is (default 10)",
+    },
+    Flag {
+        name: "--seed",
+        value: Some("N"),
+        help: "gen-fixture: the same seed always makes the same file (default 0)",
+    },
+    Flag {
+        name: "--steps",
+        value: Some("N"),
+        help: "bench: how many search steps to time (default 1000000)",
+    },
+    Flag {
+        name: "--config-matrix",
+        value: Some("FILE"),
+        help: "sweep: the search options to try, a TOML file of option = value or option = [value, ...] lines (e.g. prune-slack = [5, 10, 20]). Every combination is run",
+    },
+    Flag {
+        name: "--dry-run",
+        value: None,
+        help: "Read & filter the input and build the name graph, then print how big the search would be & how much memory it could need, and stop without searching",
+    },
+    Flag {
+        name: "--status-file",
+        value: Some("FILE"),
+        help: "Rewrite this JSON file with the progress, parameters & longest chains so far every --status-interval seconds during the search",
+    },
+    Flag {
+        name: "--status-interval",
+        value: Some("SECS"),
+        help: "How often to rewrite the --status-file. Default: 30",
+    },
+    Flag {
+        name: "--verbose",
+        value: None,
+        help: "(-v) Also print debugging details",
+    },
+    Flag {
+        name: "--quiet",
+        value: None,
+        help: "(-q) Only print errors and the summary at the end, not warnings, what's happening or the progress",
+    },
+    Flag {
+        name: "--color",
+        value: Some("WHEN"),
+        help: "Colour the console output: auto (when it's a terminal, and NO_COLOR isn't set), always, or never. Default: auto",
+    },
+    Flag {
+        name: "--log-file",
+        value: Some("FILE"),
+        help: "Also write everything, including the debugging details, to this file",
+    },
+    Flag {
+        name: "--help",
+        value: None,
+        help: "Print this help",
+    },
+];
+
+#[derive(Debug)]
+pub struct Options {
+    pub command: Command,
+    pub input_filenames: Vec<String>,
+    pub output_filename: String,
+
+    // Which rules remove a place for being in a boundary with the "same" name. Empty = off.
+    pub self_containment: Vec<SelfContainmentRule>,
+    pub fuzzy_distance: usize,
+    pub self_containment_report: Option<String>,
+    pub maproulette: Option<String>,
+
+    // Same-named places closer than this are merged into one. 0 = off
+    pub dedup_radius_m: f64,
+
+    // Only keep one boundary per (place, boundary name)
+    pub one_boundary_per_name: bool,
+    pub smallest_boundary: bool,
+    pub require_wikidata: bool,
+    pub min_name_length: usize,
+    pub remove_junk_names: bool,
+
+    pub csv_format: CsvFormat,
+
+    // Abort on unparseable rows, rather than skipping & counting them
+    pub strict: bool,
+    pub cache: bool,
+    pub mmap: bool,
+    pub errors_report: Option<String>,
+    pub unknown_tags_report: Option<String>,
+
+    pub boundary_types: BoundaryTypeFilter,
+
+    pub country_boundaries: Option<String>,
+
+    pub auto_accept_threshold: Option<usize>,
+
+    pub format: Format,
+    // None is the default, which depends on --output-dir
+    pub max_chains: Option<usize>,
+    pub output_dir: Option<String>,
+    pub template: Option<String>,
+    pub messages: Messages,
+    pub post_limit: usize,
+    pub per_country: Option<String>,
+    pub continents: Option<String>,
+    pub hubs_report: Option<String>,
+    pub name_stats: Option<String>,
+    pub graph_format: Option<GraphFormat>,
+    pub stats_out: Option<String>,
+    pub old_results: Option<String>,
+    pub chain_id: Option<usize>,
+    pub start: Option<OsmId>,
+    pub query_name: Option<String>,
+    pub query_osm: Option<OsmId>,
+    pub previous_results: Option<String>,
+    pub previous_inputs: Vec<String>,
+    pub missing_links: Option<String>,
+    pub endpoints: Option<String>,
+    pub overpass_checks: Option<String>,
+
+    pub records_parquet: Option<String>,
+
+    pub verbosity: Level,
+    pub color: Color,
+    pub log_file: Option<String>,
+    pub status_report: Option<String>,
+    pub dashboard: bool,
+    pub metrics_addr: Option<String>,
+    pub listen: String,
+    // gen-fixture
+    pub fixture_places: usize,
+    pub fixture_chain_depth: usize,
+    pub fixture_seed: u64,
+    pub completions_shell: Option<Shell>,
+    // bench
+    pub bench_steps: usize,
+    // sweep
+    pub config_matrix: Option<String>,
+    pub dry_run: bool,
+    pub watch: Option<String>,
+    pub deterministic: bool,
+    pub stream: Option<String>,
+    pub errors_json: Option<String>,
+    pub notify_url: Option<String>,
+    pub notify_email: Option<String>,
+    pub status_file: Option<String>,
+    pub status_interval: u64,
+    pub max_runtime: Option<Duration>,
+    pub search_limits: SearchLimits,
+    pub scoring: Scoring,
+    pub starts: Option<String>,
+    pub start_bbox: Option<[f64; 4]>,
+    pub distinct_place_types: Option<DistinctPlaceTypes>,
+    pub boundary_admin_at_least: Option<u8>,
+    pub within_country: Option<String>,
+    pub place_type_weights: PlaceTypeWeights,
+    pub distance_model: DistanceModel,
+    pub fill_names: Option<String>,
+    pub nominatim_url: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            command: Command::Search,
+            input_filenames: Vec::new(),
+            output_filename: String::new(),
+            self_containment: vec![SelfContainmentRule::Exact],
+            fuzzy_distance: 1,
+            self_containment_report: None,
+            maproulette: None,
+            dedup_radius_m: 0.,
+            one_boundary_per_name: false,
+            smallest_boundary: false,
+            require_wikidata: false,
+            min_name_length: 1,
+            remove_junk_names: false,
+            csv_format: CsvFormat::default(),
+            strict: false,
+            cache: false,
+            mmap: false,
+            errors_report: None,
+            unknown_tags_report: None,
+            boundary_types: BoundaryTypeFilter::default(),
+            country_boundaries: None,
+            auto_accept_threshold: None,
+            format: Format::Text,
+            max_chains: None,
+            output_dir: None,
+            template: None,
+            messages: Messages::default(),
+            post_limit: 500,
+            per_country: None,
+            continents: None,
+            hubs_report: None,
+            name_stats: None,
+            graph_format: None,
+            stats_out: None,
+            old_results: None,
+            chain_id: None,
+            start: None,
+            query_name: None,
+            query_osm: None,
+            previous_results: None,
+            previous_inputs: Vec::new(),
+            missing_links: None,
+            endpoints: None,
+            overpass_checks: None,
+            records_parquet: None,
+            verbosity: Level::Info,
+            color: Color::Auto,
+            log_file: None,
+            status_report: None,
+            dashboard: false,
+            metrics_addr: None,
+            listen: "127.0.0.1:8080".to_string(),
+            fixture_places: 1000,
+            fixture_chain_depth: 10,
+            fixture_seed: 0,
+            completions_shell: None,
+            bench_steps: 1_000_000,
+            config_matrix: None,
+            dry_run: false,
+            watch: None,
+            deterministic: false,
+            stream: None,
+            errors_json: None,
+            notify_url: None,
+            notify_email: None,
+            status_file: None,
+            status_interval: 30,
+            max_runtime: None,
+            search_limits: SearchLimits::default(),
+            scoring: Scoring::Distance,
+            starts: None,
+            start_bbox: None,
+            distinct_place_types: None,
+            boundary_admin_at_least: None,
+            within_country: None,
+            place_type_weights: PlaceTypeWeights::default(),
+            distance_model: DistanceModel::Sphere,
+            fill_names: None,
+            nominatim_url: nominatim::DEFAULT_URL.to_string(),
+        }
+    }
+}
+
+// What to do with the records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    // Find the longest chains
+    Search,
+    GraphExport,
+    Analyze,
+    Qa,
+    Verify,
+    Revalidate,
+    Extend,
+    Diff,
+    Timeline,
+    Explain,
+    Query,
+    Serve,
+    Repl,
+    GenFixture,
+    Bench,
+    Sweep,
+    Completions,
+}
+
+// (command, name, help). The command is the first argument, if there is one.
+pub const COMMANDS: &[(Command, &str, &str)] = &[
+    (
+        Command::GraphExport,
+        "graph-export",
+        "Write the graph of names (a place with this name is inside a boundary with that name) as GraphML or DOT, instead of searching",
+    ),
+    (
+        Command::Analyze,
+        "analyze",
+        "Write a markdown report of the connected components of the name graph, and an upper bound on the longest chain in each, instead of searching",
+    ),
+    (
+        Command::Qa,
+        "qa",
+        "Write a CSV of records which are probably tagging mistakes (empty or badly spaced names, odd admin_levels, places far from the rest of their boundary, cities in tiny boundaries), instead of searching",
+    ),
+    (
+        Command::Verify,
+        "verify",
+        "Check the places & boundaries in --format text or csv results files (the INPUTs) against the OSM API, and write what has been deleted, renamed or retagged since to OUTPUT as CSV. Needs curl",
+    ),
+    (
+        Command::Revalidate,
+        "revalidate",
+        "Check which chains in --old-results still hold in the INPUTs (every place still in its boundary, names unchanged), and write where each broke to OUTPUT as CSV, instead of searching",
+    ),
+    (
+        Command::Extend,
+        "extend",
+        "Only try to make the chains in --old-results longer at their ends, with the INPUTs and the current options, instead of searching from every place. Writes the chains to OUTPUT as usual, and prints which got longer",
+    ),
+    (
+        Command::Diff,
+        "diff",
+        "Compare 2 INPUTs, either results files (--format text or csv) or --stats-out files, and write what changed to OUTPUT as markdown",
+    ),
+    (
+        Command::Timeline,
+        "timeline",
+        "Search each INPUT (dated exports, e.g. planet-230501.csv.gz) separately, and write how the longest chains changed over time to OUTPUT (CSV, or JSON for a .json file)",
+    ),
+    (
+        Command::Explain,
+        "explain",
+        "Print why the --chain-id or --start chain ends where it does: every place with the name of its last boundary, and why it couldn't be next. Also written to OUTPUT",
+    ),
+    (
+        Command::Query,
+        "query",
+        "List the chains in results files (the INPUTs, --format text, csv or sqlite) which go through the --name or --osm place or boundary, and its position in each, as CSV",
+    ),
+    (
+        Command::Serve,
+        "serve",
+        "Serve the chains over HTTP on --listen, as a JSON API & a web page: list them by length or country, fetch one as GeoJSON, search by name. Either the --old-results file (then no INPUTs or OUTPUT), or search as usual first",
+    ),
+    (
+        Command::Repl,
+        "repl",
+        "Read the INPUTs, then answer questions typed in about the names & chains: name NAME, chain N, extendable? NAME, longest [--country CC]. The chains are the --old-results file (then no OUTPUT), or search as usual first",
+    ),
+    (
+        Command::GenFixture,
+        "gen-fixture",
+        "Write a made up input to OUTPUT (a .csv.gz, the only argument) for testing: one chain of --chain-depth places, which is the longest, then shorter chains, cycles & places with the same names, up to --places places",
+    ),
+    (
+        Command::Bench,
+        "bench",
+        "Time loading the INPUT (or a gen-fixture file made up with --places, --chain-depth & --seed, if there's no INPUT) and --steps steps of the search, and print records/s, steps/s, the biggest frontier & peak memory. No OUTPUT",
+    ),
+    (
+        Command::Sweep,
+        "sweep",
+        "Load the INPUT once, then search it with every combination of the search options in the --config-matrix file, and write the longest chain, time & memory of each to OUTPUT (CSV, or markdown for a .md file)",
+    ),
+    (
+        Command::Completions,
+        "completions",
+        "Print a completion script for SHELL (bash, zsh or fish), or the man page (man), the only argument. e.g. `x-in-y completions bash > /etc/bash_completion.d/x-in-y`",
+    ),
+];
+
+pub fn usage() -> String {
+    let mut s = format!(
+        "Usage: {} [COMMAND] [OPTIONS] INPUT.csv[.gz/.zst/.bz2/.xz]... OUTPUT\n\nCommands:\n",
+        option_env!("CARGO_PKG_NAME").unwrap_or("x-in-y")
+    );
+    for (_, name, help) in COMMANDS {
+        s.push_str(&format!("  {:<28} {}\n", name, help));
+    }
+    s.push_str("\nOptions:\n");
+    for flag in FLAGS {
+        let name = match flag.value {
+            Some(v) => format!("{} {}", flag.name, v),
+            None => flag.name.to_string(),
+        };
+        s.push_str(&format!("  {:<28} {}\n", name, flag.help));
+    }
+    s
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: &str) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value
+        .parse()
+        .with_context(|| format!("Invalid value for {}: {:?}", name, value))
+}
+
+// e.g. 90s, 30m, 12h or 2d. A plain number is seconds.
+fn parse_duration(s: &str) -> Result<Duration> {
+    let (number, unit_s) = match s.trim().char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1.),
+        Some((i, 'm')) => (&s[..i], 60.),
+        Some((i, 'h')) => (&s[..i], 3600.),
+        Some((i, 'd')) => (&s[..i], 86_400.),
+        _ => (s, 1.),
+    };
+    let invalid = || format!("Invalid duration {:?}, it should be like 90m or 12h", s);
+    let number: f64 = number.trim().parse().with_context(invalid)?;
+    Duration::try_from_secs_f64(number * unit_s).with_context(invalid)
+}
+
+// e.g. 24GB, 512M or 1.5GiB, in powers of 1024. A plain number is bytes.
+fn parse_size(s: &str) -> Result<u64> {
+    let invalid = || format!("Invalid size {:?}, it should be like 24GB or 512MB", s);
+    let upper = s.trim().to_uppercase();
+    let number_end = upper
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(upper.len());
+    let number: f64 = upper[..number_end].parse().with_context(invalid)?;
+    let unit: u64 = match upper[number_end..]
+        .trim()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => bail!(invalid()),
+    };
+    Ok((number * unit as f64) as u64)
+}
+
+// `n123`, or `node/123` like in OSM URLs
+pub fn parse_osm_id(s: &str) -> Result<OsmId> {
+    let invalid = || format!("Invalid OSM id {:?}, it should be like n123 or node/123", s);
+    let (osm_type, id) = match s.split_once('/') {
+        Some((osm_type, id)) => (
+            crate::results::osm_type_char(osm_type).with_context(invalid)?,
+            id,
+        ),
+        None => match s.chars().next() {
+            Some(t @ ('n' | 'w' | 'r')) => (t, &s[1..]),
+            _ => bail!(invalid()),
+        },
+    };
+    Ok((osm_type, id.parse().with_context(invalid)?))
+}
+
+impl Options {
+    // A search of these records with the settings from the command line. With `progress`, it
+    // shows how it's going, for the main search.
+    pub fn chain_search<'s, 'a>(
+        &'s self,
+        records: &'s [&'a Record],
+        progress: bool,
+    ) -> ChainSearch<'s, 'a> {
+        let search = ChainSearch::new(records)
+            .limits(self.search_limits.clone())
+            .observer(Reporter::new(progress));
+        let search = scoring::apply(search, self.scoring, &self.place_type_weights);
+        constraints::apply(search, self)
+    }
+
+    // How many chains to write out
+    pub fn max_chains(&self) -> usize {
+        match (self.max_chains, &self.output_dir) {
+            (Some(max_chains), _) => max_chains,
+            (None, Some(_)) => usize::MAX,
+            (None, None) => 1000,
+        }
+    }
+
+    // Every file this run was told to write
+    pub fn all_outputs(&self) -> Vec<String> {
+        std::iter::once(Some(&self.output_filename))
+            .chain([
+                self.stats_out.as_ref(),
+                self.per_country.as_ref(),
+                self.missing_links.as_ref(),
+                self.endpoints.as_ref(),
+                self.hubs_report.as_ref(),
+                self.name_stats.as_ref(),
+                self.overpass_checks.as_ref(),
+                self.records_parquet.as_ref(),
+                self.stream.as_ref(),
+            ])
+            .flatten()
+            .filter(|f| !f.is_empty())
+            .cloned()
+            .collect()
+    }
+
+    pub fn from_args() -> Result<Options> {
+        Options::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Options> {
+        let mut options = Options::default();
+        let mut positional = Vec::new();
+        let mut args = args.peekable();
+
+        if let Some((command, _, _)) = args
+            .peek()
+            .and_then(|arg| COMMANDS.iter().find(|(_, name, _)| name == arg))
+        {
+            options.command = *command;
+            args.next();
+        }
+
+        while let Some(mut arg) = args.next() {
+            match arg.as_str() {
+                "-v" => arg = "--verbose".to_string(),
+                "-q" => arg = "--quiet".to_string(),
+                _ => {}
+            }
+            if !arg.starts_with("--") {
+                positional.push(arg);
+                continue;
+            }
+            // Allow both `--flag value` and `--flag=value`
+            let (name, inline_value) = match arg.find('=') {
+                Some(i) => (arg[..i].to_string(), Some(arg[i + 1..].to_string())),
+                None => (arg.clone(), None),
+            };
+            let flag = match FLAGS.iter().find(|f| f.name == name) {
+                Some(f) => f,
+                None => bail!("Unknown option {}\n\n{}", name, usage()),
+            };
+            let value = match (flag.value, inline_value) {
+                (None, None) => String::new(),
+                (None, Some(_)) => bail!("Option {} doesn't take a value", name),
+                (Some(_), Some(v)) => v,
+                (Some(_), None) => args
+                    .next()
+                    .with_context(|| format!("Option {} requires a value", name))?,
+            };
+
+            match flag.name {
+                "--self-containment" => {
+                    options.self_containment = SelfContainmentRule::parse_list(&value)?
+                }
+                "--self-containment-report" => options.self_containment_report = Some(value),
+                "--maproulette" => options.maproulette = Some(value),
+                "--fuzzy-distance" => options.fuzzy_distance = parse_value(flag.name, &value)?,
+                "--dedup-radius" => options.dedup_radius_m = parse_value(flag.name, &value)?,
+                "--one-boundary-per-name" => options.one_boundary_per_name = true,
+                "--smallest-boundary" => options.smallest_boundary = true,
+                "--require-wikidata" => options.require_wikidata = true,
+                "--min-name-length" => options.min_name_length = parse_value(flag.name, &value)?,
+                "--remove-junk-names" => options.remove_junk_names = true,
+                "--column" => options
+                    .csv_format
+                    .columns
+                    .push(input::parse_column_mapping(&value)?),
+                "--delimiter" => options.csv_format.delimiter = input::parse_csv_char(&value)?,
+                "--quote" => options.csv_format.quote = Some(input::parse_csv_char(&value)?),
+                "--no-quoting" => options.csv_format.quote = None,
+                "--strict" => options.strict = true,
+                "--cache" => options.cache = true,
+                "--mmap" => options.mmap = true,
+                "--errors-report" => options.errors_report = Some(value),
+                "--unknown-tags-report" => options.unknown_tags_report = Some(value),
+                "--boundary-types" => {
+                    options.boundary_types.include = Some(BoundaryTypeFilter::parse_list(&value))
+                }
+                "--exclude-boundary-types" => options
+                    .boundary_types
+                    .exclude
+                    .extend(BoundaryTypeFilter::parse_list(&value)),
+                "--country-boundaries" => options.country_boundaries = Some(value),
+                "--auto-accept-threshold" => {
+                    options.auto_accept_threshold = Some(parse_value(flag.name, &value)?)
+                }
+                "--format" => options.format = value.parse()?,
+                "--max-chains" => options.max_chains = Some(parse_value(flag.name, &value)?),
+                "--output-dir" => options.output_dir = Some(value),
+                "--post-limit" => options.post_limit = parse_value(flag.name, &value)?,
+                "--per-country" => options.per_country = Some(value),
+                "--continents" => options.continents = Some(value),
+                "--hubs-report" => options.hubs_report = Some(value),
+                "--name-stats" => options.name_stats = Some(value),
+                "--graph-format" => options.graph_format = Some(value.parse()?),
+                "--overpass-checks" => options.overpass_checks = Some(value),
+                "--missing-links" => options.missing_links = Some(value),
+                "--endpoints" => options.endpoints = Some(value),
+                "--previous-results" => options.previous_results = Some(value),
+                "--previous-input" => options.previous_inputs.push(value),
+                "--old-results" => options.old_results = Some(value),
+                "--chain-id" => options.chain_id = Some(parse_value(flag.name, &value)?),
+                "--start" => options.start = Some(parse_osm_id(&value)?),
+                "--name" => options.query_name = Some(value),
+                "--osm" => options.query_osm = Some(parse_osm_id(&value)?),
+                "--stats-out" => options.stats_out = Some(value),
+                "--output-lang" => options.messages = Messages::load(&value)?,
+                "--template" => {
+                    options.template = Some(value);
+                    options.format = Format::Template;
+                }
+                "--records-parquet" => options.records_parquet = Some(value),
+                "--verbose" => options.verbosity = Level::Debug,
+                "--quiet" => options.verbosity = Level::Summary,
+                "--log-file" => options.log_file = Some(value),
+                "--color" => options.color = value.parse()?,
+                "--status-report" => options.status_report = Some(value),
+                "--dashboard" => options.dashboard = true,
+                "--metrics-addr" => options.metrics_addr = Some(value),
+                "--listen" => options.listen = value,
+                "--places" => options.fixture_places = parse_value(flag.name, &value)?,
+                "--chain-depth" => options.fixture_chain_depth = parse_value(flag.name, &value)?,
+                "--seed" => options.fixture_seed = parse_value(flag.name, &value)?,
+                "--steps" => options.bench_steps = parse_value(flag.name, &value)?,
+                "--config-matrix" => options.config_matrix = Some(value),
+                "--watch" => options.watch = Some(value),
+                "--deterministic" => options.deterministic = true,
+                "--dry-run" => options.dry_run = true,
+                "--stream" => options.stream = Some(value),
+                "--errors-json" => options.errors_json = Some(value),
+                "--notify-url" => options.notify_url = Some(value),
+                "--notify-email" => options.notify_email = Some(value),
+                "--status-file" => options.status_file = Some(value),
+                "--status-interval" => options.status_interval = parse_value(flag.name, &value)?,
+                "--max-runtime" => options.max_runtime = Some(parse_duration(&value)?),
+                "--memory-limit" => options.search_limits.memory_limit = Some(parse_size(&value)?),
+                "--max-chain-len" => {
+                    options.search_limits.max_chain_len = Some(parse_value(flag.name, &value)?)
+                }
+                "--max-intermediate" => {
+                    options.search_limits.max_intermediate = parse_value(flag.name, &value)?
+                }
+                "--prune-slack" => {
+                    options.search_limits.prune_slack = parse_value(flag.name, &value)?
+                }
+                "--failsafe" => options.search_limits.failsafe = value.parse()?,
+                "--score" => options.scoring = value.parse()?,
+                "--starts" => options.starts = Some(value),
+                "--start-bbox" => options.start_bbox = Some(starts::parse_bbox(&value)?),
+                "--distinct-place-types" => options.distinct_place_types = Some(value.parse()?),
+                "--within-country" => match countries::normalize_country_code(&value) {
+                    Some(code) if code.len() == 2 && code.bytes().all(|b| b.is_ascii_uppercase()) => {
+                        options.within_country = Some(code)
+                    }
+                    // Otherwise the constraint would quietly be off, or nothing would match
+                    _ => bail!(
+                        "Invalid value for {}: {:?}, it should be a 2 letter ISO 3166-1 code, e.g. IE",
+                        flag.name,
+                        value
+                    ),
+                },
+                "--boundary-admin-at-least" => {
+                    options.boundary_admin_at_least = Some(parse_value(flag.name, &value)?)
+                }
+                "--place-type-weights" => options.place_type_weights.set(&value)?,
+                "--distance-model" => options.distance_model = value.parse()?,
+                "--fill-names" => options.fill_names = Some(value),
+                "--nominatim-url" => options.nominatim_url = value,
+                "--help" => {
+                    print!("{}", usage());
+                    std::process::exit(0);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        // One or more input files, then the output file
+        if options.command == Command::Serve && options.old_results.is_some() {
+            if !positional.is_empty() {
+                bail!("serve with --old-results doesn't take INPUTs or an OUTPUT");
+            }
+        } else if options.command == Command::Repl && options.old_results.is_some() {
+            if positional.is_empty() {
+                bail!("Need at least one input csv filename\n\n{}", usage());
+            }
+            // There's no OUTPUT
+            positional.push(String::new());
+        } else if options.command == Command::GenFixture {
+            if positional.len() != 1 {
+                bail!("gen-fixture only takes the OUTPUT");
+            }
+        } else if options.command == Command::Completions {
+            if positional.len() != 1 {
+                bail!("completions only takes the SHELL: bash, zsh, fish or man");
+            }
+            options.completions_shell = Some(positional[0].parse()?);
+            // There's no OUTPUT, it's printed
+            positional[0] = String::new();
+        } else if options.command == Command::Bench {
+            if positional.len() > 1 {
+                bail!("bench takes at most one INPUT, and no OUTPUT");
+            }
+            // There's no OUTPUT
+            positional.push(String::new());
+        } else if options.command == Command::Sweep {
+            if positional.len() != 2 {
+                bail!("sweep takes one INPUT and the OUTPUT");
+            }
+            if options.config_matrix.is_none() {
+                bail!("sweep needs a --config-matrix file");
+            }
+        } else if options.output_dir.is_some() {
+            if options.command != Command::Search || options.watch.is_some() {
+                bail!("--output-dir only works for the usual search");
+            }
+            if positional.is_empty() {
+                bail!("Need at least one input csv filename\n\n{}", usage());
+            }
+            // The directory is the OUTPUT
+            positional.push(options.output_dir.clone().unwrap());
+        } else if options.watch.is_some() {
+            if options.command != Command::Search {
+                bail!("--watch only works for the usual search");
+            }
+            if positional.len() != 1 {
+                bail!("--watch only takes the OUTPUT, the inputs are the files which appear in the directory");
+            }
+        } else if positional.len() < 2 {
+            bail!(
+                "Need at least one input csv filename and an output filename\n\n{}",
+                usage()
+            );
+        }
+        options.output_filename = positional.pop().unwrap_or_default();
+        options.input_filenames = positional;
+
+        if options.format == Format::Template && options.template.is_none() {
+            bail!("--format template needs a --template file");
+        }
+        if options.previous_results.is_some() == options.previous_inputs.is_empty() {
+            bail!("--previous-results and --previous-input need to be used together");
+        }
+        if options.command == Command::Diff && options.input_filenames.len() != 2 {
+            bail!("diff needs 2 files to compare");
+        }
+        if options.dry_run && (options.command != Command::Search || options.watch.is_some()) {
+            bail!("--dry-run only works for the usual search");
+        }
+        if options.command == Command::Revalidate && options.old_results.is_none() {
+            bail!("revalidate needs an --old-results file");
+        }
+        if options.command == Command::Extend {
+            if options.old_results.is_none() {
+                bail!("extend needs an --old-results file with the chains to extend");
+            }
+            if options.previous_results.is_some() {
+                bail!("extend can't be used with --previous-results");
+            }
+        }
+        if options.command == Command::Query
+            && options.query_name.is_none()
+            && options.query_osm.is_none()
+        {
+            bail!("query needs a --name or --osm to look for");
+        }
+        if options.command == Command::Explain {
+            match (options.chain_id, options.start) {
+                (None, None) | (Some(_), Some(_)) => {
+                    bail!("explain needs either --chain-id or --start")
+                }
+                (Some(0), _) => bail!("--chain-id starts at 1"),
+                (Some(_), None) if options.old_results.is_none() => {
+                    bail!("--chain-id needs an --old-results file")
+                }
+                _ => {}
+            }
+        }
+
+        Ok(options)
+    }
+}
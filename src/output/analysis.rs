@@ -0,0 +1,85 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+use separator::Separatable;
+
+use crate::graph::Analysis;
+
+const NUM_COMPONENTS: usize = 100;
+
+// A markdown report of the structure of the name graph
+pub fn write(analysis: &Analysis, output: &mut impl Write) -> Result<()> {
+    let components = &analysis.components;
+    writeln!(output, "# Name graph analysis\n")?;
+    writeln!(
+        output,
+        "{} edges between names, in {} connected components.",
+        analysis.num_edges.separated_string(),
+        components.len().separated_string()
+    )?;
+    if let Some(biggest) = components.first() {
+        writeln!(
+            output,
+            "No chain can have more than {} places.",
+            biggest.chain_bound.separated_string()
+        )?;
+    }
+
+    writeln!(
+        output,
+        "\n## Components\n\nThe upper bound assumes a chain can go through each place in a cycle of names, and through each name outside a cycle once.\n"
+    )?;
+    writeln!(
+        output,
+        "| Example name | Names | Edges | Places | Largest SCC | Chain upper bound |\n| :-- | --: | --: | --: | --: | --: |"
+    )?;
+    for c in components.iter().take(NUM_COMPONENTS) {
+        writeln!(
+            output,
+            "| {} | {} | {} | {} | {} | {} |",
+            c.example.replace('|', "\\|"),
+            c.num_names.separated_string(),
+            c.num_edges.separated_string(),
+            c.num_places.separated_string(),
+            c.largest_scc.separated_string(),
+            c.chain_bound.separated_string(),
+        )?;
+    }
+    if components.len() > NUM_COMPONENTS {
+        writeln!(
+            output,
+            "\n…and {} more.",
+            (components.len() - NUM_COMPONENTS).separated_string()
+        )?;
+    }
+
+    writeln!(output, "\n## Component sizes\n")?;
+    writeln!(output, "| Names | Components |\n| --: | --: |")?;
+    let mut sizes = std::collections::BTreeMap::<usize, usize>::new();
+    for c in components {
+        *sizes.entry(c.num_names).or_default() += 1;
+    }
+    for (size, count) in sizes.iter().rev() {
+        writeln!(
+            output,
+            "| {} | {} |",
+            size.separated_string(),
+            count.separated_string()
+        )?;
+    }
+
+    writeln!(
+        output,
+        "\n## Strongly connected components\n\nNames which can reach each other, so a chain could go round in a cycle.\n"
+    )?;
+    writeln!(output, "| Names | SCCs |\n| --: | --: |")?;
+    for (size, count) in analysis.cyclic_scc_sizes.iter().rev() {
+        writeln!(
+            output,
+            "| {} | {} |",
+            size.separated_string(),
+            count.separated_string()
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::io::prelude::*;
+use std::time::SystemTime;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::Messages;
+use crate::output::{chain_shortlink, html_escape, overpass_url};
+use crate::record::Record;
+use crate::status_file::iso8601;
+
+// The same chain gets the same id in every run, so feed readers only show new chains as new
+fn chain_id(chain: &[&Record]) -> String {
+    let ids: Vec<String> = chain
+        .iter()
+        .map(|r| format!("{}{}", r.place_osmtype, r.place_id))
+        .chain(
+            chain
+                .last()
+                .map(|r| format!("{}{}", r.boundary_osmtype, r.boundary_id)),
+        )
+        .collect();
+    format!("urn:x-in-y:chain:{}", ids.join(","))
+}
+
+// An Atom feed with an entry for each chain, e.g. to subscribe to the results of --watch
+pub fn write(
+    chains: &[Chain],
+    messages: &Messages,
+    filename: &str,
+    output: &mut impl Write,
+) -> Result<()> {
+    let updated = iso8601(SystemTime::now());
+    let feed_name = std::path::Path::new(filename).file_stem().map_or_else(
+        || filename.to_string(),
+        |s| s.to_string_lossy().into_owned(),
+    );
+    writeln!(
+        output,
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>{}</title>
+<id>urn:x-in-y:feed:{}</id>
+<updated>{}</updated>
+<author><name>x-in-y</name></author>
+<generator uri="{}" version="{}">x-in-y</generator>"#,
+        html_escape(&messages.report_title),
+        html_escape(&feed_name),
+        updated,
+        env!("CARGO_PKG_REPOSITORY"),
+        env!("CARGO_PKG_VERSION"),
+    )?;
+    for (idx, chain) in chains.iter().enumerate() {
+        let stats = chain.stats();
+        let content = format!(
+            "<p>{}</p><p>{}</p>",
+            html_escape(&messages.story(chain, stats)),
+            messages.chain_links_html(chain, stats)
+        );
+        writeln!(
+            output,
+            r#"<entry>
+<title>{}</title>
+<id>{}</id>
+<updated>{}</updated>
+<link rel="alternate" href="{}"/>
+<link rel="related" href="{}"/>
+<summary>{}</summary>
+<content type="html">{}</content>
+</entry>"#,
+            html_escape(&messages.chain_title(idx, chain)),
+            html_escape(&chain_id(chain)),
+            updated,
+            html_escape(&chain_shortlink(stats)),
+            html_escape(&overpass_url(chain)),
+            html_escape(&messages.chain_distance(stats)),
+            html_escape(&content),
+        )?;
+    }
+    writeln!(output, "</feed>")?;
+    Ok(())
+}
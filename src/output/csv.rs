@@ -0,0 +1,70 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::freshness::timestamp_str;
+use crate::record::osm_type_name;
+
+// One row per place in each chain
+pub fn write(chains: &[Chain], output: &mut impl Write) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(output);
+    wtr.write_record([
+        "chain_id",
+        "position",
+        "place_osm_type",
+        "place_id",
+        "place_name",
+        "place_type",
+        "place_lat",
+        "place_lon",
+        "country_code",
+        "boundary_osm_type",
+        "boundary_id",
+        "boundary_name",
+        "boundary_type",
+        "boundary_admin_level",
+        "place_wikidata",
+        "place_wikipedia",
+        "boundary_wikidata",
+        "boundary_wikipedia",
+        "place_population",
+        "place_timestamp",
+        "boundary_timestamp",
+        "hop_distance_m",
+        "cumulative_distance_m",
+    ])?;
+    for (chain_idx, chain) in chains.iter().enumerate() {
+        let stats = chain.stats();
+        for (position, rec) in chain.iter().enumerate() {
+            wtr.write_record([
+                (chain_idx + 1).to_string().as_str(),
+                &position.to_string(),
+                osm_type_name(rec.place_osmtype),
+                &rec.place_id.to_string(),
+                &rec.place_name,
+                &rec.place_type,
+                &rec.place_lat.to_string(),
+                &rec.place_lon.to_string(),
+                rec.country_code.as_deref().unwrap_or(""),
+                osm_type_name(rec.boundary_osmtype),
+                &rec.boundary_id.to_string(),
+                &rec.boundary_name,
+                rec.boundary_type(),
+                &rec.admin_level_str(),
+                rec.place_wikidata.as_deref().unwrap_or(""),
+                rec.place_wikipedia.as_deref().unwrap_or(""),
+                rec.boundary_wikidata.as_deref().unwrap_or(""),
+                rec.boundary_wikipedia.as_deref().unwrap_or(""),
+                &rec.place_population
+                    .map_or(String::new(), |p| p.to_string()),
+                &rec.place_timestamp.map_or(String::new(), timestamp_str),
+                &rec.boundary_timestamp.map_or(String::new(), timestamp_str),
+                &(stats.hop_m[position].round() as u64).to_string(),
+                &(stats.cumulative_m[position].round() as u64).to_string(),
+            ])?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::json::obj;
+use crate::record::Record;
+
+// Where each chain starts & ends, with its length as the weight, for a heatmap. The end is the last
+// place, since the boundary it's in has no location in the input.
+fn endpoints<'a>(chain: &Chain<'a>) -> [(&'static str, &'a Record); 2] {
+    [("start", chain[0]), ("end", chain[chain.len() - 1])]
+}
+
+// A CSV, one row per point, which QGIS & most heatmap tools load directly
+pub fn write_csv(chains: &[Chain], output: &mut impl Write) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(output);
+    wtr.write_record(["lat", "lon", "kind", "length", "chain", "place_name"])?;
+    for (chain_idx, chain) in chains.iter().enumerate() {
+        for (kind, rec) in endpoints(chain) {
+            wtr.write_record([
+                rec.place_lat.to_string(),
+                rec.place_lon.to_string(),
+                kind.to_string(),
+                chain.len().to_string(),
+                chain_idx.to_string(),
+                rec.place_name.to_string(),
+            ])?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// A FeatureCollection of Points, one feature per line
+pub fn write_geojson(chains: &[Chain], output: &mut impl Write) -> Result<()> {
+    writeln!(output, "{{\"type\":\"FeatureCollection\",\"features\":[")?;
+    let mut first = true;
+    for (chain_idx, chain) in chains.iter().enumerate() {
+        for (kind, rec) in endpoints(chain) {
+            if !first {
+                writeln!(output, ",")?;
+            }
+            first = false;
+            let feature = obj(vec![
+                ("type", "Feature".into()),
+                (
+                    "geometry",
+                    obj(vec![
+                        ("type", "Point".into()),
+                        ("coordinates", vec![rec.place_lon, rec.place_lat].into()),
+                    ]),
+                ),
+                (
+                    "properties",
+                    obj(vec![
+                        ("kind", kind.into()),
+                        ("length", chain.len().into()),
+                        ("chain", chain_idx.into()),
+                        ("place_name", (&rec.place_name).into()),
+                    ]),
+                ),
+            ]);
+            write!(output, "{}", feature)?;
+        }
+    }
+    writeln!(output, "\n]}}")?;
+    Ok(())
+}
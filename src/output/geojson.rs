@@ -0,0 +1,87 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::freshness::timestamp_str;
+use crate::json::{obj, Value};
+use crate::output::{chain_shortlink, overpass_url, record_properties};
+use crate::record::Record;
+
+fn point(rec: &Record) -> Value {
+    vec![rec.place_lon, rec.place_lat].into()
+}
+
+// The features for one chain: a LineString through all the places, then a Point for each place
+pub fn chain_features(chain_idx: usize, chain: &Chain) -> Vec<Value> {
+    let stats = chain.stats();
+    let mut features = Vec::with_capacity(chain.len() + 1);
+    features.push(obj(vec![
+        ("type", "Feature".into()),
+        ("bbox", stats.bbox.to_vec().into()),
+        (
+            "geometry",
+            obj(vec![
+                ("type", "LineString".into()),
+                (
+                    "coordinates",
+                    Value::Array(chain.iter().map(|r| point(r)).collect()),
+                ),
+            ]),
+        ),
+        (
+            "properties",
+            obj(vec![
+                ("chain", chain_idx.into()),
+                ("length", chain.len().into()),
+                ("start", (&chain[0].place_name).into()),
+                ("end", (&chain[chain.len() - 1].boundary_name).into()),
+                ("total_distance_m", stats.total_m.round().into()),
+                ("total_population", stats.total_population.into()),
+                ("oldest_edit", stats.oldest_edit.map(timestamp_str).into()),
+                ("newest_edit", stats.newest_edit.map(timestamp_str).into()),
+                ("freshness", stats.freshness().map(|f| f.name()).into()),
+                ("overpass_url", overpass_url(chain).into()),
+                ("osm_shortlink", chain_shortlink(stats).into()),
+            ]),
+        ),
+    ]));
+    for hop in chain.iter_hops() {
+        let mut properties = vec![
+            ("chain", chain_idx.into()),
+            ("position", hop.position.into()),
+        ];
+        properties.extend(record_properties(hop.record));
+        properties.push(("hop_distance_m", hop.hop_m.round().into()));
+        properties.push(("cumulative_distance_m", hop.cumulative_m.round().into()));
+        features.push(obj(vec![
+            ("type", "Feature".into()),
+            (
+                "geometry",
+                obj(vec![
+                    ("type", "Point".into()),
+                    ("coordinates", point(hop.record)),
+                ]),
+            ),
+            ("properties", obj(properties)),
+        ]));
+    }
+    features
+}
+
+// One FeatureCollection with all the chains. Each feature is on its own line.
+pub fn write(chains: &[Chain], output: &mut impl Write) -> Result<()> {
+    writeln!(output, "{{\"type\":\"FeatureCollection\",\"features\":[")?;
+    let mut first = true;
+    for (chain_idx, chain) in chains.iter().enumerate() {
+        for feature in chain_features(chain_idx, chain) {
+            if !first {
+                writeln!(output, ",")?;
+            }
+            first = false;
+            write!(output, "{}", feature)?;
+        }
+    }
+    writeln!(output, "\n]}}")?;
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::Messages;
+use crate::output::html_escape;
+
+// Each chain is a track, and each place is also a waypoint
+pub fn write(chains: &[Chain], messages: &Messages, output: &mut impl Write) -> Result<()> {
+    writeln!(
+        output,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="{}" xmlns="http://www.topografix.com/GPX/1/1">"#,
+        option_env!("CARGO_PKG_NAME").unwrap_or("x-in-y")
+    )?;
+    // GPX wants all the waypoints before the tracks
+    for (idx, chain) in chains.iter().enumerate() {
+        let stats = chain.stats();
+        for (i, r) in chain.iter().enumerate() {
+            writeln!(
+                output,
+                "<wpt lat=\"{}\" lon=\"{}\"><name>{}</name><desc>Chain {}, place {}: place={} in {}. {}</desc><link href=\"{}\"/></wpt>",
+                r.place_lat,
+                r.place_lon,
+                html_escape(&r.place_name),
+                idx + 1,
+                i + 1,
+                html_escape(&r.place_type),
+                html_escape(&r.boundary_name),
+                html_escape(&messages.hop_distance(stats, i)),
+                html_escape(&r.place_url()),
+            )?;
+        }
+    }
+    for (idx, chain) in chains.iter().enumerate() {
+        writeln!(
+            output,
+            "<trk><name>{}</name><desc>{}</desc><trkseg>",
+            html_escape(&messages.chain_title(idx, chain)),
+            html_escape(&messages.chain_distance(chain.stats()))
+        )?;
+        for r in chain.iter() {
+            writeln!(
+                output,
+                "<trkpt lat=\"{}\" lon=\"{}\"><name>{}</name></trkpt>",
+                r.place_lat,
+                r.place_lon,
+                html_escape(&r.place_name)
+            )?;
+        }
+        writeln!(output, "</trkseg></trk>")?;
+    }
+    writeln!(output, "</gpx>")?;
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::graph::{GraphFormat, NameGraph};
+use crate::output::html_escape;
+
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// The name graph, only with the edges which could be in a chain. Returns the number of edges.
+pub fn write(graph: &NameGraph, format: GraphFormat, output: &mut impl Write) -> Result<usize> {
+    let edges: Vec<_> = graph.chain_edges().collect();
+    let nodes: BTreeSet<&str> = edges.iter().flat_map(|(a, b, _)| [*a, *b]).collect();
+    match format {
+        GraphFormat::GraphMl => {
+            writeln!(
+                output,
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+<key id="num_places" for="node" attr.name="num_places" attr.type="int"/>
+<key id="num_boundaries" for="node" attr.name="num_boundaries" attr.type="int"/>
+<key id="weight" for="edge" attr.name="weight" attr.type="int"/>
+<graph id="names" edgedefault="directed">"#
+            )?;
+            for name in nodes.iter() {
+                let n = &graph.names[name];
+                writeln!(
+                    output,
+                    "<node id=\"{}\"><data key=\"num_places\">{}</data><data key=\"num_boundaries\">{}</data></node>",
+                    html_escape(name),
+                    n.places.len(),
+                    n.boundaries.len()
+                )?;
+            }
+            for (from, to, count) in edges.iter() {
+                writeln!(
+                    output,
+                    "<edge source=\"{}\" target=\"{}\"><data key=\"weight\">{}</data></edge>",
+                    html_escape(from),
+                    html_escape(to),
+                    count
+                )?;
+            }
+            writeln!(output, "</graph>\n</graphml>")?;
+        }
+        GraphFormat::Dot => {
+            writeln!(output, "digraph names {{")?;
+            for (from, to, count) in edges.iter() {
+                writeln!(
+                    output,
+                    "  {} -> {} [weight={}];",
+                    dot_quote(from),
+                    dot_quote(to),
+                    count
+                )?;
+            }
+            writeln!(output, "}}")?;
+        }
+        // Row & column i (from 1) are line i of the labels file, see `write_labels`
+        GraphFormat::MatrixMarket => {
+            let index: BTreeMap<&str, usize> =
+                nodes.iter().enumerate().map(|(i, n)| (*n, i + 1)).collect();
+            writeln!(output, "%%MatrixMarket matrix coordinate integer general")?;
+            writeln!(
+                output,
+                "% Rows & columns are the names in the labels file. An entry is how many places with the row's name are in a boundary with the column's name"
+            )?;
+            writeln!(output, "{} {} {}", nodes.len(), nodes.len(), edges.len())?;
+            for (from, to, count) in edges.iter() {
+                writeln!(output, "{} {} {}", index[from], index[to], count)?;
+            }
+        }
+    }
+    Ok(edges.len())
+}
+
+// The labels file of --graph-format mtx: the names, one per line, in the order of the rows &
+// columns
+pub fn write_labels(graph: &NameGraph, output: &mut impl Write) -> Result<()> {
+    let nodes: BTreeSet<&str> = graph.chain_edges().flat_map(|(a, b, _)| [a, b]).collect();
+    for name in nodes {
+        // A name is a line
+        writeln!(output, "{}", name.replace(['\n', '\r'], " "))?;
+    }
+    Ok(())
+}
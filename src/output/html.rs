@@ -0,0 +1,72 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::Messages;
+use crate::output::{html_escape, Summary};
+
+pub fn write(
+    chains: &[Chain],
+    summary: &Summary,
+    messages: &Messages,
+    output: &mut impl Write,
+) -> Result<()> {
+    let title = html_escape(&messages.report_title);
+    writeln!(
+        output,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>",
+        title
+    )?;
+    writeln!(output, "<h1>{}</h1>", title)?;
+    writeln!(
+        output,
+        "<p>{}</p>",
+        html_escape(&messages.report_summary(
+            summary.num_chains(),
+            chains.first().map_or(0, |c| c.len()),
+            chains.len()
+        ))
+    )?;
+    writeln!(
+        output,
+        "<table>\n<tr><th>{}</th><th>{}</th></tr>",
+        html_escape(&messages.length),
+        html_escape(&messages.chains)
+    )?;
+    for (len, total) in summary.totals_per_len.iter().rev().filter(|(l, _)| **l > 1) {
+        writeln!(
+            output,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            len,
+            html_escape(&messages.number(*total))
+        )?;
+    }
+    writeln!(output, "</table>")?;
+
+    for (idx, chain) in chains.iter().enumerate() {
+        let stats = chain.stats();
+        writeln!(
+            output,
+            "<h2 id=\"chain-{id}\"><a href=\"#chain-{id}\">{}</a></h2>\n<p>{}. {}</p>\n<ol>",
+            html_escape(&messages.chain_title(idx, chain)),
+            html_escape(&messages.chain_distance(stats)),
+            messages.chain_links_html(chain, stats),
+            id = idx + 1,
+        )?;
+        for (i, r) in chain.iter().enumerate() {
+            write!(output, "<li>{}", messages.sentence_html(r))?;
+            if i > 0 {
+                write!(
+                    output,
+                    " ({})",
+                    html_escape(&messages.hop_distance(stats, i))
+                )?;
+            }
+            writeln!(output, "</li>")?;
+        }
+        writeln!(output, "</ol>")?;
+    }
+    writeln!(output, "</body>\n</html>")?;
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::json::{obj, Value};
+use crate::output::record_properties;
+
+// Leaflet is loaded from a CDN, everything else (incl. the chains) is in the one file.
+const LEAFLET_VERSION: &str = "1.9.4";
+
+fn chains_json(chains: &[Chain]) -> Value {
+    Value::Array(
+        chains
+            .iter()
+            .enumerate()
+            .map(|(i, chain)| {
+                obj(vec![
+                    ("rank", (i + 1).into()),
+                    ("length", chain.len().into()),
+                    (
+                        "hops",
+                        Value::Array(chain.iter().map(|r| obj(record_properties(r))).collect()),
+                    ),
+                ])
+            })
+            .collect(),
+    )
+}
+
+const SCRIPT: &str = r##"
+var map = L.map('map', {worldCopyJump: true}).setView([20, 0], 2);
+L.tileLayer('https://tile.openstreetmap.org/{z}/{x}/{y}.png', {
+    maxZoom: 19,
+    attribution: '&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a> contributors'
+}).addTo(map);
+
+var colours = ['#e6194b', '#3cb44b', '#4363d8', '#f58231', '#911eb4', '#42d4f4', '#f032e6', '#9a6324'];
+function esc(s) {
+    return String(s).replace(/[&<>"]/g, function(c) { return {'&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;'}[c]; });
+}
+function hopPopup(i, h) {
+    return (i + 1) + ': <code>place=' + esc(h.place_type) + '</code> <a href="' + h.place_url + '">' + esc(h.place_name) + '</a>'
+        + ' in <a href="' + h.boundary_url + '">' + esc(h.boundary_name) + '</a> (admin_level=' + esc(h.admin_level) + ')';
+}
+
+var layers = [];
+var list = document.getElementById('chains');
+CHAINS.forEach(function(chain, idx) {
+    var colour = colours[idx % colours.length];
+    var layer = L.featureGroup();
+    L.polyline(chain.hops.map(function(h) { return [h.place_lat, h.place_lon]; }), {color: colour, weight: 3})
+        .bindPopup('Chain #' + chain.rank + ', length ' + chain.length)
+        .addTo(layer);
+    chain.hops.forEach(function(h, i) {
+        L.circleMarker([h.place_lat, h.place_lon], {radius: 5, color: colour}).bindPopup(hopPopup(i, h)).addTo(layer);
+    });
+    layers.push(layer);
+
+    var item = document.createElement('li');
+    item.innerHTML = '<a href="#chain-' + chain.rank + '">#' + chain.rank + ' (' + chain.length + '): '
+        + esc(chain.hops[0].place_name) + ' &rarr; ' + esc(chain.hops[chain.hops.length - 1].boundary_name) + '</a>';
+    item.onclick = function() { show(idx); };
+    list.appendChild(item);
+});
+
+function show(idx) {
+    layers.forEach(function(l) { map.removeLayer(l); });
+    layers[idx].addTo(map);
+    map.fitBounds(layers[idx].getBounds(), {padding: [20, 20]});
+    var items = list.getElementsByTagName('li');
+    for (var i = 0; i < items.length; i++) {
+        items[i].className = (i == idx) ? 'selected' : '';
+    }
+}
+var start = parseInt((location.hash.match(/^#chain-(\d+)$/) || [])[1] || '1', 10) - 1;
+if (layers.length > 0) { show(Math.min(Math.max(start, 0), layers.length - 1)); }
+"##;
+
+pub fn write(chains: &[Chain], output: &mut impl Write) -> Result<()> {
+    writeln!(
+        output,
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Longest place/boundary chains</title>
+<link rel="stylesheet" href="https://unpkg.com/leaflet@{v}/dist/leaflet.css">
+<script src="https://unpkg.com/leaflet@{v}/dist/leaflet.js"></script>
+<style>
+html, body {{ margin: 0; height: 100%; font-family: sans-serif; }}
+#sidebar {{ position: absolute; top: 0; bottom: 0; left: 0; width: 320px; overflow-y: auto; font-size: 13px; }}
+#sidebar li.selected {{ font-weight: bold; }}
+#map {{ position: absolute; top: 0; bottom: 0; left: 320px; right: 0; }}
+</style>
+</head>
+<body>
+<div id="sidebar"><h3>Longest place/boundary chains</h3><ol id="chains" style="list-style: none; padding-left: 1em;"></ol></div>
+<div id="map"></div>
+<script>"#,
+        v = LEAFLET_VERSION
+    )?;
+    // Don't let a name end the <script> early
+    writeln!(
+        output,
+        "var CHAINS = {};",
+        chains_json(chains).to_string().replace("</", "<\\/")
+    )?;
+    writeln!(output, "{}</script>\n</body>\n</html>", SCRIPT)?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+use separator::Separatable;
+
+use crate::graph::NameGraph;
+
+const NUM_HUBS: usize = 100;
+
+// A markdown table of the names which are in the most chains, and have the most other names
+// going in and out
+pub fn write(graph: &NameGraph, output: &mut impl Write) -> Result<()> {
+    let mut hubs: Vec<_> = graph.linkable().collect();
+    hubs.sort_by_key(|(name, n)| {
+        (
+            std::cmp::Reverse((n.num_chains, n.in_edges.len() * n.out_edges.len())),
+            **name,
+        )
+    });
+    hubs.truncate(NUM_HUBS);
+
+    writeln!(output, "# The most connective names\n")?;
+    writeln!(
+        output,
+        "In-degree is how many different names of places are inside boundaries with this name, out-degree is how many different names of boundaries places with this name are inside.\n"
+    )?;
+    writeln!(
+        output,
+        "| Name | Chains | In-degree | Out-degree | Places | Boundaries | Longest chain |\n| :-- | --: | --: | --: | --: | --: | --: |"
+    )?;
+    for (name, n) in hubs {
+        writeln!(
+            output,
+            "| {} | {} | {} | {} | {} | {} | {} |",
+            name.replace('|', "\\|"),
+            n.num_chains.separated_string(),
+            n.in_edges.len().separated_string(),
+            n.out_edges.len().separated_string(),
+            n.places.len().separated_string(),
+            n.boundaries.len().separated_string(),
+            n.longest_chain,
+        )?;
+    }
+    Ok(())
+}
+
+// Every linkable name, as CSV
+pub fn write_csv(graph: &NameGraph, output: &mut impl Write) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(output);
+    wtr.write_record([
+        "name",
+        "num_places",
+        "num_boundaries",
+        "in_degree",
+        "out_degree",
+        "num_chains",
+        "longest_chain",
+    ])?;
+    for (name, n) in graph.linkable() {
+        wtr.write_record([
+            name.to_string(),
+            n.places.len().to_string(),
+            n.boundaries.len().to_string(),
+            n.in_edges.len().to_string(),
+            n.out_edges.len().to_string(),
+            n.num_chains.to_string(),
+            n.longest_chain.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
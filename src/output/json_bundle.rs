@@ -0,0 +1,68 @@
+// A directory of small JSON files for a website: `index.json` lists the chains, and
+// `chains/N.json` has everything about chain N, with its GeoJSON. They can be served as they are,
+// and a JS frontend only fetches the chains it shows.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::chain::Chain;
+use crate::json::{obj, Value};
+use crate::output::{ndjson, Summary};
+
+fn write_json(path: &Path, value: &Value) -> Result<()> {
+    std::fs::write(path, format!("{}\n", value))
+        .with_context(|| format!("Could not write {:?}", path))
+}
+
+pub fn write(chains: &[Chain], summary: &Summary, dirname: &str) -> Result<()> {
+    let dir = Path::new(dirname);
+    let chains_dir = dir.join("chains");
+    // Chains from an earlier run, which there might have been more of
+    if chains_dir.exists() {
+        std::fs::remove_dir_all(&chains_dir)
+            .with_context(|| format!("Could not remove old {:?}", chains_dir))?;
+    }
+    std::fs::create_dir_all(&chains_dir)
+        .with_context(|| format!("Could not create {:?}", chains_dir))?;
+
+    let mut index = Vec::with_capacity(chains.len());
+    for (chain_idx, chain) in chains.iter().enumerate() {
+        let chain_id = chain_idx + 1;
+        let file = format!("chains/{}.json", chain_id);
+        index.push(obj(vec![
+            ("id", chain_id.into()),
+            ("length", chain.len().into()),
+            ("start", (&chain[0].place_name).into()),
+            ("end", (&chain[chain.len() - 1].boundary_name).into()),
+            ("total_distance_m", chain.total_distance_m().round().into()),
+            ("bbox", chain.bounding_box().to_vec().into()),
+            ("file", file.as_str().into()),
+        ]));
+
+        let mut value = ndjson::chain_json(chain);
+        if let Value::Object(pairs) = &mut value {
+            pairs.insert(0, ("id".to_string(), chain_id.into()));
+            pairs.push(("geojson".to_string(), chain.to_geojson(chain_idx)));
+        }
+        write_json(&dir.join(file), &value)?;
+    }
+
+    write_json(
+        &dir.join("index.json"),
+        &obj(vec![
+            ("num_chains", summary.num_chains().into()),
+            (
+                "totals_per_len",
+                Value::Object(
+                    summary
+                        .totals_per_len
+                        .iter()
+                        .map(|(len, total)| (len.to_string(), (*total).into()))
+                        .collect(),
+                ),
+            ),
+            ("chains", Value::Array(index)),
+        ]),
+    )
+}
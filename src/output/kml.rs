@@ -0,0 +1,63 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::{wiki_links_html, Messages};
+use crate::output::html_escape;
+
+// One Folder per chain, with the path and a Placemark for each place
+pub fn write(chains: &[Chain], messages: &Messages, output: &mut impl Write) -> Result<()> {
+    writeln!(
+        output,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2">
+<Document>
+<name>Longest place/boundary chains</name>
+<Style id="path"><LineStyle><color>ff0000ff</color><width>3</width></LineStyle></Style>"#
+    )?;
+    for (idx, chain) in chains.iter().enumerate() {
+        let stats = chain.stats();
+        writeln!(output, "<Folder>")?;
+        writeln!(
+            output,
+            "<name>{}</name><description>{}</description>",
+            html_escape(&messages.chain_title(idx, chain)),
+            html_escape(&messages.chain_distance(stats))
+        )?;
+        writeln!(
+            output,
+            "<Placemark><name>Path</name><styleUrl>#path</styleUrl><LineString><tessellate>1</tessellate><coordinates>"
+        )?;
+        for r in chain.iter() {
+            writeln!(output, "{},{},0", r.place_lon, r.place_lat)?;
+        }
+        writeln!(output, "</coordinates></LineString></Placemark>")?;
+        for (i, r) in chain.iter().enumerate() {
+            writeln!(
+                output,
+                "<Placemark><name>{}. {}</name><description><![CDATA[<code>place={}</code> <a href=\"{}\">{}</a>{} in <a href=\"{}\">{}</a>{} (admin_level={}){}]]></description><Point><coordinates>{},{},0</coordinates></Point></Placemark>",
+                i + 1,
+                html_escape(&r.place_name),
+                html_escape(&r.place_type),
+                r.place_url(),
+                html_escape(&r.place_name),
+                wiki_links_html(r.place_wiki_urls()),
+                r.boundary_url(),
+                html_escape(&r.boundary_name),
+                wiki_links_html(r.boundary_wiki_urls()),
+                html_escape(&r.admin_level_str()),
+                if i > 0 {
+                    format!("<br>{}", html_escape(&messages.hop_distance(stats, i)))
+                } else {
+                    String::new()
+                },
+                r.place_lon,
+                r.place_lat,
+            )?;
+        }
+        writeln!(output, "</Folder>")?;
+    }
+    writeln!(output, "</Document>\n</kml>")?;
+    Ok(())
+}
@@ -0,0 +1,116 @@
+// A MapRoulette challenge, for fixing the problems in OSM which the filters find.
+// Line-by-line GeoJSON: each line is a FeatureCollection which is one task.
+// See https://learn.maproulette.org/documentation/line-by-line-geojson/
+
+use std::collections::HashSet;
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::distance;
+use crate::json::{obj, Value};
+use crate::record::{osm_type_name, Record};
+
+fn place_feature(rec: &Record, properties: Vec<(&str, Value)>) -> Value {
+    let mut all_properties = vec![
+        (
+            "@id",
+            format!("{}/{}", osm_type_name(rec.place_osmtype), rec.place_id).into(),
+        ),
+        ("name", (&rec.place_name).into()),
+        ("place", (&rec.place_type).into()),
+    ];
+    all_properties.extend(properties);
+    obj(vec![
+        ("type", "Feature".into()),
+        (
+            "geometry",
+            obj(vec![
+                ("type", "Point".into()),
+                ("coordinates", vec![rec.place_lon, rec.place_lat].into()),
+            ]),
+        ),
+        ("properties", obj(all_properties)),
+    ])
+}
+
+fn task(features: Vec<Value>) -> Value {
+    obj(vec![
+        ("type", "FeatureCollection".into()),
+        ("features", Value::Array(features)),
+    ])
+}
+
+// One task for each place in a boundary with the same name, and one for each pair of nearby
+// duplicates. Returns the number of tasks.
+pub fn write(
+    self_contained: &[(&str, Record)],
+    duplicates: &[(Record, Record)],
+    output: &mut impl Write,
+) -> Result<usize> {
+    let mut num_tasks = 0;
+    let mut seen = HashSet::new();
+    for (_rule, rec) in self_contained {
+        if !seen.insert(rec.place_key()) {
+            continue;
+        }
+        let instructions = format!(
+            "The place {} is inside the boundary {} ({}). If they're the same thing, the place node may be redundant, or could be the boundary's label node (role=label). If they're different, check the names.",
+            rec.place_name,
+            rec.boundary_name,
+            rec.boundary_url(),
+        );
+        let feature = place_feature(
+            rec,
+            vec![
+                ("issue", "place_named_like_boundary".into()),
+                (
+                    "boundary",
+                    format!(
+                        "{}/{}",
+                        osm_type_name(rec.boundary_osmtype),
+                        rec.boundary_id
+                    )
+                    .into(),
+                ),
+                ("boundary_name", (&rec.boundary_name).into()),
+                ("instructions", instructions.into()),
+            ],
+        );
+        writeln!(output, "{}", task(vec![feature]))?;
+        num_tasks += 1;
+    }
+
+    for (removed, kept) in duplicates {
+        let dist_m = distance::metres(
+            removed.place_lat,
+            removed.place_lon,
+            kept.place_lat,
+            kept.place_lon,
+        );
+        let instructions = format!(
+            "There are 2 places called {}, {:.0} m apart ({} and {}). If they're the same place, one of them should be removed.",
+            removed.place_name,
+            dist_m,
+            removed.place_url(),
+            kept.place_url(),
+        );
+        let properties = || {
+            vec![
+                ("issue", "nearby_duplicate".into()),
+                ("distance_m", dist_m.round().into()),
+                ("instructions", instructions.clone().into()),
+            ]
+        };
+        writeln!(
+            output,
+            "{}",
+            task(vec![
+                place_feature(removed, properties()),
+                place_feature(kept, properties()),
+            ])
+        )?;
+        num_tasks += 1;
+    }
+    Ok(num_tasks)
+}
@@ -0,0 +1,59 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::Messages;
+use crate::output::Summary;
+
+pub fn write(
+    chains: &[Chain],
+    summary: &Summary,
+    messages: &Messages,
+    output: &mut impl Write,
+) -> Result<()> {
+    writeln!(output, "# {}\n", messages.report_title)?;
+    writeln!(
+        output,
+        "{}\n",
+        messages.report_summary(
+            summary.num_chains(),
+            chains.first().map_or(0, |c| c.len()),
+            chains.len()
+        )
+    )?;
+    writeln!(
+        output,
+        "| {} | {} |\n| -----: | -----: |",
+        messages.length, messages.chains
+    )?;
+    for (len, total) in summary.totals_per_len.iter().rev().filter(|(l, _)| **l > 1) {
+        writeln!(output, "| {} | {} |", len, messages.number(*total))?;
+    }
+    writeln!(output)?;
+
+    for (idx, chain) in chains.iter().enumerate() {
+        writeln!(
+            output,
+            "## <a id=\"chain-{}\"></a>{}\n",
+            idx + 1,
+            messages.chain_title(idx, chain)
+        )?;
+        let stats = chain.stats();
+        writeln!(
+            output,
+            "{}. {}\n",
+            messages.chain_distance(stats),
+            messages.chain_links_markdown(chain, stats)
+        )?;
+        for (i, r) in chain.iter().enumerate() {
+            write!(output, "{}. {}", i + 1, messages.sentence_markdown(r))?;
+            if i > 0 {
+                write!(output, " ({})", messages.hop_distance(stats, i))?;
+            }
+            writeln!(output)?;
+        }
+        writeln!(output)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,391 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+use anyhow::{bail, Context, Result};
+
+use crate::chain::Chain;
+use crate::chain::ChainStats;
+use crate::freshness::timestamp_str;
+use crate::json::Value;
+use crate::options::Options;
+use crate::record::{osm_type_name, Record};
+
+pub mod analysis;
+mod atom;
+mod csv;
+pub mod endpoints;
+pub mod geojson;
+mod gpx;
+pub mod graph;
+mod html;
+mod html_map;
+pub mod hubs;
+mod json_bundle;
+mod kml;
+pub mod maproulette;
+mod markdown;
+pub mod ndjson;
+mod osmwiki;
+pub mod output_dir;
+pub mod overpass_checks;
+pub mod parquet;
+pub mod per_country;
+mod quiz;
+mod sqlite;
+mod story;
+pub mod template;
+pub mod text;
+mod thread;
+mod tree;
+mod umap;
+
+// What the output file looks like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // The original "chain of len N:" markdown-ish text
+    Text,
+    GeoJson,
+    // One HTML page with a Leaflet map of the chains
+    HtmlMap,
+    // An SQLite database file
+    Sqlite,
+    // One JSON object per chain, per line
+    NdJson,
+    // One row per place in each chain
+    Csv,
+    // Reports with a summary and headings for each chain
+    Markdown,
+    Html,
+    // For Google Earth
+    Kml,
+    // Each chain as a track
+    Gpx,
+    // Columnar, one row per place in each chain
+    Parquet,
+    // User supplied template, see `template.rs`
+    Template,
+    // Prose for each chain
+    Story,
+    // Short posts for social media
+    Thread,
+    // A MediaWiki table
+    OsmWiki,
+    // GeoJSON layers with uMap styling
+    Umap,
+    // A directory of JSON files for a website
+    JsonBundle,
+    // A feed to subscribe to
+    Atom,
+    // Hints for a guessing game, with the names as answers
+    Quiz,
+    // Chains which end the same way, drawn as trees
+    Tree,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Format> {
+        Ok(match s {
+            "text" => Format::Text,
+            "geojson" => Format::GeoJson,
+            "html-map" => Format::HtmlMap,
+            "sqlite" => Format::Sqlite,
+            "ndjson" => Format::NdJson,
+            "csv" => Format::Csv,
+            "markdown" => Format::Markdown,
+            "html" => Format::Html,
+            "kml" => Format::Kml,
+            "gpx" => Format::Gpx,
+            "parquet" => Format::Parquet,
+            "template" => Format::Template,
+            "story" => Format::Story,
+            "thread" => Format::Thread,
+            "osmwiki" => Format::OsmWiki,
+            "umap" => Format::Umap,
+            "json-bundle" => Format::JsonBundle,
+            "atom" => Format::Atom,
+            "quiz" => Format::Quiz,
+            "tree" => Format::Tree,
+            x => bail!("Unknown output format {:?}", x),
+        })
+    }
+}
+
+impl Format {
+    // For the files --output-dir makes
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Text | Format::Template | Format::Story | Format::Thread | Format::Tree => {
+                "txt"
+            }
+            Format::GeoJson => "geojson",
+            Format::HtmlMap | Format::Html => "html",
+            Format::Sqlite => "sqlite",
+            Format::NdJson => "ndjson",
+            Format::Csv => "csv",
+            Format::Markdown => "md",
+            Format::Kml => "kml",
+            Format::Gpx => "gpx",
+            Format::Parquet => "parquet",
+            Format::OsmWiki => "wiki",
+            Format::Umap => "umap",
+            Format::JsonBundle | Format::Quiz => "json",
+            Format::Atom => "atom",
+        }
+    }
+}
+
+// The names of all the fields of a record, for the JSON-ish formats and templates
+pub const RECORD_PROPERTIES: &[&str] = &[
+    "place_osm_type",
+    "place_id",
+    "place_name",
+    "place_type",
+    "place_url",
+    "place_lat",
+    "place_lon",
+    "country_code",
+    "boundary_osm_type",
+    "boundary_id",
+    "boundary_name",
+    "boundary_url",
+    "boundary_type",
+    "admin_level",
+    "place_wikidata_url",
+    "place_wikipedia_url",
+    "boundary_wikidata_url",
+    "boundary_wikipedia_url",
+    "place_population",
+    "place_timestamp",
+    "boundary_timestamp",
+];
+
+pub fn record_property(rec: &Record, name: &str) -> Option<Value> {
+    Some(match name {
+        "place_osm_type" => osm_type_name(rec.place_osmtype).into(),
+        "place_id" => rec.place_id.into(),
+        "place_name" => (&rec.place_name).into(),
+        "place_type" => (&rec.place_type).into(),
+        "place_url" => rec.place_url().into(),
+        "place_lat" => rec.place_lat.into(),
+        "place_lon" => rec.place_lon.into(),
+        "country_code" => rec.country_code.as_ref().into(),
+        "boundary_osm_type" => osm_type_name(rec.boundary_osmtype).into(),
+        "boundary_id" => rec.boundary_id.into(),
+        "boundary_name" => (&rec.boundary_name).into(),
+        "boundary_url" => rec.boundary_url().into(),
+        "boundary_type" => rec.boundary_type().into(),
+        "admin_level" => rec.admin_level_str().into(),
+        "place_wikidata_url" => rec.place_wiki_urls().1.into(),
+        "place_wikipedia_url" => rec.place_wiki_urls().0.into(),
+        "boundary_wikidata_url" => rec.boundary_wiki_urls().1.into(),
+        "boundary_wikipedia_url" => rec.boundary_wiki_urls().0.into(),
+        "place_population" => rec.place_population.into(),
+        "place_timestamp" => rec.place_timestamp.map(timestamp_str).into(),
+        "boundary_timestamp" => rec.boundary_timestamp.map(timestamp_str).into(),
+        _ => return None,
+    })
+}
+
+pub fn record_properties(rec: &Record) -> Vec<(&'static str, Value)> {
+    RECORD_PROPERTIES
+        .iter()
+        .map(|name| (*name, record_property(rec, name).unwrap()))
+        .collect()
+}
+
+// The distance (in m) from the previous place in the chain to each place. The first is 0.
+// Percent encode everything except the unreserved characters
+pub fn url_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            b => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+// Overpass query for all the places and boundaries in a chain
+pub fn overpass_query(chain: &[&Record]) -> String {
+    let mut ids: BTreeMap<char, Vec<u64>> = BTreeMap::new();
+    for r in chain {
+        ids.entry(r.place_osmtype).or_default().push(r.place_id);
+        ids.entry(r.boundary_osmtype)
+            .or_default()
+            .push(r.boundary_id);
+    }
+    let mut query = "[out:json][timeout:60];\n(\n".to_string();
+    for (t, mut ids) in ids {
+        ids.sort_unstable();
+        ids.dedup();
+        let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        query.push_str(&format!("  {}(id:{});\n", osm_type_name(t), ids.join(",")));
+    }
+    query.push_str(");\nout geom;");
+    query
+}
+
+// Overpass string literal
+fn overpass_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Overpass query which checks each link of a chain in the current OSM data: one CSV row per place,
+// with inside=1 if the place is still inside the boundary, and the current names to compare.
+pub fn overpass_check_query(chain: &[&Record]) -> String {
+    let mut query = "[out:csv(link, place, boundary, inside, expected_place_name, place_name, expected_boundary_name, boundary_name; true; \",\")][timeout:120];\n".to_string();
+    for (i, r) in chain.iter().enumerate() {
+        let place_type = osm_type_name(r.place_osmtype);
+        let place = format!("{}({})", place_type, r.place_id);
+        let boundary = format!("{}({})", osm_type_name(r.boundary_osmtype), r.boundary_id);
+        query.push_str(&format!("{}->.p{};\n", place, i));
+        query.push_str(&format!("{};map_to_area->.b{};\n", boundary, i));
+        query.push_str(&format!("{}(area.b{})->.inside{};\n", place, i, i));
+        query.push_str(&format!(
+            "make check link={i}, place=\"{}\", boundary=\"{}\", inside=inside{i}.count({}s), expected_place_name={}, place_name=p{i}.u(t[\"name\"]), expected_boundary_name={}, boundary_name=b{i}.u(t[\"name\"]);\nout;\n",
+            place,
+            boundary,
+            place_type,
+            overpass_string(&r.place_name),
+            overpass_string(&r.boundary_name),
+            i = i,
+        ));
+    }
+    query
+}
+
+// Runs the query on overpass-turbo.eu when opened
+pub fn overpass_url(chain: &[&Record]) -> String {
+    format!(
+        "https://overpass-turbo.eu/?Q={}&R",
+        url_encode(&overpass_query(chain))
+    )
+}
+
+// An osm.org shortlink for a map view, see https://wiki.openstreetmap.org/wiki/Shortlink
+fn osm_shortlink(lat: f64, lon: f64, zoom: u8) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_~";
+    let x = (((lon + 180.) * 2f64.powi(32) / 360.) as u64).min(u32::MAX as u64);
+    let y = (((lat + 90.) * 2f64.powi(32) / 180.) as u64).min(u32::MAX as u64);
+    // interleave the bits of x & y
+    let mut code: u64 = 0;
+    for i in (0..32).rev() {
+        code = (code << 1) | ((x >> i) & 1);
+        code = (code << 1) | ((y >> i) & 1);
+    }
+    let mut s = "https://osm.org/go/".to_string();
+    for i in 0..(zoom as u32 + 8).div_ceil(3) {
+        s.push(CHARS[((code >> (58 - 6 * i)) & 0x3f) as usize] as char);
+    }
+    for _ in 0..(zoom + 8) % 3 {
+        s.push('-');
+    }
+    s
+}
+
+// A map of the whole chain on osm.org
+pub fn chain_shortlink(stats: &ChainStats) -> String {
+    let [min_lon, min_lat, max_lon, max_lat] = stats.bbox;
+    let span = (max_lon - min_lon).max((max_lat - min_lat) * 2.).max(1e-6);
+    let zoom = (360. / span).log2().floor().clamp(0., 18.) as u8;
+    osm_shortlink((min_lat + max_lat) / 2., (min_lon + max_lon) / 2., zoom)
+}
+
+// Facts about the whole run, not just the chains which are written out
+#[derive(Debug, Default)]
+pub struct Summary {
+    // chain length → number of finished chains
+    pub totals_per_len: BTreeMap<usize, usize>,
+}
+
+impl Summary {
+    // Number of chains, not counting the 1 element ones
+    pub fn num_chains(&self) -> usize {
+        self.totals_per_len
+            .iter()
+            .filter(|(len, _)| **len > 1)
+            .map(|(_, total)| total)
+            .sum()
+    }
+}
+
+// So names with brackets can be the text of a link, and read back from it (see results.rs)
+pub fn markdown_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '[' | ']' | '(' | ')') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn write_chains(chains: &[Chain], summary: &Summary, options: &Options) -> Result<()> {
+    if options.output_dir.is_some() {
+        return output_dir::write(chains, summary, options);
+    }
+    write_chains_to(chains, summary, options, &options.output_filename)
+}
+
+pub fn write_chains_to(
+    chains: &[Chain],
+    summary: &Summary,
+    options: &Options,
+    filename: &str,
+) -> Result<()> {
+    let messages = &options.messages;
+    // Formats which aren't just a stream of bytes
+    if options.format == Format::Sqlite {
+        return sqlite::write(chains, filename);
+    }
+    if options.format == Format::JsonBundle {
+        return json_bundle::write(chains, summary, filename);
+    }
+    // Check the template before creating the output file
+    let template = match &options.template {
+        Some(template) => Some(template::Template::from_file(template)?),
+        None => None,
+    };
+
+    let mut output = BufWriter::new(
+        File::create(filename).with_context(|| format!("Could not create {}", filename))?,
+    );
+    match options.format {
+        Format::Text => text::write(chains, messages, &mut output)?,
+        Format::GeoJson => geojson::write(chains, &mut output)?,
+        Format::HtmlMap => html_map::write(chains, &mut output)?,
+        Format::NdJson => ndjson::write(chains, &mut output)?,
+        Format::Csv => csv::write(chains, &mut output)?,
+        Format::Markdown => markdown::write(chains, summary, messages, &mut output)?,
+        Format::Html => html::write(chains, summary, messages, &mut output)?,
+        Format::Kml => kml::write(chains, messages, &mut output)?,
+        Format::Gpx => gpx::write(chains, messages, &mut output)?,
+        Format::Parquet => parquet::write_chains(chains, &mut output)?,
+        Format::Template => template::write(template.as_ref().unwrap(), chains, &mut output)?,
+        Format::Story => story::write(chains, messages, &mut output)?,
+        Format::Thread => thread::write(chains, messages, options.post_limit, &mut output)?,
+        Format::OsmWiki => osmwiki::write(chains, messages, &mut output)?,
+        Format::Umap => umap::write(chains, messages, &mut output)?,
+        Format::Atom => atom::write(chains, messages, filename, &mut output)?,
+        Format::Quiz => quiz::write(chains, &mut output)?,
+        Format::Tree => tree::write(chains, messages, &mut output)?,
+        Format::Sqlite | Format::JsonBundle => unreachable!(),
+    }
+    output.flush()?;
+    Ok(())
+}
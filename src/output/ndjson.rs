@@ -0,0 +1,90 @@
+use std::io::prelude::*;
+
+use anyhow::{Context, Result};
+
+use crate::chain::Chain;
+use crate::freshness::timestamp_str;
+use crate::json::{obj, Value};
+use crate::output::{chain_shortlink, overpass_query, overpass_url, record_properties};
+use crate::record::Record;
+
+pub fn chain_json(chain: &Chain) -> Value {
+    let stats = chain.stats();
+    obj(vec![
+        ("length", chain.len().into()),
+        ("total_distance_m", stats.total_m.round().into()),
+        ("bbox", stats.bbox.to_vec().into()),
+        ("total_population", stats.total_population.into()),
+        ("oldest_edit", stats.oldest_edit.map(timestamp_str).into()),
+        ("newest_edit", stats.newest_edit.map(timestamp_str).into()),
+        ("freshness", stats.freshness().map(|f| f.name()).into()),
+        ("countries", chain.countries().into()),
+        ("overpass_query", overpass_query(chain).into()),
+        ("overpass_url", overpass_url(chain).into()),
+        ("osm_shortlink", chain_shortlink(stats).into()),
+        (
+            "hops",
+            Value::Array(
+                chain
+                    .iter_hops()
+                    .map(|hop| {
+                        let mut properties = record_properties(hop.record);
+                        properties.push(("hop_distance_m", hop.hop_m.round().into()));
+                        properties.push(("cumulative_distance_m", hop.cumulative_m.round().into()));
+                        obj(properties)
+                    })
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+// One JSON object per line, per chain
+pub fn write(chains: &[Chain], output: &mut impl Write) -> Result<()> {
+    for chain in chains {
+        writeln!(output, "{}", chain_json(chain))?;
+    }
+    Ok(())
+}
+
+// Chains written one at a time, while the search is still going (--stream). Each line is flushed,
+// so what's in the file survives the program crashing or being killed.
+pub struct Stream {
+    filename: String,
+    file: Option<std::fs::File>,
+    chains: usize,
+}
+
+impl Stream {
+    pub fn create(filename: &str) -> Result<Stream> {
+        let file = std::fs::File::create(filename)
+            .with_context(|| format!("Could not create {}", filename))?;
+        Ok(Stream {
+            filename: filename.to_string(),
+            file: Some(file),
+            chains: 0,
+        })
+    }
+
+    // A failed write is only a warning, & the rest aren't tried, the OUTPUT is still written at
+    // the end
+    pub fn chain(&mut self, chain: &[&Record]) {
+        if let Some(file) = &mut self.file {
+            if let Err(e) = writeln!(file, "{}", chain_json(&Chain::new(chain.to_vec()))) {
+                warn!(
+                    "Could not write to {}, not streaming any more: {}",
+                    self.filename, e
+                );
+                self.file = None;
+            } else {
+                self.chains += 1;
+            }
+        }
+    }
+
+    pub fn finish(self) {
+        if self.file.is_some() {
+            info!("Streamed {} chains to {}", self.chains, self.filename);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::Messages;
+
+// Stop names breaking the table or link markup
+fn wiki_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('|', "&#124;")
+        .replace('[', "&#91;")
+        .replace(']', "&#93;")
+        .replace('{', "&#123;")
+        .replace('}', "&#125;")
+        .replace('<', "&lt;")
+}
+
+// A MediaWiki table, to paste onto an OSM wiki page
+pub fn write(chains: &[Chain], messages: &Messages, output: &mut impl Write) -> Result<()> {
+    writeln!(output, "{{| class=\"wikitable sortable\"")?;
+    writeln!(
+        output,
+        "! {} !! {} !! {} !! {}",
+        messages.rank, messages.length, messages.distance_km, messages.places
+    )?;
+    for (idx, chain) in chains.iter().enumerate() {
+        let stats = chain.stats();
+        let hops = chain
+            .iter()
+            .map(|r| format!("[{} {}]", r.place_url(), wiki_escape(&r.place_name)))
+            .chain(std::iter::once(format!(
+                "[{} {}]",
+                chain[chain.len() - 1].boundary_url(),
+                wiki_escape(&chain[chain.len() - 1].boundary_name)
+            )))
+            .collect::<Vec<_>>()
+            .join(" → ");
+        writeln!(output, "|-")?;
+        writeln!(
+            output,
+            "| {} || {} || {} || {}",
+            idx + 1,
+            chain.len(),
+            messages.km(stats.total_m),
+            hops
+        )?;
+    }
+    writeln!(output, "|}}")?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+// --output-dir: a directory for the whole run, with `summary.json` (the number of chains of each
+// length, and an index of the chain files), `stats.json` (like --stats-out), `run.json`, and each
+// chain in its own file in the --format, e.g. `chains/len-28/0001.txt`. Then one chain can be
+// linked to, or only the longest published, without cutting up one big file.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::chain::Chain;
+use crate::json::{obj, Value};
+use crate::options::Options;
+use crate::output::{write_chains_to, Format, Summary};
+
+pub fn chains_dir(dirname: &str) -> std::path::PathBuf {
+    Path::new(dirname).join("chains")
+}
+
+pub fn write(chains: &[Chain], summary: &Summary, options: &Options) -> Result<()> {
+    if options.format == Format::JsonBundle {
+        bail!("--output-dir can't use --format json-bundle, which is a directory already");
+    }
+    let dir = Path::new(&options.output_filename);
+    let chains_dir = chains_dir(&options.output_filename);
+    // Chains from an earlier run, which there might have been more of
+    if chains_dir.exists() {
+        std::fs::remove_dir_all(&chains_dir)
+            .with_context(|| format!("Could not remove old {:?}", chains_dir))?;
+    }
+    std::fs::create_dir_all(&chains_dir)
+        .with_context(|| format!("Could not create {:?}", chains_dir))?;
+
+    let width = chains.len().to_string().len().max(4);
+    let mut index = Vec::with_capacity(chains.len());
+    for (chain_idx, chain) in chains.iter().enumerate() {
+        let rank = chain_idx + 1;
+        let len_dir = format!("len-{}", chain.len());
+        if chain_idx == 0 || chains[chain_idx - 1].len() != chain.len() {
+            std::fs::create_dir_all(chains_dir.join(&len_dir))
+                .with_context(|| format!("Could not create {:?}", chains_dir.join(&len_dir)))?;
+        }
+        let file = format!(
+            "chains/{}/{:0width$}.{}",
+            len_dir,
+            rank,
+            options.format.extension(),
+            width = width
+        );
+        let path = dir.join(&file);
+        write_chains_to(
+            std::slice::from_ref(chain),
+            summary,
+            options,
+            &path.to_string_lossy(),
+        )?;
+        index.push(obj(vec![
+            ("rank", rank.into()),
+            ("length", chain.len().into()),
+            ("start", (&chain[0].place_name).into()),
+            ("end", (&chain[chain.len() - 1].boundary_name).into()),
+            ("total_distance_m", chain.total_distance_m().round().into()),
+            ("file", file.into()),
+        ]));
+    }
+
+    let summary_json = obj(vec![
+        ("num_chains", summary.num_chains().into()),
+        ("num_written_out", chains.len().into()),
+        (
+            "totals_per_len",
+            Value::Object(
+                summary
+                    .totals_per_len
+                    .iter()
+                    .map(|(len, total)| (len.to_string(), (*total).into()))
+                    .collect(),
+            ),
+        ),
+        ("chains", Value::Array(index)),
+    ]);
+    let filename = dir.join("summary.json");
+    std::fs::write(&filename, format!("{}\n", summary_json))
+        .with_context(|| format!("Could not write {:?}", filename))?;
+    info!("Wrote {} chain files to {:?}", chains.len(), chains_dir);
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::output::{overpass_check_query, url_encode};
+
+// An Overpass query for each chain, to check it against the current OSM data. Each link is a CSV
+// row, and the chain is still true if every row has inside=1 and the names are the same.
+pub fn write(chains: &[Chain], output: &mut impl Write) -> Result<()> {
+    for (idx, chain) in chains.iter().enumerate() {
+        let query = overpass_check_query(chain);
+        writeln!(
+            output,
+            "// Chain {}: {} → … → {} ({} places)",
+            idx + 1,
+            chain[0].place_name,
+            chain[chain.len() - 1].boundary_name,
+            chain.len()
+        )?;
+        writeln!(
+            output,
+            "// https://overpass-turbo.eu/?Q={}&R",
+            url_encode(&query)
+        )?;
+        writeln!(output, "{}", query)?;
+    }
+    Ok(())
+}
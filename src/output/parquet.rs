@@ -0,0 +1,465 @@
+// A small Parquet writer: one row group, PLAIN encoded, uncompressed pages. That's enough for
+// DuckDB, Spark, pandas etc. to read it, and there's no parquet library available to us.
+
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::freshness::timestamp_str;
+use crate::record::{osm_type_name, Record};
+
+// Values per data page
+const PAGE_SIZE: usize = 64 * 1024;
+
+pub enum ColumnData {
+    Utf8(Vec<String>),
+    // Nullable strings
+    OptUtf8(Vec<Option<String>>),
+    Int64(Vec<i64>),
+    // Nullable integers
+    OptInt64(Vec<Option<i64>>),
+    Double(Vec<f64>),
+}
+
+pub struct Column {
+    pub name: &'static str,
+    pub data: ColumnData,
+}
+
+impl Column {
+    pub fn new(name: &'static str, data: ColumnData) -> Column {
+        Column { name, data }
+    }
+
+    fn len(&self) -> usize {
+        match &self.data {
+            ColumnData::Utf8(v) => v.len(),
+            ColumnData::OptUtf8(v) => v.len(),
+            ColumnData::Int64(v) => v.len(),
+            ColumnData::OptInt64(v) => v.len(),
+            ColumnData::Double(v) => v.len(),
+        }
+    }
+
+    // parquet physical type
+    fn physical_type(&self) -> i32 {
+        match &self.data {
+            ColumnData::Utf8(_) | ColumnData::OptUtf8(_) => 6, // BYTE_ARRAY
+            ColumnData::Int64(_) | ColumnData::OptInt64(_) => 2,
+            ColumnData::Double(_) => 5,
+        }
+    }
+
+    fn is_optional(&self) -> bool {
+        matches!(self.data, ColumnData::OptUtf8(_) | ColumnData::OptInt64(_))
+    }
+
+    fn is_string(&self) -> bool {
+        matches!(self.data, ColumnData::Utf8(_) | ColumnData::OptUtf8(_))
+    }
+
+    // The contents of the data page with rows `start..end`
+    fn page_data(&self, start: usize, end: usize) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match &self.data {
+            ColumnData::Utf8(v) => {
+                for s in &v[start..end] {
+                    buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                    buf.extend_from_slice(s.as_bytes());
+                }
+            }
+            ColumnData::OptUtf8(v) => {
+                definition_levels(&mut buf, v[start..end].iter().map(Option::is_some));
+                for s in v[start..end].iter().flatten() {
+                    buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                    buf.extend_from_slice(s.as_bytes());
+                }
+            }
+            ColumnData::Int64(v) => {
+                for n in &v[start..end] {
+                    buf.extend_from_slice(&n.to_le_bytes());
+                }
+            }
+            ColumnData::OptInt64(v) => {
+                definition_levels(&mut buf, v[start..end].iter().map(Option::is_some));
+                for n in v[start..end].iter().flatten() {
+                    buf.extend_from_slice(&n.to_le_bytes());
+                }
+            }
+            ColumnData::Double(v) => {
+                for n in &v[start..end] {
+                    buf.extend_from_slice(&n.to_le_bytes());
+                }
+            }
+        }
+        buf
+    }
+}
+
+// Whether each value of an optional column is there, before its values
+fn definition_levels(buf: &mut Vec<u8>, present: impl Iterator<Item = bool>) {
+    let levels = rle_levels(&present.map(|p| p as u8).collect::<Vec<u8>>());
+    buf.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&levels);
+}
+
+// Definition levels (0 or 1) in the RLE/bit-packing hybrid encoding, only using RLE runs
+fn rle_levels(levels: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut i = 0;
+    while i < levels.len() {
+        let run_end = levels[i..]
+            .iter()
+            .position(|l| *l != levels[i])
+            .map_or(levels.len(), |p| i + p);
+        varint(&mut buf, ((run_end - i) as u64) << 1);
+        buf.push(levels[i]);
+        i = run_end;
+    }
+    buf
+}
+
+fn varint(buf: &mut Vec<u8>, mut n: u64) {
+    loop {
+        if n < 0x80 {
+            buf.push(n as u8);
+            return;
+        }
+        buf.push((n as u8 & 0x7f) | 0x80);
+        n >>= 7;
+    }
+}
+
+// Just enough of the Thrift compact protocol for the parquet metadata
+#[derive(Default)]
+struct Thrift {
+    buf: Vec<u8>,
+    // last field id of each struct we're in
+    last_field: Vec<i16>,
+}
+
+const T_I32: u8 = 5;
+const T_I64: u8 = 6;
+const T_BINARY: u8 = 8;
+const T_LIST: u8 = 9;
+const T_STRUCT: u8 = 12;
+
+impl Thrift {
+    fn field(&mut self, id: i16, ty: u8) {
+        let last = self.last_field.last_mut().unwrap();
+        let delta = id - *last;
+        if delta > 0 && delta <= 15 {
+            self.buf.push(((delta as u8) << 4) | ty);
+        } else {
+            self.buf.push(ty);
+            varint(&mut self.buf, zigzag(id as i64));
+        }
+        *last = id;
+    }
+
+    fn i32(&mut self, id: i16, n: i32) {
+        self.field(id, T_I32);
+        varint(&mut self.buf, zigzag(n as i64));
+    }
+
+    fn i64(&mut self, id: i16, n: i64) {
+        self.field(id, T_I64);
+        varint(&mut self.buf, zigzag(n));
+    }
+
+    fn string(&mut self, id: i16, s: &str) {
+        self.field(id, T_BINARY);
+        varint(&mut self.buf, s.len() as u64);
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn list_header(&mut self, id: i16, elem_type: u8, len: usize) {
+        self.field(id, T_LIST);
+        if len < 15 {
+            self.buf.push(((len as u8) << 4) | elem_type);
+        } else {
+            self.buf.push(0xf0 | elem_type);
+            varint(&mut self.buf, len as u64);
+        }
+    }
+
+    fn list_i32(&mut self, id: i16, values: &[i32]) {
+        self.list_header(id, T_I32, values.len());
+        for v in values {
+            varint(&mut self.buf, zigzag(*v as i64));
+        }
+    }
+
+    fn list_string(&mut self, id: i16, values: &[&str]) {
+        self.list_header(id, T_BINARY, values.len());
+        for v in values {
+            varint(&mut self.buf, v.len() as u64);
+            self.buf.extend_from_slice(v.as_bytes());
+        }
+    }
+
+    // A struct as a field. The next calls are its fields, until `end_struct`
+    fn struct_field(&mut self, id: i16) {
+        self.field(id, T_STRUCT);
+        self.begin_struct();
+    }
+
+    // A struct as a list element, or the top level struct
+    fn begin_struct(&mut self) {
+        self.last_field.push(0);
+    }
+
+    fn end_struct(&mut self) {
+        self.buf.push(0);
+        self.last_field.pop();
+    }
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn page_header(num_values: usize, page_size: usize) -> Vec<u8> {
+    let mut t = Thrift::default();
+    t.begin_struct();
+    t.i32(1, 0); // DATA_PAGE
+    t.i32(2, page_size as i32);
+    t.i32(3, page_size as i32);
+    t.struct_field(5);
+    t.i32(1, num_values as i32);
+    t.i32(2, 0); // PLAIN
+    t.i32(3, 3); // RLE
+    t.i32(4, 3); // RLE
+    t.end_struct();
+    t.end_struct();
+    t.buf
+}
+
+pub fn write(columns: &[Column], output: &mut impl Write) -> Result<()> {
+    let num_rows = columns.first().map_or(0, |c| c.len());
+    assert!(columns.iter().all(|c| c.len() == num_rows));
+
+    output.write_all(b"PAR1")?;
+    let mut offset = 4;
+
+    // (data page offset, total size) of each column chunk
+    let mut chunks = Vec::with_capacity(columns.len());
+    for column in columns {
+        let chunk_start = offset;
+        let mut start = 0;
+        // Always at least one page, even if it's empty
+        loop {
+            let end = (start + PAGE_SIZE).min(num_rows);
+            let data = column.page_data(start, end);
+            let header = page_header(end - start, data.len());
+            output.write_all(&header)?;
+            output.write_all(&data)?;
+            offset += header.len() + data.len();
+            start = end;
+            if start >= num_rows {
+                break;
+            }
+        }
+        chunks.push((chunk_start, offset - chunk_start));
+    }
+
+    // FileMetaData
+    let mut t = Thrift::default();
+    t.begin_struct();
+    t.i32(1, 1);
+    t.list_header(2, T_STRUCT, columns.len() + 1);
+    t.begin_struct();
+    t.string(4, "schema");
+    t.i32(5, columns.len() as i32);
+    t.end_struct();
+    for column in columns {
+        t.begin_struct();
+        t.i32(1, column.physical_type());
+        t.i32(3, if column.is_optional() { 1 } else { 0 });
+        t.string(4, column.name);
+        if column.is_string() {
+            t.i32(6, 0); // UTF8
+        }
+        t.end_struct();
+    }
+    t.i64(3, num_rows as i64);
+    t.list_header(4, T_STRUCT, 1);
+    t.begin_struct();
+    t.list_header(1, T_STRUCT, columns.len());
+    for (column, (chunk_start, chunk_size)) in columns.iter().zip(chunks.iter()) {
+        t.begin_struct();
+        t.i64(2, *chunk_start as i64);
+        t.struct_field(3);
+        t.i32(1, column.physical_type());
+        t.list_i32(2, &[0, 3]); // PLAIN, RLE
+        t.list_string(3, &[column.name]);
+        t.i32(4, 0); // UNCOMPRESSED
+        t.i64(5, num_rows as i64);
+        t.i64(6, *chunk_size as i64);
+        t.i64(7, *chunk_size as i64);
+        t.i64(9, *chunk_start as i64);
+        t.end_struct();
+        t.end_struct();
+    }
+    t.i64(2, chunks.iter().map(|(_, size)| *size as i64).sum());
+    t.i64(3, num_rows as i64);
+    t.end_struct();
+    t.string(
+        6,
+        &format!(
+            "{} version {}",
+            option_env!("CARGO_PKG_NAME").unwrap_or("x-in-y"),
+            option_env!("CARGO_PKG_VERSION").unwrap_or("unknown")
+        ),
+    );
+    t.end_struct();
+
+    output.write_all(&t.buf)?;
+    output.write_all(&(t.buf.len() as u32).to_le_bytes())?;
+    output.write_all(b"PAR1")?;
+    Ok(())
+}
+
+// The columns for these records (in this order), with `extra` columns first
+fn record_columns<'a>(
+    records: impl Iterator<Item = &'a Record> + Clone,
+    mut extra: Vec<Column>,
+) -> Vec<Column> {
+    let strings = |f: fn(&Record) -> String| ColumnData::Utf8(records.clone().map(f).collect());
+    extra.extend(vec![
+        Column::new(
+            "place_osm_type",
+            strings(|r| osm_type_name(r.place_osmtype).to_string()),
+        ),
+        Column::new(
+            "place_id",
+            ColumnData::Int64(records.clone().map(|r| r.place_id as i64).collect()),
+        ),
+        Column::new("place_name", strings(|r| r.place_name.to_string())),
+        Column::new("place_type", strings(|r| r.place_type.to_string())),
+        Column::new(
+            "place_lat",
+            ColumnData::Double(records.clone().map(|r| r.place_lat).collect()),
+        ),
+        Column::new(
+            "place_lon",
+            ColumnData::Double(records.clone().map(|r| r.place_lon).collect()),
+        ),
+        Column::new(
+            "country_code",
+            ColumnData::OptUtf8(records.clone().map(|r| r.country_code.clone()).collect()),
+        ),
+        Column::new(
+            "boundary_osm_type",
+            strings(|r| osm_type_name(r.boundary_osmtype).to_string()),
+        ),
+        Column::new(
+            "boundary_id",
+            ColumnData::Int64(records.clone().map(|r| r.boundary_id as i64).collect()),
+        ),
+        Column::new("boundary_name", strings(|r| r.boundary_name.to_string())),
+        Column::new("boundary_type", strings(|r| r.boundary_type().to_string())),
+        Column::new(
+            "boundary_admin_level",
+            ColumnData::OptUtf8(
+                records
+                    .clone()
+                    .map(|r| r.boundary_admin_level.as_ref().map(|_| r.admin_level_str()))
+                    .collect(),
+            ),
+        ),
+        Column::new(
+            "place_wikidata",
+            ColumnData::OptUtf8(records.clone().map(|r| r.place_wikidata.clone()).collect()),
+        ),
+        Column::new(
+            "place_wikipedia",
+            ColumnData::OptUtf8(records.clone().map(|r| r.place_wikipedia.clone()).collect()),
+        ),
+        Column::new(
+            "boundary_wikidata",
+            ColumnData::OptUtf8(
+                records
+                    .clone()
+                    .map(|r| r.boundary_wikidata.clone())
+                    .collect(),
+            ),
+        ),
+        Column::new(
+            "boundary_wikipedia",
+            ColumnData::OptUtf8(
+                records
+                    .clone()
+                    .map(|r| r.boundary_wikipedia.clone())
+                    .collect(),
+            ),
+        ),
+        Column::new(
+            "place_population",
+            ColumnData::OptInt64(
+                records
+                    .clone()
+                    .map(|r| r.place_population.map(|p| p as i64))
+                    .collect(),
+            ),
+        ),
+        Column::new(
+            "place_timestamp",
+            ColumnData::OptUtf8(
+                records
+                    .clone()
+                    .map(|r| r.place_timestamp.map(timestamp_str))
+                    .collect(),
+            ),
+        ),
+        Column::new(
+            "boundary_timestamp",
+            ColumnData::OptUtf8(
+                records
+                    .clone()
+                    .map(|r| r.boundary_timestamp.map(timestamp_str))
+                    .collect(),
+            ),
+        ),
+    ]);
+    extra
+}
+
+// One row per place in each chain, like the csv format
+pub fn write_chains(chains: &[Chain], output: &mut impl Write) -> Result<()> {
+    let mut chain_ids = Vec::new();
+    let mut positions = Vec::new();
+    let mut distances = Vec::new();
+    let mut cumulative = Vec::new();
+    for (chain_idx, chain) in chains.iter().enumerate() {
+        let stats = chain.stats();
+        for position in 0..chain.len() {
+            chain_ids.push(chain_idx as i64 + 1);
+            positions.push(position as i64);
+            distances.push(stats.hop_m[position].round());
+            cumulative.push(stats.cumulative_m[position].round());
+        }
+    }
+    let mut columns = record_columns(
+        chains.iter().flat_map(|chain| chain.iter().copied()),
+        vec![
+            Column::new("chain_id", ColumnData::Int64(chain_ids)),
+            Column::new("position", ColumnData::Int64(positions)),
+        ],
+    );
+    columns.push(Column::new("hop_distance_m", ColumnData::Double(distances)));
+    columns.push(Column::new(
+        "cumulative_distance_m",
+        ColumnData::Double(cumulative),
+    ));
+    write(&columns, output)
+}
+
+// Every record, e.g. after filtering
+pub fn write_records<'a>(
+    records: impl Iterator<Item = &'a Record> + Clone,
+    output: &mut impl Write,
+) -> Result<()> {
+    write(&record_columns(records, Vec::new()), output)
+}
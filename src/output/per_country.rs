@@ -0,0 +1,47 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::Messages;
+
+// A markdown report of the longest chain in each country, with a table of them all first
+pub fn write(
+    country_chains: &[(&str, Chain)],
+    messages: &Messages,
+    output: &mut impl Write,
+) -> Result<()> {
+    writeln!(output, "# {}\n", messages.per_country_title)?;
+    writeln!(
+        output,
+        "| {} | {} | {} |\n| :-- | --: | --: |",
+        messages.country, messages.length, messages.distance_km
+    )?;
+    for (country, chain) in country_chains {
+        writeln!(
+            output,
+            "| [{c}](#{c}) | {} | {} |",
+            chain.len(),
+            messages.km(chain.total_distance_m()),
+            c = country
+        )?;
+    }
+    writeln!(output)?;
+
+    for (idx, (country, chain)) in country_chains.iter().enumerate() {
+        let stats = chain.stats();
+        writeln!(output, "## <a id=\"{c}\"></a>{c}\n", c = country)?;
+        writeln!(
+            output,
+            "{}. {}. {}\n",
+            messages.chain_title(idx, chain),
+            messages.chain_distance(stats),
+            messages.chain_links_markdown(chain, stats)
+        )?;
+        for (i, r) in chain.iter().enumerate() {
+            writeln!(output, "{}. {}", i + 1, messages.sentence_markdown(r))?;
+        }
+        writeln!(output)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::json::{obj, Value};
+
+// A "guess the next place" game. Each chain's hops have only hints (country, place type, how far
+// the next place is), and the names are in a separate `answers` list, so a web page can show the
+// hops as they are and check guesses against the answers. The first place is given, since it's
+// where the player starts from.
+fn chain_quiz(rank: usize, chain: &Chain) -> Value {
+    let hops = chain
+        .iter_hops()
+        .map(|hop| {
+            let next_m = chain
+                .stats()
+                .hop_m
+                .get(hop.position + 1)
+                .map(|m| m.round().into())
+                .unwrap_or(Value::Null);
+            obj(vec![
+                ("position", hop.position.into()),
+                ("country_code", hop.record.country_code.as_ref().into()),
+                ("place_type", (&hop.record.place_type).into()),
+                ("boundary_type", hop.record.boundary_type().into()),
+                (
+                    "admin_level",
+                    hop.record.admin_level().map(|l| l as usize).into(),
+                ),
+                ("distance_to_next_m", next_m),
+            ])
+        })
+        .collect();
+    obj(vec![
+        ("rank", rank.into()),
+        ("length", chain.len().into()),
+        ("start", (&chain[0].place_name).into()),
+        ("hops", Value::Array(hops)),
+        (
+            "answers",
+            Value::Array(chain.iter().map(|r| (&r.place_name).into()).collect()),
+        ),
+        (
+            "last_boundary",
+            (&chain[chain.len() - 1].boundary_name).into(),
+        ),
+    ])
+}
+
+// One JSON document, with each chain on its own line
+pub fn write(chains: &[Chain], output: &mut impl Write) -> Result<()> {
+    writeln!(output, "{{\"chains\":[")?;
+    for (idx, chain) in chains.iter().enumerate() {
+        if idx > 0 {
+            writeln!(output, ",")?;
+        }
+        write!(output, "{}", chain_quiz(idx + 1, chain))?;
+    }
+    writeln!(output, "\n]}}")?;
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+
+use crate::chain::Chain;
+use crate::freshness::timestamp_str;
+use crate::record::{osm_type_name, OsmId};
+
+const SCHEMA: &str = "
+CREATE TABLE records (
+    record_id INTEGER PRIMARY KEY,
+    place_osm_type TEXT NOT NULL,
+    place_id INTEGER NOT NULL,
+    place_name TEXT NOT NULL,
+    place_type TEXT NOT NULL,
+    place_lat REAL NOT NULL,
+    place_lon REAL NOT NULL,
+    country_code TEXT,
+    boundary_osm_type TEXT NOT NULL,
+    boundary_id INTEGER NOT NULL,
+    boundary_name TEXT NOT NULL,
+    boundary_type TEXT NOT NULL,
+    admin_level TEXT NOT NULL,
+    place_wikidata TEXT,
+    place_wikipedia TEXT,
+    boundary_wikidata TEXT,
+    boundary_wikipedia TEXT,
+    place_population INTEGER,
+    place_timestamp TEXT,
+    boundary_timestamp TEXT
+);
+CREATE TABLE chains (
+    chain_id INTEGER PRIMARY KEY,
+    length INTEGER NOT NULL,
+    start_name TEXT NOT NULL,
+    end_name TEXT NOT NULL,
+    total_distance_m REAL NOT NULL,
+    min_lon REAL NOT NULL,
+    min_lat REAL NOT NULL,
+    max_lon REAL NOT NULL,
+    max_lat REAL NOT NULL,
+    total_population INTEGER,
+    oldest_edit TEXT,
+    newest_edit TEXT,
+    freshness TEXT
+);
+CREATE TABLE chain_elements (
+    chain_id INTEGER NOT NULL REFERENCES chains(chain_id),
+    position INTEGER NOT NULL,
+    record_id INTEGER NOT NULL REFERENCES records(record_id),
+    hop_distance_m REAL NOT NULL,
+    cumulative_distance_m REAL NOT NULL,
+    PRIMARY KEY (chain_id, position)
+);
+";
+
+const INDEXES: &str = "
+CREATE INDEX records__place_name ON records (place_name);
+CREATE INDEX records__boundary_name ON records (boundary_name);
+CREATE INDEX records__place ON records (place_osm_type, place_id);
+CREATE INDEX records__boundary ON records (boundary_osm_type, boundary_id);
+CREATE INDEX records__country_code ON records (country_code);
+CREATE INDEX chains__length ON chains (length);
+CREATE INDEX chain_elements__record_id ON chain_elements (record_id);
+";
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn opt_quote(s: &Option<String>) -> String {
+    s.as_deref().map_or("NULL".to_string(), quote)
+}
+
+fn opt_int(n: Option<u64>) -> String {
+    n.map_or("NULL".to_string(), |n| n.to_string())
+}
+
+fn write_sql(chains: &[Chain], sql: &mut impl Write) -> Result<()> {
+    writeln!(sql, "BEGIN;")?;
+    writeln!(sql, "{}", SCHEMA)?;
+
+    // Records are in many chains, but only stored once
+    let mut record_ids: HashMap<(OsmId, OsmId), usize> = HashMap::new();
+    for (chain_idx, chain) in chains.iter().enumerate() {
+        let chain_id = chain_idx + 1;
+        let stats = chain.stats();
+        writeln!(
+            sql,
+            "INSERT INTO chains VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {});",
+            chain_id,
+            chain.len(),
+            quote(&chain[0].place_name),
+            quote(&chain[chain.len() - 1].boundary_name),
+            stats.total_m.round(),
+            stats.bbox[0],
+            stats.bbox[1],
+            stats.bbox[2],
+            stats.bbox[3],
+            opt_int(stats.total_population),
+            opt_quote(&stats.oldest_edit.map(timestamp_str)),
+            opt_quote(&stats.newest_edit.map(timestamp_str)),
+            opt_quote(&stats.freshness().map(|f| f.name().to_string())),
+        )?;
+        for (position, rec) in chain.iter().enumerate() {
+            let key = (rec.place_key(), rec.boundary_key());
+            let record_id = match record_ids.get(&key) {
+                Some(id) => *id,
+                None => {
+                    let id = record_ids.len() + 1;
+                    record_ids.insert(key, id);
+                    writeln!(
+                        sql,
+                        "INSERT INTO records VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {});",
+                        id,
+                        quote(osm_type_name(rec.place_osmtype)),
+                        rec.place_id,
+                        quote(&rec.place_name),
+                        quote(&rec.place_type),
+                        rec.place_lat,
+                        rec.place_lon,
+                        opt_quote(&rec.country_code),
+                        quote(osm_type_name(rec.boundary_osmtype)),
+                        rec.boundary_id,
+                        quote(&rec.boundary_name),
+                        quote(rec.boundary_type()),
+                        quote(&rec.admin_level_str()),
+                        opt_quote(&rec.place_wikidata),
+                        opt_quote(&rec.place_wikipedia),
+                        opt_quote(&rec.boundary_wikidata),
+                        opt_quote(&rec.boundary_wikipedia),
+                        opt_int(rec.place_population),
+                        opt_quote(&rec.place_timestamp.map(timestamp_str)),
+                        opt_quote(&rec.boundary_timestamp.map(timestamp_str)),
+                    )?;
+                    id
+                }
+            };
+            writeln!(
+                sql,
+                "INSERT INTO chain_elements VALUES ({}, {}, {}, {}, {});",
+                chain_id,
+                position,
+                record_id,
+                stats.hop_m[position].round(),
+                stats.cumulative_m[position].round(),
+            )?;
+        }
+    }
+
+    writeln!(sql, "{}", INDEXES)?;
+    writeln!(sql, "COMMIT;")?;
+    Ok(())
+}
+
+// There's no sqlite library available, so the `sqlite3` command line program is used.
+pub fn write(chains: &[Chain], filename: &str) -> Result<()> {
+    if std::path::Path::new(filename).exists() {
+        std::fs::remove_file(filename)
+            .with_context(|| format!("Could not remove old {}", filename))?;
+    }
+    let mut child = Command::new("sqlite3")
+        .arg("-bail")
+        .arg(filename)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("Could not run sqlite3, is it installed?")?;
+    {
+        let mut sql = std::io::BufWriter::new(child.stdin.take().unwrap());
+        write_sql(chains, &mut sql)?;
+        sql.flush()?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("sqlite3 failed writing {}: {}", filename, status);
+    }
+    Ok(())
+}
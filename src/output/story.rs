@@ -0,0 +1,15 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::Messages;
+
+// Each chain as a paragraph of prose, for a general audience, under a markdown heading
+pub fn write(chains: &[Chain], messages: &Messages, output: &mut impl Write) -> Result<()> {
+    for (idx, chain) in chains.iter().enumerate() {
+        writeln!(output, "## {}\n", messages.chain_title(idx, chain))?;
+        writeln!(output, "{}\n", messages.story(chain, chain.stats()))?;
+    }
+    Ok(())
+}
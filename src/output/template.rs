@@ -0,0 +1,192 @@
+// A very small mustache-like template language for rendering chains in your own style.
+//
+// The template is rendered once for each chain. `{{name}}` is replaced with a variable, and
+// `{{#hops}}...{{/hops}}` is repeated for each place in the chain.
+//
+// Chain variables: rank, length, total_distance_m, total_distance_km, start_name, end_name, bbox,
+// total_population (empty if no place has a population), oldest_edit, newest_edit & freshness (empty
+// without timestamps), overpass_query, overpass_url, osm_shortlink
+// Hop variables: everything in the record (place_name, place_url, boundary_name, admin_level, …),
+// index (from 0), number (from 1), hop_distance_m, hop_distance_km, cumulative_distance_m,
+// cumulative_distance_km, plus all the chain variables.
+
+use std::io::prelude::*;
+
+use anyhow::{bail, Context, Result};
+
+use crate::chain::Chain;
+use crate::freshness::timestamp_str;
+use crate::json::Value;
+use crate::output::{
+    chain_shortlink, overpass_query, overpass_url, record_property, RECORD_PROPERTIES,
+};
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Var(String),
+    Hops(Vec<Node>),
+}
+
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+const CHAIN_VARS: &[&str] = &[
+    "rank",
+    "length",
+    "total_distance_m",
+    "total_distance_km",
+    "start_name",
+    "end_name",
+    "bbox",
+    "total_population",
+    "oldest_edit",
+    "newest_edit",
+    "freshness",
+    "overpass_query",
+    "overpass_url",
+    "osm_shortlink",
+];
+const HOP_VARS: &[&str] = &[
+    "index",
+    "number",
+    "hop_distance_m",
+    "hop_distance_km",
+    "cumulative_distance_m",
+    "cumulative_distance_km",
+];
+
+impl Template {
+    pub fn from_file(filename: &str) -> Result<Template> {
+        let s = std::fs::read_to_string(filename)
+            .with_context(|| format!("Could not read template {}", filename))?;
+        Template::parse(&s).with_context(|| format!("Invalid template {}", filename))
+    }
+
+    pub fn parse(s: &str) -> Result<Template> {
+        let mut rest = s;
+        let mut nodes = Vec::new();
+        // nodes outside the {{#hops}} we're in
+        let mut outer: Option<Vec<Node>> = None;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                nodes.push(Node::Text(rest[..start].to_string()));
+            }
+            let end = match rest[start..].find("}}") {
+                Some(e) => start + e,
+                None => bail!("Unclosed {{{{"),
+            };
+            let tag = rest[start + 2..end].trim();
+            match tag {
+                "#hops" => {
+                    if outer.is_some() {
+                        bail!("{{{{#hops}}}} can't be inside another {{{{#hops}}}}");
+                    }
+                    outer = Some(std::mem::take(&mut nodes));
+                }
+                "/hops" => match outer.take() {
+                    None => bail!("{{{{/hops}}}} without {{{{#hops}}}}"),
+                    Some(mut o) => {
+                        o.push(Node::Hops(std::mem::take(&mut nodes)));
+                        nodes = o;
+                    }
+                },
+                var => {
+                    let known = CHAIN_VARS.contains(&var)
+                        || (outer.is_some()
+                            && (HOP_VARS.contains(&var) || RECORD_PROPERTIES.contains(&var)));
+                    if !known {
+                        bail!("Unknown variable {{{{{}}}}} here", var);
+                    }
+                    nodes.push(Node::Var(var.to_string()));
+                }
+            }
+            rest = &rest[end + 2..];
+        }
+        if !rest.is_empty() {
+            nodes.push(Node::Text(rest.to_string()));
+        }
+        if outer.is_some() {
+            bail!("{{{{#hops}}}} without {{{{/hops}}}}");
+        }
+        Ok(Template { nodes })
+    }
+
+    pub fn render_chain(&self, idx: usize, chain: &Chain, output: &mut impl Write) -> Result<()> {
+        let stats = chain.stats();
+        let chain_var = |name: &str| -> String {
+            match name {
+                "rank" => (idx + 1).to_string(),
+                "length" => chain.len().to_string(),
+                "total_distance_m" => format!("{:.0}", stats.total_m),
+                "total_distance_km" => format!("{:.0}", stats.total_m / 1000.),
+                "start_name" => chain[0].place_name.to_string(),
+                "end_name" => chain[chain.len() - 1].boundary_name.to_string(),
+                "bbox" => stats.bbox_str(),
+                "total_population" => stats
+                    .total_population
+                    .map_or(String::new(), |p| p.to_string()),
+                "oldest_edit" => stats.oldest_edit.map_or(String::new(), timestamp_str),
+                "newest_edit" => stats.newest_edit.map_or(String::new(), timestamp_str),
+                "freshness" => stats
+                    .freshness()
+                    .map_or(String::new(), |f| f.name().to_string()),
+                "overpass_query" => overpass_query(chain),
+                "overpass_url" => overpass_url(chain),
+                "osm_shortlink" => chain_shortlink(stats),
+                _ => unreachable!(),
+            }
+        };
+
+        for node in self.nodes.iter() {
+            match node {
+                Node::Text(t) => output.write_all(t.as_bytes())?,
+                Node::Var(v) => output.write_all(chain_var(v).as_bytes())?,
+                Node::Hops(hop_nodes) => {
+                    for (i, rec) in chain.iter().enumerate() {
+                        let (dist, cumulative) = (stats.hop_m[i], stats.cumulative_m[i]);
+                        for hop_node in hop_nodes {
+                            let text = match hop_node {
+                                Node::Text(t) => t.clone(),
+                                Node::Var(v) => match v.as_str() {
+                                    "index" => i.to_string(),
+                                    "number" => (i + 1).to_string(),
+                                    "hop_distance_m" => format!("{:.0}", dist),
+                                    "hop_distance_km" => format!("{:.0}", dist / 1000.),
+                                    "cumulative_distance_m" => format!("{:.0}", cumulative),
+                                    "cumulative_distance_km" => {
+                                        format!("{:.0}", cumulative / 1000.)
+                                    }
+                                    v if CHAIN_VARS.contains(&v) => chain_var(v),
+                                    v => value_text(&record_property(rec, v).unwrap()),
+                                },
+                                Node::Hops(_) => unreachable!(),
+                            };
+                            output.write_all(text.as_bytes())?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// A value as plain text, i.e. strings without the JSON quotes
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+pub fn write(template: &Template, chains: &[Chain], output: &mut impl Write) -> Result<()> {
+    for (idx, chain) in chains.iter().enumerate() {
+        template.render_chain(idx, chain, output)?;
+    }
+    Ok(())
+}
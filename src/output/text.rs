@@ -0,0 +1,30 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::Messages;
+
+pub fn write(chains: &[Chain], messages: &Messages, output: &mut impl Write) -> Result<()> {
+    for chain in chains {
+        let stats = chain.stats();
+        writeln!(output, "{}", messages.chain_of_len(chain.len()))?;
+        writeln!(output, "{}", messages.chain_distance(stats))?;
+        if let Some(population) = messages.chain_population(stats) {
+            writeln!(output, "{}", population)?;
+        }
+        if let Some(edits) = messages.chain_edits(stats) {
+            writeln!(output, "{}", edits)?;
+        }
+        writeln!(output, "{}\n", messages.chain_links_markdown(chain, stats))?;
+        for (i, r) in chain.iter().enumerate() {
+            write!(output, "{}: {}", i, messages.sentence_markdown(r))?;
+            if i > 0 {
+                write!(output, " ({})", messages.hop_distance(stats, i))?;
+            }
+            writeln!(output, "\n")?;
+        }
+        writeln!(output)?;
+    }
+    Ok(())
+}
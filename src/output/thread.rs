@@ -0,0 +1,62 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::Messages;
+
+// "3/17 text url", with the text cut short with "…" to fit in `limit` characters
+fn post(num: usize, total: usize, text: &str, url: Option<&str>, limit: usize) -> String {
+    let counter = format!("{}/{} ", num, total);
+    let url = url.map(|u| format!(" {}", u)).unwrap_or_default();
+    let room = limit.saturating_sub(counter.chars().count() + url.chars().count());
+    let text = if text.chars().count() <= room {
+        text.to_string()
+    } else {
+        let mut short: String = text.chars().take(room.saturating_sub(1)).collect();
+        short.push('…');
+        short
+    };
+    // Only a silly small limit won't fit the link
+    format!("{}{}{}", counter, text, url)
+        .chars()
+        .take(limit)
+        .collect()
+}
+
+// Each chain as a thread of short posts: an introduction, then one post for each place with its
+// OSM link. Posts are separated by `---` lines, and threads by `===` lines.
+pub fn write(
+    chains: &[Chain],
+    messages: &Messages,
+    limit: usize,
+    output: &mut impl Write,
+) -> Result<()> {
+    for (idx, chain) in chains.iter().enumerate() {
+        if idx > 0 {
+            writeln!(output, "===")?;
+        }
+        let total = chain.len() + 1;
+        let intro = format!(
+            "{}. {}",
+            messages.chain_title(idx, chain),
+            messages.chain_distance(chain.stats())
+        );
+        writeln!(output, "{}", post(1, total, &intro, None, limit))?;
+        for (i, r) in chain.iter().enumerate() {
+            writeln!(output, "---")?;
+            writeln!(
+                output,
+                "{}",
+                post(
+                    i + 2,
+                    total,
+                    &messages.thread_hop(r),
+                    Some(&r.place_url()),
+                    limit
+                )
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+// Many chains end the same way, and only differ in their first hop or two. This groups the chains
+// by how they end into families, and draws each as a tree: the end they share is printed once,
+// and the different ways into it are branches, each going on into the line above (`→ …`).
+
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::lang::Messages;
+use crate::record::Record;
+
+struct Node<'a> {
+    record: &'a Record,
+    // The records before this one, in the chains through it
+    children: Vec<usize>,
+    // A chain starts here
+    start: bool,
+}
+
+struct Tree<'a> {
+    nodes: Vec<Node<'a>>,
+    // One for each family, the last record of its chains
+    roots: Vec<usize>,
+}
+
+impl<'a> Tree<'a> {
+    fn new(chains: &[Chain<'a>]) -> Tree<'a> {
+        let mut tree = Tree {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+        };
+        for chain in chains {
+            let mut parent: Option<usize> = None;
+            let mut node = 0;
+            for record in chain.iter().rev() {
+                let existing = match parent {
+                    None => &tree.roots,
+                    Some(parent) => &tree.nodes[parent].children,
+                }
+                .iter()
+                .copied()
+                .find(|n| tree.nodes[*n].record == *record);
+                node = existing.unwrap_or_else(|| {
+                    tree.nodes.push(Node {
+                        record,
+                        children: Vec::new(),
+                        start: false,
+                    });
+                    let node = tree.nodes.len() - 1;
+                    match parent {
+                        None => tree.roots.push(node),
+                        Some(parent) => tree.nodes[parent].children.push(node),
+                    }
+                    node
+                });
+                parent = Some(node);
+            }
+            tree.nodes[node].start = true;
+        }
+        tree
+    }
+
+    // The most places from a start to here
+    fn height(&self, node: usize) -> usize {
+        1 + self.nodes[node]
+            .children
+            .iter()
+            .map(|c| self.height(*c))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn num_chains(&self, node: usize) -> usize {
+        self.nodes[node].start as usize
+            + self.nodes[node]
+                .children
+                .iter()
+                .map(|c| self.num_chains(*c))
+                .sum::<usize>()
+    }
+
+    // From `node` back while there's only one way in, in chain order, so the first is where the
+    // branches (if any) come in
+    fn segment(&self, node: usize) -> Vec<usize> {
+        let mut segment = vec![node];
+        let mut node = node;
+        while self.nodes[node].children.len() == 1 {
+            node = self.nodes[node].children[0];
+            segment.push(node);
+        }
+        segment.reverse();
+        segment
+    }
+
+    // The place names, with a `*` where a chain starts, but others come in before it
+    fn names(&self, segment: &[usize]) -> String {
+        segment
+            .iter()
+            .map(|n| {
+                let node = &self.nodes[*n];
+                match node.start && !node.children.is_empty() {
+                    true => format!("{}*", node.record.place_name),
+                    false => node.record.place_name.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" → ")
+    }
+
+    fn write_branches(&self, node: usize, indent: &str, output: &mut impl Write) -> Result<()> {
+        let mut children = self.nodes[node].children.clone();
+        children.sort_by_key(|c| std::cmp::Reverse(self.height(*c)));
+        for (i, child) in children.iter().enumerate() {
+            let last = i == children.len() - 1;
+            let segment = self.segment(*child);
+            writeln!(
+                output,
+                "{}{}{} → …",
+                indent,
+                if last { "└─ " } else { "├─ " },
+                self.names(&segment)
+            )?;
+            let indent = format!("{}{}", indent, if last { "   " } else { "│  " });
+            self.write_branches(segment[0], &indent, output)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn write(chains: &[Chain], messages: &Messages, output: &mut impl Write) -> Result<()> {
+    let tree = Tree::new(chains);
+    let mut families: Vec<(usize, usize, usize)> = tree
+        .roots
+        .iter()
+        .map(|root| (tree.height(*root), tree.num_chains(*root), *root))
+        .collect();
+    families.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+    if tree.nodes.iter().any(|n| n.start && !n.children.is_empty()) {
+        writeln!(output, "{}\n", messages.tree_start_note)?;
+    }
+    for (longest, num_chains, root) in families {
+        match num_chains {
+            1 => writeln!(output, "{}", messages.chain_of_len(longest))?,
+            _ => writeln!(output, "{}", messages.family_title(num_chains, longest))?,
+        }
+        let segment = tree.segment(root);
+        writeln!(
+            output,
+            "{} → {}",
+            tree.names(&segment),
+            tree.nodes[root].record.boundary_name
+        )?;
+        tree.write_branches(segment[0], "", output)?;
+        writeln!(output)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::io::prelude::*;
+
+use anyhow::Result;
+
+use crate::chain::Chain;
+use crate::json::{obj, Value};
+use crate::lang::Messages;
+use crate::record::Record;
+
+// The same colours as the html-map
+const COLOURS: &[&str] = &[
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6", "#9a6324",
+];
+
+fn point(rec: &Record) -> Value {
+    vec![rec.place_lon, rec.place_lat].into()
+}
+
+// uMap descriptions are in its own markup, where links are [[url|text]]
+fn umap_link(url: &str, text: &str) -> String {
+    format!("[[{}|{}]]", url, text.replace(['|', ']'], " "))
+}
+
+fn layer(idx: usize, chain: &Chain, messages: &Messages) -> Value {
+    let stats = chain.stats();
+    let colour = COLOURS[idx % COLOURS.len()];
+    let title = messages.chain_title(idx, chain);
+    let mut features = Vec::with_capacity(chain.len() + 1);
+    features.push(obj(vec![
+        ("type", "Feature".into()),
+        (
+            "geometry",
+            obj(vec![
+                ("type", "LineString".into()),
+                (
+                    "coordinates",
+                    Value::Array(chain.iter().map(|r| point(r)).collect()),
+                ),
+            ]),
+        ),
+        (
+            "properties",
+            obj(vec![
+                ("name", (&title).into()),
+                ("description", messages.chain_distance(stats).into()),
+            ]),
+        ),
+    ]));
+    for (i, r) in chain.iter().enumerate() {
+        let mut description = format!(
+            "{} place={}\n{} admin_level={}",
+            umap_link(&r.place_url(), &r.place_name),
+            r.place_type,
+            umap_link(&r.boundary_url(), &r.boundary_name),
+            r.admin_level_str(),
+        );
+        let (wikipedia, wikidata) = r.place_wiki_urls();
+        for (name, url) in [("Wikipedia", wikipedia), ("Wikidata", wikidata)] {
+            if let Some(url) = url {
+                description.push_str(&format!(" {}", umap_link(&url, name)));
+            }
+        }
+        if i > 0 {
+            description.push_str(&format!("\n{}", messages.hop_distance(stats, i)));
+        }
+        features.push(obj(vec![
+            ("type", "Feature".into()),
+            (
+                "geometry",
+                obj(vec![("type", "Point".into()), ("coordinates", point(r))]),
+            ),
+            (
+                "properties",
+                obj(vec![
+                    ("name", format!("{}. {}", i + 1, r.place_name).into()),
+                    ("description", description.into()),
+                ]),
+            ),
+        ]));
+    }
+    obj(vec![
+        ("type", "FeatureCollection".into()),
+        ("features", Value::Array(features)),
+        (
+            "_umap_options",
+            obj(vec![
+                ("name", title.into()),
+                ("color", colour.into()),
+                ("weight", 4u8.into()),
+                ("displayOnLoad", true.into()),
+            ]),
+        ),
+    ])
+}
+
+// A .umap file, which can be imported into a new uMap as it is. Each chain is a layer.
+pub fn write(chains: &[Chain], messages: &Messages, output: &mut impl Write) -> Result<()> {
+    // Start looking at the longest chain
+    let centre = chains.first().map_or(vec![0., 0.], |chain| {
+        let bbox = chain.stats().bbox;
+        vec![(bbox[0] + bbox[2]) / 2., (bbox[1] + bbox[3]) / 2.]
+    });
+    let umap = obj(vec![
+        ("type", "umap".into()),
+        (
+            "properties",
+            obj(vec![
+                ("name", (&messages.report_title).into()),
+                ("zoom", 3u8.into()),
+            ]),
+        ),
+        (
+            "geometry",
+            obj(vec![
+                ("type", "Point".into()),
+                ("coordinates", centre.into()),
+            ]),
+        ),
+        (
+            "layers",
+            Value::Array(
+                chains
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, chain)| layer(idx, chain, messages))
+                    .collect(),
+            ),
+        ),
+    ]);
+    writeln!(output, "{}", umap)?;
+    Ok(())
+}
@@ -0,0 +1,336 @@
+// Getting the records ready to search, as the command line does: reading the inputs (skipping
+// rows without a name, or with a place or boundary type which isn't used), then the filters. The
+// library (e.g. the Python bindings) does the same, so it finds the same chains.
+
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+use anyhow::Result;
+use separator::Separatable;
+
+use crate::options::{Command, Options};
+use crate::record::{self, OsmId, PlaceTypeUse, Record};
+use crate::stats::{self, RunStats};
+use crate::{cache, countries, exit, filters, input, metrics, mmap, nominatim};
+
+// What was read from the inputs
+pub struct Inputs {
+    // For each place, these records for that
+    pub points_in_boundary: HashMap<OsmId, Vec<Record>>,
+    pub num_records: usize,
+    pub num_duplicates: usize,
+    pub num_wrong_boundary_type: usize,
+    // place type → number of records
+    pub unknown_place_tags: HashMap<String, usize>,
+    // A few places with each unknown tag, for the report
+    pub unknown_tag_examples: HashMap<String, Vec<OsmId>>,
+    pub parse_errors: input::ParseErrors,
+    // Only kept for the QA report
+    pub empty_names: Vec<Record>,
+}
+
+// What the filters removed, for the reports
+pub struct Filtered {
+    pub self_contained: Vec<(&'static str, Record)>,
+    pub duplicates: Vec<(Record, Record)>,
+}
+
+// Read all the inputs, with --cache, --mmap, --fill-names & --auto-accept-threshold
+pub fn read(options: &Options) -> Result<Inputs> {
+    let mut points_in_boundary: HashMap<OsmId, Vec<Record>> = HashMap::new();
+
+    let mut num_records = 0;
+    let mut num_duplicates = 0;
+    let mut num_wrong_boundary_type = 0;
+
+    let mut unknown_place_tags: HashMap<String, usize> = HashMap::new();
+    // Only kept if we might auto-accept some unknown tags
+    let mut unknown_tag_records: HashMap<String, Vec<Record>> = HashMap::new();
+    // A few places with each unknown tag, for the report
+    let mut unknown_tag_examples: HashMap<String, Vec<OsmId>> = HashMap::new();
+    let mut parse_errors = input::ParseErrors::default();
+    // Only kept for the QA report
+    let mut empty_names: Vec<Record> = Vec::new();
+    // Only kept for --fill-names
+    let mut unnamed: Vec<Record> = Vec::new();
+
+    // Several inputs (e.g. per-continent exports) are merged. Places near the edges will be in
+    // more than one, so the same (place, boundary) can appear more than once.
+    for input_filename in options.input_filenames.iter() {
+        phase!("Reading in {}", input_filename);
+        let errors_before = parse_errors.errors.len();
+        let mut cached = match options.cache {
+            true => cache::Reader::open(input_filename, &options.csv_format),
+            false => None,
+        };
+        let mut cache_writer = match options.cache && cached.is_none() {
+            true => cache::Writer::create(input_filename, &options.csv_format),
+            false => None,
+        };
+        let mapped = match options.mmap && cached.is_none() {
+            true => mmap::Reader::open(input_filename, &options.csv_format)?,
+            false => None,
+        };
+        let mut rdr;
+        let rows: Box<dyn Iterator<Item = csv::Result<Record>>> = match (&mut cached, mapped) {
+            (Some(reader), _) => Box::new(reader.by_ref().map(Ok)),
+            (None, Some(mapped)) => Box::new(mapped),
+            (None, None) => {
+                rdr = input::csv_reader(input_filename, &options.csv_format)?;
+                Box::new(rdr.deserialize())
+            }
+        };
+
+        for result in rows {
+            metrics::RECORDS_READ.fetch_add(1, Ordering::Relaxed);
+            let record: Record = match result {
+                Ok(r) => {
+                    cache::Writer::write(&mut cache_writer, &r);
+                    r
+                }
+                Err(e) if options.strict => {
+                    return Err(anyhow::Error::new(e).context(exit::Status::ParseError))
+                }
+                Err(e) => {
+                    parse_errors.add(input_filename, e)?;
+                    continue;
+                }
+            };
+
+            // where name is set to empty string
+            // Initially this wasn't done, so lots of the later tweaks to reduce memory usage might
+            // be removed.
+            if record.place_name.is_empty() || record.boundary_name.is_empty() {
+                if options.command == Command::Qa {
+                    empty_names.push(record);
+                } else if options.fill_names.is_some() {
+                    unnamed.push(record);
+                }
+                continue;
+            }
+            if !options.boundary_types.allows(record.boundary_type()) {
+                num_wrong_boundary_type += 1;
+                continue;
+            }
+            match record::place_type_use(&record.place_type) {
+                PlaceTypeUse::Use => {
+                    if input::add_record(&mut points_in_boundary, record) {
+                        num_records += 1;
+                    } else {
+                        num_duplicates += 1;
+                    }
+                }
+                PlaceTypeUse::Ignore => {
+                    continue;
+                }
+                PlaceTypeUse::Unknown => {
+                    let x = record.place_type.as_ref();
+                    *unknown_place_tags.entry(x.to_string()).or_default() += 1;
+                    if options.unknown_tags_report.is_some() {
+                        let examples = unknown_tag_examples.entry(x.to_string()).or_default();
+                        if examples.len() < input::NUM_UNKNOWN_TAG_EXAMPLES
+                            && !examples.contains(&record.place_key())
+                        {
+                            examples.push(record.place_key());
+                        }
+                    }
+                    if options.auto_accept_threshold.is_some() {
+                        unknown_tag_records
+                            .entry(x.to_string())
+                            .or_default()
+                            .push(record);
+                    }
+                }
+            }
+        }
+        if let Some(reader) = cached {
+            reader.finish(input_filename, &mut parse_errors)?;
+            // As it would have when reading the CSV
+            if let Some((_, _, _, message)) = parse_errors.errors.get(errors_before) {
+                if options.strict {
+                    return Err(anyhow::anyhow!("{}", message).context(exit::Status::ParseError));
+                }
+            }
+        }
+        if let Some(writer) = cache_writer {
+            writer.finish(&parse_errors.errors[errors_before..]);
+        }
+    }
+
+    if let Some(cache_filename) = &options.fill_names {
+        for record in nominatim::fill_names(unnamed, cache_filename, &options.nominatim_url)? {
+            if !options.boundary_types.allows(record.boundary_type()) {
+                num_wrong_boundary_type += 1;
+            } else if record::place_type_use(&record.place_type) == PlaceTypeUse::Use {
+                if input::add_record(&mut points_in_boundary, record) {
+                    num_records += 1;
+                } else {
+                    num_duplicates += 1;
+                }
+            }
+        }
+    }
+
+    // Common unknown place tags are probably new or regional values that we want
+    if let Some(threshold) = options.auto_accept_threshold {
+        for (tag, records) in unknown_tag_records {
+            if unknown_place_tags[&tag] <= threshold {
+                continue;
+            }
+            info!(
+                "Auto-accepting place={} which appears {} times",
+                tag,
+                records.len().separated_string()
+            );
+            unknown_place_tags.remove(&tag);
+            for record in records {
+                if input::add_record(&mut points_in_boundary, record) {
+                    num_records += 1;
+                } else {
+                    num_duplicates += 1;
+                }
+            }
+        }
+    }
+    Ok(Inputs {
+        points_in_boundary,
+        num_records,
+        num_duplicates,
+        num_wrong_boundary_type,
+        unknown_place_tags,
+        unknown_tag_examples,
+        parse_errors,
+        empty_names,
+    })
+}
+
+// Attribute the places to countries, then run the filters the options turn on
+pub fn filter(
+    points_in_boundary: &mut HashMap<OsmId, Vec<Record>>,
+    options: &Options,
+    run_stats: &mut RunStats,
+) -> Result<Filtered> {
+    let num_records = stats::count(points_in_boundary).1;
+    phase!("Attributing places to countries");
+    let country_boundaries = match &options.country_boundaries {
+        Some(filename) => countries::read_country_boundaries(filename)?,
+        None => HashMap::new(),
+    };
+    let num_without_country =
+        countries::attribute_countries(points_in_boundary, &country_boundaries);
+    info!(
+        "{} of {} places have no country",
+        num_without_country.separated_string(),
+        points_in_boundary.len().separated_string()
+    );
+
+    if options.min_name_length > 1 || options.remove_junk_names {
+        phase!("Removing names which are too short, have no letters, or are placeholders");
+        let before = stats::count(points_in_boundary);
+        let num_removed = filters::remove_junk_names(
+            points_in_boundary,
+            options.min_name_length,
+            options.remove_junk_names,
+        );
+        run_stats.add_filter("junk_names", before, stats::count(points_in_boundary));
+        info!(
+            "Removed {} records with a junk place or boundary name",
+            num_removed.separated_string()
+        );
+    }
+
+    // Often, in OSM, there is a `place` node for each admin boundary.
+    // e.g. Paris is node 17807753 name=Paris,place=coty
+    // We want to remove that,
+    // that's against the spirit of what we're looking for.
+    // What counts as the "same" name is configurable (e.g. "City of Paris" vs "Paris")
+    phase!("Removing places which are inside a boundary with the same name");
+    let before = stats::count(points_in_boundary);
+    let self_contained = filters::remove_self_contained(
+        points_in_boundary,
+        &options.self_containment,
+        options.fuzzy_distance,
+    );
+    if let Some(report) = &options.self_containment_report {
+        info!(
+            "Writing the {} records of self-contained places to {}",
+            self_contained.len().separated_string(),
+            report
+        );
+        filters::write_self_contained_report(report, &self_contained)?;
+    }
+    run_stats.add_filter("self_containment", before, stats::count(points_in_boundary));
+
+    let total_records = points_in_boundary
+        .values()
+        .fold(0, |acc, recs| acc + recs.len());
+    if num_records > 0 {
+        info!(
+            "Have removed {} ({:.1}%) places",
+            (num_records - total_records).separated_string(),
+            ((num_records - total_records) as f32 / num_records as f32) * 100.
+        );
+    }
+
+    let mut duplicates = Vec::new();
+    if options.dedup_radius_m > 0. {
+        info!(
+            "Merging places with the same name within {}m of each other",
+            options.dedup_radius_m
+        );
+        let before = stats::count(points_in_boundary);
+        duplicates = filters::merge_nearby_duplicates(points_in_boundary, options.dedup_radius_m);
+        run_stats.add_filter("dedup_radius", before, stats::count(points_in_boundary));
+        info!(
+            "Merged {} duplicate places",
+            duplicates.len().separated_string()
+        );
+    }
+
+    if options.one_boundary_per_name {
+        info!("Keeping only the most specific boundary for each place & boundary name");
+        let before = stats::count(points_in_boundary);
+        let num_removed = filters::keep_most_specific_boundary(points_in_boundary);
+        run_stats.add_filter(
+            "one_boundary_per_name",
+            before,
+            stats::count(points_in_boundary),
+        );
+        info!(
+            "Removed {} records for less specific boundaries",
+            num_removed.separated_string()
+        );
+    }
+    if options.smallest_boundary {
+        info!("Keeping only the smallest boundary of each place");
+        let before = stats::count(points_in_boundary);
+        let num_removed = filters::keep_smallest_boundary(points_in_boundary);
+        run_stats.add_filter(
+            "smallest_boundary",
+            before,
+            stats::count(points_in_boundary),
+        );
+        info!(
+            "Removed {} records for the larger boundaries",
+            num_removed.separated_string()
+        );
+    }
+    if options.require_wikidata {
+        info!("Keeping only places & boundaries with a wikidata or wikipedia tag");
+        let before = stats::count(points_in_boundary);
+        let num_removed = filters::keep_with_wikidata(points_in_boundary);
+        run_stats.add_filter("require_wikidata", before, stats::count(points_in_boundary));
+        info!(
+            "Removed {} records without one",
+            num_removed.separated_string()
+        );
+        if points_in_boundary.is_empty() {
+            warn!("No records are left. The input needs place_wikidata/place_wikipedia & boundary_wikidata/boundary_wikipedia columns for --require-wikidata");
+        }
+    }
+
+    Ok(Filtered {
+        self_contained,
+        duplicates,
+    })
+}
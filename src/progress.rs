@@ -0,0 +1,364 @@
+// How far along the search is. On a terminal it's one status line which is redrawn in place (or
+// the --dashboard), otherwise (e.g. when stdout is a file) a new line is printed every minute.
+// The --log-file gets a line every minute either way.
+
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use separator::Separatable;
+
+use crate::dashboard::Dashboard;
+use crate::log::{self, Level, Style};
+use crate::memory::MemoryUsage;
+use crate::metrics;
+use crate::record::Record;
+use crate::search::{Checkpoint, MemoryCleanup, SearchObserver, SearchView};
+use crate::signals;
+use crate::status_file::StatusFile;
+
+const TERMINAL_INTERVAL: Duration = Duration::from_millis(500);
+const LOG_INTERVAL: Duration = Duration::from_secs(60);
+const DASHBOARD_INTERVAL: Duration = Duration::from_secs(1);
+const MEMORY_INTERVAL: Duration = Duration::from_secs(600);
+
+static USE_DASHBOARD: AtomicBool = AtomicBool::new(false);
+
+// Show the main search as a --dashboard, rather than a status line
+pub fn use_dashboard() {
+    USE_DASHBOARD.store(true, Ordering::Relaxed);
+}
+
+// A snapshot of the search
+pub struct Status {
+    pub steps: usize,
+    // intermediate chains which still have to be tried
+    pub frontier: usize,
+    pub finished: usize,
+    pub longest: usize,
+    pub memory_cleanups: usize,
+    // start records which haven't been searched from yet
+    pub starts_left: usize,
+    pub memory: MemoryUsage,
+}
+
+pub struct Progress {
+    started: Instant,
+    last_shown: Instant,
+    last_logged: Instant,
+    terminal: bool,
+    // a status line is on the screen, without a newline
+    line_shown: bool,
+    total_starts: usize,
+    dashboard: Option<Dashboard>,
+}
+
+// e.g. "2d 03h", "1h 05m" or "4m 09s"
+fn duration_str(d: Duration) -> String {
+    let s = d.as_secs();
+    if s >= 86_400 {
+        format!("{}d {:02}h", s / 86_400, (s % 86_400) / 3600)
+    } else if s >= 3600 {
+        format!("{}h {:02}m", s / 3600, (s % 3600) / 60)
+    } else {
+        format!("{}m {:02}s", s / 60, s % 60)
+    }
+}
+
+// "A → B → C", the places then the last boundary
+fn chain_names(chain: &[&Record]) -> String {
+    chain
+        .iter()
+        .map(|r| r.place_name.as_ref())
+        .chain(chain.last().map(|r| r.boundary_name.as_ref()))
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+
+impl Progress {
+    pub fn new(total_starts: usize) -> Progress {
+        let now = Instant::now();
+        // -q means no status line, but still a line in the log file
+        let terminal = std::io::stdout().is_terminal() && log::enabled(Level::Info);
+        let dashboard = if USE_DASHBOARD.swap(false, Ordering::Relaxed) {
+            if terminal {
+                Some(Dashboard::new())
+            } else {
+                warn!("--dashboard needs stdout to be a terminal, and not -q");
+                None
+            }
+        } else {
+            None
+        };
+        Progress {
+            started: now,
+            last_shown: now,
+            last_logged: now,
+            terminal,
+            line_shown: false,
+            total_starts,
+            dashboard,
+        }
+    }
+
+    fn line(&self, status: &Status) -> String {
+        let elapsed = self.started.elapsed();
+        let starts_done = self.total_starts.saturating_sub(status.starts_left);
+        // Each start is searched to the end before the next one, so the work left is roughly
+        // proportional to the starts left. Some starts take much longer than others though.
+        let eta = if starts_done == 0 {
+            "?".to_string()
+        } else {
+            duration_str(elapsed.mul_f64(status.starts_left as f64 / starts_done as f64))
+        };
+        format!(
+            "{} steps ({}/s), frontier {}, finished {}, longest {}, {} memory clean ups, {} of {} starts ({:.1}%), elapsed {}, left ~{}",
+            status.steps.separated_string(),
+            ((status.steps as f64 / elapsed.as_secs_f64().max(0.001)) as usize).separated_string(),
+            status.frontier.separated_string(),
+            status.finished.separated_string(),
+            status.longest,
+            status.memory_cleanups,
+            starts_done.separated_string(),
+            self.total_starts.separated_string(),
+            starts_done as f64 * 100. / self.total_starts.max(1) as f64,
+            duration_str(elapsed),
+            eta,
+        )
+    }
+
+    fn show(
+        &mut self,
+        status: &Status,
+        finished_chains: &HashMap<&Record, Vec<&Record>>,
+        in_progress: Option<&[&Record]>,
+    ) {
+        let line = self.line(status);
+        self.last_shown = Instant::now();
+        if let Some(dashboard) = &mut self.dashboard {
+            dashboard.draw(&line, status, finished_chains, in_progress);
+        } else if self.terminal {
+            // \x1b[K clears the rest of the old line
+            print!("\r{}\x1b[K", line);
+            let _ = std::io::stdout().flush();
+            self.line_shown = true;
+        } else {
+            info!("{}", line);
+            return;
+        }
+        if self.last_logged.elapsed() >= LOG_INTERVAL {
+            self.last_logged = Instant::now();
+            log::to_file(Level::Info, &line);
+        }
+    }
+
+    // A fuller report than the status line, for SIGUSR1
+    pub fn report(
+        &self,
+        status: &Status,
+        in_progress: Option<&[&Record]>,
+        top: &[&Vec<&Record>],
+    ) -> String {
+        let mut s = format!("Status: {}\n", self.line(status));
+        if let Some(chain) = in_progress {
+            s.push_str(&format!(
+                "Extending a chain of {}: {}\n",
+                chain.len(),
+                chain_names(chain)
+            ));
+        }
+        s.push_str(&format!("The {} longest finished chains:\n", top.len()));
+        for (i, chain) in top.iter().enumerate() {
+            s.push_str(&format!(
+                "{}. {} places: {}\n",
+                i + 1,
+                chain.len(),
+                chain_names(chain)
+            ));
+        }
+        s
+    }
+
+    // Show the status, if it's been long enough since last time. Cheap enough to call often.
+    pub fn update(
+        &mut self,
+        status: &Status,
+        finished_chains: &HashMap<&Record, Vec<&Record>>,
+        in_progress: Option<&[&Record]>,
+    ) {
+        let interval = if self.dashboard.is_some() {
+            DASHBOARD_INTERVAL
+        } else if self.terminal {
+            TERMINAL_INTERVAL
+        } else {
+            LOG_INTERVAL
+        };
+        if self.last_shown.elapsed() >= interval {
+            self.show(status, finished_chains, in_progress);
+        }
+    }
+
+    // Print a line of other output, without mangling the status line
+    pub fn message(&mut self, style: Style, msg: &str) {
+        if let Some(dashboard) = &mut self.dashboard {
+            dashboard.message(msg);
+            log::to_file(Level::Info, msg);
+            return;
+        }
+        if self.line_shown {
+            println!();
+            self.line_shown = false;
+        }
+        log::log_styled(Level::Info, style, format_args!("{}", msg));
+    }
+
+    pub fn finish(&mut self, status: &Status, finished_chains: &HashMap<&Record, Vec<&Record>>) {
+        if let Some(mut dashboard) = self.dashboard.take() {
+            dashboard.close();
+            info!("{}", self.line(status));
+            return;
+        }
+        self.show(status, finished_chains, None);
+        if self.line_shown {
+            log::to_file(Level::Info, &self.line(status));
+            println!();
+            self.line_shown = false;
+        }
+    }
+}
+
+// Shows how the main search is going: the status line (or --dashboard), the --status-file, the
+// metrics & the SIGUSR1 status reports. Without `progress` (for the searches which are only part
+// of the run) it only logs the memory clean ups.
+pub struct Reporter {
+    progress: bool,
+    display: Option<Progress>,
+    status_file: Option<StatusFile>,
+    // Where the memory goes is logged every MEMORY_INTERVAL
+    memory_logged: Instant,
+    // Each new longest chain is shown
+    longest: usize,
+}
+
+impl Reporter {
+    pub fn new(progress: bool) -> Reporter {
+        Reporter {
+            progress,
+            display: None,
+            status_file: None,
+            memory_logged: Instant::now(),
+            longest: 1,
+        }
+    }
+
+    fn message(&mut self, style: Style, msg: &str) {
+        match &mut self.display {
+            Some(display) => display.message(style, msg),
+            None => log::log_styled(Level::Info, style, format_args!("{}", msg)),
+        }
+    }
+
+    fn write_status_file(&mut self, state: &str, view: &SearchView) {
+        if let Some(f) = &mut self.status_file {
+            if let Err(e) = f.write(state, &view.status, view.finished_chains) {
+                warn!("{:#}", e);
+            }
+        }
+    }
+}
+
+impl<'a> SearchObserver<'a> for Reporter {
+    fn on_step(&mut self, view: &SearchView<'_, 'a>) {
+        if let Some(display) = &self.display {
+            if signals::status_requested() {
+                let mut top: Vec<&Vec<&Record>> = view.finished_chains.values().collect();
+                top.sort_by_key(|chain| -(chain.len() as isize));
+                top.truncate(5);
+                let report = display.report(&view.status, view.next, &top);
+                if let Err(e) = signals::write_status_report(&report) {
+                    warn!("{:#}", e);
+                }
+            }
+        }
+    }
+
+    fn on_chain_finished(&mut self, chain: &[&'a Record]) {
+        if self.progress && chain.len() > self.longest {
+            self.longest = chain.len();
+            self.message(
+                Style::Highlight,
+                &format!(
+                    "New longest chain, {} places: {}",
+                    chain.len(),
+                    chain_names(chain)
+                ),
+            );
+        }
+    }
+
+    fn on_prune(&mut self, cleanup: &MemoryCleanup) {
+        self.message(
+            Style::Plain,
+            &format!(
+            "Memory clean up: {} intermediate chains, removed {} much shorter than the longest for their start, {} by the failsafe, {} left",
+            cleanup.intermediate_before.separated_string(),
+            cleanup.removed_short.separated_string(),
+            cleanup.removed_by_failsafe.separated_string(),
+                cleanup.intermediate_after.separated_string()
+            ),
+        );
+    }
+
+    fn on_checkpoint(&mut self, checkpoint: Checkpoint, view: &SearchView<'_, 'a>) {
+        if !self.progress {
+            return;
+        }
+        match checkpoint {
+            Checkpoint::Started => {
+                self.display = Some(Progress::new(view.status.starts_left));
+                self.status_file = StatusFile::new();
+                self.write_status_file("searching", view);
+            }
+            Checkpoint::Searching => {
+                metrics::update_search(&view.status);
+                if let Some(f) = &mut self.status_file {
+                    if let Err(e) = f.update(&view.status, view.finished_chains) {
+                        warn!("{:#}", e);
+                    }
+                }
+                if let Some(display) = &mut self.display {
+                    display.update(&view.status, view.finished_chains, view.next);
+                }
+                if self.memory_logged.elapsed() >= MEMORY_INTERVAL {
+                    self.memory_logged = Instant::now();
+                    self.message(
+                        Style::Plain,
+                        &format!("Memory: {}", view.status.memory.summary()),
+                    );
+                }
+            }
+            Checkpoint::Finished | Checkpoint::Interrupted => {
+                metrics::update_search(&view.status);
+                let state = if checkpoint == Checkpoint::Interrupted {
+                    "interrupted"
+                } else {
+                    "finished"
+                };
+                self.write_status_file(state, view);
+                if let Some(display) = &mut self.display {
+                    display.finish(&view.status, view.finished_chains);
+                }
+                info!("Memory: {}", view.status.memory.summary());
+            }
+        }
+    }
+
+    fn on_message(&mut self, msg: &str) {
+        if self.progress {
+            self.message(Style::Plain, msg);
+        } else {
+            debug!("{}", msg);
+        }
+    }
+}
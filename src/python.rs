@@ -0,0 +1,261 @@
+// A C interface to load records & search them, for the Python bindings in `python/x_in_y.py`
+// (which use ctypes, so there's nothing to compile on the Python side). Built with
+//
+//     cargo rustc --release --lib --features python --crate-type cdylib
+//
+// Strings (JSON, or the last error) are UTF-8 & NUL terminated. Those returned as `*mut c_char`
+// belong to the caller, who frees them with `x_in_y_free_string`.
+
+use std::cell::RefCell;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+
+use anyhow::{bail, Context, Result};
+
+use crate::input::ParseErrors;
+use crate::json::{obj, Value};
+use crate::options::{Command, Options};
+use crate::output::record_properties;
+use crate::record::Record;
+use crate::starts::StartFilter;
+use crate::stats::RunStats;
+use crate::{freshness, log, prepare};
+
+// The records left after the same reading & filtering as the command line, with the options
+// (from the flags) which the search uses too
+pub struct Records {
+    records: Vec<Record>,
+    options: Options,
+    start_filter: Option<StartFilter>,
+    parse_errors: ParseErrors,
+}
+
+// Scores the last hop of a chain, given the indexes of its records (as for `x_in_y_record_json`)
+type ScoreFn = extern "C" fn(chain: *const usize, len: usize, user_data: *mut c_void) -> isize;
+
+// How many of the parse errors are kept, to show
+const MAX_PARSE_ERRORS: usize = 100;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(e: anyhow::Error) {
+    let msg = CString::new(format!("{:#}", e).replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+}
+
+fn to_c_string(s: String) -> *mut c_char {
+    CString::new(s.replace('\0', " ")).unwrap().into_raw()
+}
+
+/// Why the last call on this thread which returned null failed. Null if none has. Only valid until
+/// the next call.
+#[no_mangle]
+pub extern "C" fn x_in_y_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |e| e.as_ptr())
+    })
+}
+
+fn c_str<'a>(s: *const c_char, what: &str) -> Result<&'a str> {
+    anyhow::ensure!(!s.is_null(), "No {}", what);
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .with_context(|| format!("The {} isn't UTF-8", what))
+}
+
+fn load_records(
+    filename: *const c_char,
+    flags: *const *const c_char,
+    num_flags: usize,
+) -> Result<Records> {
+    let filename = c_str(filename, "filename")?;
+    let mut args = Vec::with_capacity(num_flags + 2);
+    for i in 0..num_flags {
+        args.push(c_str(unsafe { *flags.add(i) }, "flag")?.to_string());
+    }
+    // The input, & no OUTPUT
+    args.push(filename.to_string());
+    args.push(String::new());
+    let options = Options::parse(args.into_iter())?;
+    if options.command != Command::Search {
+        bail!("Only the flags for the usual search can be given");
+    }
+    log::init(
+        options.verbosity,
+        options.color,
+        options.log_file.as_deref(),
+    )?;
+
+    let prepare::Inputs {
+        mut points_in_boundary,
+        mut parse_errors,
+        ..
+    } = prepare::read(&options)?;
+    prepare::filter(&mut points_in_boundary, &options, &mut RunStats::default())?;
+    let mut records: Vec<Record> = points_in_boundary.into_values().flatten().collect();
+    // So the indexes are the same each time
+    records.sort_by_key(|r| (r.place_key(), r.boundary_key()));
+    // Only the first few are kept, with the counts of all of them
+    parse_errors.errors.truncate(MAX_PARSE_ERRORS);
+    let start_filter = StartFilter::new(&options)?;
+    Ok(Records {
+        records,
+        options,
+        start_filter,
+        parse_errors,
+    })
+}
+
+/// Read the records from a CSV file (maybe compressed), skipping & filtering them as the command
+/// line does with these `flags` (e.g. `--min-name-length 2`, or `--strict` to fail on a row which
+/// can't be parsed). Null on error. Free with `x_in_y_free_records`.
+///
+/// # Safety
+/// `flags` points to `num_flags` strings.
+#[no_mangle]
+pub unsafe extern "C" fn x_in_y_load_records(
+    filename: *const c_char,
+    flags: *const *const c_char,
+    num_flags: usize,
+) -> *mut Records {
+    match load_records(filename, flags, num_flags) {
+        Ok(records) => Box::into_raw(Box::new(records)),
+        Err(e) => {
+            set_last_error(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `records` is from `x_in_y_load_records`, and isn't used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn x_in_y_free_records(records: *mut Records) {
+    if !records.is_null() {
+        drop(Box::from_raw(records));
+    }
+}
+
+/// # Safety
+/// `records` is from `x_in_y_load_records`.
+#[no_mangle]
+pub unsafe extern "C" fn x_in_y_records_len(records: *const Records) -> usize {
+    (*records).records.len()
+}
+
+/// The rows which couldn't be parsed, & were skipped: {"total": …, "counts": {kind: count},
+/// "errors": [{"filename", "line", "kind", "message"}, …] (the first 100)}
+///
+/// # Safety
+/// `records` is from `x_in_y_load_records`.
+#[no_mangle]
+pub unsafe extern "C" fn x_in_y_parse_errors(records: *const Records) -> *mut c_char {
+    let parse_errors = &(*records).parse_errors;
+    let counts = parse_errors
+        .counts
+        .iter()
+        .map(|(kind, count)| (*kind, Value::from(*count)))
+        .collect();
+    let errors: Vec<Value> = parse_errors
+        .errors
+        .iter()
+        .map(|(filename, line, kind, message)| {
+            obj(vec![
+                ("filename", filename.into()),
+                ("line", (*line).into()),
+                ("kind", (*kind).into()),
+                ("message", message.into()),
+            ])
+        })
+        .collect();
+    let json = obj(vec![
+        ("total", parse_errors.total().into()),
+        ("counts", obj(counts)),
+        ("errors", errors.into()),
+    ]);
+    to_c_string(json.to_string())
+}
+
+/// One record, with the same properties as in the geojson & ndjson output. Null if `i` is out of
+/// range.
+///
+/// # Safety
+/// `records` is from `x_in_y_load_records`.
+#[no_mangle]
+pub unsafe extern "C" fn x_in_y_record_json(records: *const Records, i: usize) -> *mut c_char {
+    let records = &(*records).records;
+    match records.get(i) {
+        Some(record) => to_c_string(obj(record_properties(record)).to_string()),
+        None => {
+            set_last_error(anyhow::anyhow!("There is no record {}", i));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// The longest chain from each record, as a JSON list of lists of record indexes, longest first.
+/// The search is set up by the flags given to `x_in_y_load_records`, as on the command line.
+/// `max_chain_len`, if not 0, replaces `--max-chain-len`. `score`, if not null, replaces the
+/// `--score` (by default how far the last hop jumps), & is given `user_data`.
+///
+/// # Safety
+/// `records` is from `x_in_y_load_records`.
+#[no_mangle]
+pub unsafe extern "C" fn x_in_y_search(
+    records: *const Records,
+    max_chain_len: usize,
+    score: Option<ScoreFn>,
+    user_data: *mut c_void,
+) -> *mut c_char {
+    let Records {
+        records,
+        options,
+        start_filter,
+        ..
+    } = &*records;
+    let refs: Vec<&Record> = records.iter().collect();
+    // Records are found from their address in `records`
+    let base = records.as_ptr() as usize;
+    let index = |r: &Record| (r as *const Record as usize - base) / std::mem::size_of::<Record>();
+
+    freshness::set_reference(refs.iter().copied());
+    let filtered_starts = start_filter.as_ref().map(|f| f.starts(&refs));
+
+    let mut search = options.chain_search(&refs, false);
+    if let Some(starts) = &filtered_starts {
+        search = search.starts(starts);
+    }
+    if max_chain_len > 0 {
+        search = search.max_chain_len(max_chain_len);
+    }
+    if let Some(score) = score {
+        search = search.score(move |chain| {
+            let chain: Vec<usize> = chain.iter().map(|r| index(r)).collect();
+            score(chain.as_ptr(), chain.len(), user_data)
+        });
+    }
+    let (chains, _stats) = search.run();
+    let mut chains: Vec<Vec<usize>> = chains
+        .into_values()
+        .map(|chain| chain.into_iter().map(index).collect())
+        .collect();
+    chains.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    let chains: Vec<Value> = chains
+        .into_iter()
+        .map(|chain| Value::Array(chain.into_iter().map(Value::from).collect()))
+        .collect();
+    to_c_string(Value::Array(chains).to_string())
+}
+
+/// # Safety
+/// `s` is a string returned by one of these functions, and isn't used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn x_in_y_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
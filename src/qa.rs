@@ -0,0 +1,183 @@
+// Records which are probably tagging mistakes in OSM, for mappers to check
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result};
+
+use crate::distance;
+use crate::record::{osm_type_name, OsmId, Record};
+
+// A place this far from every other place in the same boundary is suspect
+const LONELY_PLACE_M: f64 = 300_000.;
+// Roughly how many degrees of latitude that is
+const LONELY_PLACE_DEG: f64 = LONELY_PLACE_M / 111_000.;
+
+// Places which are too big to be inside a boundary with an admin_level this high (or higher)
+const BIG_PLACE_TYPES: &[&str] = &["city", "town", "municipality"];
+const SMALL_BOUNDARY_ADMIN_LEVEL: u8 = 9;
+
+pub struct Suspect<'a> {
+    pub issue: &'static str,
+    pub record: &'a Record,
+    pub details: String,
+}
+
+fn bad_whitespace(name: &str) -> bool {
+    name.trim() != name || name.contains("  ")
+}
+
+// Places in each boundary which have no other place within LONELY_PLACE_M
+fn lonely_places<'a>(points_in_boundary: &'a HashMap<OsmId, Vec<Record>>) -> Vec<Suspect<'a>> {
+    let mut by_boundary: HashMap<OsmId, Vec<&Record>> = HashMap::new();
+    for rec in points_in_boundary.values().flat_map(|recs| recs.iter()) {
+        by_boundary.entry(rec.boundary_key()).or_default().push(rec);
+    }
+    let mut suspects = Vec::new();
+    for places in by_boundary.values_mut() {
+        // With only 2, it's not clear which one is out of place
+        if places.len() < 3 {
+            continue;
+        }
+        // Sorted by latitude, so we only need to look at the places near it in the list
+        places.sort_by(|a, b| a.place_lat.total_cmp(&b.place_lat));
+        for (i, rec) in places.iter().enumerate() {
+            let near = |other: &&Record| {
+                distance::metres(
+                    rec.place_lat,
+                    rec.place_lon,
+                    other.place_lat,
+                    other.place_lon,
+                ) <= LONELY_PLACE_M
+            };
+            let within_lat = |other: &&&Record| other.place_lat - rec.place_lat <= LONELY_PLACE_DEG;
+            let has_neighbour = places[i + 1..].iter().take_while(within_lat).any(near)
+                || places[..i]
+                    .iter()
+                    .rev()
+                    .take_while(|other| rec.place_lat - other.place_lat <= LONELY_PLACE_DEG)
+                    .any(near);
+            if !has_neighbour {
+                suspects.push(Suspect {
+                    issue: "lonely_place",
+                    record: rec,
+                    details: format!(
+                        "No other place in this boundary (of {}) within {} km",
+                        places.len(),
+                        LONELY_PLACE_M / 1000.
+                    ),
+                });
+            }
+        }
+    }
+    suspects
+}
+
+// Every suspect record, and the records with an empty name (which aren't otherwise kept)
+pub fn find_suspects<'a>(
+    points_in_boundary: &'a HashMap<OsmId, Vec<Record>>,
+    empty_names: &'a [Record],
+) -> Vec<Suspect<'a>> {
+    let mut suspects: Vec<Suspect> = empty_names
+        .iter()
+        .map(|rec| Suspect {
+            issue: "empty_name",
+            record: rec,
+            details: String::new(),
+        })
+        .collect();
+
+    let mut seen_boundaries = HashSet::new();
+    for rec in points_in_boundary.values().flat_map(|recs| recs.iter()) {
+        if bad_whitespace(&rec.place_name) {
+            suspects.push(Suspect {
+                issue: "place_name_whitespace",
+                record: rec,
+                details: format!("{:?}", rec.place_name),
+            });
+        }
+        if BIG_PLACE_TYPES.contains(&rec.place_type.as_ref())
+            && rec
+                .admin_level()
+                .is_some_and(|l| l >= SMALL_BOUNDARY_ADMIN_LEVEL)
+        {
+            suspects.push(Suspect {
+                issue: "place_type_conflicts_with_boundary",
+                record: rec,
+                details: format!(
+                    "place={} inside admin_level={}",
+                    rec.place_type,
+                    rec.admin_level_str()
+                ),
+            });
+        }
+
+        // The rest are about the boundary, so only once for each
+        if !seen_boundaries.insert(rec.boundary_key()) {
+            continue;
+        }
+        if bad_whitespace(&rec.boundary_name) {
+            suspects.push(Suspect {
+                issue: "boundary_name_whitespace",
+                record: rec,
+                details: format!("{:?}", rec.boundary_name),
+            });
+        }
+        let admin_level_issue = match &rec.boundary_admin_level {
+            None => Some("unusable_admin_level"),
+            Some(levels) if levels.iter().any(|l| !(1..=11).contains(l)) => {
+                Some("implausible_admin_level")
+            }
+            Some(levels) if levels.len() > 1 => Some("several_admin_levels"),
+            Some(_) => None,
+        };
+        if let Some(issue) = admin_level_issue {
+            suspects.push(Suspect {
+                issue,
+                record: rec,
+                details: format!("admin_level={}", rec.admin_level_str()),
+            });
+        }
+    }
+
+    suspects.extend(lonely_places(points_in_boundary));
+    suspects.sort_by_key(|s| (s.issue, s.record.boundary_key(), s.record.place_key()));
+    suspects
+}
+
+pub fn write_csv(filename: &str, suspects: &[Suspect]) -> Result<()> {
+    let mut wtr = csv::Writer::from_path(filename)
+        .with_context(|| format!("Could not create {}", filename))?;
+    wtr.write_record([
+        "issue",
+        "place_osm_type",
+        "place_id",
+        "place_name",
+        "place_type",
+        "place_url",
+        "boundary_osm_type",
+        "boundary_id",
+        "boundary_name",
+        "admin_level",
+        "boundary_url",
+        "details",
+    ])?;
+    for s in suspects {
+        let r = s.record;
+        wtr.write_record([
+            s.issue,
+            osm_type_name(r.place_osmtype),
+            &r.place_id.to_string(),
+            &r.place_name,
+            &r.place_type,
+            &r.place_url(),
+            osm_type_name(r.boundary_osmtype),
+            &r.boundary_id.to_string(),
+            &r.boundary_name,
+            &r.admin_level_str(),
+            &r.boundary_url(),
+            &s.details,
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,144 @@
+// Which finished chains go through a place or boundary, e.g. "is my town in a chain?"
+
+use anyhow::{Context, Result};
+
+use crate::record::{osm_type_name, OsmId};
+use crate::results::Hop;
+
+// What to look for. A name matches places & boundaries, ignoring case.
+pub struct Query {
+    pub name: Option<String>,
+    pub osm_id: Option<OsmId>,
+}
+
+// A chain which has the element
+pub struct Match<'a> {
+    pub filename: &'a str,
+    // from 1, as in the results file
+    pub chain_id: usize,
+    pub chain: &'a [Hop],
+    // from 1
+    pub position: usize,
+    // "place" or "boundary"
+    pub role: &'static str,
+}
+
+impl Query {
+    fn matches(&self, id: OsmId, name: &str) -> bool {
+        self.osm_id == Some(id)
+            || self
+                .name
+                .as_ref()
+                .is_some_and(|n| n.to_lowercase() == name.to_lowercase())
+    }
+
+    // A boundary is also the next place's boundary, so each chain is only listed once, at the
+    // first position the element is at.
+    pub fn find<'a>(&self, filename: &'a str, chains: &'a [Vec<Hop>]) -> Vec<Match<'a>> {
+        let mut matches = Vec::new();
+        for (i, chain) in chains.iter().enumerate() {
+            let found = chain.iter().enumerate().find_map(|(pos, hop)| {
+                if self.matches(hop.place, &hop.place_name) {
+                    Some((pos, "place"))
+                } else if self.matches(hop.boundary, &hop.boundary_name) {
+                    Some((pos, "boundary"))
+                } else {
+                    None
+                }
+            });
+            if let Some((pos, role)) = found {
+                matches.push(Match {
+                    filename,
+                    chain_id: i + 1,
+                    chain,
+                    position: pos + 1,
+                    role,
+                });
+            }
+        }
+        matches
+    }
+}
+
+pub fn write_csv(filename: &str, matches: &[Match]) -> Result<()> {
+    let mut wtr = csv::Writer::from_path(filename)
+        .with_context(|| format!("Could not create {}", filename))?;
+    wtr.write_record([
+        "results",
+        "chain",
+        "length",
+        "position",
+        "as",
+        "place",
+        "place_name",
+        "boundary",
+        "boundary_name",
+        "start",
+        "end",
+    ])?;
+    for m in matches {
+        let hop = &m.chain[m.position - 1];
+        let id = |(t, id): OsmId| format!("{}/{}", osm_type_name(t), id);
+        wtr.write_record([
+            m.filename.to_string(),
+            m.chain_id.to_string(),
+            m.chain.len().to_string(),
+            m.position.to_string(),
+            m.role.to_string(),
+            id(hop.place),
+            hop.place_name.clone(),
+            id(hop.boundary),
+            hop.boundary_name.clone(),
+            m.chain[0].place_name.clone(),
+            m.chain[m.chain.len() - 1].boundary_name.clone(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::Chain;
+    use crate::lang::Messages;
+    use crate::output::text;
+    use crate::{record, results};
+
+    #[test]
+    fn name_with_brackets_in_text_results() {
+        let records = record::from_csv(
+            "\
+place_osmtype,place_id,place_name,place_type,place_lat,place_lon,boundary_osmtype,boundary_id,boundary_name,boundary_admin_level
+n,1,Foo [bar],village,52.0,0.0,r,10,Baz (North),8
+n,2,Baz (North),town,52.1,0.1,r,20,Qux,6
+",
+        );
+        let filename =
+            std::env::temp_dir().join(format!("x-in-y-test-query-{}.txt", std::process::id()));
+        let mut file = std::fs::File::create(&filename).unwrap();
+        text::write(
+            &[Chain::new(records.iter().collect())],
+            &Messages::default(),
+            &mut file,
+        )
+        .unwrap();
+        let filename = filename.to_str().unwrap();
+        let chains = results::read(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        for (name, position, role) in [
+            ("Foo [bar]", 1, "place"),
+            ("baz (north)", 1, "boundary"),
+            ("Qux", 2, "boundary"),
+        ] {
+            let query = Query {
+                name: Some(name.to_string()),
+                osm_id: None,
+            };
+            let matches = query.find(filename, &chains);
+            assert_eq!(matches.len(), 1, "{}", name);
+            assert_eq!((matches[0].position, matches[0].role), (position, role));
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::borrow::Cow;
+
+use serde::Deserialize;
+
+use crate::countries::normalize_country_code;
+use crate::distance;
+use crate::freshness;
+use crate::lang::Messages;
+
+// A name or tag value from the input. Usually it's its own String, but with --mmap it can point
+// into the mapped input file instead, which is never unmapped, so it's 'static.
+pub type Text = Cow<'static, str>;
+
+// An OSM object, e.g. ('n', 123) for node 123. Places can be nodes, ways or relations, so the
+// id alone isn't unique.
+pub type OsmId = (char, u64);
+
+// This is from the CSV file
+#[derive(Debug, Deserialize, Clone)]
+pub struct Record {
+    #[serde(deserialize_with = "deserialize_osm_type")]
+    pub place_osmtype: char,
+    pub place_id: u64,
+    pub place_name: Text,
+    pub place_type: Text,
+    pub place_lat: f64,
+    pub place_lon: f64,
+    #[serde(deserialize_with = "deserialize_osm_type")]
+    pub boundary_osmtype: char,
+    pub boundary_id: u64,
+    pub boundary_name: Text,
+    // admin_level can be "7;8", empty, or something non-numeric
+    #[serde(deserialize_with = "deserialize_admin_level")]
+    pub boundary_admin_level: Option<Vec<u8>>,
+
+    // Optional columns
+
+    // The `boundary=*` tag, e.g. administrative, ceremonial
+    #[serde(default)]
+    pub boundary_type: Option<String>,
+
+    // ISO 3166-1 code of the country the place is in
+    #[serde(default, deserialize_with = "deserialize_country_code")]
+    pub country_code: Option<String>,
+
+    // The `wikidata=*` & `wikipedia=*` tags, e.g. "Q90" & "fr:Paris"
+    #[serde(default)]
+    pub place_wikidata: Option<String>,
+    #[serde(default)]
+    pub place_wikipedia: Option<String>,
+    #[serde(default)]
+    pub boundary_wikidata: Option<String>,
+    #[serde(default)]
+    pub boundary_wikipedia: Option<String>,
+
+    // The place's `population=*` tag, for --score population
+    #[serde(default, deserialize_with = "deserialize_population")]
+    pub place_population: Option<u64>,
+
+    // The boundary's area (in any unit, e.g. m²), for --smallest-boundary
+    #[serde(default)]
+    pub boundary_area: Option<f64>,
+
+    // When the place & boundary were last edited, in seconds since 1970 (see freshness)
+    #[serde(default, deserialize_with = "deserialize_timestamp")]
+    pub place_timestamp: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_timestamp")]
+    pub boundary_timestamp: Option<i64>,
+}
+
+// Population tags are often like "12,345" or "12 345", and sometimes not a number at all
+pub fn parse_population(s: &str) -> Option<u64> {
+    let digits: String = s
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ',' | '.' | ' ' | '_' | '\''))
+        .collect();
+    digits.parse().ok()
+}
+
+fn deserialize_population<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(s.as_deref().and_then(parse_population))
+}
+
+fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(s.as_deref().and_then(freshness::parse_timestamp))
+}
+
+fn deserialize_country_code<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(s.as_deref().and_then(normalize_country_code))
+}
+
+// Parse an admin_level value. Multiple values are separated by `;`. Values which aren't a number
+// are skipped, and if there are no numbers at all, it's None.
+pub fn parse_admin_level(s: &str) -> Option<Vec<u8>> {
+    let levels = s
+        .split(';')
+        .filter_map(|l| l.trim().parse::<u8>().ok())
+        .collect::<Vec<u8>>();
+    if levels.is_empty() {
+        None
+    } else {
+        Some(levels)
+    }
+}
+
+fn deserialize_admin_level<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    Ok(parse_admin_level(&s))
+}
+
+// Only accept n/w/r (or node/way/relation)
+pub fn parse_osm_type(s: &str) -> Option<char> {
+    match s {
+        "n" | "node" => Some('n'),
+        "w" | "way" => Some('w'),
+        "r" | "relation" => Some('r'),
+        _ => None,
+    }
+}
+
+fn deserialize_osm_type<'de, D>(deserializer: D) -> Result<char, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    parse_osm_type(&s)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid OSM object type {:?}", s)))
+}
+
+impl PartialOrd for Record {
+    fn partial_cmp(&self, other: &Record) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Record {
+    fn cmp(&self, other: &Record) -> std::cmp::Ordering {
+        self.place_key()
+            .cmp(&other.place_key())
+            .then(self.boundary_key().cmp(&other.boundary_key()))
+    }
+}
+
+impl PartialEq for Record {
+    fn eq(&self, other: &Record) -> bool {
+        self.place_key() == other.place_key() && self.boundary_key() == other.boundary_key()
+    }
+}
+impl Eq for Record {}
+
+impl std::hash::Hash for Record {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.place_osmtype.hash(state);
+        self.place_id.hash(state);
+        self.place_name.hash(state);
+        self.place_type.hash(state);
+
+        self.boundary_osmtype.hash(state);
+        self.boundary_id.hash(state);
+        self.boundary_name.hash(state);
+        self.boundary_admin_level.hash(state);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceTypeUse {
+    Use,
+    Ignore,
+    // Probably a new or regional value, which might be worth using
+    Unknown,
+}
+
+// Whether places with this `place=*` value can be in a chain
+pub fn place_type_use(place_type: &str) -> PlaceTypeUse {
+    match place_type {
+        // Use these `place` values
+        "city" | "town" | "village" | "suburb" | "neighbourhood" | "square" | "quarter"
+        | "islet" | "island" | "municipality" | "city_block" | "district" | "BAMYANGA"
+        | "borough" | "block" | "hamlet" => PlaceTypeUse::Use,
+        // ignore these `place` values
+        "locality" | "isolated_dwelling" | "farm" | "country" | "unknown" | "plot" | "yes"
+        | "field" | "county" | "state" | "single_dwelling" | "region" | "fixme" | "FIXME"
+        | "allotments" => PlaceTypeUse::Ignore,
+        _ => PlaceTypeUse::Unknown,
+    }
+}
+
+pub fn osm_type_name(t: char) -> &'static str {
+    match t {
+        'n' => "node",
+        'w' => "way",
+        'r' => "relation",
+        _ => unreachable!(),
+    }
+}
+
+// OSM URL of an object
+pub fn url(t: char, id: u64) -> String {
+    format!("https://www.openstreetmap.org/{}/{}", osm_type_name(t), id)
+}
+
+pub fn wikidata_url(id: &str) -> String {
+    format!("https://www.wikidata.org/wiki/{}", id.trim())
+}
+
+// From a `wikipedia=*` tag, which is "lang:Title". English if there's no language. Languages are
+// lowercase letters & dashes, e.g. de, simple, zh-yue, be-tarask, zh-min-nan.
+pub fn wikipedia_url(tag: &str) -> String {
+    let is_lang =
+        |lang: &str| !lang.is_empty() && lang.bytes().all(|b| b.is_ascii_lowercase() || b == b'-');
+    let (lang, title) = match tag.trim().split_once(':') {
+        Some((lang, title)) if is_lang(lang) => (lang, title),
+        _ => ("en", tag.trim()),
+    };
+    format!(
+        "https://{}.wikipedia.org/wiki/{}",
+        lang,
+        title.trim().replace(' ', "_").replace('?', "%3F")
+    )
+}
+
+impl Record {
+    pub fn place_key(&self) -> OsmId {
+        (self.place_osmtype, self.place_id)
+    }
+
+    pub fn boundary_key(&self) -> OsmId {
+        (self.boundary_osmtype, self.boundary_id)
+    }
+
+    // The most specific admin_level of the boundary, if it has a usable one
+    pub fn admin_level(&self) -> Option<u8> {
+        self.boundary_admin_level
+            .as_ref()
+            .and_then(|levels| levels.iter().max().copied())
+    }
+
+    // Boundaries without a boundary_type are assumed to be normal admin boundaries, since that's
+    // what the input has historically been.
+    pub fn boundary_type(&self) -> &str {
+        self.boundary_type.as_deref().unwrap_or("administrative")
+    }
+
+    // The admin_level as it'd be tagged in OSM, `?` if unknown
+    pub fn admin_level_str(&self) -> String {
+        match &self.boundary_admin_level {
+            None => "?".to_string(),
+            Some(levels) => levels
+                .iter()
+                .map(|l| l.to_string())
+                .collect::<Vec<_>>()
+                .join(";"),
+        }
+    }
+
+    pub fn place_url(&self) -> String {
+        url(self.place_osmtype, self.place_id)
+    }
+
+    pub fn boundary_url(&self) -> String {
+        url(self.boundary_osmtype, self.boundary_id)
+    }
+
+    // (wikipedia, wikidata) URLs of the place
+    pub fn place_wiki_urls(&self) -> (Option<String>, Option<String>) {
+        (
+            self.place_wikipedia.as_deref().map(wikipedia_url),
+            self.place_wikidata.as_deref().map(wikidata_url),
+        )
+    }
+
+    pub fn boundary_wiki_urls(&self) -> (Option<String>, Option<String>) {
+        (
+            self.boundary_wikipedia.as_deref().map(wikipedia_url),
+            self.boundary_wikidata.as_deref().map(wikidata_url),
+        )
+    }
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, w: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        write!(w, "{}", Messages::default().sentence_markdown(self))
+    }
+}
+
+pub fn place_dist(r1: &Record, r2: &Record) -> isize {
+    distance::metres(r1.place_lat, r1.place_lon, r2.place_lat, r2.place_lon).round() as isize
+}
+
+// On a sphere, see `distance::metres` for the --distance-model
+pub fn haversine_dist(mut th1: f64, mut ph1: f64, mut th2: f64, ph2: f64) -> f64 {
+    ph1 -= ph2;
+    ph1 = ph1.to_radians();
+    th1 = th1.to_radians();
+    th2 = th2.to_radians();
+    let dz: f64 = th1.sin() - th2.sin();
+    let dx: f64 = ph1.cos() * th1.cos() - th2.cos();
+    let dy: f64 = ph1.sin() * th1.cos();
+    static EARTH_RADIUS_M: f64 = 6_371_000.;
+    ((dx * dx + dy * dy + dz * dz).sqrt() / 2.0).asin() * 2.0 * EARTH_RADIUS_M
+}
+
+// Records from the text of an input file, for tests
+#[cfg(test)]
+pub fn from_csv(text: &str) -> Vec<Record> {
+    csv::Reader::from_reader(text.as_bytes())
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wikipedia_languages() {
+        assert_eq!(
+            wikipedia_url("de:Köln"),
+            "https://de.wikipedia.org/wiki/Köln"
+        );
+        assert_eq!(
+            wikipedia_url("zh-yue:香港"),
+            "https://zh-yue.wikipedia.org/wiki/香港"
+        );
+        assert_eq!(
+            wikipedia_url("simple:New York City"),
+            "https://simple.wikipedia.org/wiki/New_York_City"
+        );
+        assert_eq!(
+            wikipedia_url("be-tarask:Менск"),
+            "https://be-tarask.wikipedia.org/wiki/Менск"
+        );
+        assert_eq!(
+            wikipedia_url("zh-min-nan:Tâi-pak"),
+            "https://zh-min-nan.wikipedia.org/wiki/Tâi-pak"
+        );
+        // The title can have a colon, without a language
+        assert_eq!(
+            wikipedia_url("Star Wars: Episode I"),
+            "https://en.wikipedia.org/wiki/Star_Wars:_Episode_I"
+        );
+    }
+}
@@ -0,0 +1,331 @@
+// The `repl` command: load the records (& search them, or read the --old-results), then answer
+// questions about them typed on stdin, rather than a batch run for each, e.g.
+//
+//     > name Springfield
+//     > chain 17
+//     > extendable? "County Cork"
+//     > longest --country IE
+//
+// Chains are numbered as in the results, longest first.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+
+use anyhow::{bail, Context, Result};
+use separator::Separatable;
+
+use crate::countries::normalize_country_code;
+use crate::graph::NameGraph;
+use crate::record::{osm_type_name, Record};
+use crate::results::Hop;
+use crate::serve::countries;
+
+// Chains listed by `longest`, unless it's given a number
+const DEFAULT_LIMIT: usize = 10;
+// Places & boundaries listed by `name`
+const MAX_LISTED: usize = 20;
+
+const HELP: &str = "\
+name NAME                   The places & boundaries called NAME, what they're in & contain, and the chains through them
+chain N                     Chain N, place by place
+extendable? NAME            Whether a chain ending in a boundary called NAME can go on, and into what
+longest [--country CC] [N]  The N (default 10) longest chains, or those going through country CC
+help                        This
+quit                        Stop (or Ctrl-D)
+Names with spaces can be in quotes, e.g. extendable? \"County Cork\"";
+
+// The words of a line. "…" or '…' keep the spaces in a word.
+fn split_args(line: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut arg = String::new();
+        if c == '"' || c == '\'' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some(q) if q == c => break,
+                    Some(ch) => arg.push(ch),
+                    None => bail!("Unclosed {}", c),
+                }
+            }
+        } else {
+            while let Some(ch) = chars.next_if(|ch| !ch.is_whitespace()) {
+                arg.push(ch);
+            }
+        }
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+// A command's argument, with the rest of the words joined, so `name New York` works unquoted
+fn name_arg(command: &str, args: &[String]) -> Result<String> {
+    if args.is_empty() {
+        bail!("{} needs a NAME", command);
+    }
+    Ok(args.join(" "))
+}
+
+fn osm_id_str((t, id): (char, u64)) -> String {
+    format!("{}/{}", osm_type_name(t), id)
+}
+
+fn chain_line(chain_id: usize, chain: &[Hop]) -> String {
+    let countries = countries(chain);
+    format!(
+        "#{:<5} {:>3} places  {} → {}{}",
+        chain_id,
+        chain.len(),
+        chain[0].place_name,
+        chain[chain.len() - 1].boundary_name,
+        match countries.is_empty() {
+            true => String::new(),
+            false => format!("  ({})", countries.join(", ")),
+        }
+    )
+}
+
+// The most common names on one side of a name's edges, most records first
+fn top_names(edges: &BTreeMap<&str, usize>) -> String {
+    let mut edges: Vec<(&str, usize)> = edges.iter().map(|(n, c)| (*n, *c)).collect();
+    edges.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let mut names: Vec<String> = edges
+        .iter()
+        .take(MAX_LISTED)
+        .map(|(name, count)| format!("{} ({})", name, count))
+        .collect();
+    if edges.len() > MAX_LISTED {
+        names.push(format!("and {} more", edges.len() - MAX_LISTED));
+    }
+    names.join(", ")
+}
+
+struct Repl<'a> {
+    records: &'a [&'a Record],
+    graph: NameGraph<'a>,
+    chains: &'a [Vec<Hop>],
+}
+
+impl Repl<'_> {
+    fn name(&self, name: &str, output: &mut impl Write) -> Result<()> {
+        let Some(n) = self.graph.names.get(name) else {
+            writeln!(output, "Nothing is called {}", name)?;
+            return Ok(());
+        };
+        if !n.places.is_empty() {
+            writeln!(
+                output,
+                "{} places called {}, inside boundaries called: {}",
+                n.places.len().separated_string(),
+                name,
+                top_names(&n.out_edges)
+            )?;
+            for record in self
+                .records
+                .iter()
+                .filter(|r| r.place_name == name)
+                .take(MAX_LISTED)
+            {
+                writeln!(
+                    output,
+                    "  {} (place={}) in {} {} (admin_level {})",
+                    osm_id_str(record.place_key()),
+                    record.place_type,
+                    record.boundary_name,
+                    osm_id_str(record.boundary_key()),
+                    record.admin_level_str()
+                )?;
+            }
+        }
+        if !n.boundaries.is_empty() {
+            writeln!(
+                output,
+                "{} boundaries called {}, containing places called: {}",
+                n.boundaries.len().separated_string(),
+                name,
+                top_names(&n.in_edges)
+            )?;
+        }
+        let through: Vec<usize> = self
+            .chains
+            .iter()
+            .enumerate()
+            .filter(|(_, chain)| {
+                chain
+                    .iter()
+                    .any(|hop| hop.place_name == name || hop.boundary_name == name)
+            })
+            .map(|(i, _)| i + 1)
+            .collect();
+        match through.first() {
+            None => writeln!(output, "No chain goes through {}", name)?,
+            Some(first) => writeln!(
+                output,
+                "{} chains go through it, the longest is {}",
+                through.len().separated_string(),
+                chain_line(*first, &self.chains[first - 1])
+            )?,
+        }
+        Ok(())
+    }
+
+    fn chain(&self, chain_id: &str, output: &mut impl Write) -> Result<()> {
+        let chain_id: usize = chain_id
+            .trim_start_matches('#')
+            .parse()
+            .with_context(|| format!("Invalid chain number {:?}", chain_id))?;
+        if chain_id == 0 || chain_id > self.chains.len() {
+            bail!("There are chains 1 to {}", self.chains.len());
+        }
+        let chain = &self.chains[chain_id - 1];
+        writeln!(output, "{}", chain_line(chain_id, chain))?;
+        for (i, hop) in chain.iter().enumerate() {
+            writeln!(
+                output,
+                "{:>4}. {} {} (place={}{}) in {} {} (admin_level {})",
+                i + 1,
+                hop.place_name,
+                osm_id_str(hop.place),
+                hop.place_type,
+                hop.country_code
+                    .as_ref()
+                    .map_or(String::new(), |c| format!(", {}", c)),
+                hop.boundary_name,
+                osm_id_str(hop.boundary),
+                hop.admin_level
+            )?;
+        }
+        Ok(())
+    }
+
+    fn extendable(&self, name: &str, output: &mut impl Write) -> Result<()> {
+        let places = self.graph.names.get(name).filter(|n| !n.places.is_empty());
+        match places {
+            None => writeln!(
+                output,
+                "No: no place which was searched is called {}, so a chain ending in a boundary called {} can't go on. The explain command lists those which were filtered out",
+                name, name
+            )?,
+            Some(n) => {
+                writeln!(
+                    output,
+                    "Yes: {} places called {} are inside boundaries called: {}",
+                    n.places.len().separated_string(),
+                    name,
+                    top_names(&n.out_edges)
+                )?;
+                let ending: Vec<usize> = self
+                    .chains
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, chain)| chain[chain.len() - 1].boundary_name == name)
+                    .map(|(i, _)| i + 1)
+                    .collect();
+                if !ending.is_empty() {
+                    writeln!(
+                        output,
+                        "Yet {} chains end there (e.g. #{}), so those places are already in them, or were filtered out",
+                        ending.len().separated_string(),
+                        ending[0]
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn longest(&self, args: &[String], output: &mut impl Write) -> Result<()> {
+        let mut country = None;
+        let mut limit = DEFAULT_LIMIT;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--country" {
+                let code = args.next().context("--country needs a country code")?;
+                country = Some(
+                    normalize_country_code(code)
+                        .with_context(|| format!("Invalid country code {:?}", code))?,
+                );
+            } else {
+                limit = arg
+                    .parse()
+                    .with_context(|| format!("Invalid number of chains {:?}", arg))?;
+            }
+        }
+        let mut num_listed = 0;
+        for (i, chain) in self.chains.iter().enumerate() {
+            if let Some(country) = &country {
+                if !countries(chain).contains(country) {
+                    continue;
+                }
+            }
+            if num_listed == limit {
+                break;
+            }
+            writeln!(output, "{}", chain_line(i + 1, chain))?;
+            num_listed += 1;
+        }
+        if num_listed == 0 {
+            writeln!(output, "No chains")?;
+        }
+        Ok(())
+    }
+
+    // false to stop
+    fn command(&self, line: &str, output: &mut impl Write) -> Result<bool> {
+        let args = split_args(line)?;
+        let Some((command, args)) = args.split_first() else {
+            return Ok(true);
+        };
+        match command.as_str() {
+            "name" => self.name(&name_arg(command, args)?, output)?,
+            "chain" => match args {
+                [chain_id] => self.chain(chain_id, output)?,
+                _ => bail!("chain needs one chain number"),
+            },
+            "extendable?" | "extendable" => self.extendable(&name_arg(command, args)?, output)?,
+            "longest" => self.longest(args, output)?,
+            "help" | "?" => writeln!(output, "{}", HELP)?,
+            "quit" | "exit" => return Ok(false),
+            x => bail!("Unknown command {:?}, try help", x),
+        }
+        Ok(true)
+    }
+}
+
+// Until quit or the end of stdin
+pub fn run(records: &[&Record], chains: &[Vec<Hop>]) -> Result<()> {
+    let repl = Repl {
+        records,
+        graph: NameGraph::new(records),
+        chains,
+    };
+    info!(
+        "{} records, {} names ({} both a place & a boundary), {} chains. Type help for the commands",
+        records.len().separated_string(),
+        repl.graph.names.len().separated_string(),
+        repl.graph.linkable().count().separated_string(),
+        chains.len().separated_string()
+    );
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    let mut line = String::new();
+    loop {
+        write!(stdout, "> ")?;
+        stdout.flush()?;
+        line.clear();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            writeln!(stdout)?;
+            return Ok(());
+        }
+        match repl.command(&line, &mut stdout) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => writeln!(stdout, "{:#}", e)?,
+        }
+    }
+}
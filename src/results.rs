@@ -0,0 +1,365 @@
+// Reading chains back in from a results file, i.e. our own `--format text`, `--format csv`,
+// `--format ndjson` (or --stream) or `--format sqlite` output.
+
+use std::io::prelude::*;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::exit::Status;
+use crate::json::{self, Value};
+use crate::record::{OsmId, Record};
+
+// One place in a chain, as it was when the results were written
+#[derive(Debug, Clone)]
+pub struct Hop {
+    pub place: OsmId,
+    pub place_name: String,
+    pub place_type: String,
+    pub boundary: OsmId,
+    pub boundary_name: String,
+    // `?` if unknown
+    pub admin_level: String,
+    // (lat, lon), not in --format text results
+    pub place_coords: Option<(f64, f64)>,
+    pub country_code: Option<String>,
+}
+
+impl From<&Record> for Hop {
+    fn from(r: &Record) -> Hop {
+        Hop {
+            place: r.place_key(),
+            place_name: r.place_name.to_string(),
+            place_type: r.place_type.to_string(),
+            boundary: r.boundary_key(),
+            boundary_name: r.boundary_name.to_string(),
+            admin_level: r.admin_level_str(),
+            place_coords: Some((r.place_lat, r.place_lon)),
+            country_code: r.country_code.clone(),
+        }
+    }
+}
+
+// A row of a `--format csv` results file, only the columns we need
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    chain_id: usize,
+    place_osm_type: String,
+    place_id: u64,
+    place_name: String,
+    place_type: String,
+    boundary_osm_type: String,
+    boundary_id: u64,
+    boundary_name: String,
+    #[serde(default)]
+    boundary_admin_level: String,
+    #[serde(default)]
+    place_lat: Option<f64>,
+    #[serde(default)]
+    place_lon: Option<f64>,
+    #[serde(default)]
+    country_code: String,
+}
+
+pub fn osm_type_char(s: &str) -> Result<char> {
+    Ok(match s {
+        "node" => 'n',
+        "way" => 'w',
+        "relation" => 'r',
+        x => bail!("Unknown OSM type {:?}", x),
+    })
+}
+
+fn read_csv(filename: &str, rdr: impl Read) -> Result<Vec<Vec<Hop>>> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut chains: Vec<Vec<Hop>> = Vec::new();
+    let mut last_chain_id = None;
+    for row in rdr.deserialize() {
+        let row: CsvRow = row.with_context(|| {
+            format!("{} doesn't look like a --format csv results file", filename)
+        })?;
+        if last_chain_id != Some(row.chain_id) {
+            chains.push(Vec::new());
+            last_chain_id = Some(row.chain_id);
+        }
+        chains.last_mut().unwrap().push(Hop {
+            place: (osm_type_char(&row.place_osm_type)?, row.place_id),
+            place_name: row.place_name,
+            place_type: row.place_type,
+            boundary: (osm_type_char(&row.boundary_osm_type)?, row.boundary_id),
+            boundary_name: row.boundary_name,
+            admin_level: row.boundary_admin_level,
+            place_coords: row.place_lat.zip(row.place_lon),
+            country_code: Some(row.country_code).filter(|c| !c.is_empty()),
+        });
+    }
+    Ok(chains)
+}
+
+// The (name, object) of each `[Name (node 123)](https://www.openstreetmap.org/node/123)` link.
+// Brackets in the name are escaped with a `\`, so the ` (` before the id is the last unescaped one.
+fn osm_links(line: &str) -> Result<Vec<(String, OsmId)>> {
+    const PREFIX: &str = "(https://www.openstreetmap.org/";
+    let mut links = Vec::new();
+    let mut chars = line.char_indices();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+                continue;
+            }
+            '[' => {}
+            _ => continue,
+        }
+        // The link text, unescaped, and where the id starts in it
+        let mut text = String::new();
+        let mut id_start = None;
+        let mut text_end = None;
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, c)) => text.push(c),
+                    None => bail!("Unfinished \\ escape in {:?}", line),
+                },
+                ']' => {
+                    text_end = Some(i + 1);
+                    break;
+                }
+                '(' if text.ends_with(' ') => {
+                    id_start = Some(text.len() - 1);
+                    text.push(c);
+                }
+                c => text.push(c),
+            }
+        }
+        let url_start = match text_end {
+            Some(end) if line[end..].starts_with(PREFIX) => end + PREFIX.len(),
+            // Not a link to an OSM object
+            _ => continue,
+        };
+        let url_end = match line[url_start..].find(')') {
+            Some(e) => url_start + e,
+            None => bail!("Unclosed link in {:?}", line),
+        };
+        let (osm_type, id) = match line[url_start..url_end].split_once('/') {
+            Some(x) => x,
+            None => bail!("Unknown OSM URL in {:?}", line),
+        };
+        text.truncate(id_start.unwrap_or(text.len()));
+        links.push((text, (osm_type_char(osm_type)?, id.parse()?)));
+    }
+    Ok(links)
+}
+
+// The value of a `` `key=value` `` in the line
+fn backtick_tag(line: &str, key: &str) -> Option<String> {
+    let start = line.find(&format!("`{}=", key))? + key.len() + 2;
+    let end = start + line[start..].find('`')?;
+    Some(line[start..end].to_string())
+}
+
+fn read_text(filename: &str) -> Result<Vec<Vec<Hop>>> {
+    let file =
+        std::fs::File::open(filename).with_context(|| format!("Could not open {}", filename))?;
+    let mut chains: Vec<Vec<Hop>> = Vec::new();
+    for line in std::io::BufReader::new(file).lines() {
+        let line = line?;
+        // Each place in a chain is a line like `3: There is a…`
+        let (index, rest) = match line.split_once(": ") {
+            Some((index, rest)) => match index.parse::<usize>() {
+                Ok(index) => (index, rest),
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        let links = osm_links(rest)?;
+        if links.len() < 2 {
+            continue;
+        }
+        if index == 0 {
+            chains.push(Vec::new());
+        }
+        let chain = match chains.last_mut() {
+            Some(c) => c,
+            None => bail!("{} has a place before the first chain", filename),
+        };
+        chain.push(Hop {
+            place: links[0].1,
+            place_name: links[0].0.clone(),
+            place_type: backtick_tag(rest, "place").unwrap_or_default(),
+            boundary: links[1].1,
+            boundary_name: links[1].0.clone(),
+            admin_level: backtick_tag(rest, "admin_level").unwrap_or_else(|| "?".to_string()),
+            place_coords: None,
+            country_code: None,
+        });
+    }
+    Ok(chains)
+}
+
+// A place of a chain in a `--format ndjson` line
+fn ndjson_hop(hop: &Value) -> Result<Hop> {
+    let str_field = |key: &str| hop.get(key).and_then(Value::as_str);
+    let id = |type_key: &str, id_key: &str| -> Result<OsmId> {
+        let osm_type = str_field(type_key).with_context(|| format!("No {}", type_key))?;
+        let id = hop
+            .get(id_key)
+            .and_then(Value::as_f64)
+            .with_context(|| format!("No {}", id_key))?;
+        Ok((osm_type_char(osm_type)?, id as u64))
+    };
+    let place_coords = hop
+        .get("place_lat")
+        .and_then(Value::as_f64)
+        .zip(hop.get("place_lon").and_then(Value::as_f64));
+    Ok(Hop {
+        place: id("place_osm_type", "place_id")?,
+        place_name: str_field("place_name").unwrap_or_default().to_string(),
+        place_type: str_field("place_type").unwrap_or_default().to_string(),
+        boundary: id("boundary_osm_type", "boundary_id")?,
+        boundary_name: str_field("boundary_name").unwrap_or_default().to_string(),
+        admin_level: str_field("admin_level").unwrap_or("?").to_string(),
+        place_coords,
+        country_code: str_field("country_code").map(str::to_string),
+    })
+}
+
+fn read_ndjson(filename: &str) -> Result<Vec<Vec<Hop>>> {
+    let file =
+        std::fs::File::open(filename).with_context(|| format!("Could not open {}", filename))?;
+    let mut chains = Vec::new();
+    for (i, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let hops = json::parse(&line)
+            .and_then(|chain| {
+                chain
+                    .get("hops")
+                    .and_then(Value::as_array)
+                    .context("No hops")?
+                    .iter()
+                    .map(ndjson_hop)
+                    .collect::<Result<Vec<Hop>>>()
+            })
+            .with_context(|| {
+                format!(
+                    "Line {} of {} doesn't look like a --format ndjson chain",
+                    i + 1,
+                    filename
+                )
+            })?;
+        if !hops.is_empty() {
+            chains.push(hops);
+        }
+    }
+    Ok(chains)
+}
+
+// The same columns as `--format csv`, with the sqlite3 command line program
+fn read_sqlite(filename: &str) -> Result<Vec<Vec<Hop>>> {
+    let output = Command::new("sqlite3")
+        .args(["-readonly", "-csv", "-header", filename, SQLITE_QUERY])
+        .output()
+        .context("Could not run sqlite3, is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "sqlite3 failed reading {}: {}",
+            filename,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    read_csv(filename, &output.stdout[..])
+}
+
+const SQLITE_QUERY: &str = "SELECT chain_id, place_osm_type, place_id, place_name, place_type, \
+    boundary_osm_type, boundary_id, boundary_name, admin_level AS boundary_admin_level, \
+    place_lat, place_lon, country_code \
+    FROM chain_elements JOIN records USING (record_id) ORDER BY chain_id, position";
+
+// The chains in a `--format text`, `--format csv`, `--format ndjson` or `--format sqlite` file
+pub fn read(filename: &str) -> Result<Vec<Vec<Hop>>> {
+    read_any(filename).context(Status::BadInput)
+}
+
+fn read_any(filename: &str) -> Result<Vec<Vec<Hop>>> {
+    let mut first_line = Vec::new();
+    std::io::BufReader::new(
+        std::fs::File::open(filename).with_context(|| format!("Could not open {}", filename))?,
+    )
+    .read_until(b'\n', &mut first_line)?;
+    let chains = if first_line.starts_with(b"SQLite format 3\0") {
+        read_sqlite(filename)?
+    } else if first_line.starts_with(b"chain_id,") {
+        read_csv(
+            filename,
+            std::fs::File::open(filename)
+                .with_context(|| format!("Could not open {}", filename))?,
+        )?
+    } else if first_line.starts_with(b"{") {
+        read_ndjson(filename)?
+    } else {
+        read_text(filename)?
+    };
+    if chains.is_empty() {
+        bail!("No chains found in {}", filename);
+    }
+    Ok(chains)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::Chain;
+    use crate::lang::Messages;
+    use crate::output::text;
+    use crate::record;
+
+    const BRACKETED: &str = "\
+place_osmtype,place_id,place_name,place_type,place_lat,place_lon,boundary_osmtype,boundary_id,boundary_name,boundary_admin_level
+n,1,Foo [bar],village,52.0,0.0,r,10,Baz (North),8
+n,2,Baz (North),town,52.1,0.1,r,20,Qux] \\ [x] (y,6
+";
+
+    #[test]
+    fn text_links_with_brackets() {
+        let records = record::from_csv(BRACKETED);
+        let filename =
+            std::env::temp_dir().join(format!("x-in-y-test-brackets-{}.txt", std::process::id()));
+        let mut file = std::fs::File::create(&filename).unwrap();
+        text::write(
+            &[Chain::new(records.iter().collect())],
+            &Messages::default(),
+            &mut file,
+        )
+        .unwrap();
+        let chains = read(filename.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        assert_eq!(chains.len(), 1);
+        for (hop, r) in chains[0].iter().zip(&records) {
+            assert_eq!(hop.place, r.place_key());
+            assert_eq!(hop.place_name, r.place_name);
+            assert_eq!(hop.boundary, r.boundary_key());
+            assert_eq!(hop.boundary_name, r.boundary_name);
+        }
+    }
+
+    #[test]
+    fn unescaped_links() {
+        // As written before the names were escaped
+        let links = osm_links(
+            "There is a `place=village` called [Foo (old) (node 1)](https://www.openstreetmap.org/node/1) in [Bar (rel. 2)](https://www.openstreetmap.org/relation/2) ([Wikipedia](https://en.wikipedia.org/wiki/Bar))",
+        )
+        .unwrap();
+        assert_eq!(
+            links,
+            vec![
+                ("Foo (old)".to_string(), ('n', 1)),
+                ("Bar".to_string(), ('r', 2))
+            ]
+        );
+    }
+}
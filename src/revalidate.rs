@@ -0,0 +1,93 @@
+// Check whether chains from an earlier run still hold in a newer input file, without searching
+// again.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+
+use crate::record::{osm_type_name, OsmId, Record};
+use crate::results::Hop;
+
+// Where a chain first broke
+pub struct Break {
+    pub hop: usize,
+    pub problem: &'static str,
+    pub expected: String,
+    pub current: String,
+}
+
+// None if the chain is still the same in these records
+pub fn check(chain: &[Hop], records: &HashMap<(OsmId, OsmId), &Record>) -> Option<Break> {
+    for (i, hop) in chain.iter().enumerate() {
+        let broken = |problem, expected: &str, current: &str| {
+            Some(Break {
+                hop: i,
+                problem,
+                expected: expected.to_string(),
+                current: current.to_string(),
+            })
+        };
+        let rec = match records.get(&(hop.place, hop.boundary)) {
+            None => {
+                return broken(
+                    "place no longer in boundary",
+                    &format!("{} in {}", hop.place_name, hop.boundary_name),
+                    "",
+                )
+            }
+            Some(r) => r,
+        };
+        if rec.place_name != hop.place_name {
+            return broken("place renamed", &hop.place_name, &rec.place_name);
+        }
+        if rec.boundary_name != hop.boundary_name {
+            return broken("boundary renamed", &hop.boundary_name, &rec.boundary_name);
+        }
+    }
+    None
+}
+
+pub fn write_csv(filename: &str, chains: &[Vec<Hop>], breaks: &[Option<Break>]) -> Result<()> {
+    let mut wtr = csv::Writer::from_path(filename)
+        .with_context(|| format!("Could not create {}", filename))?;
+    wtr.write_record([
+        "chain",
+        "length",
+        "start",
+        "end",
+        "status",
+        "broken_hop",
+        "place",
+        "boundary",
+        "problem",
+        "expected",
+        "current",
+    ])?;
+    for (i, (chain, broken)) in chains.iter().zip(breaks).enumerate() {
+        let id = |(t, id): OsmId| format!("{}/{}", osm_type_name(t), id);
+        let mut row = vec![
+            (i + 1).to_string(),
+            chain.len().to_string(),
+            chain[0].place_name.clone(),
+            chain[chain.len() - 1].boundary_name.clone(),
+        ];
+        match broken {
+            None => {
+                row.push("ok".to_string());
+                row.extend(vec![String::new(); 6]);
+            }
+            Some(b) => row.extend(vec![
+                "broken".to_string(),
+                b.hop.to_string(),
+                id(chain[b.hop].place),
+                id(chain[b.hop].boundary),
+                b.problem.to_string(),
+                b.expected.clone(),
+                b.current.clone(),
+            ]),
+        }
+        wtr.write_record(&row)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
@@ -0,0 +1,158 @@
+// How chains of the same length are ranked (--score). The search extends the best of them first,
+// so the chains it finds (and keeps, when it has to stop early or throw some away) are the best
+// ones. The score of a chain is the sum of the score of each hop.
+
+use anyhow::{bail, Context, Result};
+
+use crate::record::{place_dist, Record};
+use crate::search::ChainSearch;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scoring {
+    // The distance of each hop, so chains which jump & zigzag over the world. The default.
+    Distance,
+    // Minus the distance of each hop, so chains which stay close together
+    Compact,
+    // Chains of the same length aren't ranked, they're taken in the order of their records
+    None,
+    // The --place-type-weights of the places, so chains of real towns & cities, rather than
+    // city_blocks & squares
+    PlaceType,
+    // The shortest hop, so chains where every hop goes (nearly) to the other side of the world,
+    // rather than a few very long hops & many short ones
+    Antipodal,
+    // The log of the places' populations (the population column), so chains of big, well known
+    // places. Places without a population score 0.
+    Population,
+}
+
+impl std::str::FromStr for Scoring {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Scoring> {
+        Ok(match s {
+            "distance" => Scoring::Distance,
+            "compact" => Scoring::Compact,
+            "none" => Scoring::None,
+            "place-type" => Scoring::PlaceType,
+            "antipodal" => Scoring::Antipodal,
+            "population" => Scoring::Population,
+            x => bail!(
+                "Unknown score {:?}, should be distance, compact, none, place-type, antipodal or population",
+                x
+            ),
+        })
+    }
+}
+
+impl Scoring {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scoring::Distance => "distance",
+            Scoring::Compact => "compact",
+            Scoring::None => "none",
+            Scoring::PlaceType => "place-type",
+            Scoring::Antipodal => "antipodal",
+            Scoring::Population => "population",
+        }
+    }
+}
+
+// Weights are stored ×1000, since the search's scores are integers
+const WEIGHT_SCALE: f64 = 1000.;
+
+// place_type → weight. Types which aren't listed weigh 1.
+#[derive(Debug, Clone)]
+pub struct PlaceTypeWeights(Vec<(String, f64)>);
+
+impl Default for PlaceTypeWeights {
+    fn default() -> Self {
+        PlaceTypeWeights(
+            [
+                ("city", 3.),
+                ("town", 2.),
+                ("municipality", 2.),
+                ("village", 1.),
+                ("hamlet", 1.),
+                ("borough", 1.),
+                ("suburb", 0.5),
+                ("quarter", 0.5),
+                ("district", 0.5),
+                ("neighbourhood", 0.3),
+                ("square", 0.2),
+                ("city_block", 0.2),
+                ("block", 0.2),
+            ]
+            .iter()
+            .map(|(t, w)| (t.to_string(), *w))
+            .collect(),
+        )
+    }
+}
+
+impl PlaceTypeWeights {
+    // Change the weights of some types, from "city=3,village=1,city_block=0.2"
+    pub fn set(&mut self, list: &str) -> Result<()> {
+        for item in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (place_type, weight) = item.split_once('=').with_context(|| {
+                format!(
+                    "Invalid place type weight {:?}, should be like city=3",
+                    item
+                )
+            })?;
+            let weight: f64 = weight.trim().parse().with_context(|| {
+                format!("Invalid weight for place type {}: {:?}", place_type, weight)
+            })?;
+            let place_type = place_type.trim();
+            match self.0.iter_mut().find(|(t, _)| t == place_type) {
+                Some((_, w)) => *w = weight,
+                None => self.0.push((place_type.to_string(), weight)),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn weight(&self, place_type: &str) -> f64 {
+        self.0
+            .iter()
+            .find(|(t, _)| t == place_type)
+            .map_or(1., |(_, w)| *w)
+    }
+}
+
+// A chain's score is the sum of its hops' scores, so for the total to be the shortest hop (in m),
+// each hop scores how much shorter the shortest hop has got. This looks at every hop, so it's
+// slower than the other scores.
+fn shortest_hop_change(chain: &[&Record]) -> isize {
+    let hops: Vec<isize> = chain.windows(2).map(|p| place_dist(p[0], p[1])).collect();
+    let (last, before) = hops.split_last().unwrap();
+    match before.iter().min() {
+        None => *last,
+        Some(shortest) => (*last).min(*shortest) - shortest,
+    }
+}
+
+// Use this scoring for the search
+pub fn apply<'s, 'a>(
+    search: ChainSearch<'s, 'a>,
+    scoring: Scoring,
+    weights: &'s PlaceTypeWeights,
+) -> ChainSearch<'s, 'a> {
+    match scoring {
+        // The search's own default
+        Scoring::Distance => search,
+        Scoring::Compact => search
+            .score(|chain: &[&Record]| -place_dist(chain[chain.len() - 2], chain[chain.len() - 1])),
+        Scoring::None => search.score(|_| 0),
+        Scoring::PlaceType => search.score(move |chain: &[&Record]| {
+            (weights.weight(&chain[chain.len() - 1].place_type) * WEIGHT_SCALE).round() as isize
+        }),
+        Scoring::Antipodal => search.score(shortest_hop_change),
+        // The log, so one huge city doesn't outweigh a chain of towns
+        Scoring::Population => search.score(|chain: &[&Record]| {
+            chain[chain.len() - 1]
+                .place_population
+                .map_or(0, |p| ((p as f64).ln_1p() * WEIGHT_SCALE).round() as isize)
+        }),
+    }
+}
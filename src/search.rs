@@ -0,0 +1,632 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use separator::Separatable;
+
+use crate::frontier::{chain_bytes, Frontier};
+use crate::memory::{self, MemoryUsage};
+use crate::metrics;
+use crate::progress::Status;
+use crate::record::{place_dist, Record};
+
+// How the search went
+#[derive(Debug, Default, Clone)]
+pub struct SearchStats {
+    pub steps: usize,
+    // The most intermediate chains there were at once
+    pub peak_frontier: usize,
+    // Stopped early by Ctrl-C or --max-runtime
+    pub interrupted: bool,
+    pub memory_cleanups: Vec<MemoryCleanup>,
+    // With the most the frontier took, and the finished chains at the end
+    pub memory: MemoryUsage,
+}
+
+// Intermediate chains were thrown away to save memory
+#[derive(Debug, Clone)]
+pub struct MemoryCleanup {
+    pub step: usize,
+    pub intermediate_before: usize,
+    // much shorter than the longest chain from the same start
+    pub removed_short: usize,
+    pub removed_by_failsafe: usize,
+    pub intermediate_after: usize,
+}
+
+// What to do if there are still too many intermediate chains after the short ones are removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failsafe {
+    // Throw away the shortest (and then least spread out) chains
+    DropShortest,
+    // Keep them all, and maybe run out of memory
+    Off,
+}
+
+impl std::str::FromStr for Failsafe {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Failsafe> {
+        match s {
+            "drop-shortest" => Ok(Failsafe::DropShortest),
+            "off" => Ok(Failsafe::Off),
+            _ => anyhow::bail!("Unknown failsafe {:?}, should be drop-shortest or off", s),
+        }
+    }
+}
+
+// Bounds on the search, from the command line
+#[derive(Debug, Clone)]
+pub struct SearchLimits {
+    // A chain this long (in places) is finished, rather than extended further
+    pub max_chain_len: Option<usize>,
+    // Bytes the whole process should stay under. The memory clean up happens when the
+    // intermediate chains would take it over this.
+    pub memory_limit: Option<u64>,
+    // How many intermediate chains to keep, without a memory_limit
+    pub max_intermediate: usize,
+    // The memory clean up keeps chains at most this much shorter than the longest from their
+    // start
+    pub prune_slack: usize,
+    pub failsafe: Failsafe,
+    // Stop after this many steps, as if interrupted
+    pub max_steps: usize,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            max_chain_len: None,
+            memory_limit: None,
+            max_intermediate: 8_000_000,
+            prune_slack: 10,
+            failsafe: Failsafe::DropShortest,
+            // Don't go forever
+            max_steps: 1e12 as usize,
+        }
+    }
+}
+
+// Make `chain` the finished chain for its start, replacing any there was
+fn keep_finished<'a>(
+    finished_chains: &mut HashMap<&'a Record, Vec<&'a Record>>,
+    finished_bytes: &mut usize,
+    chain: Vec<&'a Record>,
+) {
+    *finished_bytes += memory::finished_chain_bytes(&chain);
+    if let Some(old) = finished_chains.insert(chain[0], chain) {
+        *finished_bytes -= memory::finished_chain_bytes(&old);
+    }
+}
+
+// Roughly the most memory the intermediate chains could take, before the search has run: every
+// start as a 1 record chain, and a full frontier of chains `chain_len` long
+pub fn estimate_frontier_bytes(
+    limits: &SearchLimits,
+    num_starts: usize,
+    chain_len: usize,
+) -> usize {
+    let full = num_starts * chain_bytes(1) + limits.max_intermediate * chain_bytes(chain_len);
+    match limits.memory_limit {
+        Some(limit) => full.min(limit as usize),
+        None => full,
+    }
+}
+
+// What the search has so far, for a SearchObserver
+pub struct SearchView<'v, 'a> {
+    pub status: Status,
+    // By their first record
+    pub finished_chains: &'v HashMap<&'a Record, Vec<&'a Record>>,
+    // The intermediate chain which will be extended next
+    pub next: Option<&'v [&'a Record]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checkpoint {
+    Started,
+    // Every 1,000 steps
+    Searching,
+    Finished,
+    // Stopped early
+    Interrupted,
+}
+
+// Told what the search is doing, e.g. to show the progress. They all do nothing by default.
+pub trait SearchObserver<'a> {
+    // After each step, so it has to be quick
+    fn on_step(&mut self, _view: &SearchView<'_, 'a>) {}
+
+    // A start's chain is final, i.e. there's nothing left to search from that start, so it's
+    // called with each chain as it's found, rather than only at the end. Chains of 1 record
+    // aren't passed. Chains still being searched when the search stops early aren't either, but
+    // are in what the search returns.
+    fn on_chain_finished(&mut self, _chain: &[&'a Record]) {}
+
+    // Intermediate chains were thrown away to save memory
+    fn on_prune(&mut self, _cleanup: &MemoryCleanup) {}
+
+    fn on_checkpoint(&mut self, _checkpoint: Checkpoint, _view: &SearchView<'_, 'a>) {}
+
+    // Something to tell the user
+    fn on_message(&mut self, _msg: &str) {}
+}
+
+// For `ChainSearch::run_as_found`
+struct Found<F>(F);
+
+impl<'a, F: FnMut(&[&'a Record])> SearchObserver<'a> for Found<F> {
+    fn on_chain_finished(&mut self, chain: &[&'a Record]) {
+        (self.0)(chain)
+    }
+}
+
+// How good the last hop of a chain is. Of the chains of the same length, the ones with the highest
+// total score are extended first.
+type Score<'s, 'a> = Box<dyn Fn(&[&'a Record]) -> isize + 's>;
+// Whether a chain can be extended with this record
+type Constraint<'s, 'a> = Box<dyn Fn(&[&'a Record], &'a Record) -> bool + 's>;
+
+// Find the longest chain starting from each record, where each place has the same name as the
+// boundary the previous place is in. Chains of 1 record are included.
+//
+//     let (chains, stats) = ChainSearch::new(&records)
+//         .max_chain_len(10)
+//         .constraint(|_chain, rec| rec.country_code.as_deref() == Some("IE"))
+//         .run();
+pub struct ChainSearch<'s, 'a> {
+    records: &'s [&'a Record],
+    starts: Option<&'s [&'a Record]>,
+    prefixes: Option<&'s [Vec<&'a Record>]>,
+    limits: SearchLimits,
+    stop: Option<&'s AtomicBool>,
+    score: Score<'s, 'a>,
+    constraints: Vec<Constraint<'s, 'a>>,
+    observers: Vec<Box<dyn SearchObserver<'a> + 's>>,
+}
+
+impl<'s, 'a> ChainSearch<'s, 'a> {
+    pub fn new(records: &'s [&'a Record]) -> ChainSearch<'s, 'a> {
+        ChainSearch {
+            records,
+            starts: None,
+            prefixes: None,
+            limits: SearchLimits::default(),
+            stop: None,
+            // Chains which jump & zigzag over the world are more interesting
+            score: Box::new(|chain| place_dist(chain[chain.len() - 2], chain[chain.len() - 1])),
+            constraints: Vec::new(),
+            observers: Vec::new(),
+        }
+    }
+
+    // Only the chains starting with one of these, rather than with any record
+    pub fn starts(mut self, starts: &'s [&'a Record]) -> Self {
+        self.starts = Some(starts);
+        self
+    }
+
+    // Only make these chains longer (at the end), rather than searching from single records. A
+    // chain which can't be extended is finished as it is. They need different first records.
+    pub fn prefixes(mut self, prefixes: &'s [Vec<&'a Record>]) -> Self {
+        self.prefixes = Some(prefixes);
+        self
+    }
+
+    pub fn limits(mut self, limits: SearchLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // How many intermediate chains to keep before the memory clean up. The command line sets all
+    // the `limits` at once, so this & `max_chain_len` are for other code using the library.
+    pub fn max_frontier(mut self, max_intermediate: usize) -> Self {
+        self.limits.max_intermediate = max_intermediate;
+        self
+    }
+
+    pub fn max_chain_len(mut self, max_chain_len: usize) -> Self {
+        self.limits.max_chain_len = Some(max_chain_len);
+        self
+    }
+
+    // Stop after this many steps, with what it has so far (e.g. to time a fixed amount of work)
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.limits.max_steps = max_steps;
+        self
+    }
+
+    // Stop early, with what it has so far, when this is set (e.g. by Ctrl-C)
+    pub fn stop_when(mut self, stop: &'s AtomicBool) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    // Tell this what the search is doing. There can be more than one.
+    pub fn observer(mut self, observer: impl SearchObserver<'a> + 's) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    // Called with each new chain, to score its last hop. By default it's the distance (in m)
+    // between the last 2 places.
+    pub fn score(mut self, score: impl Fn(&[&'a Record]) -> isize + 's) -> Self {
+        self.score = Box::new(score);
+        self
+    }
+
+    // Only extend a chain with a record if this is true. A chain which can't be extended any
+    // further is finished. There can be more than one.
+    pub fn constraint(
+        mut self,
+        constraint: impl Fn(&[&'a Record], &'a Record) -> bool + 's,
+    ) -> Self {
+        self.constraints.push(Box::new(constraint));
+        self
+    }
+
+    // Like `run`, and `found` is called with each start's chain as soon as it's final, see
+    // `SearchObserver::on_chain_finished`
+    pub fn run_as_found(
+        self,
+        found: impl FnMut(&[&'a Record]) + 's,
+    ) -> (HashMap<&'a Record, Vec<&'a Record>>, SearchStats) {
+        self.observer(Found(found)).run()
+    }
+
+    pub fn run(self) -> (HashMap<&'a Record, Vec<&'a Record>>, SearchStats) {
+        let ChainSearch {
+            records,
+            starts,
+            prefixes,
+            limits,
+            stop,
+            score,
+            constraints,
+            mut observers,
+        } = self;
+        let starts = starts.unwrap_or(records);
+        let limits = &limits;
+        let mut stats = SearchStats::default();
+        debug!("Generating name lookup");
+        let place_names = records.iter().fold(
+            HashMap::with_capacity(records.len()) as HashMap<&str, Vec<&Record>>,
+            |mut map, rec| {
+                map.entry(&rec.place_name).or_default().push(rec);
+                map
+            },
+        );
+
+        // A chain, is what we are building. It's a list of records.
+
+        // Working list, the longest chain first, then the one with the highest total score of the
+        // hops (by default the geographic distance of each step, which prioritizes chains that
+        // jump / zigzag over the world)
+        let mut intermediate_chains = Frontier::default();
+
+        // Finished chains go here, indexed by their first record. We only need one chain for each
+        // "start" point. We keep the longest chain.
+        // This is to reduce memory usage, and maybe could be removed.
+        let mut finished_chains: HashMap<&Record, Vec<&Record>> = HashMap::new();
+        let mut num_steps_done = 0;
+
+        // Estimated memory used by finished_chains. The frontier keeps track of its own.
+        let mut finished_bytes = 0;
+        stats.memory.records = records.iter().map(|r| memory::record_bytes(r)).sum();
+        stats.memory.name_index = memory::name_index_bytes(&place_names);
+
+        // How many intermediate chains there are for each start. When it gets to 0, that start's
+        // finished chain is final
+        let mut searching_from: HashMap<&Record, usize> = HashMap::new();
+        macro_rules! searched {
+            ($start:expr) => {
+                let start = $start;
+                let left = searching_from.get_mut(start).unwrap();
+                *left -= 1;
+                if *left == 0 {
+                    searching_from.remove(start);
+                    if let Some(chain) = finished_chains.get(start) {
+                        if chain.len() > 1 {
+                            for observer in observers.iter_mut() {
+                                observer.on_chain_finished(chain);
+                            }
+                        }
+                    }
+                }
+            };
+        }
+
+        // The initial chains are all the "point X is in boundary Y", i.e. 1 element chains, or the
+        // prefixes
+        let mut prefix_lens: HashMap<&Record, usize> = HashMap::new();
+        match prefixes {
+            None => {
+                for rec in starts.iter().copied() {
+                    if place_names.contains_key(rec.boundary_name.as_ref()) {
+                        *searching_from.entry(rec).or_default() += 1;
+                        intermediate_chains.push(0, vec![rec]);
+                    }
+                }
+            }
+            Some(prefixes) => {
+                for chain in prefixes {
+                    prefix_lens.insert(chain[0], chain.len());
+                    *searching_from.entry(chain[0]).or_default() += 1;
+                    let chain_score = (2..=chain.len()).map(|n| score(&chain[..n])).sum::<isize>();
+                    intermediate_chains.push(chain_score, chain.clone());
+                }
+            }
+        }
+        // Whether nothing has been searched from this chain's start yet
+        let is_initial =
+            |chain: &[&Record]| chain.len() == *prefix_lens.get(chain[0]).unwrap_or(&1);
+
+        // The initial chains are only taken once every longer chain is done, so this is how many
+        // start points haven't been searched from yet
+        let mut starts_left = intermediate_chains.len();
+
+        let mut last_boundary_name;
+
+        let mut longest_seen = 1;
+
+        let max_intermediate = limits.max_intermediate;
+        // With --memory-limit, what's already used (mostly the records) is taken off the limit
+        let chain_budget = limits.memory_limit.map(|limit| {
+            let budget = limit.saturating_sub(metrics::rss_bytes().unwrap_or(0));
+            if budget == 0 {
+                warn!("Already using more than the --memory-limit, so most chains will be thrown away");
+            } else {
+                for observer in observers.iter_mut() {
+                    observer.on_message(&format!(
+                        "Intermediate chains can use about {} MB of the --memory-limit",
+                        (budget >> 20).separated_string()
+                    ));
+                }
+            }
+            budget as usize
+        });
+        let over_budget = |len: usize, bytes: usize| match chain_budget {
+            None => len > max_intermediate,
+            Some(budget) => bytes > budget,
+        };
+        // With the failsafe off, the clean up might not get under the budget
+        let mut next_cleanup_after = 0;
+
+        // For the observers
+        macro_rules! status {
+            () => {
+                Status {
+                    steps: num_steps_done,
+                    frontier: intermediate_chains.len(),
+                    finished: finished_chains.len(),
+                    longest: longest_seen,
+                    memory_cleanups: stats.memory_cleanups.len(),
+                    starts_left,
+                    memory: MemoryUsage {
+                        frontier: intermediate_chains.bytes(),
+                        finished_chains: finished_bytes,
+                        ..stats.memory
+                    },
+                }
+            };
+        }
+
+        // What the observers are shown
+        macro_rules! view {
+            () => {
+                SearchView {
+                    status: status!(),
+                    finished_chains: &finished_chains,
+                    next: intermediate_chains.peek(),
+                }
+            };
+        }
+        macro_rules! checkpoint {
+            ($checkpoint:expr) => {
+                if !observers.is_empty() {
+                    let view = view!();
+                    for observer in observers.iter_mut() {
+                        observer.on_checkpoint($checkpoint, &view);
+                    }
+                }
+            };
+        }
+
+        checkpoint!(Checkpoint::Started);
+
+        // The main loop that does the calculation.
+        // Take the longest intermediate chain we have, and see if we can extend it.
+        loop {
+            let (chain_score, chain) = match intermediate_chains.pop() {
+                // No more intermediate chains, so we're finished
+                None => {
+                    break;
+                }
+                Some(x) => x,
+            };
+            let start = chain[0];
+            longest_seen = longest_seen.max(chain.len());
+            if is_initial(&chain) {
+                starts_left -= 1;
+            }
+
+            if stop.is_some_and(|stop| stop.load(Ordering::SeqCst)) {
+                // User has pressed Ctrl C
+                for observer in observers.iter_mut() {
+                    observer.on_message("Stopping (Ctrl-C or --max-runtime), breaking out of calculation with what we have now");
+                }
+                stats.interrupted = true;
+                break;
+            }
+
+            let next_places = match limits.max_chain_len {
+                Some(max) if chain.len() >= max => None,
+                // Nothing from this start can be longer than the one it already has
+                Some(max)
+                    if finished_chains
+                        .get(chain[0])
+                        .is_some_and(|curr| curr.len() >= max) =>
+                {
+                    searched!(start);
+                    continue;
+                }
+                _ => {
+                    last_boundary_name = &chain.last().unwrap().boundary_name;
+                    place_names.get(last_boundary_name.as_ref())
+                }
+            };
+            match next_places {
+                None => {
+                    // can't go any further
+                    // Keep this chain if it is longer than the longest chain (by number of steps)
+                    // we've seen for this start point.
+                    if finished_chains
+                        .get(chain[0])
+                        .is_none_or(|curr| chain.len() > curr.len())
+                    {
+                        keep_finished(&mut finished_chains, &mut finished_bytes, chain);
+                    }
+                }
+
+                Some(records) => {
+                    for rec in records {
+                        // ensure the place isn't in the chain already.
+                        if !chain.iter().any(|r| {
+                            r.place_key() == rec.place_key()
+                                || r.boundary_key() == rec.boundary_key()
+                        }) && constraints.iter().all(|allowed| allowed(&chain, rec))
+                        {
+                            // create a new chain, and add that to the intermediate chains
+                            let mut new_chain = chain.clone();
+                            new_chain.push(rec);
+                            *searching_from.get_mut(chain[0]).unwrap() += 1;
+                            intermediate_chains.push(chain_score + score(&new_chain), new_chain);
+                        } else {
+                            // this would be a loop (or isn't allowed), so stop here and add this
+                            // chain again, only if it's longer
+                            if finished_chains
+                                .get(chain[0])
+                                .is_none_or(|curr| chain.len() > curr.len())
+                            {
+                                keep_finished(
+                                    &mut finished_chains,
+                                    &mut finished_bytes,
+                                    chain.clone(),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            searched!(start);
+
+            // memory management. stop the intermediate_chains from getting too big
+            if over_budget(intermediate_chains.len(), intermediate_chains.bytes())
+                && intermediate_chains.len() > next_cleanup_after
+            {
+                let intermediate_before = intermediate_chains.len();
+
+                // save what we have if we have an intermediate chain that's longer than a finished
+                // chain we've seen.
+                for chain in intermediate_chains.chains() {
+                    if chain.len() > 1
+                        && finished_chains
+                            .get(chain[0])
+                            .is_none_or(|curr| chain.len() > curr.len())
+                    {
+                        keep_finished(&mut finished_chains, &mut finished_bytes, chain.clone());
+                    }
+                }
+
+                // Keep the initial chains (of len 1, or the prefixes), which are the building blocks
+                // and any chain which is at least as long as the longest for this start minus the
+                // --prune-slack.
+                // i.e. throw away any intermediate chains which are much shorter than the longest for
+                // this start point
+                let mut removed_starts = Vec::new();
+                intermediate_chains.retain(|chain| {
+                    let keep = is_initial(chain)
+                        || finished_chains.get(chain[0]).is_none_or(|longest_seen| {
+                            chain.len() >= longest_seen.len().saturating_sub(limits.prune_slack)
+                        });
+                    if !keep {
+                        removed_starts.push(chain[0]);
+                    }
+                    keep
+                });
+                let removed_short = intermediate_before - intermediate_chains.len();
+
+                // failsafe, just delete the lowest ones
+                let before_failsafe = intermediate_chains.len();
+                if limits.failsafe == Failsafe::DropShortest {
+                    removed_starts.extend(intermediate_chains.drop_worst_while(over_budget));
+                } else if over_budget(intermediate_chains.len(), intermediate_chains.bytes()) {
+                    // Don't try again until there's twice as many, or it'd be cleaning up every step
+                    next_cleanup_after = intermediate_chains.len() * 2;
+                }
+                let removed_by_failsafe = before_failsafe - intermediate_chains.len();
+                for start in removed_starts {
+                    searched!(start);
+                }
+
+                let cleanup = MemoryCleanup {
+                    step: num_steps_done,
+                    intermediate_before,
+                    removed_short,
+                    removed_by_failsafe,
+                    intermediate_after: intermediate_chains.len(),
+                };
+                for observer in observers.iter_mut() {
+                    observer.on_prune(&cleanup);
+                }
+                stats.memory_cleanups.push(cleanup);
+                // the failsafe can throw away start points
+                starts_left = intermediate_chains
+                    .chains()
+                    .filter(|chain| is_initial(chain))
+                    .count();
+            }
+
+            num_steps_done += 1;
+            stats.peak_frontier = stats.peak_frontier.max(intermediate_chains.len());
+            stats.memory.frontier = stats.memory.frontier.max(intermediate_chains.bytes());
+            if !observers.is_empty() {
+                let view = view!();
+                for observer in observers.iter_mut() {
+                    observer.on_step(&view);
+                }
+            }
+            if num_steps_done % 1_000 == 0 {
+                checkpoint!(Checkpoint::Searching);
+            }
+
+            if num_steps_done >= limits.max_steps {
+                stats.interrupted = true;
+                break;
+            }
+        }
+
+        if stats.interrupted {
+            checkpoint!(Checkpoint::Interrupted);
+        } else {
+            checkpoint!(Checkpoint::Finished);
+        }
+
+        // Update the finished chains
+        for chain in intermediate_chains.into_chains() {
+            if chain.len() == 1 {
+                continue;
+            }
+            if let Some(old_chain) = finished_chains.get(chain[0]) {
+                if old_chain.len() < chain.len() {
+                    keep_finished(&mut finished_chains, &mut finished_bytes, chain);
+                }
+            } else {
+                keep_finished(&mut finished_chains, &mut finished_bytes, chain);
+            }
+        }
+        stats.memory.finished_chains = finished_bytes;
+
+        stats.steps = num_steps_done;
+        (finished_chains, stats)
+    }
+}
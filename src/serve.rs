@@ -0,0 +1,456 @@
+// Browse results over HTTP (the `serve` command), e.g. to host the latest planet results for
+// others to explore. A small JSON API, and a web page which uses it. Plain HTTP/1.0, with a thread
+// per connection & a read timeout, like the --metrics-addr, so one stuck client can't hold up the
+// rest.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::json::{obj, Value};
+use crate::record::{osm_type_name, url, OsmId};
+use crate::results::Hop;
+
+// Chains in one list response, unless there's a ?limit=
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+// The longest request line & headers read, so a client can't make a thread buffer without end
+const MAX_REQUEST_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 64 * 1024;
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(value: Value) -> Response {
+        Response {
+            status: "200 OK",
+            content_type: "application/json",
+            body: value.to_string(),
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            body: obj(vec![("error", message.into())]).to_string(),
+        }
+    }
+}
+
+// Undo the percent encoding of a query string value, `+` is a space
+fn url_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn query_params(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.split_once('=') {
+            Some((k, v)) => (url_decode(k), url_decode(v)),
+            None => (url_decode(p), String::new()),
+        })
+        .collect()
+}
+
+fn osm_id_str((t, id): OsmId) -> String {
+    format!("{}/{}", osm_type_name(t), id)
+}
+
+// The countries a chain goes through, in the order it gets to them
+pub fn countries(chain: &[Hop]) -> Vec<String> {
+    let mut countries: Vec<String> = Vec::new();
+    for code in chain.iter().filter_map(|hop| hop.country_code.as_ref()) {
+        if !countries.contains(code) {
+            countries.push(code.clone());
+        }
+    }
+    countries
+}
+
+fn chain_summary(chain_id: usize, chain: &[Hop]) -> Value {
+    obj(vec![
+        ("chain_id", chain_id.into()),
+        ("length", chain.len().into()),
+        ("start", (&chain[0].place_name).into()),
+        ("end", (&chain[chain.len() - 1].boundary_name).into()),
+        ("countries", countries(chain).into()),
+    ])
+}
+
+fn hop_json(hop: &Hop) -> Value {
+    obj(vec![
+        ("place", osm_id_str(hop.place).into()),
+        ("place_name", (&hop.place_name).into()),
+        ("place_type", (&hop.place_type).into()),
+        ("place_url", url(hop.place.0, hop.place.1).into()),
+        ("place_lat", hop.place_coords.map(|c| c.0).into()),
+        ("place_lon", hop.place_coords.map(|c| c.1).into()),
+        ("country_code", hop.country_code.as_ref().into()),
+        ("boundary", osm_id_str(hop.boundary).into()),
+        ("boundary_name", (&hop.boundary_name).into()),
+        ("boundary_url", url(hop.boundary.0, hop.boundary.1).into()),
+        ("admin_level", (&hop.admin_level).into()),
+    ])
+}
+
+fn chain_json(chain_id: usize, chain: &[Hop]) -> Value {
+    let mut value = chain_summary(chain_id, chain);
+    if let Value::Object(pairs) = &mut value {
+        pairs.push((
+            "hops".to_string(),
+            Value::Array(chain.iter().map(hop_json).collect()),
+        ));
+    }
+    value
+}
+
+// A LineString through the places, then a Point for each. None if the results file doesn't have
+// the locations (--format text).
+fn chain_geojson(chain_id: usize, chain: &[Hop]) -> Option<Value> {
+    let points = chain
+        .iter()
+        .map(|hop| {
+            hop.place_coords
+                .map(|(lat, lon)| Value::from(vec![lon, lat]))
+        })
+        .collect::<Option<Vec<Value>>>()?;
+    let mut features = vec![obj(vec![
+        ("type", "Feature".into()),
+        (
+            "geometry",
+            obj(vec![
+                ("type", "LineString".into()),
+                ("coordinates", Value::Array(points.clone())),
+            ]),
+        ),
+        ("properties", chain_summary(chain_id, chain)),
+    ])];
+    for ((i, hop), point) in chain.iter().enumerate().zip(points) {
+        let mut properties = vec![("chain_id".to_string(), chain_id.into())];
+        properties.push(("position".to_string(), (i + 1).into()));
+        if let Value::Object(pairs) = hop_json(hop) {
+            properties.extend(pairs);
+        }
+        features.push(obj(vec![
+            ("type", "Feature".into()),
+            (
+                "geometry",
+                obj(vec![("type", "Point".into()), ("coordinates", point)]),
+            ),
+            ("properties", Value::Object(properties)),
+        ]));
+    }
+    Some(obj(vec![
+        ("type", "FeatureCollection".into()),
+        ("features", Value::Array(features)),
+    ]))
+}
+
+// GET /api/chains?min_length=&max_length=&country=&offset=&limit=
+fn list_chains(chains: &[Vec<Hop>], params: &HashMap<String, String>) -> Response {
+    let number = |name: &str| -> std::result::Result<Option<usize>, Response> {
+        match params.get(name).filter(|v| !v.is_empty()) {
+            None => Ok(None),
+            Some(v) => v.parse().map(Some).map_err(|_| {
+                Response::error("400 Bad Request", &format!("{} should be a number", name))
+            }),
+        }
+    };
+    let parsed = (|| {
+        Ok((
+            number("min_length")?,
+            number("max_length")?,
+            number("offset")?.unwrap_or(0),
+            number("limit")?.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+        ))
+    })();
+    let (min_length, max_length, offset, limit) = match parsed {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+    let country = params
+        .get("country")
+        .filter(|c| !c.is_empty())
+        .map(|c| c.to_lowercase());
+
+    let matching: Vec<(usize, &Vec<Hop>)> = chains
+        .iter()
+        .enumerate()
+        .map(|(i, chain)| (i + 1, chain))
+        .filter(|(_, chain)| min_length.is_none_or(|min| chain.len() >= min))
+        .filter(|(_, chain)| max_length.is_none_or(|max| chain.len() <= max))
+        .filter(|(_, chain)| {
+            country.as_ref().is_none_or(|country| {
+                chain.iter().any(|hop| {
+                    hop.country_code
+                        .as_ref()
+                        .is_some_and(|c| c.to_lowercase() == *country)
+                })
+            })
+        })
+        .collect();
+    Response::json(obj(vec![
+        ("total", matching.len().into()),
+        ("offset", offset.into()),
+        (
+            "chains",
+            Value::Array(
+                matching
+                    .iter()
+                    .skip(offset)
+                    .take(limit)
+                    .map(|(id, chain)| chain_summary(*id, chain))
+                    .collect(),
+            ),
+        ),
+    ]))
+}
+
+// GET /api/search?name=. Places & boundaries whose name contains it, ignoring case. Each chain is
+// listed once, at the first position it matches.
+fn search(chains: &[Vec<Hop>], params: &HashMap<String, String>) -> Response {
+    let name = match params.get("name").filter(|n| !n.trim().is_empty()) {
+        Some(n) => n.trim().to_lowercase(),
+        None => return Response::error("400 Bad Request", "search needs a ?name="),
+    };
+    let mut matches = Vec::new();
+    for (i, chain) in chains.iter().enumerate() {
+        let found = chain.iter().enumerate().find_map(|(pos, hop)| {
+            if hop.place_name.to_lowercase().contains(&name) {
+                Some((pos, "place", &hop.place_name))
+            } else if hop.boundary_name.to_lowercase().contains(&name) {
+                Some((pos, "boundary", &hop.boundary_name))
+            } else {
+                None
+            }
+        });
+        if let Some((pos, role, matched)) = found {
+            let mut value = chain_summary(i + 1, chain);
+            if let Value::Object(pairs) = &mut value {
+                pairs.push(("position".to_string(), (pos + 1).into()));
+                pairs.push(("as".to_string(), role.into()));
+                pairs.push(("name".to_string(), matched.into()));
+            }
+            matches.push(value);
+            if matches.len() >= MAX_LIMIT {
+                break;
+            }
+        }
+    }
+    Response::json(obj(vec![("matches", Value::Array(matches))]))
+}
+
+fn route(chains: &[Vec<Hop>], target: &str) -> Response {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let params = query_params(query);
+    let path = path.trim_end_matches('/');
+    match path {
+        "" => {
+            return Response {
+                status: "200 OK",
+                content_type: "text/html; charset=utf-8",
+                body: INDEX_HTML.to_string(),
+            }
+        }
+        "/api/chains" => return list_chains(chains, &params),
+        "/api/search" => return search(chains, &params),
+        _ => {}
+    }
+
+    // /api/chains/N and /api/chains/N.geojson
+    let rest = match path.strip_prefix("/api/chains/") {
+        Some(rest) => rest,
+        None => return Response::error("404 Not Found", "Not found"),
+    };
+    let (id, geojson) = match rest.strip_suffix(".geojson") {
+        Some(id) => (id, true),
+        None => (rest, false),
+    };
+    let (chain_id, chain) = match id.parse::<usize>() {
+        Ok(id) if id >= 1 && id <= chains.len() => (id, &chains[id - 1]),
+        _ => return Response::error("404 Not Found", "No such chain"),
+    };
+    if !geojson {
+        return Response::json(chain_json(chain_id, chain));
+    }
+    match chain_geojson(chain_id, chain) {
+        Some(value) => Response {
+            status: "200 OK",
+            content_type: "application/geo+json",
+            body: value.to_string(),
+        },
+        None => Response::error(
+            "404 Not Found",
+            "The results file doesn't have the locations of the places, use --format csv or sqlite",
+        ),
+    }
+}
+
+// The request line, after reading the headers (which aren't used), or the error status & message
+// if either is too long. Also for the --metrics-addr.
+pub(crate) fn read_request(
+    stream: &TcpStream,
+) -> std::io::Result<Result<String, (&'static str, &'static str)>> {
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    reader
+        .by_ref()
+        .take(MAX_REQUEST_LINE as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if line.len() > MAX_REQUEST_LINE {
+        return Ok(Err(("400 Bad Request", "The request line is too long")));
+    }
+    let request_line = String::from_utf8_lossy(&line).into_owned();
+    // An HTTP/0.9 request, without a version, has no headers
+    if request_line.split_whitespace().count() < 3 {
+        return Ok(Ok(request_line));
+    }
+    let mut headers = reader.take(MAX_HEADERS as u64);
+    loop {
+        line.clear();
+        if headers.read_until(b'\n', &mut line)? == 0 || line == b"\r\n" || line == b"\n" {
+            return Ok(Ok(request_line));
+        }
+        if headers.limit() == 0 {
+            return Ok(Err((
+                "431 Request Header Fields Too Large",
+                "The headers are too long",
+            )));
+        }
+    }
+}
+
+fn respond(chains: &[Vec<Hop>], stream: TcpStream) -> std::io::Result<()> {
+    // A client which never sends anything shouldn't tie up a thread forever
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    let response = match read_request(&stream)? {
+        Ok(request_line) => {
+            let mut parts = request_line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("GET"), Some(target)) => route(chains, target),
+                _ => Response::error("405 Method Not Allowed", "Only GET is supported"),
+            }
+        }
+        Err((status, message)) => Response::error(status, message),
+    };
+    write!(
+        &stream,
+        "HTTP/1.0 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )
+}
+
+// Serve the chains until the program is stopped
+pub fn serve(addr: &str, chains: Vec<Vec<Hop>>) -> Result<()> {
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("Could not listen on {}", addr))?;
+    info!(
+        "Serving {} chains on http://{}/ Press Ctrl-C to stop",
+        chains.len(),
+        addr
+    );
+    let chains = Arc::new(chains);
+    for stream in listener.incoming().flatten() {
+        let chains = chains.clone();
+        std::thread::spawn(move || {
+            // The client going away isn't our problem
+            let _ = respond(&chains, stream);
+        });
+    }
+    Ok(())
+}
+
+const INDEX_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Place in boundary chains</title>
+<style>
+body { font-family: sans-serif; margin: 1em 2em; }
+table { border-collapse: collapse; margin-top: 1em; }
+td, th { border-bottom: 1px solid #ddd; padding: 0.2em 0.6em; text-align: left; }
+#chain { margin-top: 1em; }
+</style>
+</head>
+<body>
+<h1>Place in boundary chains</h1>
+<form id="filter">
+Length from <input name="min_length" size="4"> to <input name="max_length" size="4">
+Country <input name="country" size="3">
+<button>Filter</button>
+</form>
+<form id="search">
+Place or boundary name <input name="name">
+<button>Search</button>
+</form>
+<table><thead><tr><th>Chain</th><th>Length</th><th>From</th><th>To</th><th>Countries</th><th></th></tr></thead>
+<tbody id="chains"></tbody></table>
+<div id="chain"></div>
+<script>
+function text(s) { var d = document.createElement("div"); d.textContent = s; return d.innerHTML; }
+function show(chains) {
+  document.getElementById("chains").innerHTML = chains.map(function (c) {
+    return "<tr><td><a href='#' onclick='return chain(" + c.chain_id + ")'>" + c.chain_id + "</a></td><td>" + c.length +
+      "</td><td>" + text(c.start) + "</td><td>" + text(c.end) + "</td><td>" + text(c.countries.join(", ")) +
+      "</td><td><a href='api/chains/" + c.chain_id + ".geojson'>GeoJSON</a></td></tr>";
+  }).join("");
+}
+function chain(id) {
+  fetch("api/chains/" + id).then(function (r) { return r.json(); }).then(function (c) {
+    document.getElementById("chain").innerHTML = "<h2>Chain " + c.chain_id + ", " + c.length + " places</h2><ol>" +
+      c.hops.map(function (h) {
+        return "<li><a href='" + h.place_url + "'>" + text(h.place_name) + "</a> is in <a href='" + h.boundary_url + "'>" +
+          text(h.boundary_name) + "</a></li>";
+      }).join("") + "</ol>";
+  });
+  return false;
+}
+function load(url, key) {
+  fetch(url).then(function (r) { return r.json(); }).then(function (j) { show(j[key] || []); });
+}
+document.getElementById("filter").onsubmit = function (e) {
+  e.preventDefault();
+  load("api/chains?" + new URLSearchParams(new FormData(e.target)), "chains");
+};
+document.getElementById("search").onsubmit = function (e) {
+  e.preventDefault();
+  load("api/search?" + new URLSearchParams(new FormData(e.target)), "matches");
+};
+load("api/chains", "chains");
+</script>
+</body>
+</html>
+"#;
@@ -0,0 +1,114 @@
+// SIGUSR1 asks a running search for a status report, without stopping it. e.g. `pkill -USR1
+// x-in-y` to check in on a run on a headless server.
+//
+// SIGINT (Ctrl-C), SIGTERM (e.g. systemd stopping it) and SIGHUP (the terminal closing) stop the
+// search, which then writes out the chains it has found so far. A second one stops straight away,
+// without writing anything.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static STOP: AtomicBool = AtomicBool::new(false);
+// The signal which set STOP, 0 for none
+#[cfg(unix)]
+static STOP_SIGNAL: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+// The --dashboard is on the screen, so put the terminal back before stopping straight away
+static DASHBOARD_SHOWN: AtomicBool = AtomicBool::new(false);
+// None = print it
+static REPORT_FILE: OnceLock<Option<String>> = OnceLock::new();
+
+#[cfg(unix)]
+extern "C" fn handle_usr1(_: libc::c_int) {
+    // Only async-signal-safe things can happen here, the search loop writes the report
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+pub fn install_status_handler(report_file: Option<String>) {
+    REPORT_FILE.get_or_init(|| report_file);
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(
+            libc::SIGUSR1,
+            handle_usr1 as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn handle_stop(signal: libc::c_int) {
+    if STOP.swap(true, Ordering::SeqCst) {
+        const RESTORE: &[u8] = b"\x1b[?25h\x1b[?1049l";
+        // write & _exit are async-signal-safe, unlike println & std::process::exit
+        unsafe {
+            if DASHBOARD_SHOWN.load(Ordering::SeqCst) {
+                libc::write(1, RESTORE.as_ptr() as *const libc::c_void, RESTORE.len());
+            }
+            libc::_exit(128 + signal);
+        }
+    }
+    STOP_SIGNAL.store(signal, Ordering::SeqCst);
+}
+
+// The flag which is set when the search should stop, e.g. for `ChainSearch::stop_when`
+pub fn install_stop_handler() -> &'static AtomicBool {
+    #[cfg(unix)]
+    unsafe {
+        for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+            libc::signal(
+                signal,
+                handle_stop as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+    }
+    // There are no signals in a browser
+    #[cfg(not(any(unix, target_arch = "wasm32")))]
+    ctrlc::set_handler(|| {
+        if STOP.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    })
+    .expect("Error setting Ctrl-C handler");
+    &STOP
+}
+
+// Which signal stopped the search, if one did
+pub fn stop_signal_name() -> Option<&'static str> {
+    #[cfg(unix)]
+    match STOP_SIGNAL.load(Ordering::SeqCst) {
+        libc::SIGINT => Some("Ctrl-C (SIGINT)"),
+        libc::SIGTERM => Some("SIGTERM"),
+        libc::SIGHUP => Some("SIGHUP"),
+        _ => None,
+    }
+    #[cfg(not(unix))]
+    STOP.load(Ordering::SeqCst).then_some("Ctrl-C")
+}
+
+pub fn set_dashboard_shown(shown: bool) {
+    DASHBOARD_SHOWN.store(shown, Ordering::SeqCst);
+}
+
+// Has a report been asked for since last time?
+pub fn status_requested() -> bool {
+    REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+pub fn write_status_report(report: &str) -> Result<()> {
+    match REPORT_FILE.get().and_then(Option::as_deref) {
+        None => {
+            println!();
+            print!("{}", report);
+            std::io::stdout().flush()?;
+        }
+        Some(filename) => {
+            std::fs::write(filename, report)
+                .with_context(|| format!("Could not write status report {}", filename))?;
+            info!("Wrote a status report to {}", filename);
+        }
+    }
+    Ok(())
+}
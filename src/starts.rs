@@ -0,0 +1,128 @@
+// Which records the main search starts chains from (--starts, --start-bbox). After the first
+// place, a chain can go anywhere. Searching from a few places rather than every place on the
+// planet is much quicker.
+
+use std::collections::HashSet;
+
+use anyhow::{bail, Context, Result};
+
+use crate::options::{self, Options};
+use crate::record::{OsmId, Record};
+
+#[derive(Debug, Default)]
+pub struct StartFilter {
+    // From the --starts file. A place is a start if its OSM id or name is listed.
+    seed_ids: HashSet<OsmId>,
+    seed_names: HashSet<String>,
+    seeds: bool,
+    // --start-bbox, [min_lon, min_lat, max_lon, max_lat] like GeoJSON
+    bbox: Option<[f64; 4]>,
+}
+
+// e.g. "-10.7,51.4,-5.4,55.4". min_lon can be more than max_lon, for a box across 180°.
+pub fn parse_bbox(s: &str) -> Result<[f64; 4]> {
+    let invalid = || {
+        format!(
+            "Invalid bounding box {:?}, it should be like -10.7,51.4,-5.4,55.4 (min_lon,min_lat,max_lon,max_lat)",
+            s
+        )
+    };
+    let coords = s
+        .split(',')
+        .map(|c| c.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .with_context(invalid)?;
+    let [min_lon, min_lat, max_lon, max_lat] = coords[..] else {
+        bail!(invalid());
+    };
+    if min_lat > max_lat
+        || !(-90. ..=90.).contains(&min_lat)
+        || !(-90. ..=90.).contains(&max_lat)
+        || !(-180. ..=180.).contains(&min_lon)
+        || !(-180. ..=180.).contains(&max_lon)
+    {
+        bail!(invalid());
+    }
+    Ok([min_lon, min_lat, max_lon, max_lat])
+}
+
+impl StartFilter {
+    // None if chains can start anywhere
+    pub fn new(options: &Options) -> Result<Option<StartFilter>> {
+        if options.starts.is_none() && options.start_bbox.is_none() {
+            return Ok(None);
+        }
+        let mut filter = StartFilter {
+            bbox: options.start_bbox,
+            ..Default::default()
+        };
+        if let Some(bbox) = &filter.bbox {
+            info!(
+                "Only starting chains from places in {}",
+                bbox.map(|c| c.to_string()).join(",")
+            );
+        }
+        let Some(filename) = &options.starts else {
+            return Ok(Some(filter));
+        };
+        filter.seeds = true;
+        // One OSM id (n123 or node/123) or name per line. # starts a comment.
+        let text = std::fs::read_to_string(filename)
+            .with_context(|| format!("Could not read {}", filename))?;
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match options::parse_osm_id(line) {
+                Ok(id) => {
+                    filter.seed_ids.insert(id);
+                }
+                Err(_) => {
+                    filter.seed_names.insert(line.to_string());
+                }
+            }
+        }
+        info!(
+            "Only starting chains from the {} places & {} names in {}",
+            filter.seed_ids.len(),
+            filter.seed_names.len(),
+            filename
+        );
+        Ok(Some(filter))
+    }
+
+    pub fn allows(&self, record: &Record) -> bool {
+        let seeded = !self.seeds
+            || self.seed_ids.contains(&record.place_key())
+            || self.seed_names.contains(record.place_name.as_ref());
+        seeded
+            && self
+                .bbox
+                .is_none_or(|[min_lon, min_lat, max_lon, max_lat]| {
+                    let lon = record.place_lon;
+                    let in_lon = if min_lon <= max_lon {
+                        (min_lon..=max_lon).contains(&lon)
+                    } else {
+                        lon >= min_lon || lon <= max_lon
+                    };
+                    in_lon && (min_lat..=max_lat).contains(&record.place_lat)
+                })
+    }
+
+    // The records chains can start from
+    pub fn starts<'a>(&self, records: &[&'a Record]) -> Vec<&'a Record> {
+        let starts: Vec<&Record> = records.iter().filter(|r| self.allows(r)).copied().collect();
+        let found: HashSet<OsmId> = starts.iter().map(|r| r.place_key()).collect();
+        let missing = self.seed_ids.difference(&found).count();
+        if missing > 0 {
+            warn!(
+                "{} of the --starts places aren't in any (remaining) boundary, so no chain can start there",
+                missing
+            );
+        }
+        if starts.is_empty() {
+            warn!("No place matches --starts or --start-bbox, so there won't be any chains");
+        }
+        starts
+    }
+}
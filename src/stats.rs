@@ -0,0 +1,228 @@
+// Numbers about the whole run, for --stats-out, so runs (e.g. each month) can be compared
+// without scraping the log.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+
+use crate::chain::Chain;
+use crate::distance;
+use crate::json::{obj, Value};
+use crate::record::{OsmId, Record};
+use crate::search::SearchStats;
+
+#[derive(Debug)]
+pub struct FilterStats {
+    pub name: &'static str,
+    pub places_removed: usize,
+    pub records_removed: usize,
+    pub records_before: usize,
+}
+
+#[derive(Debug)]
+pub struct RunStats {
+    started: Instant,
+    pub num_records: usize,
+    pub num_duplicates: usize,
+    pub num_wrong_boundary_type: usize,
+    pub num_parse_errors: usize,
+    pub num_unknown_place_tags: usize,
+    pub filters: Vec<FilterStats>,
+    pub search: SearchStats,
+    pub search_s: f64,
+    pub searched_places: usize,
+    pub searched_records: usize,
+    // chain length → number of finished chains
+    pub totals_per_len: BTreeMap<usize, usize>,
+    // The total distance of each chain (of 2 or more), sorted
+    chain_distances_m: Vec<f64>,
+}
+
+// (places, records)
+pub fn count(points_in_boundary: &HashMap<OsmId, Vec<Record>>) -> (usize, usize) {
+    (
+        points_in_boundary.len(),
+        points_in_boundary.values().map(|recs| recs.len()).sum(),
+    )
+}
+
+impl Default for RunStats {
+    fn default() -> RunStats {
+        RunStats::new()
+    }
+}
+
+impl RunStats {
+    pub fn new() -> RunStats {
+        RunStats {
+            started: Instant::now(),
+            num_records: 0,
+            num_duplicates: 0,
+            num_wrong_boundary_type: 0,
+            num_parse_errors: 0,
+            num_unknown_place_tags: 0,
+            filters: Vec::new(),
+            search: SearchStats::default(),
+            search_s: 0.,
+            searched_places: 0,
+            searched_records: 0,
+            totals_per_len: BTreeMap::new(),
+            chain_distances_m: Vec::new(),
+        }
+    }
+
+    // Record what a filter did, from the (places, records) before & after
+    pub fn add_filter(
+        &mut self,
+        name: &'static str,
+        before: (usize, usize),
+        after: (usize, usize),
+    ) {
+        self.filters.push(FilterStats {
+            name,
+            places_removed: before.0 - after.0,
+            records_removed: before.1 - after.1,
+            records_before: before.1,
+        });
+    }
+
+    pub fn add_chains(&mut self, chains: &[Chain]) {
+        self.chain_distances_m = chains
+            .iter()
+            .map(|chain| chain.total_distance_m())
+            .collect();
+        self.chain_distances_m.sort_by(|a, b| a.total_cmp(b));
+    }
+
+    fn distances_json(&self) -> Value {
+        let d = &self.chain_distances_m;
+        if d.is_empty() {
+            return obj(vec![("count", 0usize.into())]);
+        }
+        // nearest rank
+        let percentile =
+            |p: f64| d[((p / 100. * d.len() as f64).ceil() as usize).clamp(1, d.len()) - 1];
+        obj(vec![
+            ("count", d.len().into()),
+            ("min", d[0].round().into()),
+            ("p25", percentile(25.).round().into()),
+            ("median", percentile(50.).round().into()),
+            ("p75", percentile(75.).round().into()),
+            ("p90", percentile(90.).round().into()),
+            ("max", d[d.len() - 1].round().into()),
+            (
+                "mean",
+                (d.iter().sum::<f64>() / d.len() as f64).round().into(),
+            ),
+        ])
+    }
+
+    pub fn to_json(&self) -> Value {
+        let percent = |part: usize, whole: usize| {
+            if whole == 0 {
+                0.
+            } else {
+                (part as f64 / whole as f64 * 1000.).round() / 10.
+            }
+        };
+        obj(vec![
+            (
+                "version",
+                option_env!("CARGO_PKG_VERSION").unwrap_or("").into(),
+            ),
+            (
+                "runtime_s",
+                ((self.started.elapsed().as_secs_f64() * 10.).round() / 10.).into(),
+            ),
+            (
+                "input",
+                obj(vec![
+                    ("records", self.num_records.into()),
+                    ("duplicates", self.num_duplicates.into()),
+                    ("wrong_boundary_type", self.num_wrong_boundary_type.into()),
+                    ("parse_errors", self.num_parse_errors.into()),
+                    ("unknown_place_tags", self.num_unknown_place_tags.into()),
+                ]),
+            ),
+            (
+                "filters",
+                Value::Array(
+                    self.filters
+                        .iter()
+                        .map(|f| {
+                            obj(vec![
+                                ("name", f.name.into()),
+                                ("places_removed", f.places_removed.into()),
+                                ("records_removed", f.records_removed.into()),
+                                (
+                                    "records_removed_percent",
+                                    percent(f.records_removed, f.records_before).into(),
+                                ),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "search",
+                obj(vec![
+                    ("places", self.searched_places.into()),
+                    ("records", self.searched_records.into()),
+                    ("steps", self.search.steps.into()),
+                    ("peak_frontier", self.search.peak_frontier.into()),
+                    ("interrupted", self.search.interrupted.into()),
+                    ("runtime_s", ((self.search_s * 10.).round() / 10.).into()),
+                    ("memory", self.search.memory.to_json()),
+                    (
+                        "memory_cleanups",
+                        Value::Array(
+                            self.search
+                                .memory_cleanups
+                                .iter()
+                                .map(|c| {
+                                    obj(vec![
+                                        ("step", c.step.into()),
+                                        ("intermediate_before", c.intermediate_before.into()),
+                                        ("removed_short", c.removed_short.into()),
+                                        ("removed_by_failsafe", c.removed_by_failsafe.into()),
+                                        ("intermediate_after", c.intermediate_after.into()),
+                                    ])
+                                })
+                                .collect(),
+                        ),
+                    ),
+                ]),
+            ),
+            (
+                "chains",
+                obj(vec![
+                    ("total", self.totals_per_len.values().sum::<usize>().into()),
+                    (
+                        "lengths",
+                        Value::Object(
+                            self.totals_per_len
+                                .iter()
+                                .map(|(len, total)| (len.to_string(), (*total).into()))
+                                .collect(),
+                        ),
+                    ),
+                    ("distance_m", self.distances_json()),
+                ]),
+            ),
+            (
+                "distance",
+                obj(vec![
+                    ("model", distance::model().name().into()),
+                    // Worked out by the slower way, see distance.rs
+                    ("not_converged", distance::num_not_converged().into()),
+                ]),
+            ),
+        ])
+    }
+
+    pub fn write(&self, filename: &str) -> Result<()> {
+        std::fs::write(filename, format!("{}\n", self.to_json()))
+            .with_context(|| format!("Could not write {}", filename))
+    }
+}
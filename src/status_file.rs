@@ -0,0 +1,150 @@
+// A JSON file (--status-file) which is rewritten every --status-interval seconds during the
+// search, with how far along it is, the parameters, and the longest chains so far. For wrapper
+// scripts, which shouldn't have to parse what's printed.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+
+use crate::chain::Chain;
+use crate::json::{obj, Value};
+use crate::output::ndjson::chain_json;
+use crate::progress::Status;
+use crate::record::Record;
+
+const NUM_CHAINS: usize = 5;
+
+struct Config {
+    filename: String,
+    interval: Duration,
+    parameters: Value,
+    started: SystemTime,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+pub fn configure(filename: &str, interval: Duration, parameters: Value) {
+    CONFIG.get_or_init(|| Config {
+        filename: filename.to_string(),
+        interval,
+        parameters,
+        started: SystemTime::now(),
+    });
+}
+
+// (year, month, day) of a number of days since 1970-01-01, Howard Hinnant's civil_from_days
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// e.g. 2024-05-01T12:34:56Z
+pub fn iso8601(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        (secs % 86_400) / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+pub struct StatusFile {
+    config: &'static Config,
+    last_written: Instant,
+}
+
+impl StatusFile {
+    // None if there's no --status-file
+    pub fn new() -> Option<StatusFile> {
+        Some(StatusFile {
+            config: CONFIG.get()?,
+            last_written: Instant::now(),
+        })
+    }
+
+    // Write it, if it's been long enough since last time
+    pub fn update(
+        &mut self,
+        status: &Status,
+        finished_chains: &HashMap<&Record, Vec<&Record>>,
+    ) -> Result<()> {
+        if self.last_written.elapsed() < self.config.interval {
+            return Ok(());
+        }
+        self.write("searching", status, finished_chains)
+    }
+
+    // state: searching, finished or interrupted
+    pub fn write(
+        &mut self,
+        state: &str,
+        status: &Status,
+        finished_chains: &HashMap<&Record, Vec<&Record>>,
+    ) -> Result<()> {
+        self.last_written = Instant::now();
+        let mut longest: Vec<Chain> = finished_chains
+            .values()
+            .filter(|chain| chain.len() > 1)
+            .map(|chain| Chain::new(chain.clone()))
+            .collect();
+        longest.sort_by_key(|chain| -(chain.len() as isize));
+        longest.truncate(NUM_CHAINS);
+
+        let now = SystemTime::now();
+        let config = self.config;
+        let json = obj(vec![
+            ("state", state.into()),
+            ("started", iso8601(config.started).into()),
+            ("updated", iso8601(now).into()),
+            (
+                "elapsed_s",
+                now.duration_since(config.started)
+                    .unwrap_or_default()
+                    .as_secs()
+                    .into(),
+            ),
+            ("parameters", config.parameters.clone()),
+            (
+                "progress",
+                obj(vec![
+                    ("steps", status.steps.into()),
+                    ("frontier", status.frontier.into()),
+                    ("finished_chains", status.finished.into()),
+                    ("longest_chain", status.longest.into()),
+                    ("memory_cleanups", status.memory_cleanups.into()),
+                    ("starts_left", status.starts_left.into()),
+                ]),
+            ),
+            (
+                "longest_chains",
+                Value::Array(longest.iter().map(chain_json).collect()),
+            ),
+        ]);
+
+        // Write then rename, so a script never reads a half written file
+        let tmp = format!("{}.tmp", config.filename);
+        std::fs::write(&tmp, format!("{}\n", json))
+            .with_context(|| format!("Could not write {}", tmp))?;
+        std::fs::rename(&tmp, &config.filename)
+            .with_context(|| format!("Could not write {}", config.filename))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+// `sweep --config-matrix sweep.toml`: load the input once, then search it with every combination
+// of some search options, and write how each did (longest chain, time, memory) as a table. For
+// tuning --max-intermediate, --prune-slack, --score etc. without a long run for each.
+//
+// The matrix is a small part of TOML: each line is an option (without the `--`) and one value or
+// a list of them, e.g.
+//
+//     score = ["distance", "compact"]
+//     prune-slack = [5, 10, 20]
+//     max-intermediate = 2000000
+//
+// The other options come from the command line, as usual.
+
+use std::io::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use separator::Separatable;
+
+use crate::options::{Options, FLAGS};
+use crate::record::Record;
+use crate::{metrics, signals, timeline};
+
+// Only these change the search, rather than which records are loaded
+const SWEEP_FLAGS: &[&str] = &[
+    "--score",
+    "--place-type-weights",
+    "--max-chain-len",
+    "--max-intermediate",
+    "--prune-slack",
+    "--memory-limit",
+    "--failsafe",
+    "--distinct-place-types",
+    "--within-country",
+    "--boundary-admin-at-least",
+];
+
+// A value in the matrix, with its quotes (& escapes) removed
+fn value(s: &str) -> Result<String> {
+    let s = s.trim();
+    if let Some(quoted) = s.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if chars.as_str().trim().is_empty() => return Ok(out),
+                '\\' => out.push(chars.next().context("Unfinished \\ escape")?),
+                c => out.push(c),
+            }
+        }
+        bail!("Unclosed string {}", s);
+    }
+    if let Some(quoted) = s.strip_prefix('\'') {
+        return quoted
+            .strip_suffix('\'')
+            .map(str::to_string)
+            .with_context(|| format!("Unclosed string {}", s));
+    }
+    if s.is_empty() || s.contains(char::is_whitespace) {
+        bail!("Invalid value {:?}, strings need quotes", s);
+    }
+    Ok(s.to_string())
+}
+
+// The line without a `# comment`, which can't be in a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+// The items of a `[a, b, c]` list, split on the commas outside strings
+fn list_items(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in list.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ',') => {
+                items.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&list[start..]);
+    items.retain(|item| !item.trim().is_empty());
+    items
+}
+
+// Each option in the matrix, and its values
+fn parse_matrix(text: &str) -> Result<Vec<(&'static str, Vec<String>)>> {
+    let mut matrix: Vec<(&'static str, Vec<String>)> = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parsed = (|| -> Result<(&'static str, Vec<String>)> {
+            if line.starts_with('[') {
+                bail!("Tables aren't supported, only option = value lines");
+            }
+            let (key, values) = line
+                .split_once('=')
+                .context("Should be like option = value")?;
+            let key = key.trim().trim_matches('"');
+            let flag = FLAGS
+                .iter()
+                .find(|f| f.name.strip_prefix("--") == Some(key))
+                .with_context(|| format!("Unknown option {}", key))?;
+            if !SWEEP_FLAGS.contains(&flag.name) {
+                bail!(
+                    "{} can't be swept, only {}",
+                    key,
+                    SWEEP_FLAGS
+                        .iter()
+                        .map(|f| &f[2..])
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            let values = values.trim();
+            let values = match values.strip_prefix('[') {
+                Some(list) => list_items(list.strip_suffix(']').context("Unclosed list")?)
+                    .into_iter()
+                    .map(value)
+                    .collect::<Result<Vec<String>>>()?,
+                None => vec![value(values)?],
+            };
+            if values.is_empty() {
+                bail!("{} has no values", key);
+            }
+            Ok((flag.name, values))
+        })()
+        .with_context(|| format!("Line {} of the --config-matrix: {}", line_no + 1, line))?;
+        if matrix.iter().any(|(name, _)| *name == parsed.0) {
+            bail!("{} is in the --config-matrix twice", &parsed.0[2..]);
+        }
+        matrix.push(parsed);
+    }
+    Ok(matrix)
+}
+
+// Every combination of the values
+fn configurations(matrix: &[(&'static str, Vec<String>)]) -> Vec<Vec<(&'static str, String)>> {
+    let mut configs = vec![Vec::new()];
+    for (flag, values) in matrix {
+        configs = configs
+            .into_iter()
+            .flat_map(|config| {
+                values.iter().map(move |v| {
+                    let mut config = config.clone();
+                    config.push((*flag, v.clone()));
+                    config
+                })
+            })
+            .collect();
+    }
+    configs
+}
+
+fn label(config: &[(&str, String)]) -> String {
+    if config.is_empty() {
+        return "default".to_string();
+    }
+    config
+        .iter()
+        .map(|(flag, value)| format!("{}={}", &flag[2..], value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+struct Row {
+    config: String,
+    longest: usize,
+    num_chains: usize,
+    search_s: f64,
+    steps: usize,
+    peak_frontier: usize,
+    // Estimated, of the intermediate & finished chains
+    chains_mb: usize,
+    // The process's peak RSS (VmHWM) when this configuration finished. It never goes down, so
+    // it's the most of this & every configuration before it, plus the records.
+    peak_rss_so_far_mb: Option<u64>,
+    interrupted: bool,
+}
+
+fn write_csv(filename: &str, rows: &[Row]) -> Result<()> {
+    let mut wtr = csv::Writer::from_path(filename)
+        .with_context(|| format!("Could not create {}", filename))?;
+    wtr.write_record([
+        "config",
+        "longest_chain",
+        "chains",
+        "search_s",
+        "steps",
+        "peak_frontier",
+        "chains_mb",
+        "peak_rss_so_far_mb",
+        "interrupted",
+    ])?;
+    for row in rows {
+        wtr.write_record([
+            row.config.clone(),
+            row.longest.to_string(),
+            row.num_chains.to_string(),
+            format!("{:.2}", row.search_s),
+            row.steps.to_string(),
+            row.peak_frontier.to_string(),
+            row.chains_mb.to_string(),
+            row.peak_rss_so_far_mb
+                .map_or(String::new(), |mb| mb.to_string()),
+            row.interrupted.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_markdown(filename: &str, rows: &[Row]) -> Result<()> {
+    let mut file = std::io::BufWriter::new(
+        std::fs::File::create(filename)
+            .with_context(|| format!("Could not create {}", filename))?,
+    );
+    writeln!(
+        file,
+        "| Configuration | Longest chain | Chains | Search time | Steps | Peak frontier | Chains memory | Peak RSS so far |"
+    )?;
+    writeln!(file, "|---|--:|--:|--:|--:|--:|--:|--:|")?;
+    for row in rows {
+        writeln!(
+            file,
+            "| `{}` | {}{} | {} | {:.2}s | {} | {} | {} MB | {} |",
+            row.config,
+            row.longest,
+            if row.interrupted { " (stopped)" } else { "" },
+            row.num_chains.separated_string(),
+            row.search_s,
+            row.steps.separated_string(),
+            row.peak_frontier.separated_string(),
+            row.chains_mb.separated_string(),
+            row.peak_rss_so_far_mb.map_or("?".to_string(), |mb| format!(
+                "{} MB",
+                mb.separated_string()
+            ))
+        )?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    let matrix_filename = options.config_matrix.as_deref().unwrap();
+    let text = std::fs::read_to_string(matrix_filename)
+        .with_context(|| format!("Could not read {}", matrix_filename))?;
+    let configs = configurations(&parse_matrix(&text)?);
+    // Each configuration is the command line, with its options on the end
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config_options = configs
+        .iter()
+        .map(|config| {
+            let extra = config
+                .iter()
+                .flat_map(|(flag, value)| [flag.to_string(), value.clone()]);
+            Options::parse(args.iter().cloned().chain(extra))
+                .with_context(|| format!("Invalid configuration {}", label(config)))
+        })
+        .collect::<Result<Vec<Options>>>()?;
+
+    let input_filename = &options.input_filenames[0];
+    phase!("Reading {}", input_filename);
+    let points_in_boundary = timeline::load(input_filename, options)?;
+    let records: Vec<&Record> = points_in_boundary
+        .values()
+        .flat_map(|recs| recs.iter())
+        .collect();
+    info!(
+        "{} records, running {} configurations",
+        records.len().separated_string(),
+        configs.len().separated_string()
+    );
+
+    let stop = signals::install_stop_handler();
+    let mut rows = Vec::new();
+    for (i, (config, config_options)) in configs.iter().zip(&config_options).enumerate() {
+        phase!(
+            "Configuration {} of {}: {}",
+            i + 1,
+            configs.len(),
+            label(config)
+        );
+        let started = Instant::now();
+        // --max-runtime is for each configuration. This stops when it's up, or on Ctrl-C.
+        let config_stop: &AtomicBool = match config_options.max_runtime {
+            None => stop,
+            Some(max_runtime) => {
+                let config_stop: &'static AtomicBool = Box::leak(Box::new(AtomicBool::new(false)));
+                std::thread::spawn(move || {
+                    while !stop.load(Ordering::SeqCst) && started.elapsed() < max_runtime {
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                    config_stop.store(true, Ordering::SeqCst);
+                });
+                config_stop
+            }
+        };
+        let (chains, search_stats) = config_options
+            .chain_search(&records, true)
+            .stop_when(config_stop)
+            .run();
+        let row = Row {
+            config: label(config),
+            longest: chains.values().map(|c| c.len()).max().unwrap_or(0),
+            num_chains: chains.values().filter(|c| c.len() > 1).count(),
+            search_s: started.elapsed().as_secs_f64(),
+            steps: search_stats.steps,
+            peak_frontier: search_stats.peak_frontier,
+            chains_mb: (search_stats.memory.frontier + search_stats.memory.finished_chains) >> 20,
+            peak_rss_so_far_mb: metrics::peak_rss_bytes().map(|b| b >> 20),
+            interrupted: search_stats.interrupted,
+        };
+        summary!(
+            "{}: longest chain {}, {:.2}s",
+            row.config,
+            row.longest,
+            row.search_s
+        );
+        rows.push(row);
+        if let Some(signal) = signals::stop_signal_name() {
+            warn!(
+                "Stopped by {}, writing out the {} configurations run so far",
+                signal,
+                rows.len()
+            );
+            break;
+        }
+    }
+
+    let output_filename = &options.output_filename;
+    if output_filename.ends_with(".md") {
+        write_markdown(output_filename, &rows)?;
+    } else {
+        write_csv(output_filename, &rows)?;
+    }
+    summary!(
+        "Wrote the comparison of {} configurations to {}",
+        rows.len(),
+        output_filename
+    );
+    Ok(())
+}
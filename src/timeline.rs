@@ -0,0 +1,283 @@
+// How the chains change over a series of dated exports, e.g. as OSM coverage improves
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::prelude::*;
+
+use anyhow::{Context, Result};
+use separator::Separatable;
+
+use crate::chain::ChainStats;
+use crate::filters;
+use crate::input;
+use crate::json::{obj, Value};
+use crate::options::Options;
+use crate::record::{self, OsmId, PlaceTypeUse, Record};
+
+// How many of the longest chains to keep for each date
+const NUM_TOP_CHAINS: usize = 10;
+
+struct TopChain {
+    length: usize,
+    distance_km: f64,
+    // The place names, then the last boundary name
+    names: Vec<String>,
+}
+
+struct Entry {
+    date: String,
+    filename: String,
+    num_records: usize,
+    // chain length → number of chains, not counting the 1 element ones
+    lengths: BTreeMap<usize, usize>,
+    top_chains: Vec<TopChain>,
+}
+
+// The date in a filename, e.g. `planet-2023-05-01.csv.gz`, `planet-20230501.csv` or
+// `planet-230501.csv` (like the planet files). Otherwise the filename itself.
+pub fn date_from_filename(filename: &str) -> String {
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    let bytes = name.as_bytes();
+    let digit = |i: usize| bytes.get(i).is_some_and(|b| b.is_ascii_digit());
+    let digits = |from: usize, len: usize| (from..from + len).all(digit);
+    for start in 0..bytes.len() {
+        if start > 0 && digit(start - 1) {
+            continue;
+        }
+        if digits(start, 4)
+            && bytes.get(start + 4) == Some(&b'-')
+            && digits(start + 5, 2)
+            && bytes.get(start + 7) == Some(&b'-')
+            && digits(start + 8, 2)
+        {
+            return name[start..start + 10].to_string();
+        }
+        let run = (start..bytes.len()).take_while(|i| digit(*i)).count();
+        if run == 8 {
+            let d = &name[start..start + 8];
+            return format!("{}-{}-{}", &d[..4], &d[4..6], &d[6..]);
+        }
+        if run == 6 {
+            let d = &name[start..start + 6];
+            return format!("20{}-{}-{}", &d[..2], &d[2..4], &d[4..]);
+        }
+    }
+    name.to_string()
+}
+
+// The records in one input, with the same filters as a normal run (except the unknown place tags,
+// which are never used)
+pub fn load(filename: &str, options: &Options) -> Result<HashMap<OsmId, Vec<Record>>> {
+    let mut points_in_boundary = HashMap::new();
+    let mut rdr = input::csv_reader(filename, &options.csv_format)?;
+    for result in rdr.deserialize() {
+        let record: Record = match result {
+            Ok(r) => r,
+            Err(e) if options.strict => return Err(e.into()),
+            Err(_) => continue,
+        };
+        if record.place_name.is_empty()
+            || record.boundary_name.is_empty()
+            || !options.boundary_types.allows(record.boundary_type())
+            || record::place_type_use(&record.place_type) != PlaceTypeUse::Use
+        {
+            continue;
+        }
+        input::add_record(&mut points_in_boundary, record);
+    }
+    filters::remove_junk_names(
+        &mut points_in_boundary,
+        options.min_name_length,
+        options.remove_junk_names,
+    );
+    filters::remove_self_contained(
+        &mut points_in_boundary,
+        &options.self_containment,
+        options.fuzzy_distance,
+    );
+    if options.dedup_radius_m > 0. {
+        filters::merge_nearby_duplicates(&mut points_in_boundary, options.dedup_radius_m);
+    }
+    if options.one_boundary_per_name {
+        filters::keep_most_specific_boundary(&mut points_in_boundary);
+    }
+    if options.smallest_boundary {
+        filters::keep_smallest_boundary(&mut points_in_boundary);
+    }
+    if options.require_wikidata {
+        filters::keep_with_wikidata(&mut points_in_boundary);
+    }
+    Ok(points_in_boundary)
+}
+
+fn entry(filename: &str, options: &Options) -> Result<Entry> {
+    let points_in_boundary = load(filename, options)?;
+    let records: Vec<&Record> = points_in_boundary
+        .values()
+        .flat_map(|recs| recs.iter())
+        .collect();
+    let (chains, _) = options.chain_search(&records, false).run();
+    let mut chains: Vec<(Vec<&Record>, f64)> = chains
+        .into_values()
+        .filter(|chain| chain.len() > 1)
+        .map(|chain| {
+            let total_m = ChainStats::new(&chain).total_m;
+            (chain, total_m)
+        })
+        .collect();
+    chains.sort_by(|(a, a_m), (b, b_m)| {
+        b.len()
+            .cmp(&a.len())
+            .then(b_m.total_cmp(a_m))
+            .then_with(|| {
+                if options.deterministic {
+                    a.cmp(b)
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+    });
+
+    let mut lengths = BTreeMap::new();
+    for (chain, _) in chains.iter() {
+        *lengths.entry(chain.len()).or_default() += 1;
+    }
+    let top_chains = chains
+        .iter()
+        .take(NUM_TOP_CHAINS)
+        .map(|(chain, total_m)| TopChain {
+            length: chain.len(),
+            distance_km: (total_m / 1000.).round(),
+            names: chain
+                .iter()
+                .map(|r| r.place_name.to_string())
+                .chain(std::iter::once(
+                    chain[chain.len() - 1].boundary_name.to_string(),
+                ))
+                .collect(),
+        })
+        .collect();
+    Ok(Entry {
+        date: date_from_filename(filename),
+        filename: filename.to_string(),
+        num_records: records.len(),
+        lengths,
+        top_chains,
+    })
+}
+
+fn write_csv(entries: &[Entry], output: impl Write) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(output);
+    wtr.write_record([
+        "date",
+        "filename",
+        "records",
+        "chains",
+        "max_length",
+        "rank",
+        "length",
+        "distance_km",
+        "start",
+        "end",
+    ])?;
+    for e in entries {
+        let max_length = e.lengths.keys().last().copied().unwrap_or(0);
+        for (i, chain) in e.top_chains.iter().enumerate() {
+            wtr.write_record([
+                e.date.as_str(),
+                &e.filename,
+                &e.num_records.to_string(),
+                &e.lengths.values().sum::<usize>().to_string(),
+                &max_length.to_string(),
+                &(i + 1).to_string(),
+                &chain.length.to_string(),
+                &chain.distance_km.to_string(),
+                &chain.names[0],
+                &chain.names[chain.names.len() - 1],
+            ])?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn to_json(entries: &[Entry]) -> Value {
+    Value::Array(
+        entries
+            .iter()
+            .map(|e| {
+                obj(vec![
+                    ("date", (&e.date).into()),
+                    ("filename", (&e.filename).into()),
+                    ("records", e.num_records.into()),
+                    ("chains", e.lengths.values().sum::<usize>().into()),
+                    (
+                        "max_length",
+                        e.lengths.keys().last().copied().unwrap_or(0).into(),
+                    ),
+                    (
+                        "lengths",
+                        Value::Object(
+                            e.lengths
+                                .iter()
+                                .map(|(len, total)| (len.to_string(), (*total).into()))
+                                .collect(),
+                        ),
+                    ),
+                    (
+                        "top_chains",
+                        Value::Array(
+                            e.top_chains
+                                .iter()
+                                .map(|c| {
+                                    obj(vec![
+                                        ("length", c.length.into()),
+                                        ("distance_km", c.distance_km.into()),
+                                        ("names", c.names.clone().into()),
+                                    ])
+                                })
+                                .collect(),
+                        ),
+                    ),
+                ])
+            })
+            .collect(),
+    )
+}
+
+// Search each input on its own, oldest first, and write the timeline as JSON (for a .json
+// filename) or CSV (one row for each of the top chains of each date)
+pub fn run(options: &Options) -> Result<()> {
+    let mut entries = Vec::new();
+    for filename in options.input_filenames.iter() {
+        info!("Searching {}", filename);
+        let e = entry(filename, options)?;
+        info!(
+            "{}: {} records, {} chains, the longest has {} places",
+            e.date,
+            e.num_records.separated_string(),
+            e.lengths.values().sum::<usize>().separated_string(),
+            e.lengths.keys().last().copied().unwrap_or(0)
+        );
+        entries.push(e);
+    }
+    entries.sort_by(|a, b| a.date.cmp(&b.date));
+
+    let filename = &options.output_filename;
+    let file = std::io::BufWriter::new(
+        std::fs::File::create(filename)
+            .with_context(|| format!("Could not create {}", filename))?,
+    );
+    if filename.ends_with(".json") {
+        let mut file = file;
+        writeln!(file, "{}", to_json(&entries))?;
+        file.flush()?;
+    } else {
+        write_csv(&entries, file)?;
+    }
+    info!(
+        "Wrote the timeline of {} inputs to {}",
+        entries.len(),
+        filename
+    );
+    Ok(())
+}
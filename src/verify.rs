@@ -0,0 +1,220 @@
+// Check that the places & boundaries in a results file are still the same in OSM, since the
+// input CSV can be months old by the time chains are published.
+//
+// Uses the OSM API with `curl`, which needs to be installed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+
+use crate::json::{self, Value};
+use crate::record::{osm_type_name, url, OsmId};
+use crate::results;
+
+const OSM_API: &str = "https://api.openstreetmap.org/api/0.6";
+// How many objects to ask the API for at once
+const BATCH_SIZE: usize = 200;
+
+// What the object looked like when the results were made
+#[derive(Debug)]
+struct Expected {
+    name: String,
+    // `place` value for places, `admin_level` for boundaries
+    role: &'static str,
+    tag_value: String,
+    chains: BTreeSet<usize>,
+}
+
+pub struct Problem {
+    pub osm_id: OsmId,
+    pub role: &'static str,
+    pub chains: String,
+    pub problem: &'static str,
+    pub expected: String,
+    pub current: String,
+}
+
+fn read_results(filenames: &[String]) -> Result<BTreeMap<OsmId, Expected>> {
+    let mut expected: BTreeMap<OsmId, Expected> = BTreeMap::new();
+    let mut chain_id = 0;
+    for filename in filenames {
+        for chain in results::read(filename)? {
+            chain_id += 1;
+            for hop in chain {
+                let objects = [
+                    (hop.place, hop.place_name, "place", hop.place_type),
+                    (hop.boundary, hop.boundary_name, "boundary", hop.admin_level),
+                ];
+                for (osm_id, name, role, tag_value) in objects {
+                    expected
+                        .entry(osm_id)
+                        .or_insert(Expected {
+                            name,
+                            role,
+                            tag_value,
+                            chains: BTreeSet::new(),
+                        })
+                        .chains
+                        .insert(chain_id);
+                }
+            }
+        }
+    }
+    Ok(expected)
+}
+
+// (HTTP status, body)
+pub fn http_get(url: &str) -> Result<(u16, String)> {
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--location"])
+        .args([
+            "--user-agent",
+            concat!("x-in-y/", env!("CARGO_PKG_VERSION")),
+        ])
+        .args(["--write-out", "\n%{http_code}"])
+        .arg(url)
+        .stdin(Stdio::null())
+        .output()
+        .context("Could not run curl")?;
+    if !output.status.success() {
+        bail!(
+            "curl {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let body = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = body.rsplit_once('\n').unwrap_or(("", &body));
+    Ok((status.trim().parse()?, body.to_string()))
+}
+
+// The current version of these objects (all of the same type). None when it doesn't exist, or
+// has been deleted.
+fn fetch(osm_type: char, ids: &[u64]) -> Result<BTreeMap<u64, Option<Value>>> {
+    let plural = format!("{}s", osm_type_name(osm_type));
+    let ids_str = ids
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let (status, body) = http_get(&format!(
+        "{}/{}.json?{}={}",
+        OSM_API, plural, plural, ids_str
+    ))?;
+    let mut objects = BTreeMap::new();
+    match status {
+        200 => {
+            let response = json::parse(&body).context("Invalid response from the OSM API")?;
+            for element in response
+                .get("elements")
+                .and_then(Value::as_array)
+                .unwrap_or(&[])
+            {
+                let id = element.get("id").and_then(Value::as_f64).unwrap_or(0.) as u64;
+                let visible = element.get("visible") != Some(&Value::Bool(false));
+                objects.insert(id, Some(element.clone()).filter(|_| visible));
+            }
+        }
+        // One of them doesn't exist, so the whole batch fails. Ask for them one at a time.
+        404 | 410 if ids.len() > 1 => {
+            for id in ids {
+                objects.extend(fetch(osm_type, &[*id])?);
+            }
+        }
+        404 | 410 => {}
+        s => bail!("OSM API returned HTTP {} for {}", s, plural),
+    }
+    for id in ids {
+        objects.entry(*id).or_insert(None);
+    }
+    Ok(objects)
+}
+
+fn tag<'a>(element: &'a Value, key: &str) -> &'a str {
+    element
+        .get("tags")
+        .and_then(|t| t.get(key))
+        .and_then(Value::as_str)
+        .unwrap_or("")
+}
+
+// Everything in these results files which has changed in OSM
+pub fn verify(filenames: &[String]) -> Result<(usize, Vec<Problem>)> {
+    let expected = read_results(filenames)?;
+    info!(
+        "Checking {} places & boundaries against the OSM API",
+        expected.len()
+    );
+    let mut problems = Vec::new();
+    for osm_type in ['n', 'w', 'r'] {
+        let ids: Vec<u64> = expected
+            .keys()
+            .filter(|(t, _)| *t == osm_type)
+            .map(|(_, id)| *id)
+            .collect();
+        for batch in ids.chunks(BATCH_SIZE) {
+            for (id, current) in fetch(osm_type, batch)? {
+                let exp = &expected[&(osm_type, id)];
+                let problem = |problem, expected: &str, current: &str| Problem {
+                    osm_id: (osm_type, id),
+                    role: exp.role,
+                    chains: exp
+                        .chains
+                        .iter()
+                        .map(|c| c.to_string())
+                        .collect::<Vec<_>>()
+                        .join(";"),
+                    problem,
+                    expected: expected.to_string(),
+                    current: current.to_string(),
+                };
+                let current = match current {
+                    None => {
+                        problems.push(problem("deleted", &exp.name, ""));
+                        continue;
+                    }
+                    Some(c) => c,
+                };
+                if tag(&current, "name") != exp.name {
+                    problems.push(problem("renamed", &exp.name, tag(&current, "name")));
+                }
+                let (key, current_value) = match exp.role {
+                    "place" => ("place", tag(&current, "place")),
+                    _ => ("admin_level", tag(&current, "admin_level")),
+                };
+                // `?` is an admin_level we couldn't understand in the first place
+                if exp.tag_value != "?" && current_value != exp.tag_value {
+                    problems.push(problem(
+                        "retagged",
+                        &format!("{}={}", key, exp.tag_value),
+                        &format!("{}={}", key, current_value),
+                    ));
+                }
+            }
+        }
+    }
+    Ok((expected.len(), problems))
+}
+
+pub fn write_csv(filename: &str, problems: &[Problem]) -> Result<()> {
+    let mut wtr = csv::Writer::from_path(filename)
+        .with_context(|| format!("Could not create {}", filename))?;
+    wtr.write_record([
+        "chains", "osm_type", "osm_id", "url", "role", "problem", "expected", "current",
+    ])?;
+    for p in problems {
+        wtr.write_record([
+            p.chains.as_str(),
+            osm_type_name(p.osm_id.0),
+            &p.osm_id.1.to_string(),
+            &url(p.osm_id.0, p.osm_id.1),
+            p.role,
+            p.problem,
+            &p.expected,
+            &p.current,
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
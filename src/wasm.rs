@@ -0,0 +1,237 @@
+// The chain search in a web page, as WebAssembly. The page fetches a dataset (an input CSV, plain
+// or gzipped, best cut down to a region so it's small), loads it, and then searches from the
+// places visitors pick. Built with
+//
+//     cargo rustc --release --lib --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+//
+// which makes target/wasm32-unknown-unknown/release/x_in_y.wasm. There are no files, threads or
+// signals here: bytes are passed in through memory from `x_in_y_alloc`, and each function which
+// returns text (always JSON) puts it in one output buffer, returning its address, with the length
+// from `x_in_y_output_len`. That's only valid until the next call. See `wasm/index.html`.
+
+use std::cell::RefCell;
+use std::io::Read;
+
+use anyhow::{bail, Context, Result};
+use flate2::read::MultiGzDecoder;
+
+use crate::graph::NameGraph;
+use crate::input::Compression;
+use crate::json::{obj, Value};
+use crate::output::record_properties;
+use crate::record::Record;
+use crate::search::{ChainSearch, SearchLimits, SearchObserver, SearchView};
+
+// Bounds on a search, so the page doesn't hang, unless it asks for others
+const DEFAULT_MAX_CHAIN_LEN: usize = 100;
+const DEFAULT_MAX_STEPS: usize = 1_000_000;
+// Places listed by `x_in_y_find`
+const MAX_FOUND: usize = 20;
+// Intermediate chains returned by `x_in_y_search`
+const MAX_SHOWN_STEPS: usize = 1000;
+
+thread_local! {
+    static RECORDS: RefCell<Vec<Record>> = const { RefCell::new(Vec::new()) };
+    static OUTPUT: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+// Put the JSON in the output buffer, or on error, {"error": "…"}
+fn output(result: Result<Value>) -> *const u8 {
+    let json = match result {
+        Ok(value) => value.to_string(),
+        Err(e) => obj(vec![("error", format!("{:#}", e).into())]).to_string(),
+    };
+    OUTPUT.with(|output| {
+        *output.borrow_mut() = json;
+        output.borrow().as_ptr()
+    })
+}
+
+/// The length of the last output
+#[no_mangle]
+pub extern "C" fn x_in_y_output_len() -> usize {
+    OUTPUT.with(|output| output.borrow().len())
+}
+
+/// Room for `len` bytes, to pass in the dataset or a name. Free with `x_in_y_dealloc`.
+#[no_mangle]
+pub extern "C" fn x_in_y_alloc(len: usize) -> *mut u8 {
+    let mut buf = Vec::<u8>::with_capacity(len);
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// # Safety
+/// `ptr` & `len` are from `x_in_y_alloc`, and `ptr` isn't used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn x_in_y_dealloc(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+fn load(data: &[u8]) -> Result<Value> {
+    let reader: Box<dyn Read + '_> = match Compression::detect(data, "") {
+        Compression::None => Box::new(data),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(data)),
+        c => bail!(
+            "The dataset is {:?} compressed, it can only be plain or gzipped",
+            c
+        ),
+    };
+    // Like the command line, rows which can't be parsed are skipped
+    let records: Vec<Record> = csv::Reader::from_reader(reader)
+        .deserialize()
+        .filter_map(|r| r.ok())
+        .collect();
+    let num_records = records.len();
+    RECORDS.with(|r| *r.borrow_mut() = records);
+    Ok(obj(vec![("records", num_records.into())]))
+}
+
+/// Read the dataset, replacing any there was. Returns {"records": number of records}.
+///
+/// # Safety
+/// `len` bytes from `data` can be read.
+#[no_mangle]
+pub unsafe extern "C" fn x_in_y_load(data: *const u8, len: usize) -> *const u8 {
+    output(load(std::slice::from_raw_parts(data, len)))
+}
+
+/// The name graph: {"linkable": names which are both a place & a boundary, "edges": [from, to,
+/// number of records] which can be in a chain}
+#[no_mangle]
+pub extern "C" fn x_in_y_graph() -> *const u8 {
+    RECORDS.with(|records| {
+        let records = records.borrow();
+        let refs: Vec<&Record> = records.iter().collect();
+        let graph = NameGraph::new(&refs);
+        output(Ok(obj(vec![
+            (
+                "linkable",
+                graph
+                    .linkable()
+                    .map(|(name, _)| Value::from(*name))
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            (
+                "edges",
+                graph
+                    .chain_edges()
+                    .map(|(from, to, count)| vec![from.into(), to.into(), Value::from(count)])
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+        ])))
+    })
+}
+
+fn name_arg<'a>(name: *const u8, len: usize) -> Result<&'a str> {
+    std::str::from_utf8(unsafe { std::slice::from_raw_parts(name, len) })
+        .context("The name isn't UTF-8")
+}
+
+fn record_json(i: usize, record: &Record) -> Value {
+    let mut properties = record_properties(record);
+    properties.insert(0, ("index", i.into()));
+    obj(properties)
+}
+
+/// Record `i`, as `x_in_y_find` & `x_in_y_search` return them
+#[no_mangle]
+pub extern "C" fn x_in_y_record(i: usize) -> *const u8 {
+    output(RECORDS.with(|records| match records.borrow().get(i) {
+        Some(record) => Ok(record_json(i, record)),
+        None => Err(anyhow::anyhow!("There is no record {}", i)),
+    }))
+}
+
+/// The records of places whose name starts with `name` (ignoring case), to start a search from.
+///
+/// # Safety
+/// `len` bytes from `name` can be read.
+#[no_mangle]
+pub unsafe extern "C" fn x_in_y_find(name: *const u8, len: usize) -> *const u8 {
+    let found = name_arg(name, len).map(|name| {
+        let name = name.to_lowercase();
+        RECORDS.with(|records| {
+            let found: Vec<Value> = records
+                .borrow()
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| r.place_name.to_lowercase().starts_with(&name))
+                .take(MAX_FOUND)
+                .map(|(i, r)| record_json(i, r))
+                .collect();
+            Value::Array(found)
+        })
+    });
+    output(found)
+}
+
+// Each chain the search is about to extend, to show the chain being built
+struct Building<'v, 'a>(&'v mut Vec<Vec<&'a Record>>);
+
+impl<'a> SearchObserver<'a> for Building<'_, 'a> {
+    fn on_step(&mut self, view: &SearchView<'_, 'a>) {
+        if let Some(next) = view.next {
+            if self.0.len() < MAX_SHOWN_STEPS && self.0.last().map(Vec::as_slice) != Some(next) {
+                self.0.push(next.to_vec());
+            }
+        }
+    }
+}
+
+fn search(start: usize, max_chain_len: usize, max_steps: usize) -> Result<Value> {
+    RECORDS.with(|records| {
+        let records = records.borrow();
+        let Some(start) = records.get(start) else {
+            bail!("There is no record {}", start);
+        };
+        let refs: Vec<&Record> = records.iter().collect();
+        let index = |r: &Record| {
+            (r as *const Record as usize - records.as_ptr() as usize)
+                / std::mem::size_of::<Record>()
+        };
+        let starts = [start];
+        let limits = SearchLimits {
+            max_chain_len: Some(match max_chain_len {
+                0 => DEFAULT_MAX_CHAIN_LEN,
+                n => n,
+            }),
+            max_steps: match max_steps {
+                0 => DEFAULT_MAX_STEPS,
+                n => n,
+            },
+            ..SearchLimits::default()
+        };
+        let mut steps = Vec::new();
+        let (chains, stats) = ChainSearch::new(&refs)
+            .starts(&starts)
+            .limits(limits)
+            .observer(Building(&mut steps))
+            .run();
+        let steps: Vec<Value> = steps
+            .iter()
+            .map(|chain| Value::Array(chain.iter().map(|r| index(r).into()).collect()))
+            .collect();
+        let chain = chains.get(start).map_or(Vec::new(), |chain| {
+            chain.iter().map(|r| record_json(index(r), r)).collect()
+        });
+        Ok(obj(vec![
+            ("chain", chain.into()),
+            ("steps", steps.into()),
+            ("search_steps", stats.steps.into()),
+            ("interrupted", stats.interrupted.into()),
+        ]))
+    })
+}
+
+/// The longest chain from record `start`: {"chain": its records, "steps": the record indexes of
+/// each chain the search extended on the way (the first 1,000), in order, to show it being built,
+/// "search_steps": …, "interrupted": true if `max_steps` ran out}. 0 for `max_chain_len` or
+/// `max_steps` is the default.
+#[no_mangle]
+pub extern "C" fn x_in_y_search(start: usize, max_chain_len: usize, max_steps: usize) -> *const u8 {
+    output(search(start, max_chain_len, max_steps))
+}
@@ -0,0 +1,146 @@
+// Wait for new exports to appear in a directory (--watch), e.g. from a weekly database export
+// job, and run the usual search on each one, with the same options, into a directory per date.
+// Each run is a separate process, so a run which fails or uses lots of memory doesn't take the
+// watcher with it.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+
+use crate::exit::Status;
+use crate::options::{Options, FLAGS};
+use crate::status_file::iso8601;
+use crate::timeline::date_from_filename;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const EXTENSIONS: &[&str] = &[".csv", ".csv.gz", ".csv.zst", ".csv.bz2", ".csv.xz"];
+
+// The flags to run each search with: the same as ours, without the --watch & OUTPUT. The
+// --log-file & --stream go in the dated directory, so each run has its own.
+fn child_args(args: &[String], dated_dir: &Path) -> Vec<String> {
+    let mut child = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") && arg != "-v" && arg != "-q" {
+            // The OUTPUT
+            continue;
+        }
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let takes_value = FLAGS.iter().any(|f| f.name == name && f.value.is_some());
+        let value = match (takes_value, inline_value) {
+            (false, _) => None,
+            (true, Some(v)) => Some(v),
+            (true, None) => args.next().cloned(),
+        };
+        match (name, value) {
+            ("--watch", _) => {}
+            ("--log-file" | "--stream", Some(filename)) => {
+                let basename = Path::new(&filename)
+                    .file_name()
+                    .map_or_else(|| filename.clone().into(), |n| n.to_owned());
+                child.push(name.to_string());
+                child.push(dated_dir.join(basename).to_string_lossy().into_owned());
+            }
+            (name, value) => {
+                child.push(name.to_string());
+                child.extend(value);
+            }
+        }
+    }
+    child
+}
+
+// The export files in the directory, and their sizes
+fn exports(dir: &Path) -> Result<HashMap<PathBuf, u64>> {
+    let mut files = HashMap::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Could not read {:?}", dir))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') || !EXTENSIONS.iter().any(|e| name.ends_with(e)) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.insert(entry.path(), metadata.len());
+        }
+    }
+    Ok(files)
+}
+
+// The date in the filename, or today if there isn't one
+fn export_date(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let date = date_from_filename(&name);
+    if date == name {
+        iso8601(SystemTime::now())[..10].to_string()
+    } else {
+        date
+    }
+}
+
+pub fn run(options: &Options, dir: &str) -> Result<()> {
+    let dir = Path::new(dir);
+    let output = Path::new(&options.output_filename);
+    let output_root = output.parent().unwrap_or(Path::new(""));
+    let output_name = output
+        .file_name()
+        .with_context(|| format!("{} isn't a filename", options.output_filename))?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let exe = std::env::current_exe().context("Could not find this program, to run it")?;
+
+    info!(
+        "Watching {:?} for new exports, every {}s. Results go in {:?}/DATE/{}",
+        dir,
+        POLL_INTERVAL.as_secs(),
+        output_root,
+        output_name.to_string_lossy()
+    );
+    // Files still being written change size between polls
+    let mut last_sizes: HashMap<PathBuf, u64> = HashMap::new();
+    // Runs which failed aren't tried again until restarted
+    let mut failed: HashSet<PathBuf> = HashSet::new();
+    loop {
+        let sizes = exports(dir)?;
+        let mut ready: Vec<&PathBuf> = sizes
+            .iter()
+            .filter(|(path, size)| **size > 0 && last_sizes.get(*path) == Some(*size))
+            .map(|(path, _)| path)
+            .filter(|path| !failed.contains(*path))
+            .collect();
+        ready.sort();
+        for input in ready {
+            let dated_dir = output_root.join(export_date(input));
+            let dated_output = dated_dir.join(output_name);
+            if dated_output.exists() {
+                continue;
+            }
+            std::fs::create_dir_all(&dated_dir)
+                .with_context(|| format!("Could not create {:?}", dated_dir))?;
+            info!("Searching {:?}, writing to {:?}", input, dated_output);
+            let status = Command::new(&exe)
+                .args(child_args(&args, &dated_dir))
+                .arg(input)
+                .arg(&dated_output)
+                .status()
+                .with_context(|| format!("Could not run {:?}", exe))?;
+            let exit_status = status.code().and_then(Status::from_code);
+            if exit_status.is_some_and(Status::wrote_results) {
+                info!("Finished {:?} ({})", input, exit_status.unwrap().name());
+            } else {
+                warn!(
+                    "Searching {:?} failed ({}), it won't be tried again until restarted",
+                    input, status
+                );
+                failed.insert(input.clone());
+            }
+        }
+        last_sizes = sizes;
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}